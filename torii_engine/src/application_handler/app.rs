@@ -0,0 +1,37 @@
+use winit::event::WindowEvent;
+
+use super::{AppHandler, WindowHandle};
+
+// Game code hooks into the event loop by implementing this trait and handing it to `AppHandler::set_app`.
+// Every hook receives the AppHandler itself, so windows, config, timing and the vulkan device are all reachable
+// from game code. All hooks default to doing nothing.
+//
+// Order within a frame: on_window_event / on_user_event as events arrive, then fixed_update (zero or more times),
+// update once, and render once per window.
+#[allow(unused_variables)]
+pub trait App<E: 'static = ()> {
+    // called once, after the first window and the vulkan device exist
+    fn init(&mut self, app_handler: &mut AppHandler<E>) {}
+
+    // called once per frame with the (clamped) time since the previous frame in `app_handler.clock()`
+    fn update(&mut self, app_handler: &mut AppHandler<E>) {}
+
+    // called at the fixed rate configured by `app.fixed_update_hz`, possibly several times per frame
+    fn fixed_update(&mut self, app_handler: &mut AppHandler<E>) {}
+
+    // called once per frame for every window, when the window is ready to be redrawn
+    fn render(&mut self, app_handler: &mut AppHandler<E>, window: WindowHandle) {}
+
+    // called before the engine handles the event itself ; not for events the debug overlay took
+    fn on_window_event(&mut self, app_handler: &mut AppHandler<E>, window: WindowHandle, event: &WindowEvent) {}
+
+    // called for events sent with `AppHandler::send_user_event`
+    fn on_user_event(&mut self, app_handler: &mut AppHandler<E>, event: E) {}
+
+    fn suspended(&mut self, app_handler: &mut AppHandler<E>) {}
+
+    fn resumed(&mut self, app_handler: &mut AppHandler<E>) {}
+
+    // called once when the event loop is about to stop
+    fn exit(&mut self, app_handler: &mut AppHandler<E>) {}
+}
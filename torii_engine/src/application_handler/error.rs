@@ -2,6 +2,9 @@
 use anyhow;
 
 use winit::error::{EventLoopError, OsError};
+use winit::window::BadIcon;
+
+use crate::vulkan_api::{RenderTargetError, VkError};
 
 #[derive(Error, Debug)]
 pub enum Error {
@@ -16,6 +19,12 @@ pub enum Error {
     #[error(transparent)]
     EventLoopProxyError(#[from] EventLoopProxyError),
     #[error(transparent)]
+    MonitorAccessError(#[from] MonitorAccessError),
+    #[error(transparent)]
+    VulkanError(#[from] VkError),
+    #[error(transparent)]
+    RenderTargetError(#[from] RenderTargetError),
+    #[error(transparent)]
     ContextError(#[from] anyhow::Error)
 }
 
@@ -39,13 +48,19 @@ pub enum StartLoopError {
 #[derive(Error, Debug)]
 pub enum WindowAccessError {
     #[error("Window corresponding to window id {0} not found in AppHandler.windows")]
-    WindowNotFoundError(u64)
+    WindowNotFoundError(u64),
+    #[error("Window corresponding to window handle {0} not found in AppHandler.windows")]
+    WindowHandleNotFoundError(u64),
+    #[error("Window with handle {0} has no render targets yet")]
+    RenderTargetsUnavailableError(u64),
 }
 
 #[derive(Error, Debug)]
 pub enum WindowCreationError {
     #[error("Failed to create window")]
     OSWindowCreationError(#[from] OsError),
+    #[error("Invalid window icon")]
+    BadIconError(#[from] BadIcon),
 }
 
 #[derive(Error, Debug)]
@@ -53,3 +68,11 @@ pub enum EventLoopProxyError {
     #[error("Failed to send event through event loop proxy ; Event loop closed")]
     EventLoopProxySendEventError,
 }
+
+#[derive(Error, Debug)]
+pub enum MonitorAccessError {
+    #[error("Monitor with index {0} not found")]
+    MonitorNotFoundError(usize),
+    #[error("Video mode {1} not found on monitor with index {0}")]
+    VideoModeNotFoundError(usize, usize),
+}
@@ -1,46 +1,72 @@
-use winit::window::{Window, WindowId};
+use std::collections::{BTreeMap, HashMap};
+
+use winit::window::WindowId;
 use winit::application::ApplicationHandler;
-use winit::dpi::LogicalSize;
 use winit::event_loop::{ActiveEventLoop, EventLoop, EventLoopProxy};
 use winit::event::WindowEvent;
+use tracing::{debug, error, info, info_span, warn};
+
+use crate::config::EngineConfig;
+use crate::debug_ui::DebugOverlay;
+use crate::vulkan_api::{VkApp, VkProp};
+use crate::vulkan_api::swapchain::Swapchain;
+use crate::vulkan_api::render_target::OffscreenTargetDesc;
+use crate::vulkan_api::requirements::VkRequirements;
 
 mod error;
 pub use error::*;
+mod window;
+pub use window::*;
+mod monitor;
+pub use monitor::*;
+mod app;
+pub use app::*;
+mod time;
+pub use time::*;
 
-#[derive(Copy, Clone)]
-pub struct WindowDetails {
-    pub window_title: &'static str,
-    pub window_height: u32,
-    pub window_width: u32,
-}
-impl Default for WindowDetails{
-    fn default() -> Self {
-        WindowDetails {
-            window_title: "Torii Application",
-            window_height: 800,
-            window_width: 600,
-        }
-    }
-}
-
-pub enum AppEvents {
-    CreateWindow,
+// Events travelling through the event loop ; `User` carries the game's own event type
+pub enum AppEvents<E: 'static = ()> {
+    CreateWindow(WindowHandle, WindowDetails),
+    CloseWindow(WindowHandle),
+    SetFullscreen(WindowHandle, FullscreenMode),
+    User(E),
 }
 
-pub struct AppHandler {
-    event_loop: Option<EventLoop<AppEvents>>,
-    event_loop_proxy: EventLoopProxy<AppEvents>,
-    window_details: Option<WindowDetails>,
-    windows: Vec<Window>,
+pub struct AppHandler<E: 'static = ()> {
+    event_loop: Option<EventLoop<AppEvents<E>>>,
+    event_loop_proxy: EventLoopProxy<AppEvents<E>>,
+    config: EngineConfig,
+    app: Option<Box<dyn App<E>>>,
+    app_initialized: bool,
+    exit_requested: bool,
+    suspended: bool,
+    clock: Clock,
+    // windows (and their swapchains) must be dropped before the vulkan device they were created from
+    windows: BTreeMap<WindowHandle, ManagedWindow>,
+    window_handles: HashMap<WindowId, WindowHandle>,
+    pending_windows: Vec<(WindowHandle, WindowDetails)>,
+    next_window_handle: u64,
+    vk_prop: Option<VkProp>,
+    vk_requirements: VkRequirements,
+    vk_app: Option<VkApp>,
+    // stops the event loop and is returned by start_loop, e.g. when no vulkan loader or device is found
+    fatal_error: Option<Error>,
     error_callback: Option<Box<dyn FnMut(Error)>>,
+    window_created_callback: Option<Box<dyn FnMut(WindowHandle)>>,
+    window_destroyed_callback: Option<Box<dyn FnMut(WindowHandle)>>,
+    // sees the events of its window before the app
+    debug_overlay: Option<DebugOverlay>,
 }
 
-impl AppHandler {
+impl<E: 'static> AppHandler<E> {
     // PUBLIC FUNCTIONS (chainable, state changing functions)
     pub fn new() -> Result<Self> {
-        let event_loop: EventLoop<AppEvents> = EventLoop::<AppEvents>::with_user_event()
+        Self::with_config(EngineConfig::default())
+    }
+    pub fn with_config(config: EngineConfig) -> Result<Self> {
+        let event_loop: EventLoop<AppEvents<E>> = EventLoop::<AppEvents<E>>::with_user_event()
             .build()
-            .map_err(|e| InitializationError::EventLoopCreationError(e))?;
+            .map_err(InitializationError::EventLoopCreationError)?;
         event_loop.set_control_flow(winit::event_loop::ControlFlow::Poll);
 
         let event_loop_proxy = event_loop.create_proxy();
@@ -48,108 +74,494 @@ impl AppHandler {
         let app_handler = AppHandler {
             event_loop: Some(event_loop),
             event_loop_proxy,
-            window_details: None,
-            windows: vec![],
+            app: None,
+            app_initialized: false,
+            exit_requested: false,
+            suspended: false,
+            clock: Clock::new(config.app.fixed_update_hz),
+            config,
+            windows: BTreeMap::new(),
+            window_handles: HashMap::new(),
+            pending_windows: vec![],
+            next_window_handle: 0,
+            vk_prop: None,
+            vk_requirements: VkRequirements::default(),
+            vk_app: None,
+            fatal_error: None,
             error_callback: None,
+            window_created_callback: None,
+            window_destroyed_callback: None,
+            debug_overlay: None,
         };
 
         Ok(app_handler)
     }
+    pub fn with_app(mut self, app: impl App<E> + 'static) -> Self {
+        self.set_app(Some(Box::new(app)));
+        self
+    }
     pub fn start_loop(mut self) -> Result<Self> {
         self.event_loop.take()
             .ok_or(StartLoopError::EventLoopAlreadyConsumedError)?
             .run_app(&mut self)
-            .map_err(|e| StartLoopError::EventLoopRunAppError(e))?;
+            .map_err(StartLoopError::EventLoopRunAppError)?;
+        if let Some(error) = self.fatal_error.take() {
+            return Err(error);
+        }
         Ok(self)
     }
     
     // SETTERS, GETTERS, CALLBACK EXECUTORS (non chainable)
+    pub fn config(&self) -> &EngineConfig {
+        &self.config
+    }
+    pub fn set_app(&mut self, app: Option<Box<dyn App<E>>>) {
+        self.app = app;
+        self.app_initialized = false;
+        self.init_app();
+    }
+    pub fn clock(&self) -> &Clock {
+        &self.clock
+    }
+    // true between ApplicationHandler::suspended and resumed ; windows have no swapchain during that time
+    pub fn is_suspended(&self) -> bool {
+        self.suspended
+    }
+    // stops the event loop once the current frame has been handled
+    pub fn request_exit(&mut self) {
+        self.exit_requested = true;
+    }
+    // details used for the window created on startup when no other window was requested
     pub fn set_window_details(&mut self, window_details: Option<WindowDetails>) {
-        self.window_details = window_details;
+        self.config.window = window_details.unwrap_or_default();
+    }
+    pub fn window_details(&self) -> &WindowDetails {
+        &self.config.window
     }
-    pub fn window_details(&self) -> &Option<WindowDetails> {
-        &self.window_details
+    pub fn window(&self, handle: WindowHandle) -> Result<&ManagedWindow> {
+        Ok(self.windows.get(&handle).ok_or(WindowAccessError::WindowHandleNotFoundError(handle.id()))?)
     }
-    pub fn windows(&self) -> &Vec<Window> {
-        &self.windows
+    pub fn window_handles(&self) -> Vec<WindowHandle> {
+        self.windows.keys().copied().collect()
+    }
+    // monitors can only be queried through a live window, so this is empty until the first window exists
+    pub fn monitors(&self) -> Vec<MonitorInfo> {
+        match self.windows.values().next() {
+            Some(managed_window) => managed_window.window
+                .available_monitors()
+                .enumerate()
+                .map(|(index, monitor)| MonitorInfo::new(index, &monitor))
+                .collect(),
+            None => vec![],
+        }
+    }
+    pub fn set_fullscreen(&mut self, handle: WindowHandle, fullscreen: FullscreenMode) -> Result<()> {
+        self.windows
+            .get_mut(&handle)
+            .ok_or(WindowAccessError::WindowHandleNotFoundError(handle.id()))?
+            .set_fullscreen(fullscreen)
+    }
+    // offscreen target of a window, following its size from then on ; fails while the window has no render targets
+    // yet (suspended or minimized since creation)
+    pub fn add_offscreen_target(&mut self, handle: WindowHandle, name: &str, desc: OffscreenTargetDesc) -> Result<()> {
+        let vk_app = self.vk_app.as_ref().ok_or(WindowAccessError::RenderTargetsUnavailableError(handle.id()))?;
+        self.windows
+            .get_mut(&handle)
+            .ok_or(WindowAccessError::WindowHandleNotFoundError(handle.id()))?
+            .render_targets
+            .as_mut()
+            .ok_or(WindowAccessError::RenderTargetsUnavailableError(handle.id()))?
+            .add_offscreen(vk_app, name, desc)?;
+        Ok(())
+    }
+    pub fn remove_offscreen_target(&mut self, handle: WindowHandle, name: &str) -> Result<bool> {
+        Ok(self.windows
+            .get_mut(&handle)
+            .ok_or(WindowAccessError::WindowHandleNotFoundError(handle.id()))?
+            .render_targets
+            .as_mut()
+            .is_some_and(|render_targets| render_targets.remove_offscreen(name)))
+    }
+    // overrides the vulkan properties otherwise derived from the engine config
+    pub fn set_vk_prop(&mut self, vk_prop: Option<VkProp>) {
+        self.vk_prop = vk_prop;
+    }
+    // extensions and features for the device created with the first window ; changes made afterwards have no effect
+    pub fn vk_requirements_mut(&mut self) -> &mut VkRequirements {
+        &mut self.vk_requirements
+    }
+    pub fn vk_app(&self) -> Option<&VkApp> {
+        self.vk_app.as_ref()
     }
     pub fn set_error_callback(&mut self, error_callback: Option<Box<dyn FnMut(Error)>>) {
         self.error_callback = error_callback;
     }
     pub fn error_callback(&mut self, error: Error) {
-        match self.error_callback.as_mut() {
-            Some(callback) => {(*callback)(error)}
-            None => {}
+        if let Some(callback) = self.error_callback.as_mut() {
+            (*callback)(error)
+        }
+    }
+    pub fn set_window_created_callback(&mut self, window_created_callback: Option<Box<dyn FnMut(WindowHandle)>>) {
+        self.window_created_callback = window_created_callback;
+    }
+    pub fn window_created_callback(&mut self, handle: WindowHandle) {
+        if let Some(callback) = self.window_created_callback.as_mut() {
+            (*callback)(handle)
+        }
+    }
+    pub fn set_window_destroyed_callback(&mut self, window_destroyed_callback: Option<Box<dyn FnMut(WindowHandle)>>) {
+        self.window_destroyed_callback = window_destroyed_callback;
+    }
+    pub fn window_destroyed_callback(&mut self, handle: WindowHandle) {
+        if let Some(callback) = self.window_destroyed_callback.as_mut() {
+            (*callback)(handle)
         }
     }
-    pub fn send_event(&mut self, event: AppEvents) -> Result<()>{
+    pub fn set_debug_overlay(&mut self, debug_overlay: Option<DebugOverlay>) {
+        self.debug_overlay = debug_overlay;
+    }
+    pub fn debug_overlay(&self) -> Option<&DebugOverlay> {
+        self.debug_overlay.as_ref()
+    }
+    pub fn debug_overlay_mut(&mut self) -> Option<&mut DebugOverlay> {
+        self.debug_overlay.as_mut()
+    }
+    pub fn send_event(&mut self, event: AppEvents<E>) -> Result<()>{
         self.event_loop_proxy.send_event(event)
             .map_err(|_| EventLoopProxyError::EventLoopProxySendEventError)?;
         Ok(())
     }
+    pub fn send_user_event(&mut self, event: E) -> Result<()> {
+        self.send_event(AppEvents::User(event))
+    }
+    // windows requested before the loop starts are created on the first resume, afterwards through the event loop
+    pub fn create_window(&mut self, window_details: WindowDetails) -> Result<WindowHandle> {
+        let handle = WindowHandle(self.next_window_handle);
+        self.next_window_handle += 1;
+
+        if self.event_loop.is_some() {
+            self.pending_windows.push((handle, window_details));
+        } else {
+            self.send_event(AppEvents::CreateWindow(handle, window_details))?;
+        }
+        Ok(handle)
+    }
+    pub fn close_window(&mut self, handle: WindowHandle) -> Result<()> {
+        if let Some(index) = self.pending_windows.iter().position(|(pending, _)| *pending == handle) {
+            self.pending_windows.remove(index);
+            return Ok(());
+        }
+        if !self.windows.contains_key(&handle) {
+            return Err(WindowAccessError::WindowHandleNotFoundError(handle.id()).into());
+        }
+        self.send_event(AppEvents::CloseWindow(handle))
+    }
 }
 
-impl AppHandler {
+impl<E: 'static> AppHandler<E> {
     // PRIVATE FUNCTIONS (called from the event loop ; no return value)
-    fn create_window(&mut self, event_loop: &ActiveEventLoop) {
-        let window_details = self.window_details().unwrap_or_default();
+    // runs a hook of the game app with access to the AppHandler ; the app is taken out for the duration of the call
+    fn dispatch(&mut self, hook: impl FnOnce(&mut dyn App<E>, &mut Self)) {
+        if let Some(mut app) = self.app.take() {
+            hook(app.as_mut(), self);
+            // the hook may have installed a different app, which then wins
+            if self.app.is_none() {
+                self.app = Some(app);
+            }
+        }
+    }
 
-        let window_attributes = Window::default_attributes()
-            .with_title(window_details.window_title)
-            .with_inner_size(LogicalSize::new(window_details.window_width, window_details.window_height));
+    fn spawn_window(&mut self, event_loop: &ActiveEventLoop, handle: WindowHandle, window_details: WindowDetails) {
+        let window_result = window_details.window_attributes(event_loop.available_monitors())
+            .and_then(|window_attributes| {
+                Ok(event_loop
+                    .create_window(window_attributes)
+                    .map_err(WindowCreationError::OSWindowCreationError)?)
+            });
 
-        let window_result = event_loop
-            .create_window(window_attributes)
-            .map_err(|e| WindowCreationError::OSWindowCreationError(e));
+        let window = match window_result {
+            Ok(window) => window,
+            Err(error) => {
+                warn!(target: "torii::window", window = handle.id(), %error, "Failed to create window");
+                self.error_callback(error);
+                return;
+            }
+        };
 
-        match window_result {
-            Ok(window) => {
-                self.windows.push(window);
+        // the vulkan device is created lazily alongside the first window, every later window only adds a swapchain
+        let vk_app = match self.vk_app.as_ref() {
+            Some(vk_app) => vk_app,
+            None => {
+                let mut vk_prop = self.vk_prop.take().unwrap_or_else(|| VkProp::from(&self.config));
+                vk_prop.requirements.merge(&self.vk_requirements);
+                match VkApp::new(Some(vk_prop), &window) {
+                    Ok(vk_app) => self.vk_app.insert(vk_app),
+                    // without a device no window can be rendered to, so the app cannot go on
+                    Err(error) => {
+                        error!(target: "torii::app", %error, "Failed to initialize vulkan");
+                        self.fatal_error = Some(error.into());
+                        event_loop.exit();
+                        return;
+                    },
+                }
             },
-            Err(error) => {
-                self.error_callback(error.into());
+        };
+        // windows created while suspended get their swapchain on the next resume
+        let swapchain = (!self.suspended).then(|| Swapchain::new(vk_app, &window));
+
+        self.window_handles.insert(window.id(), handle);
+        let scale_factor = window.scale_factor();
+        let mut managed_window = ManagedWindow {
+            swapchain,
+            render_targets: None,
+            window,
+            windowed_geometry: window_details.windowed_geometry(),
+            details: window_details,
+            scale_factor,
+        };
+        if let Err(error) = managed_window.sync_render_targets(vk_app) {
+            warn!(target: "torii::window", window = handle.id(), %error, "Failed to create render targets");
+            self.error_callback(error);
+        }
+        self.windows.insert(handle, managed_window);
+        info!(target: "torii::window", window = handle.id(), "Created window");
+        self.window_created_callback(handle);
+        self.init_app();
+    }
+
+    // App::init runs once the device exists, before any other hook of the app ; set_app runs it again for the new app
+    fn init_app(&mut self) {
+        if !self.app_initialized && self.vk_app.is_some() {
+            self.app_initialized = true;
+            self.dispatch(|app, app_handler| app.init(app_handler));
+        }
+    }
+
+    // surfaces (and thus swapchains) are only valid while the app is resumed, everything else on the device survives
+    fn destroy_swapchains(&mut self) {
+        for managed_window in self.windows.values_mut() {
+            drop(managed_window.swapchain.take());
+        }
+    }
+
+    fn recreate_swapchains(&mut self) {
+        let Some(vk_app) = self.vk_app.as_ref() else {
+            return;
+        };
+        let mut errors = vec![];
+        for managed_window in self.windows.values_mut() {
+            if managed_window.swapchain.is_none() {
+                managed_window.swapchain = Some(Swapchain::new(vk_app, &managed_window.window));
+            }
+            // the window may have been resized while suspended
+            if let Err(error) = managed_window.sync_render_targets(vk_app) {
+                errors.push(error);
             }
+        }
+        for error in errors {
+            self.error_callback(error);
+        }
+    }
+
+    fn resize_swapchain(&mut self, handle: WindowHandle) {
+        let (Some(vk_app), Some(managed_window)) = (self.vk_app.as_ref(), self.windows.get_mut(&handle)) else {
+            return;
+        };
+        let size = managed_window.window.inner_size();
+        let Some(swapchain) = managed_window.swapchain.as_mut() else {
+            return;
+        };
+        swapchain.recreate(size.width, size.height);
+        if let Err(error) = managed_window.sync_render_targets(vk_app) {
+            warn!(target: "torii::window", window = handle.id(), %error, "Failed to recreate render targets");
+            self.error_callback(error);
+        }
+    }
+
+    // the overlay's frame spans the app's updates, so the app can add widgets to it ; skipped while its window is gone
+    fn begin_debug_overlay(&mut self) {
+        let Some(debug_overlay) = self.debug_overlay.as_mut() else {
+            return;
+        };
+        let Some(managed_window) = self.windows.get(&debug_overlay.window()) else {
+            return;
+        };
+        let device_report = self.vk_app.as_ref().map(VkApp::device_report);
+        debug_overlay.begin_frame(&self.clock, managed_window.window.inner_size(), managed_window.scale_factor, device_report);
+    }
+
+    fn end_debug_overlay(&mut self) {
+        let Some(debug_overlay) = self.debug_overlay.as_mut() else {
+            return;
         };
+        let Some(managed_window) = self.windows.get(&debug_overlay.window()) else {
+            return;
+        };
+        debug_overlay.end_frame();
+        match debug_overlay.take_cursor_change() {
+            Some(Some(cursor_icon)) => {
+                managed_window.window.set_cursor_visible(true);
+                managed_window.window.set_cursor(cursor_icon);
+            },
+            Some(None) => managed_window.window.set_cursor_visible(false),
+            None => (),
+        }
+    }
+
+    fn destroy_window(&mut self, event_loop: &ActiveEventLoop, handle: WindowHandle) {
+        let Some(managed_window) = self.windows.remove(&handle) else {
+            self.error_callback(WindowAccessError::WindowHandleNotFoundError(handle.id()).into());
+            return;
+        };
+        self.window_handles.remove(&managed_window.window.id());
+        drop(managed_window);
+        info!(target: "torii::window", window = handle.id(), "Destroyed window");
+        self.window_destroyed_callback(handle);
+
+        if self.windows.is_empty() {
+            event_loop.exit();
+        }
     }
 }
 
-impl ApplicationHandler<AppEvents> for AppHandler {
+impl<E: 'static> ApplicationHandler<AppEvents<E>> for AppHandler<E> {
     fn resumed(&mut self, event_loop: &ActiveEventLoop) {
-        self.create_window(event_loop);
+        // resumed is called again after every suspend, the first window must only be created once
+        if self.suspended {
+            info!(target: "torii::app", "Resumed");
+            self.suspended = false;
+            self.recreate_swapchains();
+            self.clock.unpause();
+        }
+        
+        // the event loop is running, so the default window is spawned right away instead of going through create_window
+        if self.windows.is_empty() && self.pending_windows.is_empty() {
+            let handle = WindowHandle(self.next_window_handle);
+            self.next_window_handle += 1;
+            self.pending_windows.push((handle, self.window_details().clone()));
+        }
+        for (handle, window_details) in std::mem::take(&mut self.pending_windows) {
+            self.spawn_window(event_loop, handle, window_details);
+        }
+
+        self.init_app();
+        if !self.app_initialized {
+            return;
+        }
+        self.dispatch(|app, app_handler| app.resumed(app_handler));
+    }
+
+    fn suspended(&mut self, _event_loop: &ActiveEventLoop) {
+        info!(target: "torii::app", "Suspended");
+        self.dispatch(|app, app_handler| app.suspended(app_handler));
+        
+        self.suspended = true;
+        self.clock.pause();
+        self.destroy_swapchains();
+    }
+
+    fn about_to_wait(&mut self, event_loop: &ActiveEventLoop) {
+        if self.exit_requested {
+            event_loop.exit();
+            return;
+        }
+        if self.suspended {
+            return;
+        }
+
+        // no device yet, the app's hooks wait for its init
+        if !self.app_initialized {
+            return;
+        }
+
+        self.clock.tick();
+        let _frame_span = info_span!(target: "torii::frame", "frame", frame = self.clock.frame()).entered();
+        self.begin_debug_overlay();
+        while self.clock.consume_fixed_step() {
+            self.dispatch(|app, app_handler| app.fixed_update(app_handler));
+        }
+        self.dispatch(|app, app_handler| app.update(app_handler));
+        self.end_debug_overlay();
+        if let Some(vk_app) = self.vk_app.as_ref() {
+            vk_app.check_validation_errors();
+        }
+
+        for managed_window in self.windows.values() {
+            managed_window.window.request_redraw();
+        }
     }
 
-    fn user_event(&mut self, event_loop: &ActiveEventLoop, event: AppEvents) {
+    fn exiting(&mut self, _event_loop: &ActiveEventLoop) {
+        info!(target: "torii::app", frames = self.clock.frame(), "Exiting");
+        self.dispatch(|app, app_handler| app.exit(app_handler));
+    }
+
+    fn user_event(&mut self, event_loop: &ActiveEventLoop, event: AppEvents<E>) {
         match event {
-            AppEvents::CreateWindow => {
-                self.create_window(event_loop);
+            AppEvents::CreateWindow(handle, window_details) => {
+                self.spawn_window(event_loop, handle, window_details);
+            },
+            AppEvents::CloseWindow(handle) => {
+                self.destroy_window(event_loop, handle);
+            },
+            AppEvents::SetFullscreen(handle, fullscreen) => {
+                if let Err(error) = self.set_fullscreen(handle, fullscreen) {
+                    self.error_callback(error);
+                }
+            },
+            AppEvents::User(event) => {
+                self.dispatch(|app, app_handler| app.on_user_event(app_handler, event));
             },
         }
     }
 
     fn window_event(&mut self, event_loop: &ActiveEventLoop, window_id: WindowId, event: WindowEvent) {
-        let window_index = match self.windows.iter().position(|window| window.id() == window_id)
+        let handle = match self.window_handles.get(&window_id).copied()
             .ok_or(WindowAccessError::WindowNotFoundError(window_id.into())) {
-            Ok(idx) => idx,
+            Ok(handle) => handle,
             Err(error) => {
                 self.error_callback(error.into());
                 return;
             },
         };
+
+        // events the debug overlay takes (clicks on its panels, typing in its fields) never reach the app
+        let consumed = match (self.debug_overlay.as_mut(), self.windows.get(&handle)) {
+            (Some(debug_overlay), Some(managed_window)) if debug_overlay.window() == handle => {
+                debug_overlay.on_window_event(&event, managed_window.scale_factor)
+            },
+            _ => false,
+        };
+        if !consumed {
+            self.dispatch(|app, app_handler| app.on_window_event(app_handler, handle, &event));
+        }
         
         match event {
             WindowEvent::CloseRequested => {
-                println!("The close button was pressed; stopping");
-                if self.windows.len() > 1 {
-                    drop(self.windows.swap_remove(window_index));
-                }
-                else {
-                    event_loop.exit();
+                info!(target: "torii::window", window = handle.id(), "Close button pressed, closing window");
+                self.destroy_window(event_loop, handle);
+            },
+            WindowEvent::Resized(size) => {
+                debug!(target: "torii::window", window = handle.id(), width = size.width, height = size.height, "Resized window");
+                self.resize_swapchain(handle);
+            },
+            WindowEvent::ScaleFactorChanged { scale_factor, .. } => {
+                debug!(target: "torii::window", window = handle.id(), scale_factor, "Scale factor changed");
+                // the physical size of the surface changes with the scale factor, so the swapchain follows it
+                if let Some(managed_window) = self.windows.get_mut(&handle) {
+                    managed_window.scale_factor = scale_factor;
                 }
+                self.resize_swapchain(handle);
             },
-            WindowEvent::RedrawRequested => {
-                self.windows[window_index].request_redraw();
+            WindowEvent::RedrawRequested if !self.suspended => {
+                debug_assert!(self.app_initialized, "App::render dispatched before App::init!");
+                let _frame_span = info_span!(target: "torii::frame", "frame", frame = self.clock.frame()).entered();
+                self.dispatch(|app, app_handler| app.render(app_handler, handle));
+                if let Some(vk_app) = self.vk_app.as_ref() {
+                    vk_app.check_validation_errors();
+                }
             },
             _ => (),
         };
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,90 @@
+use winit::dpi::{PhysicalPosition, PhysicalSize};
+use winit::monitor::{MonitorHandle, VideoModeHandle};
+use winit::window::Fullscreen;
+
+use serde::{Deserialize, Serialize};
+
+use super::{MonitorAccessError, Result};
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct VideoModeInfo {
+    pub width: u32,
+    pub height: u32,
+    pub bit_depth: u16,
+    pub refresh_rate_millihertz: u32,
+}
+
+impl From<&VideoModeHandle> for VideoModeInfo {
+    fn from(video_mode: &VideoModeHandle) -> Self {
+        let size = video_mode.size();
+        VideoModeInfo {
+            width: size.width,
+            height: size.height,
+            bit_depth: video_mode.bit_depth(),
+            refresh_rate_millihertz: video_mode.refresh_rate_millihertz(),
+        }
+    }
+}
+
+// Snapshot of a monitor ; `index` is its position in the platform's monitor list and is what FullscreenMode refers to
+#[derive(Clone, Debug)]
+pub struct MonitorInfo {
+    pub index: usize,
+    pub name: Option<String>,
+    pub size: PhysicalSize<u32>,
+    pub position: PhysicalPosition<i32>,
+    pub scale_factor: f64,
+    pub refresh_rate_millihertz: Option<u32>,
+    pub video_modes: Vec<VideoModeInfo>,
+}
+
+impl MonitorInfo {
+    pub(crate) fn new(index: usize, monitor: &MonitorHandle) -> Self {
+        MonitorInfo {
+            index,
+            name: monitor.name(),
+            size: monitor.size(),
+            position: monitor.position(),
+            scale_factor: monitor.scale_factor(),
+            refresh_rate_millihertz: monitor.refresh_rate_millihertz(),
+            video_modes: monitor.video_modes().map(|video_mode| VideoModeInfo::from(&video_mode)).collect(),
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum FullscreenMode {
+    #[default]
+    Windowed,
+    // borderless window covering the monitor at its current video mode ; `None` picks the monitor the window is on
+    Borderless { monitor: Option<usize> },
+    // exclusive fullscreen switching the monitor to one of the video modes listed in MonitorInfo::video_modes
+    Exclusive { monitor: usize, video_mode: usize },
+}
+
+impl FullscreenMode {
+    pub fn is_fullscreen(&self) -> bool {
+        *self != FullscreenMode::Windowed
+    }
+
+    pub(crate) fn to_winit(self, monitors: impl Iterator<Item = MonitorHandle>) -> Result<Option<Fullscreen>> {
+        let mut monitors = monitors;
+        let fullscreen = match self {
+            FullscreenMode::Windowed => None,
+            FullscreenMode::Borderless { monitor: None } => Some(Fullscreen::Borderless(None)),
+            FullscreenMode::Borderless { monitor: Some(index) } => {
+                let monitor = monitors.nth(index).ok_or(MonitorAccessError::MonitorNotFoundError(index))?;
+                Some(Fullscreen::Borderless(Some(monitor)))
+            },
+            FullscreenMode::Exclusive { monitor: index, video_mode } => {
+                let monitor = monitors.nth(index).ok_or(MonitorAccessError::MonitorNotFoundError(index))?;
+                let video_mode_handle = monitor
+                    .video_modes()
+                    .nth(video_mode)
+                    .ok_or(MonitorAccessError::VideoModeNotFoundError(index, video_mode))?;
+                Some(Fullscreen::Exclusive(video_mode_handle))
+            },
+        };
+        Ok(fullscreen)
+    }
+}
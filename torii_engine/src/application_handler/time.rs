@@ -0,0 +1,93 @@
+use std::time::{Duration, Instant};
+
+// Frame timing of the AppHandler: variable frame delta for `App::update` plus a fixed timestep accumulator
+// for `App::fixed_update`
+pub struct Clock {
+    last_tick: Option<Instant>,
+    delta: Duration,
+    elapsed: Duration,
+    fixed_delta: Duration,
+    accumulator: Duration,
+    frame: u64,
+    paused: bool,
+}
+
+impl Clock {
+    // longest delta fed into the accumulator, so a long stall does not trigger hundreds of fixed updates
+    pub const MAX_DELTA: Duration = Duration::from_millis(250);
+
+    pub fn new(fixed_update_hz: u32) -> Self {
+        Clock {
+            last_tick: None,
+            delta: Duration::ZERO,
+            elapsed: Duration::ZERO,
+            fixed_delta: Duration::from_secs_f64(1.0 / fixed_update_hz.max(1) as f64),
+            accumulator: Duration::ZERO,
+            frame: 0,
+            paused: false,
+        }
+    }
+
+    // stops game time, e.g. while the app is suspended ; the time spent paused never shows up in a delta
+    pub(crate) fn pause(&mut self) {
+        self.paused = true;
+        self.last_tick = None;
+        self.delta = Duration::ZERO;
+    }
+
+    pub(crate) fn unpause(&mut self) {
+        self.paused = false;
+    }
+
+    pub(crate) fn tick(&mut self) {
+        if self.paused {
+            return;
+        }
+        let now = Instant::now();
+        self.delta = match self.last_tick {
+            Some(last_tick) => (now - last_tick).min(Self::MAX_DELTA),
+            None => Duration::ZERO,
+        };
+        self.last_tick = Some(now);
+        self.elapsed += self.delta;
+        self.accumulator += self.delta;
+        self.frame += 1;
+    }
+
+    // consumes one fixed step from the accumulator, returns false once less than a step is left
+    pub(crate) fn consume_fixed_step(&mut self) -> bool {
+        if self.accumulator >= self.fixed_delta {
+            self.accumulator -= self.fixed_delta;
+            true
+        } else {
+            false
+        }
+    }
+
+    // GETTERS
+
+    pub fn delta(&self) -> Duration {
+        self.delta
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        self.elapsed
+    }
+
+    pub fn fixed_delta(&self) -> Duration {
+        self.fixed_delta
+    }
+
+    pub fn frame(&self) -> u64 {
+        self.frame
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    // how far the simulation is between two fixed updates, in [0, 1) ; used to interpolate rendering
+    pub fn fixed_alpha(&self) -> f64 {
+        self.accumulator.as_secs_f64() / self.fixed_delta.as_secs_f64()
+    }
+}
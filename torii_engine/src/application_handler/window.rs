@@ -0,0 +1,184 @@
+use winit::dpi::{LogicalPosition, LogicalSize, Position, Size};
+use winit::monitor::MonitorHandle;
+use winit::window::{Icon, Window, WindowAttributes};
+
+use serde::{Deserialize, Serialize};
+
+use crate::vulkan_api::swapchain::Swapchain;
+use crate::vulkan_api::VkApp;
+use crate::vulkan_api::render_target::{RenderTargetSettings, RenderTargets};
+
+use super::{FullscreenMode, Result, WindowCreationError};
+
+// Stable identifier of a window owned by the AppHandler ; stays valid (and is never reused) even when other
+// windows are closed
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct WindowHandle(pub(crate) u64);
+
+impl WindowHandle {
+    pub fn id(&self) -> u64 {
+        self.0
+    }
+}
+
+// Raw RGBA8 pixels of a window icon
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct WindowIcon {
+    pub rgba: Vec<u8>,
+    pub width: u32,
+    pub height: u32,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct WindowDetails {
+    pub window_title: String,
+    pub window_height: u32,
+    pub window_width: u32,
+    pub window_position: Option<(i32, i32)>,
+    pub decorations: bool,
+    pub resizable: bool,
+    pub fullscreen: FullscreenMode,
+    // raw pixels do not belong in a config file, icons are set from code
+    #[serde(skip)]
+    pub icon: Option<WindowIcon>,
+}
+
+impl Default for WindowDetails {
+    fn default() -> Self {
+        WindowDetails {
+            window_title: String::from("Torii Application"),
+            window_height: 600,
+            window_width: 800,
+            window_position: None,
+            decorations: true,
+            resizable: true,
+            fullscreen: FullscreenMode::Windowed,
+            icon: None,
+        }
+    }
+}
+
+impl WindowDetails {
+    pub(crate) fn window_attributes(&self, monitors: impl Iterator<Item = MonitorHandle>) -> Result<WindowAttributes> {
+        let icon = match self.icon.as_ref() {
+            Some(icon) => Some(
+                Icon::from_rgba(icon.rgba.clone(), icon.width, icon.height)
+                    .map_err(WindowCreationError::BadIconError)?
+            ),
+            None => None,
+        };
+
+        let mut window_attributes = Window::default_attributes()
+            .with_title(self.window_title.as_str())
+            .with_inner_size(LogicalSize::new(self.window_width, self.window_height))
+            .with_decorations(self.decorations)
+            .with_resizable(self.resizable)
+            .with_window_icon(icon)
+            .with_fullscreen(self.fullscreen.to_winit(monitors)?);
+
+        if let Some((x, y)) = self.window_position {
+            window_attributes = window_attributes.with_position(LogicalPosition::new(x, y));
+        }
+
+        Ok(window_attributes)
+    }
+
+    pub(crate) fn windowed_geometry(&self) -> WindowedGeometry {
+        WindowedGeometry {
+            position: self.window_position.map(|(x, y)| LogicalPosition::new(x, y).into()),
+            size: LogicalSize::new(self.window_width, self.window_height).into(),
+        }
+    }
+}
+
+// Geometry of a window while it is not fullscreen, restored when leaving fullscreen
+#[derive(Copy, Clone, Debug)]
+pub struct WindowedGeometry {
+    pub position: Option<Position>,
+    pub size: Size,
+}
+
+// A window together with its presentation state ; the swapchain is declared first so it is dropped before the
+// window its surface was created from
+pub struct ManagedWindow {
+    pub(crate) swapchain: Option<Swapchain>,
+    // depth, msaa and offscreen targets, recreated along with the swapchain
+    pub(crate) render_targets: Option<RenderTargets>,
+    pub(crate) window: Window,
+    pub(crate) details: WindowDetails,
+    pub(crate) windowed_geometry: WindowedGeometry,
+    pub(crate) scale_factor: f64,
+}
+
+impl ManagedWindow {
+    pub fn window(&self) -> &Window {
+        &self.window
+    }
+
+    pub fn swapchain(&self) -> Option<&Swapchain> {
+        self.swapchain.as_ref()
+    }
+
+    pub fn render_targets(&self) -> Option<&RenderTargets> {
+        self.render_targets.as_ref()
+    }
+
+    pub fn details(&self) -> &WindowDetails {
+        &self.details
+    }
+
+    pub fn fullscreen(&self) -> FullscreenMode {
+        self.details.fullscreen
+    }
+
+    pub fn windowed_geometry(&self) -> WindowedGeometry {
+        self.windowed_geometry
+    }
+
+    pub fn scale_factor(&self) -> f64 {
+        self.scale_factor
+    }
+
+    // Creates the render targets once the window has a usable swapchain, or brings them in line with its new extent
+    pub(crate) fn sync_render_targets(&mut self, vk_app: &VkApp) -> Result<()> {
+        let Some(swapchain) = self.swapchain.as_ref() else {
+            return Ok(());
+        };
+        let (format, extent) = (swapchain.format().format, swapchain.extent());
+        match self.render_targets.as_mut() {
+            Some(render_targets) => render_targets.resize(vk_app, format, extent)?,
+            // a minimized window has no swapchain images yet, the targets wait for the first real size
+            None if extent.width == 0 || extent.height == 0 => (),
+            None => {
+                let settings = RenderTargetSettings::from(&vk_app.vk_prop().renderer_config);
+                self.render_targets = Some(RenderTargets::new(vk_app, settings, format, extent)?);
+            },
+        }
+        Ok(())
+    }
+
+    pub(crate) fn set_fullscreen(&mut self, fullscreen: FullscreenMode) -> Result<()> {
+        let winit_fullscreen = fullscreen.to_winit(self.window.available_monitors())?;
+
+        // remember where the window was before it leaves windowed mode so it can be put back there
+        if !self.details.fullscreen.is_fullscreen() && fullscreen.is_fullscreen() {
+            self.windowed_geometry = WindowedGeometry {
+                position: self.window.outer_position().ok().map(Position::from),
+                size: self.window.inner_size().into(),
+            };
+        }
+
+        self.window.set_fullscreen(winit_fullscreen);
+
+        if self.details.fullscreen.is_fullscreen() && !fullscreen.is_fullscreen() {
+            let _ = self.window.request_inner_size(self.windowed_geometry.size);
+            if let Some(position) = self.windowed_geometry.position {
+                self.window.set_outer_position(position);
+            }
+        }
+
+        self.details.fullscreen = fullscreen;
+        Ok(())
+    }
+}
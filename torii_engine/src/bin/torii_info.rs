@@ -0,0 +1,48 @@
+// Prints the capabilities of every vulkan device as JSON, to attach to bug reports
+//     torii-info [--loader <path to the vulkan loader library>] [--output <file>]
+
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+use torii_engine::vulkan_api::report::DeviceReport;
+
+fn main() -> ExitCode {
+    let mut loader_path: Option<PathBuf> = None;
+    let mut output_path: Option<PathBuf> = None;
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let target = match arg.as_str() {
+            "--loader" => &mut loader_path,
+            "--output" => &mut output_path,
+            _ => {
+                eprintln!("Unknown argument {arg} ; usage: torii-info [--loader <path>] [--output <file>]");
+                return ExitCode::FAILURE;
+            },
+        };
+        let Some(value) = args.next() else {
+            eprintln!("Missing value for {arg}");
+            return ExitCode::FAILURE;
+        };
+        *target = Some(PathBuf::from(value));
+    }
+
+    let reports = match DeviceReport::collect_all(loader_path.as_deref()) {
+        Ok(reports) => reports,
+        Err(error) => {
+            eprintln!("{error}");
+            return ExitCode::FAILURE;
+        },
+    };
+    let json = serde_json::to_string_pretty(&reports).expect("Device reports always serialize");
+
+    match output_path {
+        Some(output_path) => {
+            if let Err(error) = std::fs::write(&output_path, json) {
+                eprintln!("Failed to write {}: {error}", output_path.display());
+                return ExitCode::FAILURE;
+            }
+        },
+        None => println!("{json}"),
+    }
+    ExitCode::SUCCESS
+}
@@ -0,0 +1,47 @@
+use std::path::PathBuf;
+
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum ConfigError {
+    #[error("Failed to read or write config file {path}")]
+    IoError {
+        path: PathBuf,
+        #[source]
+        source: std::io::Error,
+    },
+    #[error("Unsupported config file format {0} ; expected a .toml or .ron file")]
+    UnsupportedFormatError(PathBuf),
+    #[error("Invalid TOML config {origin}: {source}")]
+    TomlParseError {
+        origin: String,
+        #[source]
+        source: toml::de::Error,
+    },
+    #[error("Invalid RON config {origin}: {source}")]
+    RonParseError {
+        origin: String,
+        #[source]
+        source: ron::error::SpannedError,
+    },
+    #[error("Failed to serialize config to TOML")]
+    TomlSerializeError(#[from] toml::ser::Error),
+    #[error("Failed to serialize config to RON")]
+    RonSerializeError(#[from] ron::Error),
+    #[error("Invalid config override {0:?} ; expected key=value")]
+    InvalidOverrideError(String),
+    #[error("Config override for {key} is invalid: {message}")]
+    OverrideValueError {
+        key: String,
+        message: String,
+    },
+    #[error("Missing value for command line argument {0}")]
+    MissingArgumentError(String),
+    #[error("Invalid config value for {key}: {message}")]
+    ValidationError {
+        key: &'static str,
+        message: String,
+    },
+}
+
+pub type Result<T> = std::result::Result<T, ConfigError>;
@@ -0,0 +1,501 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::application_handler::WindowDetails;
+
+mod error;
+pub use error::*;
+
+// Environment variables prefixed with this override config keys, `__` separating nested keys
+// (e.g. TORII__WINDOW__WINDOW_WIDTH=1280 sets window.window_width)
+pub const ENV_PREFIX: &str = "TORII__";
+// Environment variable pointing at a config file, used when no --config argument is given
+pub const ENV_CONFIG_PATH: &str = "TORII_CONFIG";
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct EngineConfig {
+    pub app: AppConfig,
+    pub window: WindowDetails,
+    pub renderer: RendererConfig,
+    pub input: InputConfig,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AppConfig {
+    pub name: String,
+    // major, minor, patch
+    pub version: (u32, u32, u32),
+    // rate of App::fixed_update
+    pub fixed_update_hz: u32,
+}
+
+impl Default for AppConfig {
+    fn default() -> Self {
+        AppConfig {
+            name: String::from("Torii Application"),
+            version: (0, 1, 0),
+            fixed_update_hz: 60,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RendererConfig {
+    pub vsync: bool,
+    pub frames_in_flight: u32,
+    // clamped to what the device supports for both color and depth attachments
+    pub msaa_samples: u32,
+    // pick a depth format with a stencil aspect for the window render targets
+    pub stencil: bool,
+    pub validation: bool,
+    // draw meshes as meshlets through task/mesh shaders when the device supports VK_EXT_mesh_shader
+    pub mesh_shading: bool,
+    // vulkan loader library to load instead of the system one ; ignored when built with the `linked` feature
+    pub loader_path: Option<PathBuf>,
+    pub shadows: ShadowConfig,
+}
+
+impl Default for RendererConfig {
+    fn default() -> Self {
+        RendererConfig {
+            vsync: true,
+            frames_in_flight: 2,
+            msaa_samples: 1,
+            stencil: false,
+            validation: cfg!(debug_assertions),
+            mesh_shading: true,
+            loader_path: None,
+            shadows: ShadowConfig::default(),
+        }
+    }
+}
+
+// Shadow maps of the forward renderer (see renderer::pbr) ; lights opt in one by one
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ShadowConfig {
+    // cascades of each directional light shadow, 1 to 4
+    pub cascade_count: u32,
+    // 0 splits the cascades evenly, 1 logarithmically ; in between blends both
+    pub split_lambda: f32,
+    // view distance the cascades cover, clamped to the camera's far plane
+    pub distance: f32,
+    pub cascade_resolution: u32,
+    // resolution of each cube face of a point light shadow
+    pub point_resolution: u32,
+    // directional lights casting shadows at once, up to 4 ; the extra ones are unshadowed
+    pub max_directional_shadows: u32,
+    // point lights casting shadows at once, up to 8
+    pub max_point_shadows: u32,
+    // PCF kernel radius in texels, each tap is itself bilinearly filtered when the device allows it
+    pub pcf_radius: u32,
+}
+
+impl Default for ShadowConfig {
+    fn default() -> Self {
+        ShadowConfig {
+            cascade_count: 4,
+            split_lambda: 0.75,
+            distance: 100.0,
+            cascade_resolution: 2048,
+            point_resolution: 512,
+            max_directional_shadows: 1,
+            max_point_shadows: 4,
+            pcf_radius: 1,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct InputConfig {
+    pub mouse_sensitivity: f32,
+    pub invert_mouse_y: bool,
+    pub gamepad_deadzone: f32,
+    // action name -> key names bound to it
+    pub key_bindings: BTreeMap<String, Vec<String>>,
+}
+
+impl Default for InputConfig {
+    fn default() -> Self {
+        InputConfig {
+            mouse_sensitivity: 1.0,
+            invert_mouse_y: false,
+            gamepad_deadzone: 0.1,
+            key_bindings: BTreeMap::new(),
+        }
+    }
+}
+
+enum ConfigFormat {
+    Toml,
+    Ron,
+}
+
+impl ConfigFormat {
+    fn from_path(path: &Path) -> Result<Self> {
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some("toml") => Ok(ConfigFormat::Toml),
+            Some("ron") => Ok(ConfigFormat::Ron),
+            _ => Err(ConfigError::UnsupportedFormatError(path.to_path_buf())),
+        }
+    }
+}
+
+impl EngineConfig {
+    // LOADING AND SAVING
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let format = ConfigFormat::from_path(path)?;
+        let contents = fs::read_to_string(path)
+            .map_err(|source| ConfigError::IoError { path: path.to_path_buf(), source })?;
+        let origin = path.display().to_string();
+
+        let config = match format {
+            ConfigFormat::Toml => Self::parse_toml(&contents, &origin)?,
+            ConfigFormat::Ron => Self::parse_ron(&contents, &origin)?,
+        };
+        config.validate()?;
+        Ok(config)
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        let path = path.as_ref();
+        let contents = match ConfigFormat::from_path(path)? {
+            ConfigFormat::Toml => self.to_toml_string()?,
+            ConfigFormat::Ron => self.to_ron_string()?,
+        };
+        fs::write(path, contents)
+            .map_err(|source| ConfigError::IoError { path: path.to_path_buf(), source })
+    }
+
+    pub fn from_toml_str(contents: &str) -> Result<Self> {
+        let config = Self::parse_toml(contents, "string")?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    pub fn from_ron_str(contents: &str) -> Result<Self> {
+        let config = Self::parse_ron(contents, "string")?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    pub fn to_toml_string(&self) -> Result<String> {
+        Ok(toml::to_string_pretty(self)?)
+    }
+
+    pub fn to_ron_string(&self) -> Result<String> {
+        Ok(ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())?)
+    }
+
+    // Builds the config the way a game binary usually wants it:
+    //   1. `--config <path>` (or the TORII_CONFIG environment variable) selects the file, defaults otherwise
+    //   2. TORII__* environment variables override keys of the file
+    //   3. `--set key=value` arguments override everything else
+    // Unrelated arguments are ignored so the game can parse its own
+    pub fn from_args_and_env(args: impl IntoIterator<Item = String>) -> Result<Self> {
+        let mut args = args.into_iter();
+        let mut config_path = None;
+        let mut overrides = vec![];
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--config" => config_path = Some(args.next().ok_or(ConfigError::MissingArgumentError(arg))?),
+                "--set" => overrides.push(args.next().ok_or(ConfigError::MissingArgumentError(arg))?),
+                _ => (),
+            }
+        }
+
+        let mut config = match config_path.or_else(|| std::env::var(ENV_CONFIG_PATH).ok()) {
+            Some(path) => Self::load(path)?,
+            None => Self::default(),
+        };
+        config.apply_env_overrides()?;
+        for config_override in overrides {
+            config.apply_override_str(&config_override)?;
+        }
+        config.validate()?;
+        Ok(config)
+    }
+
+    // OVERRIDES
+
+    pub fn apply_env_overrides(&mut self) -> Result<()> {
+        let mut env_overrides: Vec<(String, String)> = std::env::vars()
+            .filter(|(name, _)| name.starts_with(ENV_PREFIX))
+            .map(|(name, value)| (name[ENV_PREFIX.len()..].to_lowercase().replace("__", "."), value))
+            .collect();
+        env_overrides.sort();
+        for (key, value) in env_overrides {
+            self.apply_override(&key, &value)?;
+        }
+        Ok(())
+    }
+
+    // `key=value` form, as used by `--set`
+    pub fn apply_override_str(&mut self, config_override: &str) -> Result<()> {
+        let (key, value) = config_override
+            .split_once('=')
+            .ok_or_else(|| ConfigError::InvalidOverrideError(config_override.to_owned()))?;
+        self.apply_override(key.trim(), value.trim())
+    }
+
+    // Sets a dotted key (e.g. `renderer.vsync`) ; the value is parsed as a TOML value and falls back to a plain
+    // string, so both `renderer.msaa_samples=4` and `app.name=My Game` work
+    pub fn apply_override(&mut self, key: &str, value: &str) -> Result<()> {
+        let override_error = |message: String| ConfigError::OverrideValueError { key: key.to_owned(), message };
+
+        let mut root = toml::Value::try_from(&*self)?;
+        let parsed_value = toml::from_str::<toml::Table>(&format!("value = {value}"))
+            .ok()
+            .and_then(|mut table| table.remove("value"))
+            .unwrap_or_else(|| toml::Value::String(value.to_owned()));
+
+        let (parent_keys, last_key) = match key.rsplit_once('.') {
+            Some((parent_keys, last_key)) => (Some(parent_keys), last_key),
+            None => (None, key),
+        };
+        let mut table = root.as_table_mut().expect("Config always serializes to a table");
+        for parent_key in parent_keys.into_iter().flat_map(|parent_keys| parent_keys.split('.')) {
+            table = table
+                .entry(parent_key)
+                .or_insert_with(|| toml::Value::Table(toml::Table::new()))
+                .as_table_mut()
+                .ok_or_else(|| override_error(format!("{parent_key} is not a table")))?;
+        }
+        table.insert(last_key.to_owned(), parsed_value);
+
+        let config: EngineConfig = root.try_into().map_err(|error: toml::de::Error| override_error(error.message().to_owned()))?;
+        *self = config;
+        Ok(())
+    }
+
+    // VALIDATION
+
+    pub fn validate(&self) -> Result<()> {
+        if self.window.window_width == 0 || self.window.window_height == 0 {
+            return Err(ConfigError::ValidationError {
+                key: "window",
+                message: format!("window size must be non zero, got {}x{}", self.window.window_width, self.window.window_height),
+            });
+        }
+        if self.app.fixed_update_hz == 0 {
+            return Err(ConfigError::ValidationError {
+                key: "app.fixed_update_hz",
+                message: String::from("must be greater than zero"),
+            });
+        }
+        if !(1..=8).contains(&self.renderer.frames_in_flight) {
+            return Err(ConfigError::ValidationError {
+                key: "renderer.frames_in_flight",
+                message: format!("must be between 1 and 8, got {}", self.renderer.frames_in_flight),
+            });
+        }
+        if !self.renderer.msaa_samples.is_power_of_two() || self.renderer.msaa_samples > 64 {
+            return Err(ConfigError::ValidationError {
+                key: "renderer.msaa_samples",
+                message: format!("must be one of 1, 2, 4, 8, 16, 32 or 64, got {}", self.renderer.msaa_samples),
+            });
+        }
+        let shadows = &self.renderer.shadows;
+        if !(1..=4).contains(&shadows.cascade_count) {
+            return Err(ConfigError::ValidationError {
+                key: "renderer.shadows.cascade_count",
+                message: format!("must be between 1 and 4, got {}", shadows.cascade_count),
+            });
+        }
+        if !(0.0..=1.0).contains(&shadows.split_lambda) {
+            return Err(ConfigError::ValidationError {
+                key: "renderer.shadows.split_lambda",
+                message: format!("must be in [0, 1], got {}", shadows.split_lambda),
+            });
+        }
+        if shadows.distance <= 0.0 {
+            return Err(ConfigError::ValidationError {
+                key: "renderer.shadows.distance",
+                message: format!("must be greater than zero, got {}", shadows.distance),
+            });
+        }
+        if shadows.cascade_resolution == 0 || shadows.point_resolution == 0 {
+            return Err(ConfigError::ValidationError {
+                key: "renderer.shadows",
+                message: format!("resolutions must be non zero, got {} and {}", shadows.cascade_resolution, shadows.point_resolution),
+            });
+        }
+        if shadows.max_directional_shadows > 4 || shadows.max_point_shadows > 8 {
+            return Err(ConfigError::ValidationError {
+                key: "renderer.shadows",
+                message: format!(
+                    "at most 4 directional and 8 point shadows, got {} and {}",
+                    shadows.max_directional_shadows, shadows.max_point_shadows
+                ),
+            });
+        }
+        if shadows.pcf_radius > 4 {
+            return Err(ConfigError::ValidationError {
+                key: "renderer.shadows.pcf_radius",
+                message: format!("must be at most 4, got {}", shadows.pcf_radius),
+            });
+        }
+        if !(0.0..1.0).contains(&self.input.gamepad_deadzone) {
+            return Err(ConfigError::ValidationError {
+                key: "input.gamepad_deadzone",
+                message: format!("must be in [0, 1), got {}", self.input.gamepad_deadzone),
+            });
+        }
+        Ok(())
+    }
+}
+
+impl EngineConfig {
+    fn parse_toml(contents: &str, origin: &str) -> Result<Self> {
+        toml::from_str(contents).map_err(|source| ConfigError::TomlParseError { origin: origin.to_owned(), source })
+    }
+
+    fn parse_ron(contents: &str, origin: &str) -> Result<Self> {
+        ron::from_str(contents).map_err(|source| ConfigError::RonParseError { origin: origin.to_owned(), source })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // a file in the temp directory, removed when dropped
+    struct TempFile(PathBuf);
+
+    impl TempFile {
+        fn new(name: &str) -> Self {
+            TempFile(std::env::temp_dir().join(format!("torii-config-{}-{name}", std::process::id())))
+        }
+    }
+
+    impl Drop for TempFile {
+        fn drop(&mut self) {
+            let _ = fs::remove_file(&self.0);
+        }
+    }
+
+    fn custom_config() -> EngineConfig {
+        let mut config = EngineConfig::default();
+        config.app.name = String::from("Round Trip");
+        config.window.window_width = 1280;
+        config.renderer.msaa_samples = 4;
+        config.renderer.loader_path = Some(PathBuf::from("/opt/vulkan/libvulkan.so.1"));
+        config.renderer.shadows.cascade_count = 2;
+        config.input.key_bindings.insert(String::from("jump"), vec![String::from("Space")]);
+        config
+    }
+
+    #[test]
+    fn toml_and_ron_files_round_trip() {
+        let config = custom_config();
+        for name in ["config.toml", "config.ron"] {
+            let file = TempFile::new(name);
+            config.save(&file.0).unwrap();
+            let loaded = EngineConfig::load(&file.0).unwrap();
+            assert_eq!(loaded.to_toml_string().unwrap(), config.to_toml_string().unwrap(), "{name}");
+        }
+        let file = TempFile::new("config.json");
+        assert!(matches!(config.save(&file.0), Err(ConfigError::UnsupportedFormatError(_))));
+    }
+
+    #[test]
+    fn missing_keys_take_their_defaults() {
+        let config = EngineConfig::from_toml_str("[renderer]\nvsync = false\n").unwrap();
+        assert!(!config.renderer.vsync);
+        assert_eq!(config.renderer.frames_in_flight, RendererConfig::default().frames_in_flight);
+        assert_eq!(config.app.name, AppConfig::default().name);
+    }
+
+    #[test]
+    fn unknown_fields_and_bad_values_are_errors() {
+        let error = EngineConfig::from_toml_str("[renderer]\nvsinc = false\n").unwrap_err();
+        assert!(matches!(error, ConfigError::TomlParseError { .. }));
+        assert!(error.to_string().contains("vsinc"), "{error}");
+
+        let error = EngineConfig::from_toml_str("[renderer]\nmsaa_samples = \"four\"\n").unwrap_err();
+        assert!(matches!(error, ConfigError::TomlParseError { .. }));
+        let error = EngineConfig::from_ron_str("(renderer: (frames: 2))").unwrap_err();
+        assert!(matches!(error, ConfigError::RonParseError { .. }));
+    }
+
+    #[test]
+    fn validation_rejects_out_of_range_values() {
+        let cases = [
+            ("renderer.msaa_samples=3", "renderer.msaa_samples"),
+            ("renderer.msaa_samples=128", "renderer.msaa_samples"),
+            ("renderer.frames_in_flight=0", "renderer.frames_in_flight"),
+            ("app.fixed_update_hz=0", "app.fixed_update_hz"),
+            ("window.window_width=0", "window"),
+            ("renderer.shadows.cascade_count=5", "renderer.shadows.cascade_count"),
+            ("input.gamepad_deadzone=1.0", "input.gamepad_deadzone"),
+        ];
+        for (config_override, expected_key) in cases {
+            let mut config = EngineConfig::default();
+            config.apply_override_str(config_override).unwrap();
+            match config.validate() {
+                Err(ConfigError::ValidationError { key, .. }) => assert_eq!(key, expected_key, "{config_override}"),
+                result => panic!("{config_override} validated to {result:?}"),
+            }
+        }
+        assert!(EngineConfig::default().validate().is_ok());
+        // loading validates too
+        assert!(matches!(EngineConfig::from_toml_str("[renderer]\nmsaa_samples = 6\n"), Err(ConfigError::ValidationError { .. })));
+    }
+
+    #[test]
+    fn overrides_parse_values_and_report_bad_ones() {
+        let mut config = EngineConfig::default();
+        config.apply_override_str("renderer.msaa_samples = 8").unwrap();
+        config.apply_override_str("app.name=My Game").unwrap();
+        config.apply_override("renderer.shadows.split_lambda", "0.5").unwrap();
+        assert_eq!(config.renderer.msaa_samples, 8);
+        assert_eq!(config.app.name, "My Game");
+        assert_eq!(config.renderer.shadows.split_lambda, 0.5);
+
+        assert!(matches!(config.apply_override_str("renderer.vsync"), Err(ConfigError::InvalidOverrideError(_))));
+        assert!(matches!(config.apply_override("renderer.vsync", "sometimes"), Err(ConfigError::OverrideValueError { .. })));
+        assert!(matches!(config.apply_override("renderer.unknown", "1"), Err(ConfigError::OverrideValueError { .. })));
+        assert!(matches!(config.apply_override("app.name.first", "1"), Err(ConfigError::OverrideValueError { .. })));
+        // a failed override leaves the config as it was
+        assert_eq!(config.app.name, "My Game");
+    }
+
+    // the only test touching the process environment, the others never read it
+    #[test]
+    fn file_then_env_then_set_arguments() {
+        let args = |args: &[&str]| args.iter().map(|arg| arg.to_string()).collect::<Vec<_>>();
+        let file = TempFile::new("precedence.toml");
+        fs::write(&file.0, "[app]\nname = \"From File\"\nfixed_update_hz = 30\n[renderer]\nmsaa_samples = 2\n").unwrap();
+        let env_file = TempFile::new("env.toml");
+        fs::write(&env_file.0, "[app]\nname = \"From Env File\"\n").unwrap();
+
+        std::env::set_var("TORII__RENDERER__MSAA_SAMPLES", "4");
+        std::env::set_var("TORII__APP__FIXED_UPDATE_HZ", "120");
+        let config = EngineConfig::from_args_and_env(args(&[
+            "game", "--config", file.0.to_str().unwrap(), "--level", "1", "--set", "app.fixed_update_hz=90",
+        ]));
+        std::env::set_var(ENV_CONFIG_PATH, &env_file.0);
+        let env_config = EngineConfig::from_args_and_env(args(&["game"]));
+        let missing = EngineConfig::from_args_and_env(args(&["game", "--set"]));
+        std::env::remove_var("TORII__RENDERER__MSAA_SAMPLES");
+        std::env::remove_var("TORII__APP__FIXED_UPDATE_HZ");
+        std::env::remove_var(ENV_CONFIG_PATH);
+
+        let config = config.unwrap();
+        assert_eq!(config.app.name, "From File");
+        assert_eq!(config.renderer.msaa_samples, 4);
+        assert_eq!(config.app.fixed_update_hz, 90);
+        // TORII_CONFIG selects the file when no --config is given
+        assert_eq!(env_config.unwrap().app.name, "From Env File");
+        assert!(matches!(missing, Err(ConfigError::MissingArgumentError(_))));
+    }
+}
@@ -0,0 +1,168 @@
+use egui::{Event, Key, Modifiers, MouseWheelUnit, PointerButton, Pos2, RawInput, Rect, Vec2, ViewportId};
+use winit::dpi::PhysicalSize;
+use winit::event::{ElementState, Ime, MouseButton, MouseScrollDelta, WindowEvent};
+use winit::keyboard::{KeyCode, PhysicalKey};
+
+// Window events turned into egui events, kept until the next UI frame ; positions in points
+#[derive(Default)]
+pub(crate) struct UiInput {
+    events: Vec<Event>,
+    modifiers: Modifiers,
+    pointer: Option<Pos2>,
+    focused: bool,
+}
+
+impl UiInput {
+    pub(crate) fn push(&mut self, event: &WindowEvent, pixels_per_point: f32) {
+        match event {
+            WindowEvent::CursorMoved { position, .. } => {
+                let pointer = Pos2::new(position.x as f32, position.y as f32) / pixels_per_point;
+                self.pointer = Some(pointer);
+                self.events.push(Event::PointerMoved(pointer));
+            },
+            WindowEvent::CursorLeft { .. } => {
+                self.pointer = None;
+                self.events.push(Event::PointerGone);
+            },
+            WindowEvent::MouseInput { state, button, .. } => {
+                let (Some(pos), Some(button)) = (self.pointer, pointer_button(*button)) else {
+                    return;
+                };
+                self.events.push(Event::PointerButton {
+                    pos,
+                    button,
+                    pressed: *state == ElementState::Pressed,
+                    modifiers: self.modifiers,
+                });
+            },
+            WindowEvent::MouseWheel { delta, .. } => {
+                let (unit, delta) = match *delta {
+                    MouseScrollDelta::LineDelta(x, y) => (MouseWheelUnit::Line, Vec2::new(x, y)),
+                    MouseScrollDelta::PixelDelta(position) => (MouseWheelUnit::Point, Vec2::new(position.x as f32, position.y as f32) / pixels_per_point),
+                };
+                self.events.push(Event::MouseWheel { unit, delta, modifiers: self.modifiers });
+            },
+            WindowEvent::ModifiersChanged(modifiers) => {
+                let state = modifiers.state();
+                self.modifiers = Modifiers {
+                    alt: state.alt_key(),
+                    ctrl: state.control_key(),
+                    shift: state.shift_key(),
+                    mac_cmd: cfg!(target_os = "macos") && state.super_key(),
+                    command: if cfg!(target_os = "macos") { state.super_key() } else { state.control_key() },
+                };
+            },
+            WindowEvent::KeyboardInput { event: key_event, .. } => {
+                let pressed = key_event.state == ElementState::Pressed;
+                let key = match key_event.physical_key {
+                    PhysicalKey::Code(code) => key(code),
+                    PhysicalKey::Unidentified(_) => None,
+                };
+                if let Some(key) = key {
+                    // no clipboard access, copying and cutting only clear the selection
+                    match key {
+                        Key::C if pressed && self.modifiers.command => self.events.push(Event::Copy),
+                        Key::X if pressed && self.modifiers.command => self.events.push(Event::Cut),
+                        _ => (),
+                    }
+                    self.events.push(Event::Key {
+                        key,
+                        physical_key: Some(key),
+                        pressed,
+                        repeat: key_event.repeat,
+                        modifiers: self.modifiers,
+                    });
+                }
+                let typing = pressed && !self.modifiers.ctrl && !self.modifiers.mac_cmd;
+                if let Some(text) = key_event.text.as_ref().filter(|_| typing) {
+                    let text: String = text.chars().filter(|character| !character.is_control()).collect();
+                    if !text.is_empty() {
+                        self.events.push(Event::Text(text));
+                    }
+                }
+            },
+            WindowEvent::Ime(Ime::Commit(text)) => self.events.push(Event::Text(text.clone())),
+            WindowEvent::Focused(focused) => {
+                self.focused = *focused;
+                self.events.push(Event::WindowFocused(*focused));
+            },
+            _ => (),
+        }
+    }
+
+    // the events since the last call, for a window of `size` pixels
+    pub(crate) fn take(&mut self, size: PhysicalSize<u32>, pixels_per_point: f32, time: f64, max_texture_side: usize) -> RawInput {
+        let mut raw_input = RawInput {
+            screen_rect: Some(Rect::from_min_size(Pos2::ZERO, Vec2::new(size.width as f32, size.height as f32) / pixels_per_point)),
+            max_texture_side: Some(max_texture_side),
+            time: Some(time),
+            modifiers: self.modifiers,
+            events: std::mem::take(&mut self.events),
+            focused: self.focused,
+            ..RawInput::default()
+        };
+        raw_input.viewports.entry(ViewportId::ROOT).or_default().native_pixels_per_point = Some(pixels_per_point);
+        raw_input
+    }
+
+    // GETTERS
+
+    // None while the cursor is outside the window
+    pub(crate) fn pointer(&self) -> Option<Pos2> {
+        self.pointer
+    }
+}
+
+fn pointer_button(button: MouseButton) -> Option<PointerButton> {
+    match button {
+        MouseButton::Left => Some(PointerButton::Primary),
+        MouseButton::Right => Some(PointerButton::Secondary),
+        MouseButton::Middle => Some(PointerButton::Middle),
+        MouseButton::Back => Some(PointerButton::Extra1),
+        MouseButton::Forward => Some(PointerButton::Extra2),
+        MouseButton::Other(_) => None,
+    }
+}
+
+// keys egui widgets react to, by position on the keyboard
+fn key(code: KeyCode) -> Option<Key> {
+    let key = match code {
+        KeyCode::ArrowDown => Key::ArrowDown,
+        KeyCode::ArrowLeft => Key::ArrowLeft,
+        KeyCode::ArrowRight => Key::ArrowRight,
+        KeyCode::ArrowUp => Key::ArrowUp,
+        KeyCode::Escape => Key::Escape,
+        KeyCode::Tab => Key::Tab,
+        KeyCode::Backspace => Key::Backspace,
+        KeyCode::Enter | KeyCode::NumpadEnter => Key::Enter,
+        KeyCode::Space => Key::Space,
+        KeyCode::Insert => Key::Insert,
+        KeyCode::Delete => Key::Delete,
+        KeyCode::Home => Key::Home,
+        KeyCode::End => Key::End,
+        KeyCode::PageUp => Key::PageUp,
+        KeyCode::PageDown => Key::PageDown,
+        KeyCode::Minus | KeyCode::NumpadSubtract => Key::Minus,
+        KeyCode::Equal => Key::Equals,
+        KeyCode::NumpadAdd => Key::Plus,
+        KeyCode::F1 => Key::F1,
+        KeyCode::F2 => Key::F2,
+        KeyCode::F3 => Key::F3,
+        KeyCode::F4 => Key::F4,
+        KeyCode::F5 => Key::F5,
+        KeyCode::F6 => Key::F6,
+        KeyCode::F7 => Key::F7,
+        KeyCode::F8 => Key::F8,
+        KeyCode::F9 => Key::F9,
+        KeyCode::F10 => Key::F10,
+        KeyCode::F11 => Key::F11,
+        KeyCode::F12 => Key::F12,
+        // KeyA..KeyZ and Digit0..Digit9 share their names with egui's keys once the prefix is gone
+        _ => {
+            let name = format!("{code:?}");
+            let name = name.strip_prefix("Key").or_else(|| name.strip_prefix("Digit"))?;
+            return Key::from_name(name);
+        },
+    };
+    Some(key)
+}
@@ -0,0 +1,13 @@
+// Immediate mode debug UI (egui) drawn over a window: the overlay takes the window's input before the game and
+// shows built-in panels for frame timings, the vulkan device, the game's entities and the render graph, next to
+// whatever the game draws itself ; the renderer puts it on the swapchain image at the end of the frame.
+
+mod input;
+mod overlay;
+pub use overlay::*;
+mod panels;
+mod renderer;
+pub use renderer::*;
+
+// the game builds its own widgets with it
+pub use egui;
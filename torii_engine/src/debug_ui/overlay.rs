@@ -0,0 +1,333 @@
+use std::collections::{HashSet, VecDeque};
+use std::time::Instant;
+
+use egui::{ClippedPrimitive, Context, TexturesDelta};
+use winit::dpi::PhysicalSize;
+use winit::event::{ElementState, MouseButton, WindowEvent};
+use winit::keyboard::{KeyCode, PhysicalKey};
+use winit::window::CursorIcon;
+
+use crate::application_handler::{Clock, WindowHandle};
+use crate::vulkan_api::render_graph::{CompiledGraph, PassSummary};
+use crate::vulkan_api::report::DeviceReport;
+
+use super::input::UiInput;
+use super::panels;
+
+// frames kept for the timing graph and averages
+const TIMING_HISTORY: usize = 240;
+// for devices that haven't said yet
+const DEFAULT_MAX_TEXTURE_SIDE: usize = 2048;
+
+// What a DebugUiRenderer draws for one frame, positions in points
+pub struct UiOutput {
+    pub primitives: Vec<ClippedPrimitive>,
+    // accumulated since the last output taken, so no upload is lost when a frame isn't rendered
+    pub textures_delta: TexturesDelta,
+    pub pixels_per_point: f32,
+}
+
+// The built-in panels shown while the overlay is visible
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct DebugPanels {
+    pub frame_timings: bool,
+    pub device: bool,
+    pub entities: bool,
+    pub render_graph: bool,
+}
+
+impl Default for DebugPanels {
+    fn default() -> Self {
+        DebugPanels {
+            frame_timings: true,
+            device: false,
+            entities: false,
+            render_graph: false,
+        }
+    }
+}
+
+// Lets the entity panel list and edit the game's entities ; the engine has no entity storage of its own, so the game
+// implements this on whatever holds them (an ECS world, a scene graph) and hands it to
+// DebugOverlay::show_entity_inspector every frame
+pub trait EntityInspector {
+    // every entity with a display name, in the order they're listed
+    fn entities(&self) -> Vec<(u64, String)>;
+
+    // widgets showing (and possibly editing) the components of `entity`
+    fn inspect(&mut self, entity: u64, ui: &mut egui::Ui);
+}
+
+// Milliseconds spent on a frame, and on the game's update within it
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub(crate) struct FrameTiming {
+    pub frame_ms: f32,
+    pub update_ms: f32,
+}
+
+// An immediate mode debug UI (egui) over one window. The AppHandler feeds it the window's events first, so clicks
+// and keys landing on the UI never reach the game, and runs its frame around App::fixed_update and App::update: the
+// game adds its own widgets through context() during update. Rendering is up to the game, passing take_output to
+// a DebugUiRenderer once its frame is drawn.
+pub struct DebugOverlay {
+    context: Context,
+    window: WindowHandle,
+    input: UiInput,
+    pub visible: bool,
+    // shows and hides the overlay, never seen by the game ; None to only toggle it from code
+    pub toggle_key: Option<KeyCode>,
+    pub panels: DebugPanels,
+    timings: VecDeque<FrameTiming>,
+    frame: u64,
+    elapsed: f64,
+    frame_started: Option<Instant>,
+    device_report: Option<DeviceReport>,
+    render_graph: Vec<PassSummary>,
+    selected_entity: Option<u64>,
+    entity_filter: String,
+    entities_shown: bool,
+    // pressed over the UI, so their release is the UI's too
+    captured_buttons: HashSet<MouseButton>,
+    pixels_per_point: f32,
+    primitives: Vec<ClippedPrimitive>,
+    textures_delta: TexturesDelta,
+    has_output: bool,
+    cursor_icon: egui::CursorIcon,
+    cursor_change: Option<Option<CursorIcon>>,
+}
+
+impl DebugOverlay {
+    // over `window`, visible and toggled with F1
+    pub fn new(window: WindowHandle) -> Self {
+        DebugOverlay {
+            context: Context::default(),
+            window,
+            input: UiInput::default(),
+            visible: true,
+            toggle_key: Some(KeyCode::F1),
+            panels: DebugPanels::default(),
+            timings: VecDeque::with_capacity(TIMING_HISTORY),
+            frame: 0,
+            elapsed: 0.0,
+            frame_started: None,
+            device_report: None,
+            render_graph: vec![],
+            selected_entity: None,
+            entity_filter: String::new(),
+            entities_shown: false,
+            captured_buttons: HashSet::new(),
+            pixels_per_point: 1.0,
+            primitives: vec![],
+            textures_delta: TexturesDelta::default(),
+            has_output: false,
+            cursor_icon: egui::CursorIcon::Default,
+            cursor_change: None,
+        }
+    }
+
+    // Feeds an event of the overlay's window to the UI ; true when the UI took it and the game shouldn't see it.
+    // Pointer motion, focus and modifier changes always go through to the game as well.
+    pub fn on_window_event(&mut self, event: &WindowEvent, scale_factor: f64) -> bool {
+        if let WindowEvent::KeyboardInput { event: key_event, .. } = event {
+            if self.toggle_key.is_some_and(|toggle_key| key_event.physical_key == PhysicalKey::Code(toggle_key)) {
+                if key_event.state == ElementState::Pressed && !key_event.repeat {
+                    self.visible = !self.visible;
+                }
+                return true;
+            }
+        }
+        self.input.push(event, scale_factor as f32);
+        if !self.visible {
+            self.captured_buttons.clear();
+            return false;
+        }
+
+        match event {
+            WindowEvent::MouseInput { state: ElementState::Pressed, button, .. } => {
+                let captured = self.is_pointer_over_ui() || self.context.is_using_pointer();
+                if captured {
+                    self.captured_buttons.insert(*button);
+                }
+                captured
+            },
+            WindowEvent::MouseInput { state: ElementState::Released, button, .. } => self.captured_buttons.remove(button),
+            WindowEvent::MouseWheel { .. } => self.is_pointer_over_ui(),
+            WindowEvent::KeyboardInput { .. } | WindowEvent::Ime(_) => self.context.wants_keyboard_input(),
+            _ => false,
+        }
+    }
+
+    // whether the pointer is over a window or widget of the UI, as of the last frame's layout ; egui's background
+    // layer covers the whole window and doesn't count
+    fn is_pointer_over_ui(&self) -> bool {
+        self.input
+            .pointer()
+            .and_then(|pointer| self.context.layer_id_at(pointer))
+            .is_some_and(|layer| layer.order != egui::Order::Background)
+    }
+
+    // Starts the UI frame, before App::fixed_update ; `device_report` is kept the first time it is given
+    pub(crate) fn begin_frame(&mut self, clock: &Clock, size: PhysicalSize<u32>, scale_factor: f64, device_report: Option<&DeviceReport>) {
+        if self.device_report.is_none() {
+            self.device_report = device_report.cloned();
+        }
+        let max_texture_side = self
+            .device_report
+            .as_ref()
+            .map_or(DEFAULT_MAX_TEXTURE_SIDE, |report| report.limits.max_image_dimension_2d as usize);
+
+        if self.timings.len() == TIMING_HISTORY {
+            self.timings.pop_front();
+        }
+        self.timings.push_back(FrameTiming {
+            frame_ms: clock.delta().as_secs_f32() * 1000.0,
+            update_ms: 0.0,
+        });
+        self.frame = clock.frame();
+        self.elapsed = clock.elapsed().as_secs_f64();
+        self.frame_started = Some(Instant::now());
+        self.entities_shown = false;
+
+        self.pixels_per_point = scale_factor as f32;
+        let raw_input = self.input.take(size, self.pixels_per_point, self.elapsed, max_texture_side);
+        self.context.begin_pass(raw_input);
+    }
+
+    // Draws the built-in panels and finishes the UI frame, after App::update
+    pub(crate) fn end_frame(&mut self) {
+        // e.g. installed during App::update
+        let Some(started) = self.frame_started.take() else {
+            return;
+        };
+        if let Some(timing) = self.timings.back_mut() {
+            timing.update_ms = started.elapsed().as_secs_f32() * 1000.0;
+        }
+        if self.visible {
+            self.show_panels();
+        }
+
+        let output = self.context.end_pass();
+        self.primitives = self.context.tessellate(output.shapes, output.pixels_per_point);
+        self.pixels_per_point = output.pixels_per_point;
+        self.textures_delta.append(output.textures_delta);
+        self.has_output = true;
+
+        let cursor_icon = output.platform_output.cursor_icon;
+        if cursor_icon != self.cursor_icon {
+            self.cursor_icon = cursor_icon;
+            self.cursor_change = Some(winit_cursor(cursor_icon));
+        }
+    }
+
+    fn show_panels(&mut self) {
+        let context = self.context.clone();
+        panels::controls(&context, &mut self.panels, self.toggle_key, &self.timings);
+        if self.panels.frame_timings {
+            panels::frame_timings(&context, &mut self.panels.frame_timings, &self.timings, self.frame, self.elapsed);
+        }
+        if self.panels.device {
+            panels::device(&context, &mut self.panels.device, self.device_report.as_ref());
+        }
+        if self.panels.render_graph {
+            panels::render_graph(&context, &mut self.panels.render_graph, &self.render_graph);
+        }
+        if self.panels.entities && !self.entities_shown {
+            panels::entities(&context, &mut self.panels.entities, None, &mut self.selected_entity, &mut self.entity_filter);
+        }
+    }
+
+    // Shows `inspector`'s entities in the entity panel, when it's open ; once per frame during App::update
+    pub fn show_entity_inspector(&mut self, inspector: &mut dyn EntityInspector) {
+        if !self.visible || !self.panels.entities || self.entities_shown || self.frame_started.is_none() {
+            return;
+        }
+        self.entities_shown = true;
+        let context = self.context.clone();
+        panels::entities(&context, &mut self.panels.entities, Some(inspector), &mut self.selected_entity, &mut self.entity_filter);
+    }
+
+    // passes of `graph` for the render graph panel, e.g. right after compiling the frame's graph
+    pub fn set_render_graph(&mut self, graph: &CompiledGraph) {
+        self.render_graph = graph.pass_summaries();
+    }
+
+    // The UI of the last frame, once ; None when no frame ended since the last call
+    pub fn take_output(&mut self) -> Option<UiOutput> {
+        if !std::mem::take(&mut self.has_output) {
+            return None;
+        }
+        Some(UiOutput {
+            primitives: std::mem::take(&mut self.primitives),
+            textures_delta: std::mem::take(&mut self.textures_delta),
+            pixels_per_point: self.pixels_per_point,
+        })
+    }
+
+    // cursor asked for by the UI since the last call ; None to hide it
+    pub(crate) fn take_cursor_change(&mut self) -> Option<Option<CursorIcon>> {
+        self.cursor_change.take()
+    }
+
+    // GETTERS
+
+    // for the game's own widgets, between the start of the frame and the end of App::update
+    pub fn context(&self) -> &Context {
+        &self.context
+    }
+
+    pub fn window(&self) -> WindowHandle {
+        self.window
+    }
+
+    // true while a widget has the keyboard, e.g. a text field, the game should ignore key state meanwhile
+    pub fn wants_keyboard(&self) -> bool {
+        self.visible && self.context.wants_keyboard_input()
+    }
+
+    pub fn wants_pointer(&self) -> bool {
+        self.visible && (self.is_pointer_over_ui() || self.context.is_using_pointer() || !self.captured_buttons.is_empty())
+    }
+}
+
+// None for egui's hidden cursor
+fn winit_cursor(icon: egui::CursorIcon) -> Option<CursorIcon> {
+    use egui::CursorIcon as Egui;
+    let icon = match icon {
+        Egui::None => return None,
+        Egui::Default => CursorIcon::Default,
+        Egui::ContextMenu => CursorIcon::ContextMenu,
+        Egui::Help => CursorIcon::Help,
+        Egui::PointingHand => CursorIcon::Pointer,
+        Egui::Progress => CursorIcon::Progress,
+        Egui::Wait => CursorIcon::Wait,
+        Egui::Cell => CursorIcon::Cell,
+        Egui::Crosshair => CursorIcon::Crosshair,
+        Egui::Text => CursorIcon::Text,
+        Egui::VerticalText => CursorIcon::VerticalText,
+        Egui::Alias => CursorIcon::Alias,
+        Egui::Copy => CursorIcon::Copy,
+        Egui::Move => CursorIcon::Move,
+        Egui::NoDrop => CursorIcon::NoDrop,
+        Egui::NotAllowed => CursorIcon::NotAllowed,
+        Egui::Grab => CursorIcon::Grab,
+        Egui::Grabbing => CursorIcon::Grabbing,
+        Egui::AllScroll => CursorIcon::AllScroll,
+        Egui::ResizeHorizontal => CursorIcon::EwResize,
+        Egui::ResizeNeSw => CursorIcon::NeswResize,
+        Egui::ResizeNwSe => CursorIcon::NwseResize,
+        Egui::ResizeVertical => CursorIcon::NsResize,
+        Egui::ResizeEast => CursorIcon::EResize,
+        Egui::ResizeSouthEast => CursorIcon::SeResize,
+        Egui::ResizeSouth => CursorIcon::SResize,
+        Egui::ResizeSouthWest => CursorIcon::SwResize,
+        Egui::ResizeWest => CursorIcon::WResize,
+        Egui::ResizeNorthWest => CursorIcon::NwResize,
+        Egui::ResizeNorth => CursorIcon::NResize,
+        Egui::ResizeNorthEast => CursorIcon::NeResize,
+        Egui::ResizeColumn => CursorIcon::ColResize,
+        Egui::ResizeRow => CursorIcon::RowResize,
+        Egui::ZoomIn => CursorIcon::ZoomIn,
+        Egui::ZoomOut => CursorIcon::ZoomOut,
+    };
+    Some(icon)
+}
@@ -0,0 +1,187 @@
+use std::collections::VecDeque;
+
+use egui::{pos2, CollapsingHeader, Color32, Context, Grid, RichText, ScrollArea, Sense, Shape, Stroke, Vec2, Window};
+use winit::keyboard::KeyCode;
+
+use crate::vulkan_api::render_graph::PassSummary;
+use crate::vulkan_api::report::DeviceReport;
+
+use super::overlay::{DebugPanels, EntityInspector, FrameTiming};
+
+const FRAME_COLOR: Color32 = Color32::from_rgb(100, 200, 100);
+const UPDATE_COLOR: Color32 = Color32::from_rgb(220, 160, 60);
+// milliseconds of a 60 Hz frame, the reference line of the graph
+const FRAME_BUDGET_MS: f32 = 1000.0 / 60.0;
+
+// The window listing the panels, always shown with the overlay
+pub(crate) fn controls(context: &Context, panels: &mut DebugPanels, toggle_key: Option<KeyCode>, timings: &VecDeque<FrameTiming>) {
+    Window::new("Torii debug").default_pos([8.0, 8.0]).resizable(false).show(context, |ui| {
+        let frame_ms = average(timings.iter().map(|timing| timing.frame_ms));
+        ui.label(format!("{:.0} fps ({frame_ms:.2} ms)", if frame_ms > 0.0 { 1000.0 / frame_ms } else { 0.0 }));
+        ui.separator();
+        ui.checkbox(&mut panels.frame_timings, "Frame timings");
+        ui.checkbox(&mut panels.device, "Device");
+        ui.checkbox(&mut panels.entities, "Entities");
+        ui.checkbox(&mut panels.render_graph, "Render graph");
+        if let Some(toggle_key) = toggle_key {
+            ui.separator();
+            ui.weak(format!("{toggle_key:?} hides the overlay"));
+        }
+    });
+}
+
+pub(crate) fn frame_timings(context: &Context, open: &mut bool, timings: &VecDeque<FrameTiming>, frame: u64, elapsed: f64) {
+    Window::new("Frame timings").open(open).default_pos([8.0, 180.0]).default_width(320.0).show(context, |ui| {
+        let frame_times = || timings.iter().map(|timing| timing.frame_ms);
+        let (min, max) = frame_times().fold((f32::MAX, 0.0f32), |(min, max), frame_ms| (min.min(frame_ms), max.max(frame_ms)));
+        Grid::new("frame timings").num_columns(2).show(ui, |ui| {
+            ui.label("Frame");
+            ui.label(format!("{frame} ({elapsed:.1} s)"));
+            ui.end_row();
+            ui.colored_label(FRAME_COLOR, "Frame time");
+            ui.label(format!("{:.2} ms (min {:.2}, max {max:.2})", average(frame_times()), if timings.is_empty() { 0.0 } else { min }));
+            ui.end_row();
+            ui.colored_label(UPDATE_COLOR, "Update");
+            ui.label(format!("{:.2} ms", average(timings.iter().map(|timing| timing.update_ms))));
+            ui.end_row();
+        });
+
+        // the last frames left to right, scaled to the slowest one but never below a 30 Hz frame
+        let (response, painter) = ui.allocate_painter(Vec2::new(ui.available_width(), 80.0), Sense::hover());
+        let rect = response.rect;
+        painter.rect_filled(rect, 2.0, Color32::from_black_alpha(96));
+        let scale = max.max(2.0 * FRAME_BUDGET_MS);
+        let point = |index: usize, milliseconds: f32| {
+            let x = rect.left() + rect.width() * index as f32 / timings.len().saturating_sub(1).max(1) as f32;
+            pos2(x, rect.bottom() - rect.height() * (milliseconds / scale).min(1.0))
+        };
+        let budget = rect.bottom() - rect.height() * FRAME_BUDGET_MS / scale;
+        painter.hline(rect.x_range(), budget, Stroke::new(1.0, Color32::from_gray(90)));
+        let update_line = timings.iter().enumerate().map(|(index, timing)| point(index, timing.update_ms)).collect();
+        let frame_line = timings.iter().enumerate().map(|(index, timing)| point(index, timing.frame_ms)).collect();
+        painter.add(Shape::line(update_line, Stroke::new(1.0, UPDATE_COLOR)));
+        painter.add(Shape::line(frame_line, Stroke::new(1.0, FRAME_COLOR)));
+        ui.weak(format!("line at {FRAME_BUDGET_MS:.1} ms (60 Hz)"));
+    });
+}
+
+pub(crate) fn device(context: &Context, open: &mut bool, report: Option<&DeviceReport>) {
+    Window::new("Device").open(open).default_pos([340.0, 8.0]).default_width(340.0).show(context, |ui| {
+        let Some(report) = report else {
+            ui.label("No vulkan device yet");
+            return;
+        };
+        Grid::new("device").num_columns(2).striped(true).show(ui, |ui| {
+            let mut row = |name: &str, value: String| {
+                ui.label(name);
+                ui.label(value);
+                ui.end_row();
+            };
+            row("Name", report.name.clone());
+            row("Type", report.device_type.clone());
+            row("Vendor / device", format!("{:#06x} / {:#06x}", report.vendor_id, report.device_id));
+            row("Vulkan", report.api_version.clone());
+            row("Driver", format!("{:#x}", report.driver_version));
+            row("Max image 2D", report.limits.max_image_dimension_2d.to_string());
+            row("Max push constants", format!("{} B", report.limits.max_push_constants_size));
+            row("Max anisotropy", report.limits.max_sampler_anisotropy.to_string());
+            row("Color samples", report.limits.framebuffer_color_sample_counts.clone());
+        });
+        CollapsingHeader::new(format!("Memory heaps ({})", report.memory_heaps.len())).show(ui, |ui| {
+            for (index, heap) in report.memory_heaps.iter().enumerate() {
+                ui.label(format!("{index}: {:.1} MiB {}", heap.size as f64 / (1024.0 * 1024.0), heap.flags));
+            }
+        });
+        let supported = report.features.values().filter(|supported| **supported).count();
+        CollapsingHeader::new(format!("Features ({supported} / {})", report.features.len())).show(ui, |ui| {
+            ScrollArea::vertical().id_salt("features").max_height(200.0).show(ui, |ui| {
+                for (feature, supported) in &report.features {
+                    let text = RichText::new(feature).monospace();
+                    if *supported { ui.label(text) } else { ui.label(text.weak().strikethrough()) };
+                }
+            });
+        });
+        CollapsingHeader::new(format!("Extensions ({})", report.extensions.len())).show(ui, |ui| {
+            ScrollArea::vertical().id_salt("extensions").max_height(200.0).show(ui, |ui| {
+                for extension in &report.extensions {
+                    ui.monospace(format!("{} v{}", extension.name, extension.spec_version));
+                }
+            });
+        });
+    });
+}
+
+pub(crate) fn render_graph(context: &Context, open: &mut bool, passes: &[PassSummary]) {
+    Window::new("Render graph").open(open).default_pos([340.0, 300.0]).default_width(340.0).show(context, |ui| {
+        if passes.is_empty() {
+            ui.label("No graph yet: call DebugOverlay::set_render_graph after compiling one");
+            return;
+        }
+        let live = passes.iter().filter(|pass| pass.barriers.is_some()).count();
+        let barriers: usize = passes.iter().filter_map(|pass| pass.barriers).sum();
+        ui.label(format!("{live} passes, {} culled, {barriers} barriers", passes.len() - live));
+        ScrollArea::vertical().max_height(400.0).show(ui, |ui| {
+            for (index, pass) in passes.iter().enumerate() {
+                let title = match pass.barriers {
+                    Some(barriers) => RichText::new(format!("{index}. {} ({:?}, {barriers} barriers)", pass.name, pass.kind)),
+                    None => RichText::new(format!("{} ({:?}, culled)", pass.name, pass.kind)).weak(),
+                };
+                CollapsingHeader::new(title).id_salt(("pass", index)).show(ui, |ui| {
+                    for read in &pass.reads {
+                        ui.monospace(format!("reads  {read}"));
+                    }
+                    for write in &pass.writes {
+                        ui.monospace(format!("writes {write}"));
+                    }
+                });
+            }
+        });
+    });
+}
+
+// The entity list on the left, the selected entity's widgets on the right ; without an inspector the panel tells how
+// to attach one
+pub(crate) fn entities(
+    context: &Context,
+    open: &mut bool,
+    inspector: Option<&mut dyn EntityInspector>,
+    selected: &mut Option<u64>,
+    filter: &mut String,
+) {
+    Window::new("Entities").open(open).default_pos([8.0, 400.0]).default_size([420.0, 300.0]).show(context, |ui| {
+        let Some(inspector) = inspector else {
+            ui.label("No entity inspector attached: implement EntityInspector and call DebugOverlay::show_entity_inspector from App::update");
+            return;
+        };
+        let entities = inspector.entities();
+        if selected.is_some_and(|selected| !entities.iter().any(|(entity, _)| *entity == selected)) {
+            *selected = None;
+        }
+        ui.horizontal(|ui| {
+            ui.label("Filter");
+            ui.text_edit_singleline(filter);
+        });
+        ui.separator();
+        let filter = filter.to_lowercase();
+        ui.columns(2, |columns| {
+            ScrollArea::vertical().id_salt("entity list").show(&mut columns[0], |ui| {
+                for (entity, name) in entities.iter().filter(|(_, name)| name.to_lowercase().contains(&filter)) {
+                    if ui.selectable_label(*selected == Some(*entity), format!("{name} #{entity}")).clicked() {
+                        *selected = Some(*entity);
+                    }
+                }
+            });
+            ScrollArea::vertical().id_salt("entity components").show(&mut columns[1], |ui| match *selected {
+                Some(entity) => inspector.inspect(entity, ui),
+                None => {
+                    ui.weak(format!("{} entities, none selected", entities.len()));
+                },
+            });
+        });
+    });
+}
+
+fn average(values: impl Iterator<Item = f32>) -> f32 {
+    let (sum, count) = values.fold((0.0, 0), |(sum, count), value| (sum + value, count + 1));
+    if count == 0 { 0.0 } else { sum / count as f32 }
+}
@@ -0,0 +1,375 @@
+use std::collections::HashMap;
+
+use ash::{vk, Device};
+use egui::epaint::{Primitive, Vertex};
+use egui::{ImageData, TextureFilter, TextureId, TextureOptions};
+use tracing::{info, warn};
+
+use crate::vulkan_api::VkApp;
+use crate::vulkan_api::descriptors::{DescriptorAllocator, DescriptorBinding, DescriptorBindings};
+use crate::vulkan_api::image::{GpuImage, ImageDesc, ImageState};
+use crate::vulkan_api::memory::{as_bytes, GpuBuffer, MemoryLocation};
+use crate::vulkan_api::pipeline::{BlendMode, GraphicsPipeline, GraphicsPipelineBuilder, VertexLayout};
+use crate::vulkan_api::sampler::{Sampler, SamplerDesc};
+use crate::vulkan_api::shader::{Shader, ShaderStage};
+
+use crate::renderer::post::is_srgb_format;
+use crate::renderer::{RendererError, RendererResult};
+
+use super::UiOutput;
+
+const VERTEX_SHADER: &str = include_str!("../renderer/shaders/debug_ui/ui.vert");
+const FRAGMENT_SHADER: &str = include_str!("../renderer/shaders/debug_ui/ui.frag");
+
+// egui textures: its font atlas and the images widgets uploaded
+struct UiTexture {
+    image: GpuImage,
+    nearest: bool,
+}
+
+struct FrameResources {
+    vertex_buffer: GpuBuffer,
+    index_buffer: GpuBuffer,
+    staging_buffer: GpuBuffer,
+    descriptor_allocator: DescriptorAllocator,
+    // replaced or freed during this frame, dropped when its slot comes around again
+    retired_textures: Vec<GpuImage>,
+}
+
+// Draws the output of a DebugOverlay (or any egui context) on top of a target, usually the swapchain image once
+// everything else was rendered into it
+pub struct DebugUiRenderer {
+    device: Device,
+    pipeline: GraphicsPipeline,
+    linear_sampler: Sampler,
+    nearest_sampler: Sampler,
+    textures: HashMap<TextureId, UiTexture>,
+    // images of the game shown with egui::Image, the game keeps the views alive
+    user_textures: HashMap<u64, (vk::ImageView, bool)>,
+    next_user_texture: u64,
+    frames: Vec<FrameResources>,
+}
+
+impl DebugUiRenderer {
+    // `color_format` is the format of the targets record draws into
+    pub fn new(vk_app: &VkApp, color_format: vk::Format) -> RendererResult<Self> {
+        let vertex = Shader::from_glsl(VERTEX_SHADER, ShaderStage::Vertex)?;
+        let defines: &[(&str, &str)] = if is_srgb_format(color_format) { &[("LINEAR_OUTPUT", "1")] } else { &[] };
+        let fragment = Shader::from_glsl_with_defines(FRAGMENT_SHADER, ShaderStage::Fragment, defines)?;
+        let pipeline = GraphicsPipelineBuilder::new()
+            .vertex(&vertex, vertex_layout())
+            .fragment(&fragment)
+            .color_formats(&[color_format])
+            .cull_mode(vk::CullModeFlags::NONE, vk::FrontFace::COUNTER_CLOCKWISE)
+            .blend(BlendMode::PremultipliedAlpha)
+            .build(vk_app, "debug ui")?;
+
+        let frames = (0..vk_app.vk_prop().renderer_config.frames_in_flight.max(1))
+            .map(|frame| {
+                Ok(FrameResources {
+                    vertex_buffer: ui_buffer(vk_app, 0, vk::BufferUsageFlags::VERTEX_BUFFER, frame)?,
+                    index_buffer: ui_buffer(vk_app, 0, vk::BufferUsageFlags::INDEX_BUFFER, frame)?,
+                    staging_buffer: ui_buffer(vk_app, 0, vk::BufferUsageFlags::TRANSFER_SRC, frame)?,
+                    descriptor_allocator: DescriptorAllocator::new(vk_app.device())?,
+                    retired_textures: vec![],
+                })
+            })
+            .collect::<RendererResult<Vec<_>>>()?;
+        info!(target: "torii::renderer", ?color_format, "Created debug ui renderer");
+
+        Ok(DebugUiRenderer {
+            device: vk_app.device().clone(),
+            pipeline,
+            linear_sampler: Sampler::new(vk_app, SamplerDesc::linear_clamp(), "debug ui linear sampler")?,
+            nearest_sampler: Sampler::new(vk_app, SamplerDesc::nearest_clamp(), "debug ui nearest sampler")?,
+            textures: HashMap::new(),
+            user_textures: HashMap::new(),
+            next_user_texture: 0,
+            frames,
+        })
+    }
+
+    // Makes `view` (SHADER_READ_ONLY_OPTIMAL whenever the UI is recorded) drawable with egui::Image ; the view must
+    // outlive its registration
+    pub fn register_user_texture(&mut self, view: vk::ImageView, options: TextureOptions) -> TextureId {
+        let id = self.next_user_texture;
+        self.next_user_texture += 1;
+        self.user_textures.insert(id, (view, options.magnification == TextureFilter::Nearest));
+        TextureId::User(id)
+    }
+
+    pub fn unregister_user_texture(&mut self, texture: TextureId) {
+        if let TextureId::User(id) = texture {
+            self.user_textures.remove(&id);
+        }
+    }
+
+    // Uploads the texture changes of `output` then draws it over `target` (COLOR_ATTACHMENT_OPTIMAL, the format given
+    // to new) in a pass of its own ; outside of any pass. Frame `frame_index` (modulo the frames in flight) reuses the
+    // buffers of the previous frame with the same slot, which must have completed on the GPU.
+    pub fn record(
+        &mut self,
+        vk_app: &VkApp,
+        command_buffer: vk::CommandBuffer,
+        frame_index: usize,
+        output: &UiOutput,
+        target: vk::ImageView,
+        extent: vk::Extent2D,
+    ) -> RendererResult<()> {
+        let frame_slot = frame_index % self.frames.len();
+        let frame = &mut self.frames[frame_slot];
+        frame.descriptor_allocator.reset()?;
+        frame.retired_textures.clear();
+
+        self.upload_textures(vk_app, command_buffer, frame_slot, output)?;
+        self.draw(vk_app, command_buffer, frame_slot, output, target, extent)?;
+
+        // egui frees textures once the frame showing them for the last time is painted
+        let frame = &mut self.frames[frame_slot];
+        for texture in &output.textures_delta.free {
+            if let Some(texture) = self.textures.remove(texture) {
+                frame.retired_textures.push(texture.image);
+            }
+        }
+        Ok(())
+    }
+
+    fn upload_textures(&mut self, vk_app: &VkApp, command_buffer: vk::CommandBuffer, frame_slot: usize, output: &UiOutput) -> RendererResult<()> {
+        let deltas = &output.textures_delta.set;
+        if deltas.is_empty() {
+            return Ok(());
+        }
+        // every delta goes through the staging buffer at once, egui's pixels are already RGBA8
+        let pixels: Vec<Vec<u8>> = deltas
+            .iter()
+            .map(|(_, delta)| match &delta.image {
+                ImageData::Color(image) => image.pixels.iter().flat_map(|pixel| pixel.to_array()).collect(),
+            })
+            .collect();
+        let frame = &mut self.frames[frame_slot];
+        let staging_size = pixels.iter().map(Vec::len).sum::<usize>() as vk::DeviceSize;
+        if frame.staging_buffer.size() < staging_size {
+            frame.staging_buffer = ui_buffer(vk_app, staging_size, vk::BufferUsageFlags::TRANSFER_SRC, frame_slot)?;
+        }
+
+        let mut offset = 0;
+        for ((id, delta), pixels) in deltas.iter().zip(&pixels) {
+            let [width, height] = delta.image.size().map(|side| side as u32);
+            let nearest = delta.options.magnification == TextureFilter::Nearest;
+            let (texture, from, x, y) = match delta.pos {
+                // a part of an existing texture
+                Some([x, y]) => {
+                    let Some(texture) = self.textures.get_mut(id) else {
+                        warn!(target: "torii::renderer", texture = ?id, "Partial update of an unknown ui texture, skipping it");
+                        continue;
+                    };
+                    (texture, ImageState::SHADER_READ, x as i32, y as i32)
+                },
+                // a whole new texture, replacing the one with the same id
+                None => {
+                    let desc = ImageDesc::new(vk::Format::R8G8B8A8_SRGB, vk::Extent2D { width, height });
+                    let image = GpuImage::new(vk_app, desc, vk::ImageUsageFlags::SAMPLED | vk::ImageUsageFlags::TRANSFER_DST, &format!("debug ui texture {id:?}"))?;
+                    if let Some(replaced) = self.textures.insert(*id, UiTexture { image, nearest }) {
+                        frame.retired_textures.push(replaced.image);
+                    }
+                    (self.textures.get_mut(id).expect("Ui texture was just inserted!"), ImageState::UNDEFINED, 0, 0)
+                },
+            };
+            texture.nearest = nearest;
+
+            frame.staging_buffer.write(offset, pixels);
+            let region = vk::BufferImageCopy::default()
+                .buffer_offset(offset)
+                .image_subresource(
+                    vk::ImageSubresourceLayers::default()
+                        .aspect_mask(vk::ImageAspectFlags::COLOR)
+                        .mip_level(0)
+                        .base_array_layer(0)
+                        .layer_count(1),
+                )
+                .image_offset(vk::Offset3D { x, y, z: 0 })
+                .image_extent(vk::Extent3D { width, height, depth: 1 });
+            texture.image.cmd_transition(&self.device, command_buffer, from, ImageState::TRANSFER_DST);
+            unsafe {
+                self.device.cmd_copy_buffer_to_image(
+                    command_buffer,
+                    frame.staging_buffer.buffer(),
+                    texture.image.image(),
+                    vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                    std::slice::from_ref(&region),
+                );
+            }
+            texture.image.cmd_transition(&self.device, command_buffer, ImageState::TRANSFER_DST, ImageState::SHADER_READ);
+            offset += pixels.len() as vk::DeviceSize;
+        }
+        Ok(())
+    }
+
+    fn draw(
+        &mut self,
+        vk_app: &VkApp,
+        command_buffer: vk::CommandBuffer,
+        frame_slot: usize,
+        output: &UiOutput,
+        target: vk::ImageView,
+        extent: vk::Extent2D,
+    ) -> RendererResult<()> {
+        let meshes: Vec<_> = output
+            .primitives
+            .iter()
+            .filter_map(|primitive| match &primitive.primitive {
+                Primitive::Mesh(mesh) if !mesh.indices.is_empty() => Some((primitive.clip_rect, mesh)),
+                Primitive::Mesh(_) => None,
+                Primitive::Callback(_) => {
+                    warn!(target: "torii::renderer", "Paint callbacks aren't supported by the debug ui, skipping one");
+                    None
+                },
+            })
+            .collect();
+        if meshes.is_empty() {
+            return Ok(());
+        }
+
+        // all the meshes share one vertex and one index buffer
+        let vertex_count: usize = meshes.iter().map(|(_, mesh)| mesh.vertices.len()).sum();
+        let index_count: usize = meshes.iter().map(|(_, mesh)| mesh.indices.len()).sum();
+        let frame = &mut self.frames[frame_slot];
+        let vertex_size = (vertex_count * size_of::<Vertex>()) as vk::DeviceSize;
+        if frame.vertex_buffer.size() < vertex_size {
+            frame.vertex_buffer = ui_buffer(vk_app, vertex_size, vk::BufferUsageFlags::VERTEX_BUFFER, frame_slot)?;
+        }
+        let index_size = (index_count * size_of::<u32>()) as vk::DeviceSize;
+        if frame.index_buffer.size() < index_size {
+            frame.index_buffer = ui_buffer(vk_app, index_size, vk::BufferUsageFlags::INDEX_BUFFER, frame_slot)?;
+        }
+        let (mut vertex_offset, mut index_offset) = (0, 0);
+        for (_, mesh) in &meshes {
+            frame.vertex_buffer.write((vertex_offset * size_of::<Vertex>()) as vk::DeviceSize, as_bytes(&mesh.vertices));
+            frame.index_buffer.write((index_offset * size_of::<u32>()) as vk::DeviceSize, as_bytes(&mesh.indices));
+            vertex_offset += mesh.vertices.len();
+            index_offset += mesh.indices.len();
+        }
+
+        let color_attachment = vk::RenderingAttachmentInfo::default()
+            .image_view(target)
+            .image_layout(vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL)
+            .load_op(vk::AttachmentLoadOp::LOAD)
+            .store_op(vk::AttachmentStoreOp::STORE);
+        let render_area = vk::Rect2D { offset: vk::Offset2D::default(), extent };
+        let rendering_info = vk::RenderingInfo::default()
+            .render_area(render_area)
+            .layer_count(1)
+            .color_attachments(std::slice::from_ref(&color_attachment));
+        let viewport = vk::Viewport {
+            x: 0.0,
+            y: 0.0,
+            width: extent.width as f32,
+            height: extent.height as f32,
+            min_depth: 0.0,
+            max_depth: 1.0,
+        };
+        let pixels_per_point = output.pixels_per_point;
+        let screen_size = [extent.width as f32 / pixels_per_point, extent.height as f32 / pixels_per_point];
+        unsafe {
+            self.device.cmd_begin_rendering(command_buffer, &rendering_info);
+            self.device.cmd_set_viewport(command_buffer, 0, std::slice::from_ref(&viewport));
+            self.device.cmd_bind_pipeline(command_buffer, vk::PipelineBindPoint::GRAPHICS, self.pipeline.pipeline());
+            self.device.cmd_bind_vertex_buffers(command_buffer, 0, &[frame.vertex_buffer.buffer()], &[0]);
+            self.device.cmd_bind_index_buffer(command_buffer, frame.index_buffer.buffer(), 0, vk::IndexType::UINT32);
+        }
+
+        // the rendering is ended either way, the command buffer stays usable
+        let mut result = Ok(());
+        let (mut vertex_offset, mut index_offset) = (0, 0);
+        for (clip_rect, mesh) in &meshes {
+            let first_index = index_offset;
+            let first_vertex = vertex_offset;
+            index_offset += mesh.indices.len();
+            vertex_offset += mesh.vertices.len();
+
+            // the clip rect in whole pixels, inside the target
+            let min_x = (clip_rect.min.x * pixels_per_point).round().clamp(0.0, extent.width as f32) as u32;
+            let min_y = (clip_rect.min.y * pixels_per_point).round().clamp(0.0, extent.height as f32) as u32;
+            let max_x = (clip_rect.max.x * pixels_per_point).round().clamp(min_x as f32, extent.width as f32) as u32;
+            let max_y = (clip_rect.max.y * pixels_per_point).round().clamp(min_y as f32, extent.height as f32) as u32;
+            if max_x == min_x || max_y == min_y {
+                continue;
+            }
+            let Some((view, nearest)) = texture_view(&self.textures, &self.user_textures, mesh.texture_id) else {
+                warn!(target: "torii::renderer", texture = ?mesh.texture_id, "Ui mesh uses an unknown texture, skipping it");
+                continue;
+            };
+            let sampler = if nearest { &self.nearest_sampler } else { &self.linear_sampler };
+            let bindings = DescriptorBindings::new()
+                .bind(0, 0, DescriptorBinding::sampled_image(view))
+                .bind(0, 1, DescriptorBinding::Sampler(sampler.sampler()))
+                .with_push_constants(as_bytes(&screen_size));
+            if let Err(error) = frame.descriptor_allocator.bind(command_buffer, vk::PipelineBindPoint::GRAPHICS, self.pipeline.layout(), &bindings) {
+                result = Err(RendererError::from(error));
+                break;
+            }
+            let scissor = vk::Rect2D {
+                offset: vk::Offset2D { x: min_x as i32, y: min_y as i32 },
+                extent: vk::Extent2D { width: max_x - min_x, height: max_y - min_y },
+            };
+            unsafe {
+                self.device.cmd_set_scissor(command_buffer, 0, std::slice::from_ref(&scissor));
+                self.device.cmd_draw_indexed(command_buffer, mesh.indices.len() as u32, 1, first_index as u32, first_vertex as i32, 0);
+            }
+        }
+        unsafe {
+            self.device.cmd_end_rendering(command_buffer);
+        }
+        result
+    }
+
+    // GETTERS
+
+    // textures egui uploaded, the font atlas included
+    pub fn texture_count(&self) -> usize {
+        self.textures.len()
+    }
+}
+
+// view of a ui texture and whether it's sampled with nearest filtering
+fn texture_view(textures: &HashMap<TextureId, UiTexture>, user_textures: &HashMap<u64, (vk::ImageView, bool)>, texture: TextureId) -> Option<(vk::ImageView, bool)> {
+    match texture {
+        TextureId::Managed(_) => textures.get(&texture).map(|texture| (texture.image.view(), texture.nearest)),
+        TextureId::User(id) => user_textures.get(&id).copied(),
+    }
+}
+
+// egui's Vertex: position and uv in f32, then the color in 4 bytes
+fn vertex_layout() -> VertexLayout {
+    let attribute = |location: u32, format: vk::Format, offset: u32| {
+        vk::VertexInputAttributeDescription::default()
+            .location(location)
+            .binding(0)
+            .format(format)
+            .offset(offset)
+    };
+    VertexLayout {
+        bindings: vec![
+            vk::VertexInputBindingDescription::default()
+                .binding(0)
+                .stride(size_of::<Vertex>() as u32)
+                .input_rate(vk::VertexInputRate::VERTEX),
+        ],
+        attributes: vec![
+            attribute(0, vk::Format::R32G32_SFLOAT, 0),
+            attribute(1, vk::Format::R32G32_SFLOAT, 8),
+            attribute(2, vk::Format::R8G8B8A8_UNORM, 16),
+        ],
+    }
+}
+
+// host visible, sized to the next power of two so growing buffers are rarely recreated
+fn ui_buffer(vk_app: &VkApp, size: vk::DeviceSize, usage: vk::BufferUsageFlags, frame: impl std::fmt::Display) -> RendererResult<GpuBuffer> {
+    let name = match usage {
+        vk::BufferUsageFlags::VERTEX_BUFFER => "vertices",
+        vk::BufferUsageFlags::INDEX_BUFFER => "indices",
+        _ => "staging",
+    };
+    let size = size.max(1024).next_power_of_two();
+    Ok(GpuBuffer::new(vk_app, size, usage, MemoryLocation::HostVisible, &format!("debug ui {name} [frame {frame}]"))?)
+}
@@ -1,2 +1,5 @@
 pub mod application_handler;
+pub mod config;
 pub mod vulkan_api;
+pub mod renderer;
+pub mod debug_ui;
@@ -0,0 +1,184 @@
+use ash::vk;
+use glam::{Mat4, Vec2, Vec3, Vec4};
+
+// Right handed, looking down -Z in view space ; the projection targets vulkan clip space (depth 0..1, Y down), so
+// counter clockwise triangles stay front facing
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Camera {
+    pub position: Vec3,
+    pub view: Mat4,
+    pub projection: Mat4,
+    // clip distances of the projection, used to slice the view depth (clustered lighting)
+    pub near: f32,
+    pub far: f32,
+    // subpixel offset of the projection in NDC units, moved every frame for temporal anti-aliasing (see
+    // post::PostProcessStack::jitter) ; culling and motion vectors ignore it
+    pub jitter: Vec2,
+}
+
+impl Default for Camera {
+    fn default() -> Self {
+        Camera::look_at(Vec3::new(0.0, 0.0, 3.0), Vec3::ZERO, Vec3::Y)
+            .with_perspective(60f32.to_radians(), 16.0 / 9.0, 0.1, 1000.0)
+    }
+}
+
+impl Camera {
+    pub fn look_at(position: Vec3, target: Vec3, up: Vec3) -> Self {
+        Camera {
+            position,
+            view: Mat4::look_at_rh(position, target, up),
+            projection: Mat4::IDENTITY,
+            near: 0.0,
+            far: 1.0,
+            jitter: Vec2::ZERO,
+        }
+    }
+
+    pub fn with_perspective(mut self, fov_y: f32, aspect_ratio: f32, near: f32, far: f32) -> Self {
+        self.projection = Mat4::perspective_rh(fov_y, aspect_ratio, near, far);
+        self.projection.y_axis.y *= -1.0;
+        self.near = near;
+        self.far = far;
+        self
+    }
+
+    pub fn with_orthographic(mut self, left: f32, right: f32, bottom: f32, top: f32, near: f32, far: f32) -> Self {
+        self.projection = Mat4::orthographic_rh(left, right, bottom, top, near, far);
+        self.projection.y_axis.y *= -1.0;
+        self.near = near;
+        self.far = far;
+        self
+    }
+
+    pub fn with_jitter(mut self, jitter: Vec2) -> Self {
+        self.jitter = jitter;
+        self
+    }
+
+    pub fn view_projection(&self) -> Mat4 {
+        self.projection * self.view
+    }
+
+    // what the geometry is rendered with
+    pub fn jittered_view_projection(&self) -> Mat4 {
+        Mat4::from_translation(self.jitter.extend(0.0)) * self.view_projection()
+    }
+
+    // left, right, bottom, top, near, far ; world space, normalized, normals pointing inside
+    pub fn frustum_planes(&self) -> [Vec4; 6] {
+        let view_projection = self.view_projection();
+        let rows = [0, 1, 2, 3].map(|row| view_projection.row(row));
+        [
+            rows[3] + rows[0],
+            rows[3] - rows[0],
+            rows[3] + rows[1],
+            rows[3] - rows[1],
+            rows[2],
+            rows[3] - rows[2],
+        ]
+        .map(|plane| plane / plane.truncate().length())
+    }
+
+    pub fn gpu_data(&self) -> CameraData {
+        CameraData {
+            view_projection: self.jittered_view_projection(),
+            frustum: self.frustum_planes(),
+            position: self.position.extend(1.0),
+            view: self.view,
+            // no history here, renderers producing motion vectors fill in the previous frame's
+            previous_view_projection: self.view_projection(),
+            jitter: self.jitter.extend(0.0).extend(0.0),
+        }
+    }
+}
+
+// distance of the 2D camera to the z = 0 plane its view is centered on
+const CAMERA_2D_DEPTH: f32 = 1000.0;
+
+// Orthographic camera for 2D scenes on the XY plane, +Y up. With `pixel_perfect` set, the zoom is rounded to a
+// whole number, the rotation ignored and the position snapped so texel edges land on pixel edges: one texel covers
+// exactly `zoom` x `zoom` pixels (sample with nearest filtering).
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Camera2d {
+    // world point at the center of the view
+    pub position: Vec2,
+    // radians, counter clockwise
+    pub rotation: f32,
+    // screen pixels per texel
+    pub zoom: f32,
+    // texels per world unit of the sprites
+    pub pixels_per_unit: f32,
+    pub pixel_perfect: bool,
+}
+
+impl Default for Camera2d {
+    fn default() -> Self {
+        Camera2d {
+            position: Vec2::ZERO,
+            rotation: 0.0,
+            zoom: 1.0,
+            pixels_per_unit: 1.0,
+            pixel_perfect: false,
+        }
+    }
+}
+
+impl Camera2d {
+    pub fn pixel_perfect(pixels_per_unit: f32, zoom: u32) -> Self {
+        Camera2d {
+            zoom: zoom.max(1) as f32,
+            pixels_per_unit,
+            pixel_perfect: true,
+            ..Self::default()
+        }
+    }
+
+    // The view over a target of `extent` pixels, as a Camera the renderers take
+    pub fn camera(&self, extent: vk::Extent2D) -> Camera {
+        let (zoom, rotation) = match self.pixel_perfect {
+            true => (self.zoom.round().max(1.0), 0.0),
+            false => (self.zoom, self.rotation),
+        };
+        // screen pixels per world unit
+        let scale = (zoom * self.pixels_per_unit).max(f32::EPSILON);
+        let size = Vec2::new(extent.width.max(1) as f32, extent.height.max(1) as f32);
+        let (position, left_bottom) = match self.pixel_perfect {
+            // whole pixels from the center to the edges, odd sizes put the center in the middle of a pixel
+            true => ((self.position * scale).round() / scale, -(size * 0.5).floor() / scale),
+            false => (self.position, -size * 0.5 / scale),
+        };
+        let right_top = left_bottom + size / scale;
+        let up = Vec3::new(-rotation.sin(), rotation.cos(), 0.0);
+        Camera::look_at(position.extend(CAMERA_2D_DEPTH), position.extend(0.0), up).with_orthographic(
+            left_bottom.x,
+            right_top.x,
+            left_bottom.y,
+            right_top.y,
+            0.0,
+            CAMERA_2D_DEPTH * 2.0,
+        )
+    }
+
+    // World position under the pixel `screen` (from the top left corner) of a target of `extent` pixels
+    pub fn screen_to_world(&self, screen: Vec2, extent: vk::Extent2D) -> Vec2 {
+        let size = Vec2::new(extent.width.max(1) as f32, extent.height.max(1) as f32);
+        let ndc = screen / size * 2.0 - 1.0;
+        self.camera(extent).view_projection().inverse().project_point3(ndc.extend(0.5)).truncate()
+    }
+}
+
+// `Camera` uniform block as the shaders declare it (std140) ; shaders that don't need the trailing members may leave
+// them out
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+pub struct CameraData {
+    pub view_projection: Mat4,
+    pub frustum: [Vec4; 6],
+    pub position: Vec4,
+    pub view: Mat4,
+    // unjittered
+    pub previous_view_projection: Mat4,
+    // xy the jitter of view_projection
+    pub jitter: Vec4,
+}
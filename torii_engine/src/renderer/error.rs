@@ -0,0 +1,51 @@
+use std::path::PathBuf;
+
+use thiserror::Error;
+
+use crate::vulkan_api::{ComputeError, DescriptorError, MemoryError, PipelineError, ShaderError};
+
+#[derive(Error, Debug)]
+pub enum RendererError {
+    #[error(transparent)]
+    ShaderError(#[from] ShaderError),
+    #[error(transparent)]
+    PipelineError(#[from] PipelineError),
+    #[error(transparent)]
+    ComputeError(#[from] ComputeError),
+    #[error(transparent)]
+    MemoryError(#[from] MemoryError),
+    #[error(transparent)]
+    DescriptorError(#[from] DescriptorError),
+    #[error("Mesh has {0} indices, which is not a multiple of 3")]
+    InvalidIndexCountError(usize),
+    #[error("Vertices {start}..{end} are out of the mesh's {count} vertices")]
+    VertexRangeError { start: usize, end: usize, count: usize },
+    #[error("Dynamic meshes need the synchronization2 feature, which the device does not have enabled")]
+    Synchronization2UnavailableError,
+    #[error("Texture data is {given} bytes, expected {expected} for its size and format")]
+    TextureDataSizeError { expected: usize, given: usize },
+    #[error("Failed to read {path}")]
+    IoError {
+        path: PathBuf,
+        #[source]
+        source: std::io::Error,
+    },
+    #[error("Invalid Radiance HDR image: {0}")]
+    HdrDecodeError(String),
+    #[error("Invalid skybox faces: {0}")]
+    SkyboxFacesError(String),
+    #[error("Invalid sprite atlas: {0}")]
+    AtlasParseError(String),
+    #[error("Failed to pack the sprite atlas: {0}")]
+    AtlasPackError(String),
+    #[error("Invalid font: {0}")]
+    FontParseError(String),
+    #[error("Invalid .cube color lookup table: {0}")]
+    CubeLutParseError(String),
+    #[error("Invalid post processing chain: {0}")]
+    PostChainError(String),
+    #[error("Vulkan call failed while rendering")]
+    VulkanCallError(#[from] ash::vk::Result),
+}
+
+pub type RendererResult<T> = std::result::Result<T, RendererError>;
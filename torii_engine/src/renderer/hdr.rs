@@ -0,0 +1,128 @@
+// Radiance .hdr (RGBE) images, the usual format of HDR environment maps ; flat and run length encoded scanlines
+// are both read, the image must be stored top to bottom (-Y height +X width).
+
+use std::path::Path;
+
+use super::{RendererError, RendererResult};
+
+// Linear RGB pixels row by row from the top, alpha set to 1
+#[derive(Clone, Debug, Default)]
+pub struct HdrImage {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<[f32; 4]>,
+}
+
+impl HdrImage {
+    pub fn load(path: impl AsRef<Path>) -> RendererResult<Self> {
+        let path = path.as_ref();
+        let bytes = std::fs::read(path).map_err(|source| RendererError::IoError { path: path.to_path_buf(), source })?;
+        Self::decode(&bytes)
+    }
+
+    pub fn decode(bytes: &[u8]) -> RendererResult<Self> {
+        let error = |message: &str| RendererError::HdrDecodeError(message.to_owned());
+        let mut reader = Reader { bytes, position: 0 };
+
+        let magic = reader.line().ok_or_else(|| error("missing header"))?;
+        if !magic.starts_with("#?") {
+            return Err(error("missing #? signature"));
+        }
+        loop {
+            let line = reader.line().ok_or_else(|| error("unterminated header"))?;
+            if line.is_empty() {
+                break;
+            }
+            match line.strip_prefix("FORMAT=") {
+                Some(format) if format != "32-bit_rle_rgbe" => {
+                    return Err(RendererError::HdrDecodeError(format!("unsupported pixel format {format}")));
+                },
+                _ => {},
+            }
+        }
+        let resolution = reader.line().ok_or_else(|| error("missing resolution"))?;
+        let (height, width) = match resolution.split_whitespace().collect::<Vec<_>>()[..] {
+            ["-Y", height, "+X", width] => (height.parse::<u32>().ok(), width.parse::<u32>().ok()),
+            _ => return Err(RendererError::HdrDecodeError(format!("unsupported orientation {resolution}"))),
+        };
+        let (Some(height), Some(width)) = (height, width) else {
+            return Err(RendererError::HdrDecodeError(format!("invalid resolution {resolution}")));
+        };
+
+        let mut pixels = Vec::with_capacity(width as usize * height as usize);
+        let mut scanline = vec![[0u8; 4]; width as usize];
+        for _ in 0..height {
+            reader.scanline(&mut scanline).ok_or_else(|| error("truncated pixel data"))?;
+            pixels.extend(scanline.iter().map(|&rgbe| rgbe_to_linear(rgbe)));
+        }
+        Ok(HdrImage { width, height, pixels })
+    }
+}
+
+fn rgbe_to_linear([r, g, b, e]: [u8; 4]) -> [f32; 4] {
+    if e == 0 {
+        return [0.0, 0.0, 0.0, 1.0];
+    }
+    let scale = 2f32.powi(e as i32 - (128 + 8));
+    [r as f32 * scale, g as f32 * scale, b as f32 * scale, 1.0]
+}
+
+struct Reader<'b> {
+    bytes: &'b [u8],
+    position: usize,
+}
+
+impl Reader<'_> {
+    fn byte(&mut self) -> Option<u8> {
+        let byte = *self.bytes.get(self.position)?;
+        self.position += 1;
+        Some(byte)
+    }
+
+    fn line(&mut self) -> Option<String> {
+        let rest = self.bytes.get(self.position..)?;
+        let length = rest.iter().position(|&byte| byte == b'\n')?;
+        self.position += length + 1;
+        Some(String::from_utf8_lossy(&rest[..length]).trim_end().to_owned())
+    }
+
+    fn scanline(&mut self, scanline: &mut [[u8; 4]]) -> Option<()> {
+        let width = scanline.len();
+        let start = self.bytes.get(self.position..self.position + 4)?;
+        let run_length_encoded = (8..0x8000).contains(&width) && start[0] == 2 && start[1] == 2 && start[2] & 0x80 == 0;
+        if !run_length_encoded {
+            for pixel in scanline.iter_mut() {
+                *pixel = [self.byte()?, self.byte()?, self.byte()?, self.byte()?];
+            }
+            return Some(());
+        }
+        if ((start[2] as usize) << 8 | start[3] as usize) != width {
+            return None;
+        }
+        self.position += 4;
+        // each channel is encoded on its own, as runs of one repeated value or literal stretches
+        for channel in 0..4 {
+            let mut x = 0;
+            while x < width {
+                let count = self.byte()? as usize;
+                if count > 128 {
+                    let count = count - 128;
+                    let value = self.byte()?;
+                    for pixel in scanline.get_mut(x..x + count)? {
+                        pixel[channel] = value;
+                    }
+                    x += count;
+                } else {
+                    if count == 0 {
+                        return None;
+                    }
+                    for pixel in scanline.get_mut(x..x + count)? {
+                        pixel[channel] = self.byte()?;
+                    }
+                    x += count;
+                }
+            }
+        }
+        Some(())
+    }
+}
@@ -0,0 +1,343 @@
+use std::ops::Range;
+
+use ash::{vk, Device};
+use glam::{Vec2, Vec3};
+
+use crate::vulkan_api::VkApp;
+use crate::vulkan_api::memory::{as_bytes, GpuBuffer, MemoryLocation};
+use crate::vulkan_api::pipeline::{is_mesh_shading_enabled, VertexLayout};
+use crate::vulkan_api::render_graph::{AccessInfo, BufferAccess};
+
+use super::meshlet::MeshletBuilder;
+use super::{RendererError, RendererResult};
+
+// Interleaved vertex shared by every mesh path ; `tangent.w` is the bitangent sign
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct Vertex {
+    pub position: [f32; 3],
+    pub normal: [f32; 3],
+    pub tangent: [f32; 4],
+    pub uv: [f32; 2],
+}
+
+impl Vertex {
+    pub fn new(position: [f32; 3], normal: [f32; 3], uv: [f32; 2]) -> Self {
+        Vertex {
+            position,
+            normal,
+            tangent: [0.0; 4],
+            uv,
+        }
+    }
+
+    // locations 0 position, 1 normal, 2 tangent, 3 uv
+    pub fn layout() -> VertexLayout {
+        let attribute = |location: u32, format: vk::Format, offset: usize| {
+            vk::VertexInputAttributeDescription::default()
+                .location(location)
+                .binding(0)
+                .format(format)
+                .offset(offset as u32)
+        };
+        VertexLayout {
+            bindings: vec![
+                vk::VertexInputBindingDescription::default()
+                    .binding(0)
+                    .stride(std::mem::size_of::<Vertex>() as u32)
+                    .input_rate(vk::VertexInputRate::VERTEX),
+            ],
+            attributes: vec![
+                attribute(0, vk::Format::R32G32B32_SFLOAT, std::mem::offset_of!(Vertex, position)),
+                attribute(1, vk::Format::R32G32B32_SFLOAT, std::mem::offset_of!(Vertex, normal)),
+                attribute(2, vk::Format::R32G32B32A32_SFLOAT, std::mem::offset_of!(Vertex, tangent)),
+                attribute(3, vk::Format::R32G32_SFLOAT, std::mem::offset_of!(Vertex, uv)),
+            ],
+        }
+    }
+}
+
+// Indexed triangle list on the CPU side
+#[derive(Clone, Debug, Default)]
+pub struct MeshData {
+    pub vertices: Vec<Vertex>,
+    pub indices: Vec<u32>,
+}
+
+impl MeshData {
+    pub fn new(vertices: Vec<Vertex>, indices: Vec<u32>) -> Self {
+        MeshData { vertices, indices }
+    }
+
+    pub fn triangle_count(&self) -> usize {
+        self.indices.len() / 3
+    }
+
+    pub fn positions(&self) -> impl Iterator<Item = Vec3> + '_ {
+        self.vertices.iter().map(|vertex| Vec3::from(vertex.position))
+    }
+
+    // Fills the tangents from the positions, normals and uvs (needed for normal mapping) ; per triangle tangents are
+    // accumulated on their vertices then orthogonalized against the vertex normal
+    pub fn compute_tangents(&mut self) {
+        let mut tangents = vec![Vec3::ZERO; self.vertices.len()];
+        let mut bitangents = vec![Vec3::ZERO; self.vertices.len()];
+        for triangle in self.indices.chunks_exact(3) {
+            let [a, b, c] = [0, 1, 2].map(|corner| triangle[corner] as usize);
+            let position = |index: usize| Vec3::from(self.vertices[index].position);
+            let uv = |index: usize| Vec2::from(self.vertices[index].uv);
+            let (edge_1, edge_2) = (position(b) - position(a), position(c) - position(a));
+            let (delta_uv_1, delta_uv_2) = (uv(b) - uv(a), uv(c) - uv(a));
+            let determinant = delta_uv_1.x * delta_uv_2.y - delta_uv_2.x * delta_uv_1.y;
+            if determinant.abs() < f32::EPSILON {
+                continue;
+            }
+            let tangent = (edge_1 * delta_uv_2.y - edge_2 * delta_uv_1.y) / determinant;
+            let bitangent = (edge_2 * delta_uv_1.x - edge_1 * delta_uv_2.x) / determinant;
+            for index in [a, b, c] {
+                tangents[index] += tangent;
+                bitangents[index] += bitangent;
+            }
+        }
+        for ((vertex, tangent), bitangent) in self.vertices.iter_mut().zip(tangents).zip(bitangents) {
+            let normal = Vec3::from(vertex.normal);
+            let tangent = (tangent - normal * normal.dot(tangent)).normalize_or_zero();
+            let sign = if normal.cross(tangent).dot(bitangent) < 0.0 { -1.0 } else { 1.0 };
+            vertex.tangent = tangent.extend(sign).to_array();
+        }
+    }
+}
+
+// Meshlet data of an uploaded mesh, only present on the mesh shader path
+pub struct GpuMeshlets {
+    pub(crate) meshlet_buffer: GpuBuffer,
+    pub(crate) vertex_index_buffer: GpuBuffer,
+    pub(crate) triangle_buffer: GpuBuffer,
+    pub(crate) meshlet_count: u32,
+}
+
+impl GpuMeshlets {
+    pub fn meshlet_count(&self) -> u32 {
+        self.meshlet_count
+    }
+}
+
+// A mesh uploaded to device local buffers ; the vertex buffer doubles as a storage buffer for the mesh shaders
+pub struct GpuMesh {
+    pub(crate) vertex_buffer: GpuBuffer,
+    pub(crate) index_buffer: GpuBuffer,
+    pub(crate) index_count: u32,
+    pub(crate) vertex_count: usize,
+    pub(crate) meshlets: Option<GpuMeshlets>,
+}
+
+impl GpuMesh {
+    // Uploads `mesh`, also splitting it into meshlets when `meshlets` is set (the mesh shader path needs them)
+    pub fn new(vk_app: &VkApp, mesh: &MeshData, meshlets: bool, name: &str) -> RendererResult<Self> {
+        if !mesh.indices.len().is_multiple_of(3) {
+            return Err(RendererError::InvalidIndexCountError(mesh.indices.len()));
+        }
+        let vertex_buffer = GpuBuffer::with_data(
+            vk_app,
+            as_bytes(&mesh.vertices),
+            vk::BufferUsageFlags::VERTEX_BUFFER | vk::BufferUsageFlags::STORAGE_BUFFER,
+            &format!("{name} [vertices]"),
+        )?;
+        let index_buffer = GpuBuffer::with_data(vk_app, as_bytes(&mesh.indices), vk::BufferUsageFlags::INDEX_BUFFER, &format!("{name} [indices]"))?;
+
+        let meshlets = match meshlets {
+            true => {
+                let meshlet_mesh = MeshletBuilder::new().build(mesh);
+                let storage = vk::BufferUsageFlags::STORAGE_BUFFER;
+                Some(GpuMeshlets {
+                    meshlet_buffer: GpuBuffer::with_data(vk_app, as_bytes(&meshlet_mesh.meshlets), storage, &format!("{name} [meshlets]"))?,
+                    vertex_index_buffer: GpuBuffer::with_data(vk_app, as_bytes(&meshlet_mesh.vertices), storage, &format!("{name} [meshlet vertices]"))?,
+                    triangle_buffer: GpuBuffer::with_data(vk_app, as_bytes(&meshlet_mesh.triangles), storage, &format!("{name} [meshlet triangles]"))?,
+                    meshlet_count: meshlet_mesh.meshlets.len() as u32,
+                })
+            },
+            false => None,
+        };
+
+        Ok(GpuMesh {
+            vertex_buffer,
+            index_buffer,
+            index_count: mesh.indices.len() as u32,
+            vertex_count: mesh.vertices.len(),
+            meshlets,
+        })
+    }
+
+    // Overwrites the vertices from `first_vertex` on and waits for the copy ; frames in flight drawing the mesh must
+    // have completed. The meshlet bounds aren't recomputed, meshes moving far from their original shape should stay
+    // off the mesh shader path.
+    pub fn update_vertices(&self, vk_app: &VkApp, first_vertex: usize, vertices: &[Vertex]) -> RendererResult<()> {
+        let end = first_vertex + vertices.len();
+        if end > self.vertex_count {
+            return Err(RendererError::VertexRangeError { start: first_vertex, end, count: self.vertex_count });
+        }
+        let offset = (first_vertex * size_of::<Vertex>()) as vk::DeviceSize;
+        self.vertex_buffer.upload(vk_app, offset, as_bytes(vertices))?;
+        Ok(())
+    }
+
+    // GETTERS
+
+    pub fn vertex_buffer(&self) -> &GpuBuffer {
+        &self.vertex_buffer
+    }
+
+    pub fn index_buffer(&self) -> &GpuBuffer {
+        &self.index_buffer
+    }
+
+    pub fn index_count(&self) -> u32 {
+        self.index_count
+    }
+
+    pub fn vertex_count(&self) -> usize {
+        self.vertex_count
+    }
+
+    pub fn meshlets(&self) -> Option<&GpuMeshlets> {
+        self.meshlets.as_ref()
+    }
+}
+
+// A mesh whose vertices change at runtime, keeping its CPU copy next to the uploaded one: edits go through
+// vertices_mut, which remembers the ranges touched, and flush records copies of only those into the frame's command
+// buffer. The topology (indices) is fixed.
+pub struct DynamicMesh {
+    device: Device,
+    data: MeshData,
+    gpu_mesh: GpuMesh,
+    // vertex ranges edited since the last flush, unsorted and possibly overlapping
+    dirty: Vec<Range<usize>>,
+    // one per frame in flight, large enough for every vertex
+    staging: Vec<GpuBuffer>,
+    // every stage and access reading the vertex buffer
+    readers: AccessInfo,
+}
+
+impl DynamicMesh {
+    pub fn new(vk_app: &VkApp, data: MeshData, meshlets: bool, name: &str) -> RendererResult<Self> {
+        // flush records its barriers with vkCmdPipelineBarrier2
+        if !vk_app.is_feature_enabled(crate::device_feature!(vulkan13.synchronization2)) {
+            return Err(RendererError::Synchronization2UnavailableError);
+        }
+        let gpu_mesh = GpuMesh::new(vk_app, &data, meshlets, name)?;
+        let size = std::mem::size_of_val(data.vertices.as_slice()) as vk::DeviceSize;
+        let staging = (0..vk_app.vk_prop().renderer_config.frames_in_flight.max(1))
+            .map(|frame| {
+                let name = format!("{name} [vertex staging {frame}]");
+                GpuBuffer::new(vk_app, size, vk::BufferUsageFlags::TRANSFER_SRC, MemoryLocation::HostVisible, &name)
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        // vertex input on the classic path, storage reads from the vertex or mesh shader on the meshlet one
+        let vertex_input = BufferAccess::VertexBuffer.info();
+        let mut readers = AccessInfo::new(
+            vertex_input.stage | vk::PipelineStageFlags2::VERTEX_SHADER,
+            vertex_input.access | vk::AccessFlags2::SHADER_STORAGE_READ,
+            vk::ImageLayout::UNDEFINED,
+        );
+        if gpu_mesh.meshlets.is_some() && is_mesh_shading_enabled(vk_app, false) {
+            readers.stage |= vk::PipelineStageFlags2::MESH_SHADER_EXT;
+        }
+        Ok(DynamicMesh {
+            device: vk_app.device().clone(),
+            data,
+            gpu_mesh,
+            dirty: vec![],
+            staging,
+            readers,
+        })
+    }
+
+    // Vertices of `range`, marked for the next flush ; panics when out of range like slicing
+    pub fn vertices_mut(&mut self, range: Range<usize>) -> &mut [Vertex] {
+        let vertices = &mut self.data.vertices[range.clone()];
+        if !range.is_empty() {
+            self.dirty.push(range);
+        }
+        vertices
+    }
+
+    pub fn set_vertex(&mut self, index: usize, vertex: Vertex) {
+        self.vertices_mut(index..index + 1)[0] = vertex;
+    }
+
+    // Records the upload of the edited ranges into `command_buffer`, outside of any pass and before the draws of frame
+    // `frame_index` (modulo the frames in flight), and returns how many vertices were sent. The ranges go through the
+    // frame's staging buffer, overlapping and adjacent ones merged, in a single copy ; the previous frame that used the
+    // same slot must have completed on the GPU, earlier draws of the mesh on the same queue are waited for.
+    pub fn flush(&mut self, command_buffer: vk::CommandBuffer, frame_index: usize) -> usize {
+        let mut dirty = std::mem::take(&mut self.dirty);
+        if dirty.is_empty() {
+            return 0;
+        }
+        dirty.sort_by_key(|range| range.start);
+        let mut merged: Vec<Range<usize>> = Vec::with_capacity(dirty.len());
+        for range in dirty {
+            match merged.last_mut() {
+                Some(last) if range.start <= last.end => last.end = last.end.max(range.end),
+                _ => merged.push(range),
+            }
+        }
+
+        // packed one after the other in the staging buffer
+        let frame = frame_index % self.staging.len();
+        let staging = &mut self.staging[frame];
+        let vertex_size = size_of::<Vertex>() as vk::DeviceSize;
+        let mut regions = Vec::with_capacity(merged.len());
+        let mut uploaded = 0;
+        for range in merged {
+            let src_offset = uploaded as vk::DeviceSize * vertex_size;
+            staging.write(src_offset, as_bytes(&self.data.vertices[range.clone()]));
+            regions.push(vk::BufferCopy {
+                src_offset,
+                dst_offset: range.start as vk::DeviceSize * vertex_size,
+                size: range.len() as vk::DeviceSize * vertex_size,
+            });
+            uploaded += range.len();
+        }
+
+        let vertex_buffer = self.gpu_mesh.vertex_buffer.buffer();
+        let transfer = BufferAccess::TransferDst.info();
+        let buffer_barrier = |src: AccessInfo, dst: AccessInfo| {
+            vk::BufferMemoryBarrier2::default()
+                .src_stage_mask(src.stage)
+                .src_access_mask(src.access)
+                .dst_stage_mask(dst.stage)
+                .dst_access_mask(dst.access)
+                .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+                .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+                .buffer(vertex_buffer)
+                .offset(0)
+                .size(vk::WHOLE_SIZE)
+        };
+        // the draws of earlier frames still read the old vertices, only their execution needs waiting for
+        let before_copy = [buffer_barrier(AccessInfo { access: vk::AccessFlags2::NONE, ..self.readers }, transfer)];
+        let after_copy = [buffer_barrier(transfer, self.readers)];
+        unsafe {
+            self.device.cmd_pipeline_barrier2(command_buffer, &vk::DependencyInfo::default().buffer_memory_barriers(&before_copy));
+            self.device.cmd_copy_buffer(command_buffer, staging.buffer(), vertex_buffer, &regions);
+            self.device.cmd_pipeline_barrier2(command_buffer, &vk::DependencyInfo::default().buffer_memory_barriers(&after_copy));
+        }
+        uploaded
+    }
+
+    // GETTERS
+
+    pub fn data(&self) -> &MeshData {
+        &self.data
+    }
+
+    // what to draw, the last flush included
+    pub fn gpu_mesh(&self) -> &GpuMesh {
+        &self.gpu_mesh
+    }
+
+    pub fn is_dirty(&self) -> bool {
+        !self.dirty.is_empty()
+    }
+}
@@ -0,0 +1,198 @@
+use ash::{vk, Device};
+use glam::Mat4;
+use tracing::info;
+
+use crate::vulkan_api::VkApp;
+use crate::vulkan_api::descriptors::{DescriptorAllocator, DescriptorBinding, DescriptorBindings};
+use crate::vulkan_api::memory::{as_bytes, GpuBuffer, MemoryLocation};
+use crate::vulkan_api::pipeline::{self, GraphicsPipeline, GraphicsPipelineBuilder};
+use crate::vulkan_api::render_target::RenderTargets;
+use crate::vulkan_api::shader::{Shader, ShaderStage};
+
+use super::camera::{Camera, CameraData};
+use super::mesh::{GpuMesh, MeshData, Vertex};
+use super::RendererResult;
+
+const MESHLET_SHADER: &str = include_str!("shaders/meshlet.wgsl");
+const MESH_VERTEX_SHADER: &str = include_str!("shaders/mesh.vert");
+const MESH_FRAGMENT_SHADER: &str = include_str!("shaders/mesh.frag");
+// task shader workgroup size, one invocation per meshlet
+const MESHLETS_PER_TASK_GROUP: u32 = 32;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum MeshPath {
+    // task shader culls meshlets against the frustum and their normal cone, mesh shader emits the survivors
+    Meshlets,
+    // indexed draws through the vertex stage
+    Classic,
+}
+
+// Attachments the renderer draws into
+#[derive(Copy, Clone, Debug)]
+pub struct TargetFormats {
+    pub color: vk::Format,
+    pub depth: vk::Format,
+    pub samples: vk::SampleCountFlags,
+    // motion vector attachment (location 1) for the renderers that write one, UNDEFINED for none
+    pub velocity: vk::Format,
+}
+
+impl From<&RenderTargets> for TargetFormats {
+    fn from(render_targets: &RenderTargets) -> Self {
+        TargetFormats {
+            color: render_targets.color_format(),
+            depth: render_targets.depth_format().unwrap_or(vk::Format::UNDEFINED),
+            samples: render_targets.samples(),
+            velocity: vk::Format::UNDEFINED,
+        }
+    }
+}
+
+// `DrawConstants` push constant block of the meshlet shaders
+#[repr(C)]
+#[derive(Copy, Clone)]
+struct MeshletDrawConstants {
+    model: Mat4,
+    meshlet_count: u32,
+    scale: f32,
+    _padding: [u32; 2],
+}
+
+struct FrameResources {
+    camera_buffer: GpuBuffer,
+    descriptor_allocator: DescriptorAllocator,
+}
+
+// Draws meshes with VK_EXT_mesh_shader when the device has it enabled (and renderer.mesh_shading is on), with the
+// classic vertex pipeline otherwise ; meshes must be uploaded through the renderer so they carry what its path needs
+pub struct MeshRenderer {
+    device: Device,
+    path: MeshPath,
+    pipeline: GraphicsPipeline,
+    mesh_shader_device: Option<ash::ext::mesh_shader::Device>,
+    // one per frame in flight, the slot of a frame is only reused once the GPU is done with it
+    frames: Vec<FrameResources>,
+    frame_index: usize,
+}
+
+impl MeshRenderer {
+    pub fn new(vk_app: &VkApp, formats: TargetFormats) -> RendererResult<Self> {
+        let use_meshlets = vk_app.vk_prop().renderer_config.mesh_shading && pipeline::is_mesh_shading_enabled(vk_app, true);
+        let path = if use_meshlets { MeshPath::Meshlets } else { MeshPath::Classic };
+
+        let fragment = Shader::from_glsl(MESH_FRAGMENT_SHADER, ShaderStage::Fragment)?;
+        let builder = GraphicsPipelineBuilder::new()
+            .fragment(&fragment)
+            .color_formats(&[formats.color])
+            .depth(formats.depth, true, true, vk::CompareOp::LESS_OR_EQUAL)
+            .samples(formats.samples);
+        let pipeline = match path {
+            MeshPath::Meshlets => {
+                let task = Shader::from_wgsl(MESHLET_SHADER, ShaderStage::Task)?;
+                let mesh = Shader::from_wgsl(MESHLET_SHADER, ShaderStage::Mesh)?;
+                builder.task(&task).mesh(&mesh).build(vk_app, "mesh renderer [meshlets]")?
+            },
+            MeshPath::Classic => {
+                let vertex = Shader::from_glsl(MESH_VERTEX_SHADER, ShaderStage::Vertex)?;
+                builder.vertex(&vertex, Vertex::layout()).build(vk_app, "mesh renderer [classic]")?
+            },
+        };
+        info!(target: "torii::renderer", path = ?path, "Created mesh renderer");
+
+        let frames = (0..vk_app.vk_prop().renderer_config.frames_in_flight.max(1))
+            .map(|frame| {
+                Ok(FrameResources {
+                    camera_buffer: GpuBuffer::new(
+                        vk_app,
+                        std::mem::size_of::<CameraData>() as vk::DeviceSize,
+                        vk::BufferUsageFlags::UNIFORM_BUFFER,
+                        MemoryLocation::HostVisible,
+                        &format!("mesh renderer camera [frame {frame}]"),
+                    )?,
+                    descriptor_allocator: DescriptorAllocator::new(vk_app.device())?,
+                })
+            })
+            .collect::<RendererResult<Vec<_>>>()?;
+
+        Ok(MeshRenderer {
+            device: vk_app.device().clone(),
+            path,
+            pipeline,
+            mesh_shader_device: (path == MeshPath::Meshlets).then(|| ash::ext::mesh_shader::Device::new(vk_app.instance(), vk_app.device())),
+            frames,
+            frame_index: 0,
+        })
+    }
+
+    // Uploads `mesh`, splitting it into meshlets first on the mesh shader path
+    pub fn upload(&self, vk_app: &VkApp, mesh: &MeshData, name: &str) -> RendererResult<GpuMesh> {
+        GpuMesh::new(vk_app, mesh, self.path == MeshPath::Meshlets, name)
+    }
+
+    // Starts recording frame `frame_index` (modulo the frames in flight) ; the previous frame that used the same
+    // slot must have completed on the GPU
+    pub fn begin_frame(&mut self, frame_index: usize, camera: &Camera) -> RendererResult<()> {
+        self.frame_index = frame_index % self.frames.len();
+        let frame = &mut self.frames[self.frame_index];
+        frame.descriptor_allocator.reset()?;
+        frame.camera_buffer.write(0, as_bytes(&[camera.gpu_data()]));
+        Ok(())
+    }
+
+    // Records `mesh` into a pass the caller began with dynamic rendering, viewport and scissor already set
+    pub fn draw(&mut self, command_buffer: vk::CommandBuffer, mesh: &GpuMesh, model: Mat4) -> RendererResult<()> {
+        let frame = &mut self.frames[self.frame_index];
+        let camera = DescriptorBinding::whole_buffer(frame.camera_buffer.buffer());
+        unsafe {
+            self.device.cmd_bind_pipeline(command_buffer, vk::PipelineBindPoint::GRAPHICS, self.pipeline.pipeline());
+        }
+
+        match (self.path, mesh.meshlets.as_ref(), self.mesh_shader_device.as_ref()) {
+            (MeshPath::Meshlets, Some(meshlets), Some(mesh_shader_device)) => {
+                if meshlets.meshlet_count == 0 {
+                    return Ok(());
+                }
+                let constants = MeshletDrawConstants {
+                    model,
+                    meshlet_count: meshlets.meshlet_count,
+                    // bounding spheres assume a uniform scale, the largest axis keeps them conservative
+                    scale: model.x_axis.truncate().length().max(model.y_axis.truncate().length()).max(model.z_axis.truncate().length()),
+                    _padding: [0; 2],
+                };
+                let bindings = DescriptorBindings::new()
+                    .bind_named("camera", camera)
+                    .bind_named("meshlets", DescriptorBinding::whole_buffer(meshlets.meshlet_buffer.buffer()))
+                    .bind_named("vertices", DescriptorBinding::whole_buffer(mesh.vertex_buffer.buffer()))
+                    .bind_named("meshlet_vertices", DescriptorBinding::whole_buffer(meshlets.vertex_index_buffer.buffer()))
+                    .bind_named("meshlet_triangles", DescriptorBinding::whole_buffer(meshlets.triangle_buffer.buffer()))
+                    .with_push_constants(&as_bytes(&[constants])[..self.pipeline.layout().push_constant_size() as usize]);
+                frame.descriptor_allocator.bind(command_buffer, vk::PipelineBindPoint::GRAPHICS, self.pipeline.layout(), &bindings)?;
+                unsafe {
+                    mesh_shader_device.cmd_draw_mesh_tasks(command_buffer, meshlets.meshlet_count.div_ceil(MESHLETS_PER_TASK_GROUP), 1, 1);
+                }
+            },
+            _ => {
+                let bindings = DescriptorBindings::new()
+                    .bind(0, 0, camera)
+                    .with_push_constants(as_bytes(&[model]));
+                frame.descriptor_allocator.bind(command_buffer, vk::PipelineBindPoint::GRAPHICS, self.pipeline.layout(), &bindings)?;
+                unsafe {
+                    self.device.cmd_bind_vertex_buffers(command_buffer, 0, &[mesh.vertex_buffer.buffer()], &[0]);
+                    self.device.cmd_bind_index_buffer(command_buffer, mesh.index_buffer.buffer(), 0, vk::IndexType::UINT32);
+                    self.device.cmd_draw_indexed(command_buffer, mesh.index_count, 1, 0, 0, 0);
+                }
+            },
+        }
+        Ok(())
+    }
+
+    // GETTERS
+
+    pub fn path(&self) -> MeshPath {
+        self.path
+    }
+
+    pub fn pipeline(&self) -> &GraphicsPipeline {
+        &self.pipeline
+    }
+}
@@ -0,0 +1,301 @@
+use glam::Vec3;
+
+use super::mesh::MeshData;
+
+// what shaders/meshlet.wgsl is compiled for
+pub const MAX_MESHLET_VERTICES: usize = 64;
+pub const MAX_MESHLET_TRIANGLES: usize = 124;
+
+// One cluster as the mesh shaders read it (std430: two vec4 then four u32)
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct Meshlet {
+    // xyz center, w radius, in mesh space
+    pub bounds: [f32; 4],
+    // xyz axis of the normal cone, w sine of its half angle ; w = 1 when the normals spread too wide to cull
+    pub cone: [f32; 4],
+    // into MeshletMesh::vertices and MeshletMesh::triangles
+    pub vertex_offset: u32,
+    pub triangle_offset: u32,
+    pub vertex_count: u32,
+    pub triangle_count: u32,
+}
+
+impl Meshlet {
+    pub fn center(&self) -> Vec3 {
+        Vec3::new(self.bounds[0], self.bounds[1], self.bounds[2])
+    }
+
+    pub fn radius(&self) -> f32 {
+        self.bounds[3]
+    }
+
+    pub fn cone_axis(&self) -> Vec3 {
+        Vec3::new(self.cone[0], self.cone[1], self.cone[2])
+    }
+
+    pub fn cone_cutoff(&self) -> f32 {
+        self.cone[3]
+    }
+
+    // true when every triangle of the cluster faces away from `camera_position`, in mesh space
+    pub fn is_backfacing(&self, camera_position: Vec3) -> bool {
+        let to_center = self.center() - camera_position;
+        self.cone_cutoff() < 1.0 && to_center.dot(self.cone_axis()) >= self.cone_cutoff() * to_center.length() + self.radius()
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct MeshletMesh {
+    pub meshlets: Vec<Meshlet>,
+    // indices into the mesh vertices, meshlet after meshlet
+    pub vertices: Vec<u32>,
+    // one triangle per word: three 8 bit indices into the meshlet's slice of `vertices`
+    pub triangles: Vec<u32>,
+}
+
+impl MeshletMesh {
+    // the three mesh vertex indices of a meshlet triangle
+    pub fn triangle(&self, meshlet: &Meshlet, triangle: usize) -> [u32; 3] {
+        let packed = self.triangles[meshlet.triangle_offset as usize + triangle];
+        [0, 8, 16].map(|shift| self.vertices[meshlet.vertex_offset as usize + ((packed >> shift) & 0xff) as usize])
+    }
+}
+
+// Splits an indexed triangle list into clusters of at most `max_vertices` vertices and `max_triangles` triangles.
+// Clusters grow greedily through shared vertices, picking the neighbouring triangle that adds the fewest new
+// vertices (the closest one to the cluster center on ties), so they stay compact and their bounds tight.
+#[derive(Copy, Clone, Debug)]
+pub struct MeshletBuilder {
+    max_vertices: usize,
+    max_triangles: usize,
+}
+
+impl Default for MeshletBuilder {
+    fn default() -> Self {
+        MeshletBuilder {
+            max_vertices: MAX_MESHLET_VERTICES,
+            max_triangles: MAX_MESHLET_TRIANGLES,
+        }
+    }
+}
+
+impl MeshletBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // smaller limits than the defaults suit some GPUs better ; larger ones need matching shaders
+    pub fn with_limits(mut self, max_vertices: usize, max_triangles: usize) -> Self {
+        assert!((3..=256).contains(&max_vertices), "Meshlet vertex limit must be within 3..=256, local indices are 8 bit");
+        assert!(max_triangles >= 1, "Meshlet triangle limit must be at least 1");
+        self.max_vertices = max_vertices;
+        self.max_triangles = max_triangles;
+        self
+    }
+
+    pub fn build(&self, mesh: &MeshData) -> MeshletMesh {
+        let positions: Vec<Vec3> = mesh.positions().collect();
+        let triangle_count = mesh.triangle_count();
+        let triangle = |index: usize| [mesh.indices[index * 3], mesh.indices[index * 3 + 1], mesh.indices[index * 3 + 2]];
+
+        // vertex -> triangles using it, compressed rows
+        let mut adjacency_offsets = vec![0u32; positions.len() + 1];
+        for &index in mesh.indices.iter().take(triangle_count * 3) {
+            adjacency_offsets[index as usize + 1] += 1;
+        }
+        for vertex in 0..positions.len() {
+            adjacency_offsets[vertex + 1] += adjacency_offsets[vertex];
+        }
+        let mut adjacency = vec![0u32; triangle_count * 3];
+        let mut fill = adjacency_offsets.clone();
+        for triangle_index in 0..triangle_count {
+            for vertex in triangle(triangle_index) {
+                adjacency[fill[vertex as usize] as usize] = triangle_index as u32;
+                fill[vertex as usize] += 1;
+            }
+        }
+        let neighbours = |vertex: u32| {
+            &adjacency[adjacency_offsets[vertex as usize] as usize..adjacency_offsets[vertex as usize + 1] as usize]
+        };
+
+        let mut result = MeshletMesh::default();
+        let mut emitted = vec![false; triangle_count];
+        // mesh vertex -> local index in the meshlet being built
+        let mut local_index = vec![u32::MAX; positions.len()];
+        let mut meshlet_vertices: Vec<u32> = vec![];
+        let mut meshlet_triangles: Vec<[u32; 3]> = vec![];
+        let mut next_seed = 0;
+
+        loop {
+            let new_vertices = |triangle_index: usize, local_index: &[u32]| {
+                triangle(triangle_index).iter().filter(|&&vertex| local_index[vertex as usize] == u32::MAX).count()
+            };
+            let fits = |triangle_index: usize, local_index: &[u32], vertex_count: usize, triangle_count: usize| {
+                vertex_count + new_vertices(triangle_index, local_index) <= self.max_vertices && triangle_count < self.max_triangles
+            };
+
+            // best neighbour of the current meshlet, ties going to the one closest to its center, then the next
+            // triangle not emitted yet
+            let meshlet_center = meshlet_vertices.iter().map(|&vertex| positions[vertex as usize]).sum::<Vec3>() / meshlet_vertices.len().max(1) as f32;
+            let distance = |triangle_index: usize| {
+                let centroid = triangle(triangle_index).iter().map(|&vertex| positions[vertex as usize]).sum::<Vec3>() / 3.0;
+                centroid.distance_squared(meshlet_center)
+            };
+            let candidate = meshlet_vertices
+                .iter()
+                .flat_map(|&vertex| neighbours(vertex).iter().map(|&triangle_index| triangle_index as usize))
+                .filter(|&triangle_index| !emitted[triangle_index])
+                .filter(|&triangle_index| fits(triangle_index, &local_index, meshlet_vertices.len(), meshlet_triangles.len()))
+                .min_by(|&a, &b| {
+                    new_vertices(a, &local_index)
+                        .cmp(&new_vertices(b, &local_index))
+                        .then(distance(a).total_cmp(&distance(b)))
+                        .then(a.cmp(&b))
+                });
+            let candidate = match candidate {
+                Some(candidate) => Some(candidate),
+                None => {
+                    while next_seed < triangle_count && emitted[next_seed] {
+                        next_seed += 1;
+                    }
+                    (next_seed < triangle_count).then_some(next_seed)
+                },
+            };
+            let Some(triangle_index) = candidate else { break };
+
+            if !fits(triangle_index, &local_index, meshlet_vertices.len(), meshlet_triangles.len()) {
+                self.flush(&positions, &mut result, &mut meshlet_vertices, &mut meshlet_triangles, &mut local_index);
+            }
+            let local_triangle = triangle(triangle_index).map(|vertex| {
+                if local_index[vertex as usize] == u32::MAX {
+                    local_index[vertex as usize] = meshlet_vertices.len() as u32;
+                    meshlet_vertices.push(vertex);
+                }
+                local_index[vertex as usize]
+            });
+            meshlet_triangles.push(local_triangle);
+            emitted[triangle_index] = true;
+        }
+        self.flush(&positions, &mut result, &mut meshlet_vertices, &mut meshlet_triangles, &mut local_index);
+        result
+    }
+
+    fn flush(&self, positions: &[Vec3], result: &mut MeshletMesh, vertices: &mut Vec<u32>, triangles: &mut Vec<[u32; 3]>, local_index: &mut [u32]) {
+        if triangles.is_empty() {
+            return;
+        }
+        let meshlet_positions: Vec<Vec3> = vertices.iter().map(|&vertex| positions[vertex as usize]).collect();
+        let (center, radius) = bounding_sphere(&meshlet_positions);
+        let normals: Vec<Vec3> = triangles
+            .iter()
+            .map(|&[a, b, c]| {
+                let [a, b, c] = [a, b, c].map(|local| meshlet_positions[local as usize]);
+                (b - a).cross(c - a)
+            })
+            .filter(|normal| normal.length_squared() > f32::EPSILON * f32::EPSILON)
+            .map(Vec3::normalize)
+            .collect();
+
+        result.meshlets.push(Meshlet {
+            bounds: center.extend(radius).to_array(),
+            cone: normal_cone(&normals),
+            vertex_offset: result.vertices.len() as u32,
+            triangle_offset: result.triangles.len() as u32,
+            vertex_count: vertices.len() as u32,
+            triangle_count: triangles.len() as u32,
+        });
+        result.triangles.extend(triangles.iter().map(|&[a, b, c]| a | (b << 8) | (c << 16)));
+        for &vertex in vertices.iter() {
+            local_index[vertex as usize] = u32::MAX;
+        }
+        result.vertices.append(vertices);
+        triangles.clear();
+    }
+}
+
+// Ritter's approximation: grows a sphere around the two most distant points until it holds every point
+fn bounding_sphere(points: &[Vec3]) -> (Vec3, f32) {
+    let Some(&first) = points.first() else { return (Vec3::ZERO, 0.0) };
+    let farthest_from = |from: Vec3| points.iter().copied().max_by(|a, b| a.distance_squared(from).total_cmp(&b.distance_squared(from))).unwrap();
+    let a = farthest_from(first);
+    let b = farthest_from(a);
+    let mut center = (a + b) * 0.5;
+    let mut radius = a.distance(b) * 0.5;
+    for &point in points.iter() {
+        let distance = point.distance(center);
+        if distance > radius {
+            let new_radius = (radius + distance) * 0.5;
+            center += (point - center) * ((new_radius - radius) / distance);
+            radius = new_radius;
+        }
+    }
+    (center, radius)
+}
+
+// Axis averaging the unit triangle normals ; the cone test culls when even the normal closest to the view direction
+// faces away, which needs the sine of the widest angle between a normal and the axis
+fn normal_cone(normals: &[Vec3]) -> [f32; 4] {
+    let disabled = [0.0, 0.0, 0.0, 1.0];
+    let axis = normals.iter().copied().sum::<Vec3>();
+    if axis.length_squared() < f32::EPSILON {
+        return disabled;
+    }
+    let axis = axis.normalize();
+    let min_dot = normals.iter().map(|normal| normal.dot(axis)).fold(1.0f32, f32::min);
+    // spread close to or past 90 degrees, almost never culled, not worth the test
+    if min_dot <= 0.1 {
+        return [axis.x, axis.y, axis.z, 1.0];
+    }
+    let cutoff = (1.0 - min_dot * min_dot).sqrt();
+    [axis.x, axis.y, axis.z, cutoff]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::renderer::primitives;
+
+    fn check(mesh: &MeshData, builder: MeshletBuilder, max_vertices: usize, max_triangles: usize) {
+        let meshlet_mesh = builder.build(mesh);
+        let mut emitted = vec![0; mesh.triangle_count()];
+        for meshlet in meshlet_mesh.meshlets.iter() {
+            assert!(meshlet.vertex_count as usize <= max_vertices);
+            assert!(meshlet.triangle_count as usize <= max_triangles);
+            assert!(meshlet.triangle_count > 0);
+
+            let vertices = &meshlet_mesh.vertices[meshlet.vertex_offset as usize..(meshlet.vertex_offset + meshlet.vertex_count) as usize];
+            for &vertex in vertices {
+                let distance = Vec3::from(mesh.vertices[vertex as usize].position).distance(meshlet.center());
+                assert!(distance <= meshlet.radius() * 1.0001 + 1e-5, "vertex {vertex} is {distance} from the center, radius {}", meshlet.radius());
+            }
+
+            for triangle in 0..meshlet.triangle_count as usize {
+                let corners = meshlet_mesh.triangle(meshlet, triangle);
+                // the mesh triangle with the same corners in the same winding
+                let found = mesh.indices.chunks_exact(3).enumerate().find(|(index, original)| {
+                    emitted[*index] == 0 && (0..3).any(|rotation| (0..3).all(|corner| original[(corner + rotation) % 3] == corners[corner]))
+                });
+                let (index, _) = found.unwrap_or_else(|| panic!("triangle {corners:?} isn't in the mesh or is emitted twice"));
+                emitted[index] += 1;
+            }
+        }
+        assert!(emitted.iter().all(|&count| count == 1), "some triangles were never emitted");
+    }
+
+    #[test]
+    fn default_limits_hold() {
+        check(&primitives::uv_sphere(1.0, 32, 16), MeshletBuilder::new(), MAX_MESHLET_VERTICES, MAX_MESHLET_TRIANGLES);
+    }
+
+    #[test]
+    fn small_limits_hold() {
+        check(&primitives::torus(1.0, 0.25, 24, 12), MeshletBuilder::new().with_limits(16, 10), 16, 10);
+        check(&primitives::cube(2.0, 3), MeshletBuilder::new().with_limits(3, 1), 3, 1);
+    }
+
+    #[test]
+    fn empty_mesh_has_no_meshlets() {
+        assert!(MeshletBuilder::new().build(&MeshData::default()).meshlets.is_empty());
+    }
+}
@@ -0,0 +1,18 @@
+// Rendering built on top of vulkan_api: cameras, meshes and the renderers drawing them. Renderers record into a
+// command buffer inside a pass the caller began (see vulkan_api::render_graph), they never submit on their own.
+
+mod error;
+pub use error::*;
+pub mod camera;
+pub mod hdr;
+pub mod mesh;
+pub mod meshlet;
+mod mesh_renderer;
+pub use mesh_renderer::*;
+pub mod pbr;
+pub mod post;
+pub mod primitives;
+pub mod sky;
+pub mod sprite;
+pub mod text;
+pub mod texture;
@@ -0,0 +1,237 @@
+use ash::vk;
+use tracing::info;
+
+use crate::vulkan_api::VkApp;
+use crate::vulkan_api::commands::one_time_submit;
+use crate::vulkan_api::compute::ComputePipeline;
+use crate::vulkan_api::descriptors::{DescriptorAllocator, DescriptorBinding, DescriptorBindings};
+use crate::vulkan_api::image::{GpuImage, ImageDesc, ImageState};
+use crate::vulkan_api::memory::as_bytes;
+use crate::vulkan_api::sampler::{Sampler, SamplerDesc};
+use crate::vulkan_api::shader::{Shader, ShaderStage};
+
+use crate::renderer::hdr::HdrImage;
+use crate::renderer::texture::Texture;
+use crate::renderer::RendererResult;
+
+use super::with_includes;
+
+const EQUIRECT_TO_CUBE_SHADER: &str = include_str!("../shaders/pbr/equirect_to_cube.comp");
+const IRRADIANCE_SHADER: &str = include_str!("../shaders/pbr/irradiance.comp");
+const PREFILTER_SHADER: &str = include_str!("../shaders/pbr/prefilter.comp");
+const BRDF_LUT_SHADER: &str = include_str!("../shaders/pbr/brdf_lut.comp");
+pub(crate) const ENVIRONMENT_FORMAT: vk::Format = vk::Format::R16G16B16A16_SFLOAT;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct EnvironmentSettings {
+    // face size of the cube the equirectangular map is resampled into
+    pub cube_size: u32,
+    pub irradiance_size: u32,
+    // face size of the first prefiltered mip, the roughest one uses the last of `prefiltered_mips`
+    pub prefiltered_size: u32,
+    pub prefiltered_mips: u32,
+    pub prefilter_samples: u32,
+    pub brdf_lut_size: u32,
+}
+
+impl Default for EnvironmentSettings {
+    fn default() -> Self {
+        EnvironmentSettings {
+            cube_size: 512,
+            irradiance_size: 32,
+            prefiltered_size: 128,
+            prefiltered_mips: 5,
+            prefilter_samples: 512,
+            brdf_lut_size: 256,
+        }
+    }
+}
+
+// Image based lighting of a scene: the environment cube, its diffuse irradiance, its specular radiance prefiltered
+// per roughness, and the split sum BRDF lookup table ; all generated on the GPU when loading
+pub struct Environment {
+    cube: GpuImage,
+    irradiance: GpuImage,
+    prefiltered: GpuImage,
+    brdf_lut: GpuImage,
+    // scales the ambient light
+    pub intensity: f32,
+}
+
+impl Environment {
+    pub fn from_hdr(vk_app: &VkApp, image: &HdrImage, settings: EnvironmentSettings, name: &str) -> RendererResult<Self> {
+        let equirect = Texture::from_rgba_f32(vk_app, image.width, image.height, &image.pixels, &format!("{name} [equirect]"))?;
+        Self::from_equirect(vk_app, &equirect, settings, name)
+    }
+
+    // `equirect` is a linear equirectangular (latitude-longitude) map, +Y up
+    pub fn from_equirect(vk_app: &VkApp, equirect: &Texture, settings: EnvironmentSettings, name: &str) -> RendererResult<Self> {
+        let device = vk_app.device();
+        let storage = vk::ImageUsageFlags::STORAGE | vk::ImageUsageFlags::SAMPLED;
+        let cube = equirect_to_cube(vk_app, equirect, settings.cube_size, &format!("{name} [cube]"))?;
+        let irradiance = GpuImage::new(vk_app, ImageDesc::new_cube(ENVIRONMENT_FORMAT, settings.irradiance_size), storage, &format!("{name} [irradiance]"))?;
+        let prefiltered_desc = ImageDesc::new_cube(ENVIRONMENT_FORMAT, settings.prefiltered_size);
+        let prefiltered_mips = settings.prefiltered_mips.clamp(1, ImageDesc::full_mip_chain(prefiltered_desc.extent));
+        let prefiltered = GpuImage::new(vk_app, prefiltered_desc.with_mip_levels(prefiltered_mips), storage, &format!("{name} [prefiltered]"))?;
+        let lut_extent = vk::Extent2D { width: settings.brdf_lut_size, height: settings.brdf_lut_size };
+        let brdf_lut = GpuImage::new(vk_app, ImageDesc::new(ENVIRONMENT_FORMAT, lut_extent), storage, &format!("{name} [brdf lut]"))?;
+
+        let compile = |source: &str, pipeline_name: &str| -> RendererResult<ComputePipeline> {
+            let shader = Shader::from_glsl(&with_includes(source), ShaderStage::Compute)?;
+            Ok(ComputePipeline::new(vk_app, &shader, pipeline_name)?)
+        };
+        let irradiance_pipeline = compile(IRRADIANCE_SHADER, "irradiance convolution")?;
+        let prefilter_pipeline = compile(PREFILTER_SHADER, "specular prefilter")?;
+        let brdf_lut_pipeline = compile(BRDF_LUT_SHADER, "brdf lut")?;
+        let sampler = Sampler::new(vk_app, SamplerDesc::linear_clamp(), "environment generation sampler")?;
+
+        // storage writes go through 2D array views, one mip each
+        let irradiance_faces = irradiance.create_view(vk_app, vk::ImageViewType::TYPE_2D_ARRAY, 0, 1, &format!("{name} [irradiance faces]"))?;
+        let prefiltered_faces = (0..prefiltered_mips)
+            .map(|mip| prefiltered.create_view(vk_app, vk::ImageViewType::TYPE_2D_ARRAY, mip, 1, &format!("{name} [prefiltered faces mip {mip}]")))
+            .collect::<Result<Vec<_>, _>>()?;
+        let mut descriptor_allocator = DescriptorAllocator::new(device)?;
+
+        let mut recorded: RendererResult<()> = Ok(());
+        one_time_submit(vk_app, |command_buffer| {
+            let mut dispatch = |pipeline: &ComputePipeline, bindings: DescriptorBindings, extent: vk::Extent2D, layers: u32| -> RendererResult<()> {
+                descriptor_allocator.bind(command_buffer, vk::PipelineBindPoint::COMPUTE, pipeline.layout(), &bindings)?;
+                let [x, y, _] = pipeline.group_count([extent.width, extent.height, 1]);
+                unsafe {
+                    device.cmd_bind_pipeline(command_buffer, vk::PipelineBindPoint::COMPUTE, pipeline.pipeline());
+                    device.cmd_dispatch(command_buffer, x, y, layers);
+                }
+                Ok(())
+            };
+            recorded = (|| {
+                irradiance.cmd_transition(device, command_buffer, ImageState::UNDEFINED, ImageState::STORAGE_WRITE_COMPUTE);
+                prefiltered.cmd_transition(device, command_buffer, ImageState::UNDEFINED, ImageState::STORAGE_WRITE_COMPUTE);
+                brdf_lut.cmd_transition(device, command_buffer, ImageState::UNDEFINED, ImageState::STORAGE_WRITE_COMPUTE);
+
+                let bindings = DescriptorBindings::new()
+                    .bind(0, 0, DescriptorBinding::sampled_image(cube.view()))
+                    .bind(0, 1, DescriptorBinding::Sampler(sampler.sampler()))
+                    .bind(0, 2, DescriptorBinding::storage_image(irradiance_faces.view()));
+                dispatch(&irradiance_pipeline, bindings, irradiance.extent(), 6)?;
+
+                for (mip, faces) in prefiltered_faces.iter().enumerate() {
+                    let roughness = match prefiltered_mips {
+                        1 => 0.0,
+                        _ => mip as f32 / (prefiltered_mips - 1) as f32,
+                    };
+                    let constants = (roughness, cube.extent().width as f32, settings.prefilter_samples.max(1), 0u32);
+                    let push_constants = [constants.0.to_bits(), constants.1.to_bits(), constants.2, constants.3];
+                    let bindings = DescriptorBindings::new()
+                        .bind(0, 0, DescriptorBinding::sampled_image(cube.view()))
+                        .bind(0, 1, DescriptorBinding::Sampler(sampler.sampler()))
+                        .bind(0, 2, DescriptorBinding::storage_image(faces.view()))
+                        .with_push_constants(as_bytes(&push_constants));
+                    dispatch(&prefilter_pipeline, bindings, prefiltered.desc().mip_extent(mip as u32), 6)?;
+                }
+
+                let bindings = DescriptorBindings::new().bind(0, 0, DescriptorBinding::storage_image(brdf_lut.view()));
+                dispatch(&brdf_lut_pipeline, bindings, brdf_lut.extent(), 1)?;
+
+                irradiance.cmd_transition(device, command_buffer, ImageState::STORAGE_WRITE_COMPUTE, ImageState::SHADER_READ);
+                prefiltered.cmd_transition(device, command_buffer, ImageState::STORAGE_WRITE_COMPUTE, ImageState::SHADER_READ);
+                brdf_lut.cmd_transition(device, command_buffer, ImageState::STORAGE_WRITE_COMPUTE, ImageState::SHADER_READ);
+                Ok(())
+            })();
+        })?;
+        recorded?;
+
+        info!(
+            target: "torii::renderer",
+            name,
+            cube_size = settings.cube_size,
+            prefiltered_mips,
+            "Generated environment lighting"
+        );
+        Ok(Environment {
+            cube,
+            irradiance,
+            prefiltered,
+            brdf_lut,
+            intensity: 1.0,
+        })
+    }
+
+    // Black 1x1 images, bound while no environment is set (the shaders fall back to the ambient color)
+    pub(crate) fn fallback(vk_app: &VkApp) -> RendererResult<Self> {
+        let black_cube = |name: &str| GpuImage::with_data(vk_app, ImageDesc::new_cube(ENVIRONMENT_FORMAT, 1), vk::ImageUsageFlags::SAMPLED, &[0; 6 * 8], false, name);
+        let one_texel = vk::Extent2D { width: 1, height: 1 };
+        Ok(Environment {
+            cube: black_cube("fallback environment [cube]")?,
+            irradiance: black_cube("fallback environment [irradiance]")?,
+            prefiltered: black_cube("fallback environment [prefiltered]")?,
+            brdf_lut: GpuImage::with_data(vk_app, ImageDesc::new(ENVIRONMENT_FORMAT, one_texel), vk::ImageUsageFlags::SAMPLED, &[0; 8], false, "fallback environment [brdf lut]")?,
+            intensity: 0.0,
+        })
+    }
+
+    pub fn with_intensity(mut self, intensity: f32) -> Self {
+        self.intensity = intensity;
+        self
+    }
+
+    // GETTERS
+
+    pub fn cube(&self) -> &GpuImage {
+        &self.cube
+    }
+
+    pub fn irradiance(&self) -> &GpuImage {
+        &self.irradiance
+    }
+
+    pub fn prefiltered(&self) -> &GpuImage {
+        &self.prefiltered
+    }
+
+    pub fn brdf_lut(&self) -> &GpuImage {
+        &self.brdf_lut
+    }
+
+    pub fn prefiltered_mip_count(&self) -> u32 {
+        self.prefiltered.desc().mip_levels
+    }
+}
+
+// Resamples the linear equirectangular map `equirect` (+Y up) into a cube of `size` texels faces with a full mip
+// chain, left readable by the shaders
+pub(crate) fn equirect_to_cube(vk_app: &VkApp, equirect: &Texture, size: u32, name: &str) -> RendererResult<GpuImage> {
+    let device = vk_app.device();
+    let cube = GpuImage::new(
+        vk_app,
+        ImageDesc::new_cube(ENVIRONMENT_FORMAT, size).with_full_mip_chain(),
+        vk::ImageUsageFlags::STORAGE | vk::ImageUsageFlags::SAMPLED | vk::ImageUsageFlags::TRANSFER_SRC | vk::ImageUsageFlags::TRANSFER_DST,
+        name,
+    )?;
+    let shader = Shader::from_glsl(&with_includes(EQUIRECT_TO_CUBE_SHADER), ShaderStage::Compute)?;
+    let pipeline = ComputePipeline::new(vk_app, &shader, "equirect to cube")?;
+    let sampler = Sampler::new(vk_app, SamplerDesc::linear_clamp(), "equirect sampler")?;
+    let cube_faces = cube.create_view(vk_app, vk::ImageViewType::TYPE_2D_ARRAY, 0, 1, &format!("{name} [faces]"))?;
+    let mut descriptor_allocator = DescriptorAllocator::new(device)?;
+
+    let bindings = DescriptorBindings::new()
+        .bind(0, 0, DescriptorBinding::sampled_image(equirect.view()))
+        .bind(0, 1, DescriptorBinding::Sampler(sampler.sampler()))
+        .bind(0, 2, DescriptorBinding::storage_image(cube_faces.view()));
+    let mut recorded: RendererResult<()> = Ok(());
+    one_time_submit(vk_app, |command_buffer| {
+        cube.cmd_transition_mips(device, command_buffer, 0, 1, ImageState::UNDEFINED, ImageState::STORAGE_WRITE_COMPUTE);
+        recorded = descriptor_allocator
+            .bind(command_buffer, vk::PipelineBindPoint::COMPUTE, pipeline.layout(), &bindings)
+            .map_err(Into::into);
+        if recorded.is_ok() {
+            let [x, y, _] = pipeline.group_count([size, size, 1]);
+            unsafe {
+                device.cmd_bind_pipeline(command_buffer, vk::PipelineBindPoint::COMPUTE, pipeline.pipeline());
+                device.cmd_dispatch(command_buffer, x, y, 6);
+            }
+        }
+        cube.cmd_generate_mips(device, command_buffer, ImageState::STORAGE_WRITE_COMPUTE);
+    })?;
+    recorded?;
+    Ok(cube)
+}
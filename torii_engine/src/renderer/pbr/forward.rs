@@ -0,0 +1,333 @@
+use ash::{vk, Device};
+use glam::{Mat4, Vec3};
+use tracing::{info, warn};
+
+use crate::vulkan_api::VkApp;
+use crate::vulkan_api::compute::ComputePipeline;
+use crate::vulkan_api::descriptors::{DescriptorAllocator, DescriptorBinding, DescriptorBindings};
+use crate::vulkan_api::memory::{as_bytes, GpuBuffer, MemoryLocation};
+use crate::vulkan_api::pipeline::{BlendMode, GraphicsPipeline, GraphicsPipelineBuilder};
+use crate::vulkan_api::render_graph::RenderGraph;
+use crate::vulkan_api::sampler::{Sampler, SamplerDesc};
+use crate::vulkan_api::shader::{Shader, ShaderStage};
+
+use crate::renderer::camera::{Camera, CameraData};
+use crate::renderer::mesh::{GpuMesh, Vertex};
+use crate::renderer::{RendererResult, TargetFormats};
+
+use super::environment::Environment;
+use super::light::{GpuLight, Light};
+use super::material::{AlphaMode, DefaultTextures, GpuMaterial, Material};
+use super::shadow::{ShadowData, ShadowImages, ShadowMaps};
+use super::with_includes;
+
+const VERTEX_SHADER: &str = include_str!("../shaders/pbr/pbr.vert");
+const FRAGMENT_SHADER: &str = include_str!("../shaders/pbr/pbr.frag");
+const LIGHT_CULL_SHADER: &str = include_str!("../shaders/pbr/light_cull.comp");
+
+// screen tiles along x and y, exponential depth slices along z
+pub const CLUSTER_GRID: [u32; 3] = [16, 9, 24];
+pub const MAX_LIGHTS_PER_CLUSTER: u32 = 128;
+// directional and local lights together, the rest of a frame's lights is dropped
+pub const MAX_LIGHTS: usize = 1024;
+const CLUSTER_COUNT: u32 = CLUSTER_GRID[0] * CLUSTER_GRID[1] * CLUSTER_GRID[2];
+
+// `Lighting` struct of lighting.glsl (std140)
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+struct LightingData {
+    inverse_projection: Mat4,
+    view: Mat4,
+    cluster_grid: [u32; 4],
+    screen: [f32; 4],
+    slicing: [f32; 4],
+    counts: [u32; 4],
+    ambient: [f32; 4],
+    exposure: [f32; 4],
+}
+
+// `Draw` push constant block of pbr.vert
+#[repr(C)]
+#[derive(Copy, Clone)]
+struct DrawConstants {
+    model: Mat4,
+    previous_model: Mat4,
+}
+
+struct FrameResources {
+    camera_buffer: GpuBuffer,
+    lighting_buffer: GpuBuffer,
+    light_buffer: GpuBuffer,
+    // light count of each cluster, then MAX_LIGHTS_PER_CLUSTER light index slots per cluster ; written by the cull pass
+    light_grid_buffer: GpuBuffer,
+    light_index_buffer: GpuBuffer,
+    shadow_buffer: GpuBuffer,
+    descriptor_allocator: DescriptorAllocator,
+}
+
+// Forward+ renderer for glTF metallic-roughness materials. Each frame: begin_frame uploads the camera and lights,
+// add_shadow_passes adds the render graph passes filling the shadow maps of the lights casting shadows, cull_lights
+// (recorded outside any pass) assigns the local lights to the clusters, then draw shades meshes in a pass the caller
+// began. Outputs linear radiance scaled by the exposure, and motion vectors when the target formats have a velocity
+// attachment.
+pub struct ForwardRenderer {
+    device: Device,
+    // indexed by `pipeline_index`
+    pipelines: Vec<GraphicsPipeline>,
+    cull_pipeline: ComputePipeline,
+    shadow_maps: ShadowMaps,
+    default_textures: DefaultTextures,
+    material_sampler: Sampler,
+    environment_sampler: Sampler,
+    fallback_environment: Environment,
+    environment: Option<Environment>,
+    // ambient light without environment, linear RGB
+    pub ambient_color: Vec3,
+    pub exposure: f32,
+    frames: Vec<FrameResources>,
+    frame_index: usize,
+    light_count: u32,
+    // the last frame's camera, for the motion vectors
+    previous_view_projection: Option<Mat4>,
+}
+
+impl ForwardRenderer {
+    pub fn new(vk_app: &VkApp, formats: TargetFormats) -> RendererResult<Self> {
+        let vertex = Shader::from_glsl(VERTEX_SHADER, ShaderStage::Vertex)?;
+        let with_velocity = formats.velocity != vk::Format::UNDEFINED;
+        let defines: &[(&str, &str)] = if with_velocity { &[("VELOCITY", "1")] } else { &[] };
+        let fragment = Shader::from_glsl_with_defines(&with_includes(FRAGMENT_SHADER), ShaderStage::Fragment, defines)?;
+        let color_formats = if with_velocity { vec![formats.color, formats.velocity] } else { vec![formats.color] };
+        // opaque / blended, then single / double sided
+        let pipelines = [(false, false), (false, true), (true, false), (true, true)]
+            .into_iter()
+            .map(|(blend, double_sided)| {
+                let cull_mode = if double_sided { vk::CullModeFlags::NONE } else { vk::CullModeFlags::BACK };
+                GraphicsPipelineBuilder::new()
+                    .vertex(&vertex, Vertex::layout())
+                    .fragment(&fragment)
+                    .color_formats(&color_formats)
+                    .depth(formats.depth, true, !blend, vk::CompareOp::LESS_OR_EQUAL)
+                    .samples(formats.samples)
+                    .cull_mode(cull_mode, vk::FrontFace::COUNTER_CLOCKWISE)
+                    .blend(if blend { BlendMode::Alpha } else { BlendMode::Opaque })
+                    .build(vk_app, &format!("forward renderer [blend {blend}, double sided {double_sided}]"))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let cull_shader = Shader::from_glsl(&with_includes(LIGHT_CULL_SHADER), ShaderStage::Compute)?;
+        let cull_pipeline = ComputePipeline::new(vk_app, &cull_shader, "light culling")?;
+
+        let frames = (0..vk_app.vk_prop().renderer_config.frames_in_flight.max(1))
+            .map(|frame| {
+                let buffer = |size: usize, usage: vk::BufferUsageFlags, location: MemoryLocation, name: &str| {
+                    GpuBuffer::new(vk_app, size as vk::DeviceSize, usage, location, &format!("forward renderer {name} [frame {frame}]"))
+                };
+                let storage = vk::BufferUsageFlags::STORAGE_BUFFER;
+                Ok(FrameResources {
+                    camera_buffer: buffer(size_of::<CameraData>(), vk::BufferUsageFlags::UNIFORM_BUFFER, MemoryLocation::HostVisible, "camera")?,
+                    lighting_buffer: buffer(size_of::<LightingData>(), vk::BufferUsageFlags::UNIFORM_BUFFER, MemoryLocation::HostVisible, "lighting")?,
+                    light_buffer: buffer(MAX_LIGHTS * size_of::<GpuLight>(), storage, MemoryLocation::HostVisible, "lights")?,
+                    light_grid_buffer: buffer(CLUSTER_COUNT as usize * 4, storage, MemoryLocation::DeviceLocal, "light grid")?,
+                    light_index_buffer: buffer((CLUSTER_COUNT * MAX_LIGHTS_PER_CLUSTER) as usize * 4, storage, MemoryLocation::DeviceLocal, "light indices")?,
+                    shadow_buffer: buffer(size_of::<ShadowData>(), vk::BufferUsageFlags::UNIFORM_BUFFER, MemoryLocation::HostVisible, "shadows")?,
+                    descriptor_allocator: DescriptorAllocator::new(vk_app.device())?,
+                })
+            })
+            .collect::<RendererResult<Vec<_>>>()?;
+        info!(target: "torii::renderer", clusters = ?CLUSTER_GRID, max_lights = MAX_LIGHTS, "Created forward renderer");
+
+        Ok(ForwardRenderer {
+            device: vk_app.device().clone(),
+            pipelines,
+            cull_pipeline,
+            shadow_maps: ShadowMaps::new(vk_app, vk_app.vk_prop().renderer_config.shadows.clone())?,
+            default_textures: DefaultTextures::new(vk_app)?,
+            material_sampler: Sampler::new(vk_app, SamplerDesc::linear_repeat(), "material sampler")?,
+            environment_sampler: Sampler::new(vk_app, SamplerDesc::linear_clamp(), "environment sampler")?,
+            fallback_environment: Environment::fallback(vk_app)?,
+            environment: None,
+            ambient_color: Vec3::splat(0.03),
+            exposure: 1.0,
+            frames,
+            frame_index: 0,
+            light_count: 0,
+            previous_view_projection: None,
+        })
+    }
+
+    // Uploads `material`, its missing textures replaced by the renderer's defaults
+    pub fn create_material(&self, vk_app: &VkApp, material: &Material, name: &str) -> RendererResult<GpuMaterial> {
+        GpuMaterial::new(vk_app, material, &self.default_textures, name)
+    }
+
+    // Replaces the image based lighting, returning the previous environment ; it may still be in use by frames in
+    // flight, drop it once they completed
+    pub fn set_environment(&mut self, environment: Option<Environment>) -> Option<Environment> {
+        std::mem::replace(&mut self.environment, environment)
+    }
+
+    // Starts recording frame `frame_index` (modulo the frames in flight) into a target of `extent` ; the previous frame
+    // that used the same slot must have completed on the GPU
+    pub fn begin_frame(&mut self, frame_index: usize, camera: &Camera, lights: &[Light], extent: vk::Extent2D) -> RendererResult<()> {
+        self.frame_index = frame_index % self.frames.len();
+        let frame = &mut self.frames[self.frame_index];
+        frame.descriptor_allocator.reset()?;
+        let mut camera_data = camera.gpu_data();
+        if let Some(previous_view_projection) = self.previous_view_projection {
+            camera_data.previous_view_projection = previous_view_projection;
+        }
+        self.previous_view_projection = Some(camera.view_projection());
+        frame.camera_buffer.write(0, as_bytes(&[camera_data]));
+
+        // directional lights first, they light every fragment and skip the clusters
+        let mut ordered_lights: Vec<&Light> = lights.iter().filter(|light| light.is_directional()).collect();
+        let directional_count = ordered_lights.len().min(MAX_LIGHTS);
+        ordered_lights.extend(lights.iter().filter(|light| !light.is_directional()));
+        if ordered_lights.len() > MAX_LIGHTS {
+            warn!(target: "torii::renderer", lights = ordered_lights.len(), max_lights = MAX_LIGHTS, "Too many lights, dropping the extra ones");
+            ordered_lights.truncate(MAX_LIGHTS);
+        }
+        let mut gpu_lights: Vec<GpuLight> = ordered_lights.iter().map(|light| light.gpu_data()).collect();
+        let shadows = self.shadow_maps.prepare(camera, &ordered_lights, &mut gpu_lights);
+        frame.shadow_buffer.write(0, as_bytes(&[shadows]));
+        frame.light_buffer.write(0, as_bytes(&gpu_lights));
+        self.light_count = gpu_lights.len() as u32;
+
+        let near = camera.near.max(1e-3);
+        let far = camera.far.max(near * 1.001);
+        let depth_ratio = (far / near).ln();
+        let slices = CLUSTER_GRID[2] as f32;
+        let (width, height) = (extent.width.max(1), extent.height.max(1));
+        let environment = self.environment.as_ref();
+        let lighting = LightingData {
+            inverse_projection: camera.projection.inverse(),
+            view: camera.view,
+            cluster_grid: [CLUSTER_GRID[0], CLUSTER_GRID[1], CLUSTER_GRID[2], MAX_LIGHTS_PER_CLUSTER],
+            screen: [width as f32, height as f32, near, far],
+            slicing: [
+                slices / depth_ratio,
+                slices * near.ln() / depth_ratio,
+                width.div_ceil(CLUSTER_GRID[0]) as f32,
+                height.div_ceil(CLUSTER_GRID[1]) as f32,
+            ],
+            counts: [
+                directional_count as u32,
+                self.light_count - directional_count as u32,
+                environment.map_or(1, Environment::prefiltered_mip_count),
+                environment.is_some() as u32,
+            ],
+            ambient: self.ambient_color.extend(environment.map_or(0.0, |environment| environment.intensity)).to_array(),
+            exposure: [self.exposure, 0.0, 0.0, 0.0],
+        };
+        frame.lighting_buffer.write(0, as_bytes(&[lighting]));
+        Ok(())
+    }
+
+    // Adds the shadow passes of the lights casting shadows this frame to `graph`, rendering `casters` into their maps,
+    // after begin_frame ; no pass is added when no light casts shadows. The pass drawing with this renderer declares
+    // its reads of the maps with ShadowImages::sampled_by.
+    pub fn add_shadow_passes<'a>(&mut self, graph: &mut RenderGraph<'a>, casters: &'a [(&'a GpuMesh, Mat4)]) -> RendererResult<ShadowImages> {
+        let frame = &mut self.frames[self.frame_index];
+        self.shadow_maps.add_passes(graph, &mut frame.descriptor_allocator, frame.shadow_buffer.buffer(), casters)
+    }
+
+    // Records the light culling dispatch, outside of any pass, between begin_frame and the draws
+    pub fn cull_lights(&mut self, command_buffer: vk::CommandBuffer) -> RendererResult<()> {
+        let frame = &mut self.frames[self.frame_index];
+        let bindings = DescriptorBindings::new()
+            .bind(0, 0, DescriptorBinding::whole_buffer(frame.lighting_buffer.buffer()))
+            .bind(0, 1, DescriptorBinding::whole_buffer(frame.light_buffer.buffer()))
+            .bind(0, 2, DescriptorBinding::whole_buffer(frame.light_grid_buffer.buffer()))
+            .bind(0, 3, DescriptorBinding::whole_buffer(frame.light_index_buffer.buffer()));
+        frame.descriptor_allocator.bind(command_buffer, vk::PipelineBindPoint::COMPUTE, self.cull_pipeline.layout(), &bindings)?;
+        let [x, y, z] = self.cull_pipeline.group_count([CLUSTER_COUNT, 1, 1]);
+        // the cluster lists are read by the fragment shaders of the following pass
+        let barrier = vk::MemoryBarrier::default()
+            .src_access_mask(vk::AccessFlags::SHADER_WRITE)
+            .dst_access_mask(vk::AccessFlags::SHADER_READ);
+        unsafe {
+            self.device.cmd_bind_pipeline(command_buffer, vk::PipelineBindPoint::COMPUTE, self.cull_pipeline.pipeline());
+            self.device.cmd_dispatch(command_buffer, x, y, z);
+            self.device.cmd_pipeline_barrier(
+                command_buffer,
+                vk::PipelineStageFlags::COMPUTE_SHADER,
+                vk::PipelineStageFlags::FRAGMENT_SHADER,
+                vk::DependencyFlags::empty(),
+                std::slice::from_ref(&barrier),
+                &[],
+                &[],
+            );
+        }
+        Ok(())
+    }
+
+    // Records `mesh` shaded with `material` into a pass the caller began with dynamic rendering, viewport and scissor
+    // already set ; blended materials should be drawn after the opaque ones, back to front
+    pub fn draw(&mut self, command_buffer: vk::CommandBuffer, mesh: &GpuMesh, material: &GpuMaterial, model: Mat4) -> RendererResult<()> {
+        self.draw_moving(command_buffer, mesh, material, model, model)
+    }
+
+    // Same as draw for a mesh that moved since the last frame, `previous_model` being its last model matrix, so the
+    // motion vectors follow it
+    pub fn draw_moving(
+        &mut self,
+        command_buffer: vk::CommandBuffer,
+        mesh: &GpuMesh,
+        material: &GpuMaterial,
+        model: Mat4,
+        previous_model: Mat4,
+    ) -> RendererResult<()> {
+        let frame = &mut self.frames[self.frame_index];
+        let environment = self.environment.as_ref().unwrap_or(&self.fallback_environment);
+        let pipeline = &self.pipelines[Self::pipeline_index(material)];
+        let constants = DrawConstants { model, previous_model };
+        let [base_color, metallic_roughness, normal, occlusion, emissive] = &material.textures;
+        let bindings = DescriptorBindings::new()
+            .bind(0, 0, DescriptorBinding::whole_buffer(frame.camera_buffer.buffer()))
+            .bind(0, 1, DescriptorBinding::whole_buffer(frame.lighting_buffer.buffer()))
+            .bind(0, 2, DescriptorBinding::whole_buffer(frame.light_buffer.buffer()))
+            .bind(0, 3, DescriptorBinding::whole_buffer(frame.light_grid_buffer.buffer()))
+            .bind(0, 4, DescriptorBinding::whole_buffer(frame.light_index_buffer.buffer()))
+            .bind(0, 5, DescriptorBinding::sampled_image(environment.irradiance().view()))
+            .bind(0, 6, DescriptorBinding::sampled_image(environment.prefiltered().view()))
+            .bind(0, 7, DescriptorBinding::sampled_image(environment.brdf_lut().view()))
+            .bind(0, 8, DescriptorBinding::Sampler(self.environment_sampler.sampler()))
+            .bind(0, 9, DescriptorBinding::whole_buffer(frame.shadow_buffer.buffer()))
+            .bind(0, 10, DescriptorBinding::sampled_image(self.shadow_maps.directional_maps().view()))
+            .bind(0, 11, DescriptorBinding::sampled_image(self.shadow_maps.point_maps().view()))
+            .bind(0, 12, DescriptorBinding::Sampler(self.shadow_maps.sampler().sampler()))
+            .bind(1, 0, DescriptorBinding::whole_buffer(material.uniform_buffer.buffer()))
+            .bind(1, 1, DescriptorBinding::sampled_image(base_color.view()))
+            .bind(1, 2, DescriptorBinding::sampled_image(metallic_roughness.view()))
+            .bind(1, 3, DescriptorBinding::sampled_image(normal.view()))
+            .bind(1, 4, DescriptorBinding::sampled_image(occlusion.view()))
+            .bind(1, 5, DescriptorBinding::sampled_image(emissive.view()))
+            .bind(1, 6, DescriptorBinding::Sampler(self.material_sampler.sampler()))
+            .with_push_constants(as_bytes(&[constants]));
+        unsafe {
+            self.device.cmd_bind_pipeline(command_buffer, vk::PipelineBindPoint::GRAPHICS, pipeline.pipeline());
+        }
+        frame.descriptor_allocator.bind(command_buffer, vk::PipelineBindPoint::GRAPHICS, pipeline.layout(), &bindings)?;
+        unsafe {
+            self.device.cmd_bind_vertex_buffers(command_buffer, 0, &[mesh.vertex_buffer.buffer()], &[0]);
+            self.device.cmd_bind_index_buffer(command_buffer, mesh.index_buffer.buffer(), 0, vk::IndexType::UINT32);
+            self.device.cmd_draw_indexed(command_buffer, mesh.index_count, 1, 0, 0, 0);
+        }
+        Ok(())
+    }
+
+    fn pipeline_index(material: &GpuMaterial) -> usize {
+        (material.alpha_mode() == AlphaMode::Blend) as usize * 2 + material.double_sided() as usize
+    }
+
+    // GETTERS
+
+    pub fn environment(&self) -> Option<&Environment> {
+        self.environment.as_ref()
+    }
+
+    // lights uploaded by the last begin_frame
+    pub fn light_count(&self) -> u32 {
+        self.light_count
+    }
+}
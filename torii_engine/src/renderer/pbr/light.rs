@@ -0,0 +1,114 @@
+use glam::Vec3;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum LightKind {
+    // infinitely far away, `direction` is where the light travels ; intensity in lux
+    Directional { direction: Vec3 },
+    // intensity in candela, falls off with the inverse square of the distance and reaches zero at `range`
+    Point { position: Vec3, range: f32 },
+    // a point light restricted to a cone ; full intensity inside `inner_cone_angle`, none past `outer_cone_angle`
+    // (half angles in radians)
+    Spot { position: Vec3, direction: Vec3, range: f32, inner_cone_angle: f32, outer_cone_angle: f32 },
+}
+
+// Shadow casting settings of one light ; biases push the compared depth towards the light to avoid acne, too much
+// of them detaches shadows from their casters
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct LightShadow {
+    // world units
+    pub depth_bias: f32,
+    // world units, scaled by the tangent of the angle between the surface normal and the light
+    pub slope_bias: f32,
+    // shadow map texels the lookup moves along the surface normal
+    pub normal_bias: f32,
+}
+
+impl Default for LightShadow {
+    fn default() -> Self {
+        LightShadow {
+            depth_bias: 0.005,
+            slope_bias: 0.01,
+            normal_bias: 1.0,
+        }
+    }
+}
+
+// glTF KHR_lights_punctual style light
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Light {
+    pub kind: LightKind,
+    // linear RGB
+    pub color: Vec3,
+    pub intensity: f32,
+    // directional lights cast cascaded shadows, point lights cube shadows ; spot lights don't cast any yet
+    pub shadow: Option<LightShadow>,
+}
+
+impl Light {
+    pub fn directional(direction: Vec3, color: Vec3, intensity: f32) -> Self {
+        Light {
+            kind: LightKind::Directional { direction },
+            color,
+            intensity,
+            shadow: None,
+        }
+    }
+
+    pub fn point(position: Vec3, range: f32, color: Vec3, intensity: f32) -> Self {
+        Light {
+            kind: LightKind::Point { position, range },
+            color,
+            intensity,
+            shadow: None,
+        }
+    }
+
+    pub fn spot(position: Vec3, direction: Vec3, range: f32, inner_cone_angle: f32, outer_cone_angle: f32, color: Vec3, intensity: f32) -> Self {
+        Light {
+            kind: LightKind::Spot { position, direction, range, inner_cone_angle, outer_cone_angle },
+            color,
+            intensity,
+            shadow: None,
+        }
+    }
+
+    pub fn with_shadow(mut self, shadow: LightShadow) -> Self {
+        self.shadow = Some(shadow);
+        self
+    }
+
+    pub fn is_directional(&self) -> bool {
+        matches!(self.kind, LightKind::Directional { .. })
+    }
+
+    pub(crate) fn gpu_data(&self) -> GpuLight {
+        let (position, range, direction, kind, spot) = match self.kind {
+            LightKind::Directional { direction } => (Vec3::ZERO, 0.0, direction.normalize_or_zero(), 0, [0.0; 2]),
+            LightKind::Point { position, range } => (position, range, Vec3::ZERO, 1, [0.0; 2]),
+            LightKind::Spot { position, direction, range, inner_cone_angle, outer_cone_angle } => {
+                let (cos_inner, cos_outer) = (inner_cone_angle.cos(), outer_cone_angle.cos());
+                let scale = 1.0 / (cos_inner - cos_outer).max(1e-3);
+                (position, range, direction.normalize_or_zero(), 2, [scale, -cos_outer * scale])
+            },
+        };
+        GpuLight {
+            position_range: position.extend(range.max(1e-3)).to_array(),
+            direction_kind: direction.extend(kind as f32).to_array(),
+            color_intensity: self.color.extend(self.intensity).to_array(),
+            spot: [spot[0], spot[1], 0.0, 0.0],
+            shadow: [-1.0, 0.0, 0.0, 0.0],
+        }
+    }
+}
+
+// `Light` struct of the forward+ shaders (std430)
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default)]
+pub(crate) struct GpuLight {
+    pub position_range: [f32; 4],
+    pub direction_kind: [f32; 4],
+    pub color_intensity: [f32; 4],
+    pub spot: [f32; 4],
+    // slot, constant, slope and normal bias ; set once the shadow slots are handed out
+    pub shadow: [f32; 4],
+}
@@ -0,0 +1,153 @@
+use std::sync::Arc;
+
+use ash::vk;
+use glam::{Vec3, Vec4};
+
+use crate::vulkan_api::VkApp;
+use crate::vulkan_api::memory::{as_bytes, GpuBuffer};
+
+use crate::renderer::texture::Texture;
+use crate::renderer::RendererResult;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum AlphaMode {
+    // alpha is ignored
+    Opaque,
+    // fragments below `cutoff` alpha are discarded, the rest is opaque
+    Mask { cutoff: f32 },
+    // blended over what is behind without writing depth ; blended draws should come last, sorted back to front
+    Blend,
+}
+
+// glTF metallic-roughness material ; every factor multiplies its texture, missing textures count as white (flat
+// for the normal map)
+#[derive(Clone)]
+pub struct Material {
+    pub base_color_factor: Vec4,
+    pub metallic_factor: f32,
+    pub roughness_factor: f32,
+    pub emissive_factor: Vec3,
+    pub normal_scale: f32,
+    pub occlusion_strength: f32,
+    pub alpha_mode: AlphaMode,
+    pub double_sided: bool,
+    // sRGB
+    pub base_color_texture: Option<Arc<Texture>>,
+    // linear, roughness in G and metallic in B
+    pub metallic_roughness_texture: Option<Arc<Texture>>,
+    // linear, tangent space
+    pub normal_texture: Option<Arc<Texture>>,
+    // linear, occlusion in R
+    pub occlusion_texture: Option<Arc<Texture>>,
+    // sRGB
+    pub emissive_texture: Option<Arc<Texture>>,
+}
+
+impl Default for Material {
+    fn default() -> Self {
+        Material {
+            base_color_factor: Vec4::ONE,
+            metallic_factor: 1.0,
+            roughness_factor: 1.0,
+            emissive_factor: Vec3::ZERO,
+            normal_scale: 1.0,
+            occlusion_strength: 1.0,
+            alpha_mode: AlphaMode::Opaque,
+            double_sided: false,
+            base_color_texture: None,
+            metallic_roughness_texture: None,
+            normal_texture: None,
+            occlusion_texture: None,
+            emissive_texture: None,
+        }
+    }
+}
+
+impl Material {
+    // untextured material
+    pub fn new(base_color: Vec4, metallic: f32, roughness: f32) -> Self {
+        Material {
+            base_color_factor: base_color,
+            metallic_factor: metallic,
+            roughness_factor: roughness,
+            ..Default::default()
+        }
+    }
+}
+
+// `Material` uniform block of pbr.frag (std140)
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+struct MaterialData {
+    base_color_factor: [f32; 4],
+    emissive_normal_scale: [f32; 4],
+    factors: [f32; 4],
+    flags: [u32; 4],
+}
+
+// Stand ins for the textures a material leaves out
+pub(crate) struct DefaultTextures {
+    pub white_srgb: Arc<Texture>,
+    pub white_linear: Arc<Texture>,
+    pub flat_normal: Arc<Texture>,
+}
+
+impl DefaultTextures {
+    pub fn new(vk_app: &VkApp) -> RendererResult<Self> {
+        Ok(DefaultTextures {
+            white_srgb: Arc::new(Texture::solid(vk_app, [255; 4], true, "default texture [white srgb]")?),
+            white_linear: Arc::new(Texture::solid(vk_app, [255; 4], false, "default texture [white linear]")?),
+            flat_normal: Arc::new(Texture::solid(vk_app, [128, 128, 255, 255], false, "default texture [flat normal]")?),
+        })
+    }
+}
+
+// A material ready to draw with the ForwardRenderer that created it, keeping its textures alive
+pub struct GpuMaterial {
+    pub(crate) uniform_buffer: GpuBuffer,
+    // base color, metallic-roughness, normal, occlusion, emissive
+    pub(crate) textures: [Arc<Texture>; 5],
+    alpha_mode: AlphaMode,
+    double_sided: bool,
+}
+
+impl GpuMaterial {
+    pub(crate) fn new(vk_app: &VkApp, material: &Material, defaults: &DefaultTextures, name: &str) -> RendererResult<Self> {
+        let (alpha_mode, alpha_cutoff) = match material.alpha_mode {
+            AlphaMode::Opaque => (0, 0.0),
+            AlphaMode::Mask { cutoff } => (1, cutoff),
+            AlphaMode::Blend => (2, 0.0),
+        };
+        let data = MaterialData {
+            base_color_factor: material.base_color_factor.to_array(),
+            emissive_normal_scale: material.emissive_factor.extend(material.normal_scale).to_array(),
+            factors: [material.metallic_factor, material.roughness_factor, material.occlusion_strength, alpha_cutoff],
+            flags: [alpha_mode, material.double_sided as u32, 0, 0],
+        };
+        let uniform_buffer = GpuBuffer::with_data(vk_app, as_bytes(&[data]), vk::BufferUsageFlags::UNIFORM_BUFFER, &format!("{name} [material]"))?;
+        let or_default = |texture: &Option<Arc<Texture>>, default: &Arc<Texture>| texture.as_ref().unwrap_or(default).clone();
+
+        Ok(GpuMaterial {
+            uniform_buffer,
+            textures: [
+                or_default(&material.base_color_texture, &defaults.white_srgb),
+                or_default(&material.metallic_roughness_texture, &defaults.white_linear),
+                or_default(&material.normal_texture, &defaults.flat_normal),
+                or_default(&material.occlusion_texture, &defaults.white_linear),
+                or_default(&material.emissive_texture, &defaults.white_srgb),
+            ],
+            alpha_mode: material.alpha_mode,
+            double_sided: material.double_sided,
+        })
+    }
+
+    // GETTERS
+
+    pub fn alpha_mode(&self) -> AlphaMode {
+        self.alpha_mode
+    }
+
+    pub fn double_sided(&self) -> bool {
+        self.double_sided
+    }
+}
@@ -0,0 +1,24 @@
+// Physically based forward+ rendering: glTF metallic-roughness materials, directional / point / spot lights culled
+// into a grid of view space clusters by a compute pass, cascaded and cube shadow maps, and image based ambient
+// lighting generated from an HDR environment map.
+
+mod environment;
+pub use environment::*;
+mod forward;
+pub use forward::*;
+mod light;
+pub use light::*;
+mod material;
+pub use material::*;
+mod shadow;
+pub use shadow::{ShadowImages, MAX_CASCADES, MAX_DIRECTIONAL_SHADOWS, MAX_POINT_SHADOWS};
+
+const LIGHTING_INCLUDE: &str = include_str!("../shaders/pbr/lighting.glsl");
+const IBL_INCLUDE: &str = include_str!("../shaders/pbr/ibl.glsl");
+
+// naga's GLSL frontend has no #include, the shared sources are spliced in place of the directive
+pub(crate) fn with_includes(source: &str) -> String {
+    source
+        .replacen("#include \"lighting.glsl\"", LIGHTING_INCLUDE, 1)
+        .replacen("#include \"ibl.glsl\"", IBL_INCLUDE, 1)
+}
@@ -0,0 +1,430 @@
+use std::f32::consts::FRAC_PI_2;
+
+use ash::vk;
+use glam::{Mat4, Vec2, Vec3};
+use tracing::info;
+
+use crate::config::ShadowConfig;
+use crate::vulkan_api::VkApp;
+use crate::vulkan_api::commands::one_time_submit;
+use crate::vulkan_api::descriptors::{DescriptorAllocator, DescriptorBinding, DescriptorBindings};
+use crate::vulkan_api::image::{find_supported_format, GpuImage, GpuImageView, ImageDesc, ImageState};
+use crate::vulkan_api::memory::as_bytes;
+use crate::vulkan_api::pipeline::{GraphicsPipeline, GraphicsPipelineBuilder};
+use crate::vulkan_api::render_graph::{ImageAccess, ImageHandle, PassBuilder, PassContext, PassKind, RenderGraph};
+use crate::vulkan_api::sampler::{Sampler, SamplerDesc};
+use crate::vulkan_api::shader::{Shader, ShaderStage};
+
+use crate::renderer::camera::Camera;
+use crate::renderer::mesh::{GpuMesh, Vertex};
+use crate::renderer::RendererResult;
+
+use super::light::{GpuLight, Light, LightKind};
+use super::with_includes;
+
+const SHADOW_VERTEX_SHADER: &str = include_str!("../shaders/pbr/shadow.vert");
+const POINT_SHADOW_FRAGMENT_SHADER: &str = include_str!("../shaders/pbr/shadow_point.frag");
+
+// sizes of the `Shadows` block, see lighting.glsl
+pub const MAX_CASCADES: usize = 4;
+pub const MAX_DIRECTIONAL_SHADOWS: usize = 4;
+pub const MAX_POINT_SHADOWS: usize = 8;
+const POINT_SHADOW_MATRICES: usize = MAX_CASCADES * MAX_DIRECTIONAL_SHADOWS;
+const POINT_SHADOW_NEAR: f32 = 0.02;
+// direction and up vector of each cube face, +X -X +Y -Y +Z -Z
+const CUBE_FACES: [(Vec3, Vec3); 6] = [
+    (Vec3::X, Vec3::NEG_Y),
+    (Vec3::NEG_X, Vec3::NEG_Y),
+    (Vec3::Y, Vec3::Z),
+    (Vec3::NEG_Y, Vec3::NEG_Z),
+    (Vec3::Z, Vec3::NEG_Y),
+    (Vec3::NEG_Z, Vec3::NEG_Y),
+];
+
+// `Shadows` struct of lighting.glsl (std140)
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+pub(crate) struct ShadowData {
+    cascade_splits: [f32; 4],
+    cascade_texel_sizes: [f32; 4],
+    cascade_depth_ranges: [f32; 4],
+    params: [u32; 4],
+    texel_size: [f32; 4],
+    point_lights: [[f32; 4]; MAX_POINT_SHADOWS],
+    matrices: [Mat4; POINT_SHADOW_MATRICES + MAX_POINT_SHADOWS * 6],
+}
+
+// `ShadowDraw` push constant block of the shadow shaders
+#[repr(C)]
+#[derive(Copy, Clone)]
+struct ShadowDrawConstants {
+    model: Mat4,
+    matrix_index: u32,
+    point_slot: u32,
+    _padding: [u32; 2],
+}
+
+// The shadow maps in a render graph, see ShadowMaps::add_passes
+#[derive(Copy, Clone, Debug)]
+pub struct ShadowImages {
+    pub directional: ImageHandle,
+    pub point: ImageHandle,
+}
+
+impl ShadowImages {
+    // declares the reads of a pass shading with the maps
+    pub fn sampled_by<'g, 'a>(&self, pass: PassBuilder<'g, 'a>) -> PassBuilder<'g, 'a> {
+        pass.read_image(self.directional, ImageAccess::SampledFragment)
+            .read_image(self.point, ImageAccess::SampledFragment)
+    }
+}
+
+// What a shadow pass records, copied out of the maps so the graph does not borrow them
+struct LayerPass {
+    pipeline: vk::Pipeline,
+    layout: vk::PipelineLayout,
+    push_constant_stages: vk::ShaderStageFlags,
+    descriptor_sets: Vec<vk::DescriptorSet>,
+    extent: vk::Extent2D,
+    // (layer view, matrix index, point slot) of every layer to render
+    layers: Vec<(vk::ImageView, usize, u32)>,
+}
+
+// Shadow maps of the forward renderer: a layer per cascade of each shadowed directional light, six layers (cube
+// faces) per shadowed point light. Slots are handed out every frame in light order, the lights past the configured
+// maximums go unshadowed.
+pub(crate) struct ShadowMaps {
+    config: ShadowConfig,
+    directional_maps: GpuImage,
+    directional_layers: Vec<GpuImageView>,
+    point_maps: GpuImage,
+    point_layers: Vec<GpuImageView>,
+    cascade_pipeline: GraphicsPipeline,
+    point_pipeline: GraphicsPipeline,
+    sampler: Sampler,
+    // shadows handed out by the last prepare
+    directional_count: u32,
+    point_count: u32,
+}
+
+impl ShadowMaps {
+    pub fn new(vk_app: &VkApp, config: ShadowConfig) -> RendererResult<Self> {
+        let usage = vk::FormatFeatureFlags::DEPTH_STENCIL_ATTACHMENT | vk::FormatFeatureFlags::SAMPLED_IMAGE;
+        // D16 is guaranteed to be both
+        let format = find_supported_format(vk_app, &[vk::Format::D32_SFLOAT, vk::Format::D16_UNORM], usage).unwrap_or(vk::Format::D16_UNORM);
+        let filter = match find_supported_format(vk_app, &[format], vk::FormatFeatureFlags::SAMPLED_IMAGE_FILTER_LINEAR) {
+            Some(_) => vk::Filter::LINEAR,
+            None => vk::Filter::NEAREST,
+        };
+
+        let image_usage = vk::ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT | vk::ImageUsageFlags::SAMPLED | vk::ImageUsageFlags::TRANSFER_DST;
+        let square = |size: u32| vk::Extent2D { width: size, height: size };
+        let directional_layer_count = (config.max_directional_shadows * config.cascade_count).max(1);
+        let directional_maps = GpuImage::new(
+            vk_app,
+            ImageDesc::new(format, square(config.cascade_resolution)).with_array_layers(directional_layer_count),
+            image_usage,
+            "directional shadow maps",
+        )?;
+        let point_layer_count = config.max_point_shadows.max(1) * 6;
+        let point_maps = GpuImage::new(
+            vk_app,
+            ImageDesc::new(format, square(config.point_resolution)).with_array_layers(point_layer_count),
+            image_usage,
+            "point shadow maps",
+        )?;
+        let directional_layers = (0..directional_layer_count)
+            .map(|layer| directional_maps.create_layer_view(vk_app, layer, &format!("directional shadow maps [layer {layer}]")))
+            .collect::<Result<Vec<_>, _>>()?;
+        let point_layers = (0..point_layer_count)
+            .map(|layer| point_maps.create_layer_view(vk_app, layer, &format!("point shadow maps [layer {layer}]")))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        // fully lit until a light casts into them
+        let device = vk_app.device();
+        let clear_range = |image: &GpuImage| image.desc().subresource_range();
+        one_time_submit(vk_app, |command_buffer| unsafe {
+            for image in [&directional_maps, &point_maps] {
+                image.cmd_transition(device, command_buffer, ImageState::UNDEFINED, ImageState::TRANSFER_DST);
+                device.cmd_clear_depth_stencil_image(
+                    command_buffer,
+                    image.image(),
+                    vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                    &vk::ClearDepthStencilValue { depth: 1.0, stencil: 0 },
+                    std::slice::from_ref(&clear_range(image)),
+                );
+                image.cmd_transition(device, command_buffer, ImageState::TRANSFER_DST, ImageState::SHADER_READ);
+            }
+        })?;
+
+        let vertex = Shader::from_glsl(&with_includes(SHADOW_VERTEX_SHADER), ShaderStage::Vertex)?;
+        let point_fragment = Shader::from_glsl(&with_includes(POINT_SHADOW_FRAGMENT_SHADER), ShaderStage::Fragment)?;
+        // both faces cast, thin geometry would leak light otherwise
+        let builder = || {
+            GraphicsPipelineBuilder::new()
+                .vertex(&vertex, Vertex::layout())
+                .depth(format, true, true, vk::CompareOp::LESS_OR_EQUAL)
+                .cull_mode(vk::CullModeFlags::NONE, vk::FrontFace::COUNTER_CLOCKWISE)
+        };
+        let depth_clamp = vk_app.is_feature_enabled(crate::device_feature!(core.depth_clamp));
+        let cascade_pipeline = builder().depth_clamp(depth_clamp).build(vk_app, "cascade shadow pass")?;
+        let point_pipeline = builder().fragment(&point_fragment).build(vk_app, "point shadow pass")?;
+        info!(
+            target: "torii::renderer",
+            format = ?format,
+            filter = ?filter,
+            cascades = config.cascade_count,
+            depth_clamp,
+            "Created shadow maps"
+        );
+
+        Ok(ShadowMaps {
+            config,
+            directional_maps,
+            directional_layers,
+            point_maps,
+            point_layers,
+            cascade_pipeline,
+            point_pipeline,
+            sampler: Sampler::new(vk_app, SamplerDesc::shadow(filter), "shadow sampler")?,
+            directional_count: 0,
+            point_count: 0,
+        })
+    }
+
+    // Hands the shadow slots out to `lights` (in the order of the light buffer), filling the shadow parameters of
+    // their `gpu_lights`, and computes the cascade and cube face matrices
+    pub fn prepare(&mut self, camera: &Camera, lights: &[&Light], gpu_lights: &mut [GpuLight]) -> ShadowData {
+        let cascade_count = self.config.cascade_count as usize;
+        let near = camera.near.max(1e-3);
+        let far = self.config.distance.min(camera.far).max(near * 1.001);
+        let splits = self.cascade_splits(near, far);
+        let mut data = ShadowData {
+            cascade_splits: [far; 4],
+            cascade_texel_sizes: [0.0; 4],
+            cascade_depth_ranges: [1.0; 4],
+            params: [self.config.cascade_count, self.config.pcf_radius, 0, 0],
+            texel_size: [1.0 / self.config.cascade_resolution as f32, 1.0 / self.config.point_resolution as f32, 0.0, 0.0],
+            point_lights: [[0.0; 4]; MAX_POINT_SHADOWS],
+            matrices: [Mat4::IDENTITY; POINT_SHADOW_MATRICES + MAX_POINT_SHADOWS * 6],
+        };
+        data.cascade_splits[..cascade_count].copy_from_slice(&splits[1..=cascade_count]);
+
+        let (mut directional_count, mut point_count) = (0, 0);
+        for (light, gpu_light) in lights.iter().zip(gpu_lights.iter_mut()) {
+            let Some(shadow) = light.shadow else {
+                continue;
+            };
+            let slot = match light.kind {
+                LightKind::Directional { direction } if directional_count < self.config.max_directional_shadows => {
+                    for cascade in 0..cascade_count {
+                        let (matrix, texel_size, depth_range) = self.cascade_matrix(camera, direction, splits[cascade], splits[cascade + 1]);
+                        data.matrices[directional_count as usize * MAX_CASCADES + cascade] = matrix;
+                        // every light shares the cascade bounds, so their texel sizes and depth ranges match too
+                        data.cascade_texel_sizes[cascade] = texel_size;
+                        data.cascade_depth_ranges[cascade] = depth_range;
+                    }
+                    directional_count += 1;
+                    directional_count - 1
+                },
+                LightKind::Point { position, range } if point_count < self.config.max_point_shadows => {
+                    let projection = flip_y(Mat4::perspective_rh(FRAC_PI_2, 1.0, POINT_SHADOW_NEAR, range.max(POINT_SHADOW_NEAR * 2.0)));
+                    for (face, (direction, up)) in CUBE_FACES.into_iter().enumerate() {
+                        let view = Mat4::look_at_rh(position, position + direction, up);
+                        data.matrices[POINT_SHADOW_MATRICES + point_count as usize * 6 + face] = projection * view;
+                    }
+                    data.point_lights[point_count as usize] = position.extend(range.max(1e-3)).to_array();
+                    point_count += 1;
+                    point_count - 1
+                },
+                _ => continue,
+            };
+            gpu_light.shadow = [slot as f32, shadow.depth_bias, shadow.slope_bias, shadow.normal_bias];
+        }
+        self.directional_count = directional_count;
+        self.point_count = point_count;
+        data
+    }
+
+    // Adds the passes rendering `casters` into the layers of the shadows handed out by the last prepare to `graph`,
+    // one per kind of map in use. The maps are imported left readable by fragment shaders: the passes shading with
+    // them read the returned handles so they run after the maps are written.
+    pub fn add_passes<'a>(
+        &self,
+        graph: &mut RenderGraph<'a>,
+        descriptor_allocator: &mut DescriptorAllocator,
+        shadow_buffer: vk::Buffer,
+        casters: &'a [(&'a GpuMesh, Mat4)],
+    ) -> RendererResult<ShadowImages> {
+        // the previous frame's shading reads the maps, the next one finds them as this one leaves them
+        let sampled = ImageAccess::SampledFragment.info();
+        let import = |maps: &GpuImage| maps.imported().with_initial_access(sampled).with_final_access(Some(sampled));
+        let images = ShadowImages {
+            directional: graph.import_image("directional shadow maps", import(&self.directional_maps)),
+            point: graph.import_image("point shadow maps", import(&self.point_maps)),
+        };
+
+        let bindings = DescriptorBindings::new().bind(0, 0, DescriptorBinding::whole_buffer(shadow_buffer));
+        if self.directional_count > 0 {
+            let cascade_count = self.config.cascade_count;
+            let layers = (0..self.directional_count).flat_map(|slot| {
+                (0..cascade_count).map(move |cascade| (slot * cascade_count + cascade, slot as usize * MAX_CASCADES + cascade as usize, 0))
+            });
+            let pass = LayerPass::new(descriptor_allocator, &bindings, &self.cascade_pipeline, &self.directional_maps, &self.directional_layers, layers)?;
+            graph
+                .add_pass("directional shadows", PassKind::Graphics)
+                .write_image(images.directional, ImageAccess::DepthAttachmentWrite)
+                .execute(move |context| pass.record(context, casters));
+        }
+        if self.point_count > 0 {
+            let layers = (0..self.point_count).flat_map(|slot| {
+                (0..6).map(move |face| (slot * 6 + face, POINT_SHADOW_MATRICES + (slot * 6 + face) as usize, slot))
+            });
+            let pass = LayerPass::new(descriptor_allocator, &bindings, &self.point_pipeline, &self.point_maps, &self.point_layers, layers)?;
+            graph
+                .add_pass("point shadows", PassKind::Graphics)
+                .write_image(images.point, ImageAccess::DepthAttachmentWrite)
+                .execute(move |context| pass.record(context, casters));
+        }
+        Ok(images)
+    }
+
+    // view depths bounding the cascades, `near` first ; blends uniform and logarithmic splits by split_lambda
+    fn cascade_splits(&self, near: f32, far: f32) -> [f32; MAX_CASCADES + 1] {
+        let cascade_count = self.config.cascade_count as usize;
+        let lambda = self.config.split_lambda;
+        let mut splits = [far; MAX_CASCADES + 1];
+        for (cascade, split) in splits.iter_mut().enumerate().take(cascade_count) {
+            let ratio = cascade as f32 / cascade_count as f32;
+            let logarithmic = near * (far / near).powf(ratio);
+            let uniform = near + (far - near) * ratio;
+            *split = lambda * logarithmic + (1.0 - lambda) * uniform;
+        }
+        splits
+    }
+
+    // Light space projection of the camera frustum between view depths `start` and `end` ; bounds the slice with
+    // a sphere so the projection size stays put as the camera turns, and snaps it to whole texels so the shadow
+    // edges don't shimmer as it moves. Also returns the world size of a texel and the depth range.
+    fn cascade_matrix(&self, camera: &Camera, direction: Vec3, start: f32, end: f32) -> (Mat4, f32, f32) {
+        let inverse_projection = camera.projection.inverse();
+        let inverse_view = camera.view.inverse();
+        // works for any projection, see light_cull.comp
+        let corner = |ndc: Vec2, depth: f32| {
+            let near_point = inverse_projection.project_point3(ndc.extend(0.0));
+            let far_point = inverse_projection.project_point3(ndc.extend(1.0));
+            let t = (-depth - near_point.z) / (far_point.z - near_point.z);
+            inverse_view.transform_point3(near_point.lerp(far_point, t))
+        };
+        let corners: Vec<Vec3> = [start, end]
+            .into_iter()
+            .flat_map(|depth| [Vec2::new(-1.0, -1.0), Vec2::new(1.0, -1.0), Vec2::new(-1.0, 1.0), Vec2::new(1.0, 1.0)].map(|ndc| corner(ndc, depth)))
+            .collect();
+        let center = corners.iter().sum::<Vec3>() / corners.len() as f32;
+        let radius = corners.iter().map(|corner| corner.distance(center)).fold(0.0, f32::max);
+        let radius = (radius * 16.0).ceil() / 16.0;
+
+        let direction = direction.try_normalize().unwrap_or(Vec3::NEG_Y);
+        let up = if direction.y.abs() > 0.99 { Vec3::Z } else { Vec3::Y };
+        let rotation = Mat4::look_at_rh(Vec3::ZERO, direction, up);
+        let texel_size = 2.0 * radius / self.config.cascade_resolution as f32;
+        let light_center = rotation.transform_point3(center);
+        let snapped = (light_center.truncate() / texel_size).floor() * texel_size;
+        // casters up to the shadow distance towards the light still land in the map
+        let caster_margin = self.config.distance;
+        let (near, far) = (-light_center.z - radius - caster_margin, -light_center.z + radius);
+        let projection = flip_y(Mat4::orthographic_rh(snapped.x - radius, snapped.x + radius, snapped.y - radius, snapped.y + radius, near, far));
+        (projection * rotation, texel_size, far - near)
+    }
+
+    // GETTERS
+
+    pub fn directional_maps(&self) -> &GpuImage {
+        &self.directional_maps
+    }
+
+    pub fn point_maps(&self) -> &GpuImage {
+        &self.point_maps
+    }
+
+    pub fn sampler(&self) -> &Sampler {
+        &self.sampler
+    }
+}
+
+impl LayerPass {
+    // (layer, matrix index, point slot) of every layer to render
+    fn new(
+        descriptor_allocator: &mut DescriptorAllocator,
+        bindings: &DescriptorBindings,
+        pipeline: &GraphicsPipeline,
+        maps: &GpuImage,
+        layer_views: &[GpuImageView],
+        layers: impl Iterator<Item = (u32, usize, u32)>,
+    ) -> RendererResult<Self> {
+        let layout = pipeline.layout();
+        Ok(LayerPass {
+            pipeline: pipeline.pipeline(),
+            layout: layout.layout(),
+            push_constant_stages: layout.push_constant_stages(),
+            descriptor_sets: descriptor_allocator.write_sets(layout, bindings)?,
+            extent: maps.extent(),
+            layers: layers.map(|(layer, matrix_index, point_slot)| (layer_views[layer as usize].view(), matrix_index, point_slot)).collect(),
+        })
+    }
+
+    // the graph has moved the maps to DEPTH_STENCIL_ATTACHMENT_OPTIMAL ; every layer is cleared first
+    fn record(&self, context: &PassContext, casters: &[(&GpuMesh, Mat4)]) {
+        let (device, command_buffer) = (context.device, context.command_buffer);
+        let render_area = vk::Rect2D { offset: vk::Offset2D::default(), extent: self.extent };
+        let viewport = vk::Viewport {
+            x: 0.0,
+            y: 0.0,
+            width: self.extent.width as f32,
+            height: self.extent.height as f32,
+            min_depth: 0.0,
+            max_depth: 1.0,
+        };
+        unsafe {
+            device.cmd_bind_pipeline(command_buffer, vk::PipelineBindPoint::GRAPHICS, self.pipeline);
+            device.cmd_bind_descriptor_sets(command_buffer, vk::PipelineBindPoint::GRAPHICS, self.layout, 0, &self.descriptor_sets, &[]);
+        }
+
+        for &(view, matrix_index, point_slot) in self.layers.iter() {
+            let depth_attachment = vk::RenderingAttachmentInfo::default()
+                .image_view(view)
+                .image_layout(vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL)
+                .load_op(vk::AttachmentLoadOp::CLEAR)
+                .store_op(vk::AttachmentStoreOp::STORE)
+                .clear_value(vk::ClearValue { depth_stencil: vk::ClearDepthStencilValue { depth: 1.0, stencil: 0 } });
+            let rendering_info = vk::RenderingInfo::default()
+                .render_area(render_area)
+                .layer_count(1)
+                .depth_attachment(&depth_attachment);
+            unsafe {
+                device.cmd_begin_rendering(command_buffer, &rendering_info);
+                device.cmd_set_viewport(command_buffer, 0, std::slice::from_ref(&viewport));
+                device.cmd_set_scissor(command_buffer, 0, std::slice::from_ref(&render_area));
+                for &(mesh, model) in casters {
+                    let constants = ShadowDrawConstants {
+                        model,
+                        matrix_index: matrix_index as u32,
+                        point_slot,
+                        _padding: [0; 2],
+                    };
+                    device.cmd_push_constants(command_buffer, self.layout, self.push_constant_stages, 0, as_bytes(&[constants]));
+                    device.cmd_bind_vertex_buffers(command_buffer, 0, &[mesh.vertex_buffer.buffer()], &[0]);
+                    device.cmd_bind_index_buffer(command_buffer, mesh.index_buffer.buffer(), 0, vk::IndexType::UINT32);
+                    device.cmd_draw_indexed(command_buffer, mesh.index_count, 1, 0, 0, 0);
+                }
+                device.cmd_end_rendering(command_buffer);
+            }
+        }
+    }
+}
+
+// vulkan clip space has Y down, as in Camera
+fn flip_y(mut projection: Mat4) -> Mat4 {
+    projection.y_axis.y *= -1.0;
+    projection
+}
@@ -0,0 +1,171 @@
+use ash::vk;
+
+use crate::vulkan_api::VkApp;
+use crate::vulkan_api::commands::one_time_submit;
+use crate::vulkan_api::descriptors::{DescriptorBinding, DescriptorBindings};
+use crate::vulkan_api::image::{GpuImage, ImageDesc, ImageState};
+use crate::vulkan_api::memory::as_bytes;
+use crate::vulkan_api::pipeline::BlendMode;
+use crate::vulkan_api::shader::{Shader, ShaderStage};
+
+use crate::renderer::RendererResult;
+
+use super::{FullscreenPass, PostContext, PostFormats, PostPass, PostSettings, PostStage, DISCARD_AFTER_READ};
+
+const TAA_SHADER: &str = include_str!("../shaders/post/taa.frag");
+const FXAA_SHADER: &str = include_str!("../shaders/post/fxaa.frag");
+
+// weight of the current frame in the temporal blend, lower is smoother but slower to react
+const TAA_BLEND: f32 = 0.1;
+// FXAA 3.11 "quality" defaults
+const FXAA_EDGE_THRESHOLD: f32 = 0.166;
+const FXAA_EDGE_THRESHOLD_MIN: f32 = 0.0833;
+
+// Temporal AA needs the scene rendered with PostProcessStack::jitter applied to the camera and motion vectors written
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum Antialiasing {
+    None,
+    #[default]
+    Fxaa,
+    Taa,
+}
+
+// `Temporal` push constant block of taa.frag
+#[repr(C)]
+#[derive(Copy, Clone)]
+struct TemporalConstants {
+    texel_size: [f32; 2],
+    blend: f32,
+    history_valid: u32,
+}
+
+// `Fxaa` push constant block of fxaa.frag
+#[repr(C)]
+#[derive(Copy, Clone)]
+struct FxaaConstants {
+    texel_size: [f32; 2],
+    edge_threshold: f32,
+    edge_threshold_min: f32,
+}
+
+// Temporal anti-aliasing on the HDR image: the jittered frames accumulate into a history reprojected with the motion
+// vectors, written next to the pass output
+pub struct TaaPass {
+    pass: FullscreenPass,
+    // read one, write the other, swapped every frame ; created by prepare
+    history: Vec<GpuImage>,
+    current: usize,
+    // the history only holds something usable when the pass ran the frame before
+    last_frame: Option<u64>,
+}
+
+impl TaaPass {
+    pub fn new(vk_app: &VkApp, formats: PostFormats) -> RendererResult<Self> {
+        let fragment = Shader::from_glsl(TAA_SHADER, ShaderStage::Fragment)?;
+        Ok(TaaPass {
+            pass: FullscreenPass::new(vk_app, &fragment, &[formats.hdr, formats.hdr], BlendMode::Opaque, "temporal anti-aliasing")?,
+            history: vec![],
+            current: 0,
+            last_frame: None,
+        })
+    }
+}
+
+impl PostPass for TaaPass {
+    fn name(&self) -> &str {
+        "taa"
+    }
+
+    fn stage(&self) -> PostStage {
+        PostStage::Hdr
+    }
+
+    fn prepare(&mut self, vk_app: &VkApp, formats: PostFormats, extent: vk::Extent2D) -> RendererResult<()> {
+        self.history = (0..2)
+            .map(|index| {
+                GpuImage::new(
+                    vk_app,
+                    ImageDesc::new(formats.hdr, extent),
+                    vk::ImageUsageFlags::COLOR_ATTACHMENT | vk::ImageUsageFlags::SAMPLED,
+                    &format!("taa history [{index}]"),
+                )
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        self.last_frame = None;
+        // the history is bound before anything is written into it, it has to be readable
+        let device = vk_app.device();
+        one_time_submit(vk_app, |command_buffer| {
+            for image in self.history.iter() {
+                image.cmd_transition(device, command_buffer, ImageState::UNDEFINED, ImageState::SHADER_READ);
+            }
+        })?;
+        Ok(())
+    }
+
+    fn enabled(&self, settings: &PostSettings) -> bool {
+        settings.antialiasing == Antialiasing::Taa
+    }
+
+    fn record(&mut self, context: &mut PostContext, input: vk::ImageView, output: vk::ImageView) -> RendererResult<()> {
+        let history_valid = context.frame_number > 0 && self.last_frame == Some(context.frame_number - 1);
+        let (read, write) = (&self.history[self.current], &self.history[1 - self.current]);
+        let constants = TemporalConstants {
+            texel_size: [1.0 / context.extent.width as f32, 1.0 / context.extent.height as f32],
+            blend: TAA_BLEND,
+            history_valid: history_valid as u32,
+        };
+        let bindings = DescriptorBindings::new()
+            .bind(0, 0, DescriptorBinding::sampled_image(input))
+            .bind(0, 1, DescriptorBinding::sampled_image(read.view()))
+            .bind(0, 2, DescriptorBinding::sampled_image(context.velocity))
+            .bind(0, 3, DescriptorBinding::Sampler(context.linear_sampler))
+            .with_push_constants(as_bytes(&[constants]));
+        write.cmd_transition(context.device, context.command_buffer, DISCARD_AFTER_READ, ImageState::COLOR_ATTACHMENT);
+        self.pass.draw(context, &[output, write.view()], context.extent, false, &bindings)?;
+        write.cmd_transition(context.device, context.command_buffer, ImageState::COLOR_ATTACHMENT, ImageState::SHADER_READ);
+        self.current = 1 - self.current;
+        self.last_frame = Some(context.frame_number);
+        Ok(())
+    }
+}
+
+// FXAA on the tone mapped image
+pub struct FxaaPass {
+    pass: FullscreenPass,
+}
+
+impl FxaaPass {
+    pub fn new(vk_app: &VkApp, formats: PostFormats) -> RendererResult<Self> {
+        let fragment = Shader::from_glsl(FXAA_SHADER, ShaderStage::Fragment)?;
+        Ok(FxaaPass {
+            pass: FullscreenPass::new(vk_app, &fragment, &[formats.output], BlendMode::Opaque, "fxaa")?,
+        })
+    }
+}
+
+impl PostPass for FxaaPass {
+    fn name(&self) -> &str {
+        "fxaa"
+    }
+
+    fn stage(&self) -> PostStage {
+        PostStage::Ldr
+    }
+
+    fn enabled(&self, settings: &PostSettings) -> bool {
+        settings.antialiasing == Antialiasing::Fxaa
+    }
+
+    fn record(&mut self, context: &mut PostContext, input: vk::ImageView, output: vk::ImageView) -> RendererResult<()> {
+        let constants = FxaaConstants {
+            texel_size: [1.0 / context.extent.width as f32, 1.0 / context.extent.height as f32],
+            edge_threshold: FXAA_EDGE_THRESHOLD,
+            edge_threshold_min: FXAA_EDGE_THRESHOLD_MIN,
+        };
+        let bindings = DescriptorBindings::new()
+            .bind(0, 0, DescriptorBinding::sampled_image(input))
+            .bind(0, 1, DescriptorBinding::Sampler(context.linear_sampler))
+            .with_push_constants(as_bytes(&[constants]));
+        self.pass.draw(context, &[output], context.extent, false, &bindings)
+    }
+}
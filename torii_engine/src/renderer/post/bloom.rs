@@ -0,0 +1,151 @@
+use ash::vk;
+
+use crate::vulkan_api::VkApp;
+use crate::vulkan_api::descriptors::{DescriptorBinding, DescriptorBindings};
+use crate::vulkan_api::image::{GpuImage, GpuImageView, ImageDesc, ImageState};
+use crate::vulkan_api::memory::as_bytes;
+use crate::vulkan_api::pipeline::BlendMode;
+use crate::vulkan_api::shader::{Shader, ShaderStage};
+
+use crate::renderer::RendererResult;
+
+use super::{FullscreenPass, PostContext, PostFormats, PostPass, PostSettings, PostStage, DISCARD_AFTER_READ};
+
+const DOWNSAMPLE_SHADER: &str = include_str!("../shaders/post/bloom_downsample.frag");
+const UPSAMPLE_SHADER: &str = include_str!("../shaders/post/bloom_upsample.frag");
+const COMPOSITE_SHADER: &str = include_str!("../shaders/post/bloom_composite.frag");
+
+// mips of the bloom chain, the first one at half the target's resolution
+pub const BLOOM_MIPS: u32 = 6;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct BloomSettings {
+    pub enabled: bool,
+    // share of the blurred image in the result
+    pub intensity: f32,
+    // radius of the upsampling filter, in texels of the mip being upsampled ; larger spreads the glow further
+    pub filter_radius: f32,
+}
+
+impl Default for BloomSettings {
+    fn default() -> Self {
+        BloomSettings {
+            enabled: true,
+            intensity: 0.04,
+            filter_radius: 1.0,
+        }
+    }
+}
+
+// `Downsample` push constant block of bloom_downsample.frag
+#[repr(C)]
+#[derive(Copy, Clone)]
+struct DownsampleConstants {
+    source_texel_size: [f32; 2],
+    karis_average: u32,
+}
+
+// Physically based bloom, no threshold: the HDR image is downsampled through a mip chain, the mips are upsampled back
+// and accumulated, and the result is blended over the image by the intensity
+pub struct BloomPass {
+    downsample: FullscreenPass,
+    upsample: FullscreenPass,
+    composite: FullscreenPass,
+    // half resolution mip chain and a view per mip, created by prepare
+    chain: Option<GpuImage>,
+    mip_views: Vec<GpuImageView>,
+}
+
+impl BloomPass {
+    pub fn new(vk_app: &VkApp, formats: PostFormats) -> RendererResult<Self> {
+        let shader = |source: &str| Shader::from_glsl(source, ShaderStage::Fragment);
+        Ok(BloomPass {
+            downsample: FullscreenPass::new(vk_app, &shader(DOWNSAMPLE_SHADER)?, &[formats.hdr], BlendMode::Opaque, "bloom downsample")?,
+            upsample: FullscreenPass::new(vk_app, &shader(UPSAMPLE_SHADER)?, &[formats.hdr], BlendMode::Additive, "bloom upsample")?,
+            composite: FullscreenPass::new(vk_app, &shader(COMPOSITE_SHADER)?, &[formats.hdr], BlendMode::Opaque, "bloom composite")?,
+            chain: None,
+            mip_views: vec![],
+        })
+    }
+}
+
+impl PostPass for BloomPass {
+    fn name(&self) -> &str {
+        "bloom"
+    }
+
+    fn stage(&self) -> PostStage {
+        PostStage::Hdr
+    }
+
+    fn prepare(&mut self, vk_app: &VkApp, formats: PostFormats, extent: vk::Extent2D) -> RendererResult<()> {
+        let half_extent = vk::Extent2D {
+            width: (extent.width / 2).max(1),
+            height: (extent.height / 2).max(1),
+        };
+        let mip_count = BLOOM_MIPS.min(ImageDesc::full_mip_chain(half_extent));
+        self.mip_views.clear();
+        let chain = GpuImage::new(
+            vk_app,
+            ImageDesc::new(formats.hdr, half_extent).with_mip_levels(mip_count),
+            vk::ImageUsageFlags::COLOR_ATTACHMENT | vk::ImageUsageFlags::SAMPLED,
+            "bloom chain",
+        )?;
+        self.mip_views = (0..mip_count)
+            .map(|mip| chain.create_view(vk_app, vk::ImageViewType::TYPE_2D, mip, 1, &format!("bloom chain [mip {mip}]")))
+            .collect::<Result<Vec<_>, _>>()?;
+        self.chain = Some(chain);
+        Ok(())
+    }
+
+    fn enabled(&self, settings: &PostSettings) -> bool {
+        settings.bloom.enabled
+    }
+
+    fn record(&mut self, context: &mut PostContext, input: vk::ImageView, output: vk::ImageView) -> RendererResult<()> {
+        let chain = self.chain.as_ref().expect("Bloom pass recorded before being prepared!");
+        let (device, command_buffer) = (context.device, context.command_buffer);
+        let mip_count = self.mip_views.len() as u32;
+        let texel_size = |extent: vk::Extent2D| [1.0 / extent.width as f32, 1.0 / extent.height as f32];
+
+        chain.cmd_transition(device, command_buffer, DISCARD_AFTER_READ, ImageState::COLOR_ATTACHMENT);
+        for mip in 0..mip_count {
+            let (source, source_extent) = match mip {
+                0 => (input, context.extent),
+                _ => (self.mip_views[mip as usize - 1].view(), chain.desc().mip_extent(mip - 1)),
+            };
+            let constants = DownsampleConstants {
+                source_texel_size: texel_size(source_extent),
+                karis_average: (mip == 0) as u32,
+            };
+            let bindings = DescriptorBindings::new()
+                .bind(0, 0, DescriptorBinding::sampled_image(source))
+                .bind(0, 1, DescriptorBinding::Sampler(context.linear_sampler))
+                .with_push_constants(as_bytes(&[constants]));
+            let target = self.mip_views[mip as usize].view();
+            self.downsample.draw(context, &[target], chain.desc().mip_extent(mip), false, &bindings)?;
+            chain.cmd_transition_mips(device, command_buffer, mip, 1, ImageState::COLOR_ATTACHMENT, ImageState::SHADER_READ);
+        }
+
+        // each mip accumulates the upsampled, already accumulated, mip below it
+        let filter_radius = context.settings.bloom.filter_radius;
+        for mip in (0..mip_count.saturating_sub(1)).rev() {
+            chain.cmd_transition_mips(device, command_buffer, mip, 1, ImageState::SHADER_READ, ImageState::COLOR_ATTACHMENT);
+            let radius = texel_size(chain.desc().mip_extent(mip + 1)).map(|size| size * filter_radius);
+            let bindings = DescriptorBindings::new()
+                .bind(0, 0, DescriptorBinding::sampled_image(self.mip_views[mip as usize + 1].view()))
+                .bind(0, 1, DescriptorBinding::Sampler(context.linear_sampler))
+                .with_push_constants(as_bytes(&[radius]));
+            let target = self.mip_views[mip as usize].view();
+            self.upsample.draw(context, &[target], chain.desc().mip_extent(mip), true, &bindings)?;
+            chain.cmd_transition_mips(device, command_buffer, mip, 1, ImageState::COLOR_ATTACHMENT, ImageState::SHADER_READ);
+        }
+
+        let bindings = DescriptorBindings::new()
+            .bind(0, 0, DescriptorBinding::sampled_image(input))
+            .bind(0, 1, DescriptorBinding::sampled_image(self.mip_views[0].view()))
+            .bind(0, 2, DescriptorBinding::Sampler(context.linear_sampler))
+            .with_push_constants(as_bytes(&[context.settings.bloom.intensity]));
+        self.composite.draw(context, &[output], context.extent, false, &bindings)
+    }
+}
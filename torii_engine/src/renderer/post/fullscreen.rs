@@ -0,0 +1,151 @@
+use ash::vk;
+
+use crate::vulkan_api::VkApp;
+use crate::vulkan_api::descriptors::{DescriptorBinding, DescriptorBindings};
+use crate::vulkan_api::pipeline::{BlendMode, GraphicsPipeline, GraphicsPipelineBuilder, VertexLayout};
+use crate::vulkan_api::shader::{Shader, ShaderStage};
+
+use crate::renderer::{RendererError, RendererResult};
+
+use super::{PostContext, PostFormats, PostPass, PostSettings, PostStage};
+
+const FULLSCREEN_VERTEX_SHADER: &str = include_str!("../shaders/post/fullscreen.vert");
+
+// A fragment shader run over a whole target, the building block of the post passes ; the fragment shader gets the
+// target's `uv` at location 0, (0, 0) being the top left corner
+pub struct FullscreenPass {
+    pipeline: GraphicsPipeline,
+}
+
+impl FullscreenPass {
+    pub fn new(vk_app: &VkApp, fragment: &Shader, color_formats: &[vk::Format], blend: BlendMode, name: &str) -> RendererResult<Self> {
+        let vertex = Shader::from_glsl(FULLSCREEN_VERTEX_SHADER, ShaderStage::Vertex)?;
+        let pipeline = GraphicsPipelineBuilder::new()
+            .vertex(&vertex, VertexLayout::default())
+            .fragment(fragment)
+            .color_formats(color_formats)
+            .cull_mode(vk::CullModeFlags::NONE, vk::FrontFace::COUNTER_CLOCKWISE)
+            .blend(blend)
+            .build(vk_app, name)?;
+        Ok(FullscreenPass { pipeline })
+    }
+
+    // Renders into `targets` (COLOR_ATTACHMENT_OPTIMAL, one per color format), blending onto their contents when
+    // `load` is set and overwriting them otherwise ; outside of any pass
+    pub fn draw(
+        &self,
+        context: &mut PostContext,
+        targets: &[vk::ImageView],
+        extent: vk::Extent2D,
+        load: bool,
+        bindings: &DescriptorBindings,
+    ) -> RendererResult<()> {
+        let load_op = if load { vk::AttachmentLoadOp::LOAD } else { vk::AttachmentLoadOp::DONT_CARE };
+        let color_attachments: Vec<vk::RenderingAttachmentInfo> = targets
+            .iter()
+            .map(|&view| {
+                vk::RenderingAttachmentInfo::default()
+                    .image_view(view)
+                    .image_layout(vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL)
+                    .load_op(load_op)
+                    .store_op(vk::AttachmentStoreOp::STORE)
+            })
+            .collect();
+        let render_area = vk::Rect2D { offset: vk::Offset2D::default(), extent };
+        let rendering_info = vk::RenderingInfo::default()
+            .render_area(render_area)
+            .layer_count(1)
+            .color_attachments(&color_attachments);
+        let viewport = vk::Viewport {
+            x: 0.0,
+            y: 0.0,
+            width: extent.width as f32,
+            height: extent.height as f32,
+            min_depth: 0.0,
+            max_depth: 1.0,
+        };
+        let (device, command_buffer) = (context.device, context.command_buffer);
+        unsafe {
+            device.cmd_begin_rendering(command_buffer, &rendering_info);
+            device.cmd_set_viewport(command_buffer, 0, std::slice::from_ref(&viewport));
+            device.cmd_set_scissor(command_buffer, 0, std::slice::from_ref(&render_area));
+            device.cmd_bind_pipeline(command_buffer, vk::PipelineBindPoint::GRAPHICS, self.pipeline.pipeline());
+        }
+        // the rendering is ended either way, the command buffer stays usable
+        let bound = context.descriptor_allocator.bind(command_buffer, vk::PipelineBindPoint::GRAPHICS, self.pipeline.layout(), bindings);
+        unsafe {
+            if bound.is_ok() {
+                device.cmd_draw(command_buffer, 3, 1, 0, 0);
+            }
+            device.cmd_end_rendering(command_buffer);
+        }
+        bound.map_err(RendererError::from)
+    }
+
+    // GETTERS
+
+    pub fn pipeline(&self) -> &GraphicsPipeline {
+        &self.pipeline
+    }
+}
+
+// A custom effect from a single GLSL fragment shader, reading the chain's image as `texture2D` (set 0, binding 0)
+// with a `sampler` (set 0, binding 1) and writing location 0 ; push constants are whatever set_push_constants was
+// last given. Effects needing more implement PostPass on top of a FullscreenPass.
+pub struct ShaderPass {
+    name: String,
+    stage: PostStage,
+    fragment: Shader,
+    // created once the stack tells the output format
+    pass: Option<FullscreenPass>,
+    push_constants: Vec<u8>,
+    pub enabled: bool,
+}
+
+impl ShaderPass {
+    pub fn new(name: &str, fragment_source: &str, stage: PostStage) -> RendererResult<Self> {
+        Ok(ShaderPass {
+            name: name.to_owned(),
+            stage,
+            fragment: Shader::from_glsl(fragment_source, ShaderStage::Fragment)?,
+            pass: None,
+            push_constants: vec![],
+            enabled: true,
+        })
+    }
+
+    pub fn set_push_constants(&mut self, bytes: &[u8]) {
+        self.push_constants = bytes.to_vec();
+    }
+}
+
+impl PostPass for ShaderPass {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn stage(&self) -> PostStage {
+        self.stage
+    }
+
+    fn prepare(&mut self, vk_app: &VkApp, formats: PostFormats, _extent: vk::Extent2D) -> RendererResult<()> {
+        if self.pass.is_none() {
+            let format = formats.of(self.stage);
+            self.pass = Some(FullscreenPass::new(vk_app, &self.fragment, &[format], BlendMode::Opaque, &self.name)?);
+        }
+        Ok(())
+    }
+
+    fn enabled(&self, _settings: &PostSettings) -> bool {
+        self.enabled
+    }
+
+    fn record(&mut self, context: &mut PostContext, input: vk::ImageView, output: vk::ImageView) -> RendererResult<()> {
+        let pass = self.pass.as_ref().expect("Shader pass recorded before being prepared!");
+        let bindings = DescriptorBindings::new()
+            .bind(0, 0, DescriptorBinding::sampled_image(input))
+            .bind(0, 1, DescriptorBinding::Sampler(context.linear_sampler))
+            .with_push_constants(&self.push_constants);
+        pass.draw(context, &[output], context.extent, false, &bindings)
+    }
+}
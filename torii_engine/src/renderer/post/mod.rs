@@ -0,0 +1,89 @@
+// Post processing: the scene renders into an HDR target (plus motion vectors), then a chain of full screen passes
+// takes it to the output image. Built in passes cover temporal anti-aliasing, bloom, exposure with tone mapping and
+// color grading, and FXAA ; game code adds its own passes through the PostPass trait (or ShaderPass for a single
+// fragment shader).
+
+use std::any::Any;
+
+use ash::{vk, Device};
+
+use crate::vulkan_api::VkApp;
+use crate::vulkan_api::descriptors::DescriptorAllocator;
+use crate::vulkan_api::image::ImageState;
+
+use super::RendererResult;
+
+mod antialiasing;
+pub use antialiasing::*;
+mod bloom;
+pub use bloom::*;
+mod fullscreen;
+pub use fullscreen::*;
+mod stack;
+pub use stack::*;
+mod tonemap;
+pub use tonemap::*;
+
+// the previous frame's passes may still read the image, its contents are thrown away
+const DISCARD_AFTER_READ: ImageState = ImageState::new(vk::PipelineStageFlags::FRAGMENT_SHADER, vk::AccessFlags::empty(), vk::ImageLayout::UNDEFINED);
+
+// What a pass writes: linear HDR radiance (before tone mapping) or display referred colors in the output format
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum PostStage {
+    Hdr,
+    Ldr,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct PostFormats {
+    pub hdr: vk::Format,
+    pub output: vk::Format,
+}
+
+impl PostFormats {
+    pub fn of(&self, stage: PostStage) -> vk::Format {
+        match stage {
+            PostStage::Hdr => self.hdr,
+            PostStage::Ldr => self.output,
+        }
+    }
+}
+
+// Everything a pass records with, for one frame
+pub struct PostContext<'c> {
+    pub device: &'c Device,
+    pub command_buffer: vk::CommandBuffer,
+    // reset with the stack's frame
+    pub descriptor_allocator: &'c mut DescriptorAllocator,
+    pub settings: &'c PostSettings,
+    pub extent: vk::Extent2D,
+    // motion vectors of the scene in uv units, from the previous frame's position to this one's
+    pub velocity: vk::ImageView,
+    // linear filtering, clamped to the edges
+    pub linear_sampler: vk::Sampler,
+    // seconds since the last frame
+    pub delta_time: f32,
+    // frames recorded by the stack before this one
+    pub frame_number: u64,
+}
+
+// One full screen effect of the chain
+pub trait PostPass: Any {
+    fn name(&self) -> &str;
+
+    fn stage(&self) -> PostStage;
+
+    // (re)creates what depends on the formats and the extent ; called when the pass joins a stack and on every resize
+    fn prepare(&mut self, _vk_app: &VkApp, _formats: PostFormats, _extent: vk::Extent2D) -> RendererResult<()> {
+        Ok(())
+    }
+
+    // disabled passes are skipped, their input going to the next pass as is
+    fn enabled(&self, _settings: &PostSettings) -> bool {
+        true
+    }
+
+    // Reads `input` (SHADER_READ_ONLY_OPTIMAL) and writes every pixel of `output` (COLOR_ATTACHMENT_OPTIMAL, contents
+    // undefined), both of the context's extent ; outside of any pass, the pass begins and ends its own rendering
+    fn record(&mut self, context: &mut PostContext, input: vk::ImageView, output: vk::ImageView) -> RendererResult<()>;
+}
@@ -0,0 +1,316 @@
+use std::sync::Arc;
+
+use ash::{vk, Device};
+use glam::Vec2;
+use tracing::info;
+
+use crate::vulkan_api::VkApp;
+use crate::vulkan_api::descriptors::DescriptorAllocator;
+use crate::vulkan_api::image::{GpuImage, ImageDesc, ImageState};
+use crate::vulkan_api::sampler::{Sampler, SamplerDesc};
+
+use crate::renderer::{RendererError, RendererResult, TargetFormats};
+
+use super::{
+    Antialiasing, BloomPass, BloomSettings, ColorLut, Exposure, FxaaPass, PostContext, PostFormats, PostPass, PostStage, TaaPass,
+    TonemapPass, Tonemapper, DISCARD_AFTER_READ,
+};
+
+// what the scene renders into
+pub const HDR_FORMAT: vk::Format = vk::Format::R16G16B16A16_SFLOAT;
+pub const VELOCITY_FORMAT: vk::Format = vk::Format::R16G16_SFLOAT;
+// frames before the jitter pattern repeats
+const JITTER_PHASES: u64 = 8;
+
+// Read by the built in passes every frame, changes apply from the next record
+#[derive(Clone, Default)]
+pub struct PostSettings {
+    pub exposure: Exposure,
+    pub bloom: BloomSettings,
+    pub tonemapper: Tonemapper,
+    pub antialiasing: Antialiasing,
+    // applied after tone mapping ; the stack may still read the lut of frames in flight when it is replaced
+    pub color_grading: Option<Arc<ColorLut>>,
+}
+
+// Images the chain runs through, recreated on resize
+struct PostTargets {
+    scene: GpuImage,
+    velocity: GpuImage,
+    // ping pong intermediates of each stage, the last pass writes the output instead
+    hdr: [GpuImage; 2],
+    ldr: [GpuImage; 2],
+}
+
+// Where a pass' input lives
+#[derive(Copy, Clone, PartialEq, Eq)]
+enum Slot {
+    Scene,
+    Hdr(usize),
+    Ldr(usize),
+}
+
+// The post processing chain. The scene renders into the HDR scene target and its motion vectors (begin_scene,
+// scene_attachments), then record runs the enabled passes in order, the last one writing the output image. Passes
+// writing HDR must come before the ones writing the output format ; the default chain is taa, bloom, tonemap, fxaa.
+// The scene target is single sampled, MSAA scenes resolve into it (and into the velocity target).
+// The chain records its own transitions instead of render graph passes: which targets a pass reads and writes is only
+// known once the enabled passes are picked while recording, and the passes keep mutable state (the TAA history) and
+// write their descriptors fallibly, neither of which a graph callback allows. In a graph, run record from one pass
+// writing `output`.
+pub struct PostProcessStack {
+    device: Device,
+    formats: PostFormats,
+    extent: vk::Extent2D,
+    pub settings: PostSettings,
+    passes: Vec<Box<dyn PostPass>>,
+    targets: PostTargets,
+    linear_sampler: Sampler,
+    // one per frame in flight
+    descriptor_allocators: Vec<DescriptorAllocator>,
+    frame_number: u64,
+}
+
+impl PostProcessStack {
+    // `output_format` is the format of the images record writes to, usually the swapchain's
+    pub fn new(vk_app: &VkApp, output_format: vk::Format, extent: vk::Extent2D, settings: PostSettings) -> RendererResult<Self> {
+        let formats = PostFormats { hdr: HDR_FORMAT, output: output_format };
+        let passes: Vec<Box<dyn PostPass>> = vec![
+            Box::new(TaaPass::new(vk_app, formats)?),
+            Box::new(BloomPass::new(vk_app, formats)?),
+            Box::new(TonemapPass::new(vk_app, formats)?),
+            Box::new(FxaaPass::new(vk_app, formats)?),
+        ];
+        let descriptor_allocators = (0..vk_app.vk_prop().renderer_config.frames_in_flight.max(1))
+            .map(|_| DescriptorAllocator::new(vk_app.device()))
+            .collect::<Result<Vec<_>, _>>()?;
+        let mut stack = PostProcessStack {
+            device: vk_app.device().clone(),
+            formats,
+            extent,
+            settings,
+            passes,
+            targets: PostTargets::new(vk_app, formats, extent)?,
+            linear_sampler: Sampler::new(vk_app, SamplerDesc::linear_clamp(), "post processing sampler")?,
+            descriptor_allocators,
+            frame_number: 0,
+        };
+        for pass in stack.passes.iter_mut() {
+            pass.prepare(vk_app, formats, extent)?;
+        }
+        info!(target: "torii::renderer", output_format = ?output_format, width = extent.width, height = extent.height, "Created post processing stack");
+        Ok(stack)
+    }
+
+    // Recreates the targets and the passes' resources ; nothing recorded with the previous ones may still be in flight
+    pub fn resize(&mut self, vk_app: &VkApp, extent: vk::Extent2D) -> RendererResult<()> {
+        self.targets = PostTargets::new(vk_app, self.formats, extent)?;
+        self.extent = extent;
+        for pass in self.passes.iter_mut() {
+            pass.prepare(vk_app, self.formats, extent)?;
+        }
+        Ok(())
+    }
+
+    // Adds `pass` at the end of its stage: HDR passes before the first pass writing the output format, the others last
+    pub fn add_pass(&mut self, vk_app: &VkApp, pass: Box<dyn PostPass>) -> RendererResult<()> {
+        let index = match pass.stage() {
+            PostStage::Hdr => self.passes.iter().position(|pass| pass.stage() == PostStage::Ldr).unwrap_or(self.passes.len()),
+            PostStage::Ldr => self.passes.len(),
+        };
+        self.insert_pass(vk_app, index, pass)
+    }
+
+    pub fn insert_pass(&mut self, vk_app: &VkApp, index: usize, mut pass: Box<dyn PostPass>) -> RendererResult<()> {
+        pass.prepare(vk_app, self.formats, self.extent)?;
+        self.passes.insert(index.min(self.passes.len()), pass);
+        Ok(())
+    }
+
+    // the pass may still be used by frames in flight, drop it once they completed
+    pub fn remove_pass(&mut self, name: &str) -> Option<Box<dyn PostPass>> {
+        let index = self.passes.iter().position(|pass| pass.name() == name)?;
+        Some(self.passes.remove(index))
+    }
+
+    // the first pass named `name`, if it is a `T`
+    pub fn pass<T: PostPass>(&self, name: &str) -> Option<&T> {
+        let pass = self.passes.iter().find(|pass| pass.name() == name)?;
+        (pass.as_ref() as &dyn std::any::Any).downcast_ref()
+    }
+
+    pub fn pass_mut<T: PostPass>(&mut self, name: &str) -> Option<&mut T> {
+        let pass = self.passes.iter_mut().find(|pass| pass.name() == name)?;
+        (pass.as_mut() as &mut dyn std::any::Any).downcast_mut()
+    }
+
+    // What renderers drawing the scene for this stack are created with
+    pub fn scene_formats(&self, depth: vk::Format) -> TargetFormats {
+        TargetFormats {
+            color: HDR_FORMAT,
+            depth,
+            samples: vk::SampleCountFlags::TYPE_1,
+            velocity: VELOCITY_FORMAT,
+        }
+    }
+
+    // Subpixel offset for the camera of the frame about to be rendered (see Camera::jitter), a Halton (2, 3) sequence
+    // while temporal AA is on and zero otherwise
+    pub fn jitter(&self) -> Vec2 {
+        if self.settings.antialiasing != Antialiasing::Taa {
+            return Vec2::ZERO;
+        }
+        let index = self.frame_number % JITTER_PHASES + 1;
+        let offset = Vec2::new(halton(index, 2), halton(index, 3)) - 0.5;
+        offset * 2.0 / Vec2::new(self.extent.width.max(1) as f32, self.extent.height.max(1) as f32)
+    }
+
+    // Makes the scene and velocity targets renderable, outside of any pass ; their previous contents are discarded
+    pub fn begin_scene(&self, command_buffer: vk::CommandBuffer) {
+        for image in [&self.targets.scene, &self.targets.velocity] {
+            image.cmd_transition(&self.device, command_buffer, DISCARD_AFTER_READ, ImageState::COLOR_ATTACHMENT);
+        }
+    }
+
+    // Color attachments of the scene pass, the scene target then the velocity target (cleared to no motion)
+    pub fn scene_attachments(&self, clear_color: Option<[f32; 4]>) -> [vk::RenderingAttachmentInfo<'static>; 2] {
+        let attachment = |view: vk::ImageView| {
+            vk::RenderingAttachmentInfo::default()
+                .image_view(view)
+                .image_layout(vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL)
+                .store_op(vk::AttachmentStoreOp::STORE)
+        };
+        let scene = match clear_color {
+            Some(float32) => attachment(self.targets.scene.view())
+                .load_op(vk::AttachmentLoadOp::CLEAR)
+                .clear_value(vk::ClearValue { color: vk::ClearColorValue { float32 } }),
+            None => attachment(self.targets.scene.view()).load_op(vk::AttachmentLoadOp::DONT_CARE),
+        };
+        let velocity = attachment(self.targets.velocity.view())
+            .load_op(vk::AttachmentLoadOp::CLEAR)
+            .clear_value(vk::ClearValue { color: vk::ClearColorValue { float32: [0.0; 4] } });
+        [scene, velocity]
+    }
+
+    // Runs the chain on the scene rendered since begin_scene, writing `output` (COLOR_ATTACHMENT_OPTIMAL, of the
+    // output format and the stack's extent) ; outside of any pass. The previous frame that used the same
+    // `frame_index` slot must have completed on the GPU.
+    pub fn record(&mut self, command_buffer: vk::CommandBuffer, frame_index: usize, output: vk::ImageView, delta_time: f32) -> RendererResult<()> {
+        let enabled: Vec<usize> = (0..self.passes.len()).filter(|&index| self.passes[index].enabled(&self.settings)).collect();
+        self.check_chain(&enabled)?;
+
+        let frame = frame_index % self.descriptor_allocators.len();
+        let descriptor_allocator = &mut self.descriptor_allocators[frame];
+        descriptor_allocator.reset()?;
+        let targets = &self.targets;
+        for image in [&targets.scene, &targets.velocity] {
+            image.cmd_transition(&self.device, command_buffer, ImageState::COLOR_ATTACHMENT, ImageState::SHADER_READ);
+        }
+        let mut context = PostContext {
+            device: &self.device,
+            command_buffer,
+            descriptor_allocator,
+            settings: &self.settings,
+            extent: self.extent,
+            velocity: targets.velocity.view(),
+            linear_sampler: self.linear_sampler.sampler(),
+            delta_time,
+            frame_number: self.frame_number,
+        };
+
+        let mut input = Slot::Scene;
+        for (position, &index) in enabled.iter().enumerate() {
+            let pass = &mut self.passes[index];
+            let input_view = targets.image(input).view();
+            if position + 1 == enabled.len() {
+                pass.record(&mut context, input_view, output)?;
+                break;
+            }
+            let slot = match (pass.stage(), input) {
+                (PostStage::Hdr, Slot::Hdr(current)) => Slot::Hdr(1 - current),
+                (PostStage::Hdr, _) => Slot::Hdr(0),
+                (PostStage::Ldr, Slot::Ldr(current)) => Slot::Ldr(1 - current),
+                (PostStage::Ldr, _) => Slot::Ldr(0),
+            };
+            let image = targets.image(slot);
+            image.cmd_transition(&self.device, command_buffer, DISCARD_AFTER_READ, ImageState::COLOR_ATTACHMENT);
+            pass.record(&mut context, input_view, image.view())?;
+            image.cmd_transition(&self.device, command_buffer, ImageState::COLOR_ATTACHMENT, ImageState::SHADER_READ);
+            input = slot;
+        }
+        self.frame_number += 1;
+        Ok(())
+    }
+
+    // the HDR passes first, and a last pass writing the output format
+    fn check_chain(&self, enabled: &[usize]) -> RendererResult<()> {
+        let stages: Vec<PostStage> = enabled.iter().map(|&index| self.passes[index].stage()).collect();
+        if let Some(position) = stages.windows(2).position(|pair| pair == [PostStage::Ldr, PostStage::Hdr]) {
+            let name = self.passes[enabled[position + 1]].name();
+            return Err(RendererError::PostChainError(format!("HDR pass {name} comes after the tone mapping")));
+        }
+        if stages.last() != Some(&PostStage::Ldr) {
+            return Err(RendererError::PostChainError("the last enabled pass must write the output format (tone mapping)".to_owned()));
+        }
+        Ok(())
+    }
+
+    // GETTERS
+
+    pub fn formats(&self) -> PostFormats {
+        self.formats
+    }
+
+    pub fn extent(&self) -> vk::Extent2D {
+        self.extent
+    }
+
+    pub fn scene_target(&self) -> &GpuImage {
+        &self.targets.scene
+    }
+
+    pub fn velocity_target(&self) -> &GpuImage {
+        &self.targets.velocity
+    }
+
+    pub fn pass_names(&self) -> impl Iterator<Item = &str> {
+        self.passes.iter().map(|pass| pass.name())
+    }
+
+    // frames recorded so far
+    pub fn frame_number(&self) -> u64 {
+        self.frame_number
+    }
+}
+
+impl PostTargets {
+    fn new(vk_app: &VkApp, formats: PostFormats, extent: vk::Extent2D) -> RendererResult<Self> {
+        let usage = vk::ImageUsageFlags::COLOR_ATTACHMENT | vk::ImageUsageFlags::SAMPLED;
+        let image = |format: vk::Format, name: &str| GpuImage::new(vk_app, ImageDesc::new(format, extent), usage, name);
+        Ok(PostTargets {
+            scene: image(formats.hdr, "post scene target")?,
+            velocity: image(VELOCITY_FORMAT, "post velocity target")?,
+            hdr: [image(formats.hdr, "post hdr target [0]")?, image(formats.hdr, "post hdr target [1]")?],
+            ldr: [image(formats.output, "post ldr target [0]")?, image(formats.output, "post ldr target [1]")?],
+        })
+    }
+
+    fn image(&self, slot: Slot) -> &GpuImage {
+        match slot {
+            Slot::Scene => &self.scene,
+            Slot::Hdr(index) => &self.hdr[index],
+            Slot::Ldr(index) => &self.ldr[index],
+        }
+    }
+}
+
+// radical inverse of `index` in `base`, in [0, 1)
+fn halton(mut index: u64, base: u64) -> f32 {
+    let (mut result, mut fraction) = (0.0, 1.0);
+    while index > 0 {
+        fraction /= base as f32;
+        result += fraction * (index % base) as f32;
+        index /= base;
+    }
+    result
+}
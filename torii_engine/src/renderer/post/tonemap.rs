@@ -0,0 +1,415 @@
+use std::path::Path;
+
+use ash::{vk, Device};
+
+use crate::vulkan_api::VkApp;
+use crate::vulkan_api::compute::ComputePipeline;
+use crate::vulkan_api::debug_utils::name_from_asset_path;
+use crate::vulkan_api::descriptors::{DescriptorBinding, DescriptorBindings};
+use crate::vulkan_api::memory::{as_bytes, GpuBuffer};
+use crate::vulkan_api::pipeline::BlendMode;
+use crate::vulkan_api::shader::{Shader, ShaderStage};
+
+use crate::renderer::texture::Texture;
+use crate::renderer::{RendererError, RendererResult};
+
+use super::{FullscreenPass, PostContext, PostFormats, PostPass, PostStage};
+
+const TONEMAP_SHADER: &str = include_str!("../shaders/post/tonemap.frag");
+const HISTOGRAM_SHADER: &str = include_str!("../shaders/post/histogram.wgsl");
+const EXPOSURE_SHADER: &str = include_str!("../shaders/post/exposure.wgsl");
+
+const HISTOGRAM_BINS: usize = 256;
+// the shader reads the lut with a single 2D texture, its width is size²
+const MAX_LUT_SIZE: u32 = 64;
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum Tonemapper {
+    // clamps to [0, 1]
+    None,
+    #[default]
+    Aces,
+    AgX,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Exposure {
+    // scale applied to the scene radiance
+    Manual(f32),
+    // metered from the luminance histogram of every frame
+    Auto(AutoExposure),
+}
+
+impl Default for Exposure {
+    fn default() -> Self {
+        Exposure::Manual(1.0)
+    }
+}
+
+impl Exposure {
+    // photographic exposure value at ISO 100, e.g. ~15 for a sunny day and ~7 indoors, for radiance in physical units
+    pub fn from_ev100(ev100: f32) -> Self {
+        Exposure::Manual(1.0 / (1.2 * ev100.exp2()))
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct AutoExposure {
+    // log2 luminance range the histogram covers, what's outside is clamped to it
+    pub min_log_luminance: f32,
+    pub max_log_luminance: f32,
+    // how fast the eye adapts, 1 / seconds
+    pub speed: f32,
+    // in stops, positive brightens
+    pub compensation: f32,
+}
+
+impl Default for AutoExposure {
+    fn default() -> Self {
+        AutoExposure {
+            min_log_luminance: -10.0,
+            max_log_luminance: 10.0,
+            speed: 1.5,
+            compensation: 0.0,
+        }
+    }
+}
+
+// A 3D color lookup table on the CPU: size³ RGB entries, red varying fastest then green then blue (the .cube order),
+// mapping sRGB encoded colors
+#[derive(Clone, Debug, PartialEq)]
+pub struct ColorLutData {
+    pub size: u32,
+    pub values: Vec<[f32; 3]>,
+}
+
+impl ColorLutData {
+    // maps every color to itself, a starting point for grading tools
+    pub fn identity(size: u32) -> Self {
+        let size = size.clamp(2, MAX_LUT_SIZE);
+        let scale = 1.0 / (size - 1) as f32;
+        let values = (0..size * size * size)
+            .map(|index| [index % size, index / size % size, index / (size * size)].map(|channel| channel as f32 * scale))
+            .collect();
+        ColorLutData { size, values }
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> RendererResult<Self> {
+        let path = path.as_ref();
+        let text = std::fs::read_to_string(path).map_err(|source| RendererError::IoError { path: path.to_path_buf(), source })?;
+        Self::parse_cube(&text)
+    }
+
+    // Adobe / Resolve .cube text ; only 3D tables over the default [0, 1] domain
+    pub fn parse_cube(text: &str) -> RendererResult<Self> {
+        let error = |message: String| RendererError::CubeLutParseError(message);
+        let mut size = None;
+        let mut values = vec![];
+        for (number, line) in text.lines().enumerate().map(|(index, line)| (index + 1, line.trim())) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let mut words = line.split_whitespace();
+            let keyword = words.next().unwrap_or_default();
+            match keyword {
+                "TITLE" => {},
+                "LUT_3D_SIZE" => {
+                    let parsed = words.next().and_then(|word| word.parse::<u32>().ok());
+                    match parsed {
+                        Some(parsed) if (2..=MAX_LUT_SIZE).contains(&parsed) => size = Some(parsed),
+                        _ => return Err(error(format!("line {number}: LUT_3D_SIZE must be between 2 and {MAX_LUT_SIZE}"))),
+                    }
+                },
+                "LUT_1D_SIZE" => return Err(error(format!("line {number}: 1D tables are not supported"))),
+                "DOMAIN_MIN" | "DOMAIN_MAX" => {
+                    let expected = if keyword == "DOMAIN_MIN" { 0.0 } else { 1.0 };
+                    if !words.all(|word| word.parse::<f32>().ok() == Some(expected)) {
+                        return Err(error(format!("line {number}: only the default [0, 1] domain is supported")));
+                    }
+                },
+                _ => {
+                    let channels: Vec<f32> = line.split_whitespace().filter_map(|word| word.parse().ok()).collect();
+                    match channels[..] {
+                        [r, g, b] if line.split_whitespace().count() == 3 => values.push([r, g, b]),
+                        _ => return Err(error(format!("line {number}: expected 3 numbers or a keyword, got {line}"))),
+                    }
+                },
+            }
+        }
+        let size = size.ok_or_else(|| error("missing LUT_3D_SIZE".to_owned()))?;
+        let expected = (size * size * size) as usize;
+        if values.len() != expected {
+            return Err(error(format!("{} entries for a size of {size}, expected {expected}", values.len())));
+        }
+        Ok(ColorLutData { size, values })
+    }
+}
+
+// A color grading lut on the GPU, stored as a strip of `size` slices side by side (one per blue value) so it samples
+// as a 2D texture
+pub struct ColorLut {
+    texture: Texture,
+    size: u32,
+}
+
+impl ColorLut {
+    pub fn new(vk_app: &VkApp, data: &ColorLutData, name: &str) -> RendererResult<Self> {
+        let size = data.size as usize;
+        if data.values.len() != size * size * size {
+            return Err(RendererError::TextureDataSizeError { expected: size * size * size, given: data.values.len() });
+        }
+        // row g holds, for every blue slice b, the reds of (b, g)
+        let mut pixels = vec![[0.0; 4]; size * size * size];
+        for (index, &[r, g, b]) in data.values.iter().enumerate() {
+            let (red, green, blue) = (index % size, index / size % size, index / (size * size));
+            pixels[green * size * size + blue * size + red] = [r, g, b, 1.0];
+        }
+        let texture = Texture::from_rgba_f32(vk_app, (size * size) as u32, size as u32, &pixels, name)?;
+        Ok(ColorLut { texture, size: data.size })
+    }
+
+    pub fn load(vk_app: &VkApp, path: impl AsRef<Path>) -> RendererResult<Self> {
+        let path = path.as_ref();
+        Self::new(vk_app, &ColorLutData::load(path)?, &format!("{} [color lut]", name_from_asset_path(path)))
+    }
+
+    // GETTERS
+
+    pub fn texture(&self) -> &Texture {
+        &self.texture
+    }
+
+    pub fn size(&self) -> u32 {
+        self.size
+    }
+}
+
+// `Histogram` push constants of histogram.wgsl
+#[repr(C)]
+#[derive(Copy, Clone)]
+struct HistogramConstants {
+    min_log_luminance: f32,
+    inverse_log_luminance_range: f32,
+}
+
+// `Adaptation` push constants of exposure.wgsl
+#[repr(C)]
+#[derive(Copy, Clone)]
+struct AdaptationConstants {
+    min_log_luminance: f32,
+    log_luminance_range: f32,
+    rate: f32,
+    compensation: f32,
+    pixel_count: u32,
+}
+
+// `Tonemap` push constant block of tonemap.frag
+#[repr(C)]
+#[derive(Copy, Clone)]
+struct TonemapConstants {
+    manual_exposure: f32,
+    auto_exposure: u32,
+    tonemapper: u32,
+    lut_size: u32,
+    encode_srgb: u32,
+}
+
+// Exposure, tone mapping and color grading, the step from HDR radiance to display referred colors ; with auto
+// exposure, the histogram and adaptation compute passes run first
+pub struct TonemapPass {
+    device: Device,
+    pass: FullscreenPass,
+    histogram_pipeline: ComputePipeline,
+    exposure_pipeline: ComputePipeline,
+    // kept across frames, the exposure adapts from the last one
+    histogram_buffer: GpuBuffer,
+    exposure_buffer: GpuBuffer,
+    // bound when no grading lut is set
+    fallback_lut: Texture,
+    encode_srgb: bool,
+}
+
+impl TonemapPass {
+    pub fn new(vk_app: &VkApp, formats: PostFormats) -> RendererResult<Self> {
+        let fragment = Shader::from_glsl(TONEMAP_SHADER, ShaderStage::Fragment)?;
+        let histogram_shader = Shader::from_wgsl(HISTOGRAM_SHADER, ShaderStage::Compute)?;
+        let exposure_shader = Shader::from_wgsl(EXPOSURE_SHADER, ShaderStage::Compute)?;
+        let usage = vk::BufferUsageFlags::STORAGE_BUFFER;
+        // an adapted luminance of zero makes the first metered frame snap to its target
+        Ok(TonemapPass {
+            device: vk_app.device().clone(),
+            pass: FullscreenPass::new(vk_app, &fragment, &[formats.output], BlendMode::Opaque, "tone mapping")?,
+            histogram_pipeline: ComputePipeline::new(vk_app, &histogram_shader, "luminance histogram")?,
+            exposure_pipeline: ComputePipeline::new(vk_app, &exposure_shader, "exposure adaptation")?,
+            histogram_buffer: GpuBuffer::with_data(vk_app, &[0; HISTOGRAM_BINS * 4], usage, "luminance histogram")?,
+            exposure_buffer: GpuBuffer::with_data(vk_app, &[0; 16], usage, "exposure")?,
+            fallback_lut: Texture::solid(vk_app, [255; 4], false, "fallback color lut")?,
+            encode_srgb: !is_srgb_format(formats.output),
+        })
+    }
+
+    fn record_auto_exposure(&self, context: &mut PostContext, input: vk::ImageView, settings: AutoExposure) -> RendererResult<()> {
+        let device = &self.device;
+        let command_buffer = context.command_buffer;
+        let log_luminance_range = (settings.max_log_luminance - settings.min_log_luminance).max(1e-3);
+        let histogram = HistogramConstants {
+            min_log_luminance: settings.min_log_luminance,
+            inverse_log_luminance_range: 1.0 / log_luminance_range,
+        };
+        let adaptation = AdaptationConstants {
+            min_log_luminance: settings.min_log_luminance,
+            log_luminance_range,
+            rate: 1.0 - (-context.delta_time.max(0.0) * settings.speed).exp(),
+            compensation: settings.compensation,
+            pixel_count: context.extent.width * context.extent.height,
+        };
+        let histogram_bindings = DescriptorBindings::new()
+            .bind(0, 0, DescriptorBinding::sampled_image(input))
+            .bind(0, 1, DescriptorBinding::whole_buffer(self.histogram_buffer.buffer()))
+            .with_push_constants(as_bytes(&[histogram]));
+        let exposure_bindings = DescriptorBindings::new()
+            .bind(0, 0, DescriptorBinding::whole_buffer(self.histogram_buffer.buffer()))
+            .bind(0, 1, DescriptorBinding::whole_buffer(self.exposure_buffer.buffer()))
+            .with_push_constants(as_bytes(&[adaptation]));
+        let [x, y, z] = self.histogram_pipeline.group_count([context.extent.width, context.extent.height, 1]);
+
+        // the last frame's adaptation cleared the histogram and its tone mapping read the exposure
+        memory_barrier(
+            device,
+            command_buffer,
+            vk::PipelineStageFlags::COMPUTE_SHADER | vk::PipelineStageFlags::FRAGMENT_SHADER,
+            vk::PipelineStageFlags::COMPUTE_SHADER,
+        );
+        unsafe {
+            device.cmd_bind_pipeline(command_buffer, vk::PipelineBindPoint::COMPUTE, self.histogram_pipeline.pipeline());
+        }
+        context.descriptor_allocator.bind(command_buffer, vk::PipelineBindPoint::COMPUTE, self.histogram_pipeline.layout(), &histogram_bindings)?;
+        unsafe {
+            device.cmd_dispatch(command_buffer, x, y, z);
+        }
+        memory_barrier(device, command_buffer, vk::PipelineStageFlags::COMPUTE_SHADER, vk::PipelineStageFlags::COMPUTE_SHADER);
+        unsafe {
+            device.cmd_bind_pipeline(command_buffer, vk::PipelineBindPoint::COMPUTE, self.exposure_pipeline.pipeline());
+        }
+        context.descriptor_allocator.bind(command_buffer, vk::PipelineBindPoint::COMPUTE, self.exposure_pipeline.layout(), &exposure_bindings)?;
+        unsafe {
+            device.cmd_dispatch(command_buffer, 1, 1, 1);
+        }
+        memory_barrier(device, command_buffer, vk::PipelineStageFlags::COMPUTE_SHADER, vk::PipelineStageFlags::FRAGMENT_SHADER);
+        Ok(())
+    }
+
+    // GETTERS
+
+    // adapted luminance then exposure, as vec4 ; written on the GPU by auto exposure
+    pub fn exposure_buffer(&self) -> &GpuBuffer {
+        &self.exposure_buffer
+    }
+}
+
+impl PostPass for TonemapPass {
+    fn name(&self) -> &str {
+        "tonemap"
+    }
+
+    fn stage(&self) -> PostStage {
+        PostStage::Ldr
+    }
+
+    fn record(&mut self, context: &mut PostContext, input: vk::ImageView, output: vk::ImageView) -> RendererResult<()> {
+        let settings = context.settings;
+        let (manual_exposure, auto_exposure) = match settings.exposure {
+            Exposure::Manual(exposure) => (exposure, None),
+            Exposure::Auto(auto_exposure) => (1.0, Some(auto_exposure)),
+        };
+        if let Some(auto_exposure) = auto_exposure {
+            self.record_auto_exposure(context, input, auto_exposure)?;
+        }
+        let grading = settings.color_grading.as_ref();
+        let constants = TonemapConstants {
+            manual_exposure,
+            auto_exposure: auto_exposure.is_some() as u32,
+            tonemapper: match settings.tonemapper {
+                Tonemapper::None => 0,
+                Tonemapper::Aces => 1,
+                Tonemapper::AgX => 2,
+            },
+            lut_size: grading.map_or(0, |lut| lut.size()),
+            encode_srgb: self.encode_srgb as u32,
+        };
+        let lut_view = grading.map_or(self.fallback_lut.view(), |lut| lut.texture().view());
+        let bindings = DescriptorBindings::new()
+            .bind(0, 0, DescriptorBinding::sampled_image(input))
+            .bind(0, 1, DescriptorBinding::sampled_image(lut_view))
+            .bind(0, 2, DescriptorBinding::Sampler(context.linear_sampler))
+            .bind(0, 3, DescriptorBinding::whole_buffer(self.exposure_buffer.buffer()))
+            .with_push_constants(as_bytes(&[constants]));
+        self.pass.draw(context, &[output], context.extent, false, &bindings)
+    }
+}
+
+fn memory_barrier(device: &Device, command_buffer: vk::CommandBuffer, src_stage: vk::PipelineStageFlags, dst_stage: vk::PipelineStageFlags) {
+    let barrier = vk::MemoryBarrier::default()
+        .src_access_mask(vk::AccessFlags::SHADER_WRITE)
+        .dst_access_mask(vk::AccessFlags::SHADER_READ | vk::AccessFlags::SHADER_WRITE);
+    unsafe {
+        device.cmd_pipeline_barrier(command_buffer, src_stage, dst_stage, vk::DependencyFlags::empty(), std::slice::from_ref(&barrier), &[], &[]);
+    }
+}
+
+// the hardware encodes on write, otherwise the shader does
+pub(crate) fn is_srgb_format(format: vk::Format) -> bool {
+    matches!(
+        format,
+        vk::Format::R8G8B8A8_SRGB | vk::Format::B8G8R8A8_SRGB | vk::Format::A8B8G8R8_SRGB_PACK32 | vk::Format::R8G8B8_SRGB | vk::Format::B8G8R8_SRGB
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // the identity of size 2 as a .cube file, with `header` after the size
+    fn cube(header: &str) -> String {
+        let entries: Vec<String> = ColorLutData::identity(2).values.iter().map(|[r, g, b]| format!("{r} {g} {b}")).collect();
+        format!("# graded\nTITLE \"identity\"\nLUT_3D_SIZE 2\n{header}\n\n{}\n", entries.join("\n"))
+    }
+
+    fn parse_error(text: &str) -> String {
+        match ColorLutData::parse_cube(text) {
+            Err(RendererError::CubeLutParseError(message)) => message,
+            other => panic!("expected a parse error, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn parses_a_3d_table() {
+        assert_eq!(ColorLutData::parse_cube(&cube("")).unwrap(), ColorLutData::identity(2));
+        // the default domain may be spelled out
+        let explicit = cube("DOMAIN_MIN 0 0 0\nDOMAIN_MAX 1.0 1.0 1.0");
+        assert_eq!(ColorLutData::parse_cube(&explicit).unwrap(), ColorLutData::identity(2));
+    }
+
+    #[test]
+    fn entry_count_must_match_the_size() {
+        let missing = cube("").replace("1 1 1\n", "");
+        assert!(parse_error(&missing).contains("7 entries for a size of 2, expected 8"));
+        let extra = cube("") + "0.5 0.5 0.5\n";
+        assert!(parse_error(&extra).contains("9 entries"));
+        assert!(parse_error("0 0 0\n").contains("missing LUT_3D_SIZE"));
+        assert!(parse_error(&cube("").replace("LUT_3D_SIZE 2", "LUT_3D_SIZE 65")).contains("between 2 and 64"));
+    }
+
+    #[test]
+    fn only_the_default_domain_is_supported() {
+        assert!(parse_error(&cube("DOMAIN_MIN -0.1 0 0")).contains("line 4: only the default [0, 1] domain"));
+        assert!(parse_error(&cube("DOMAIN_MAX 1 1 4")).contains("only the default [0, 1] domain"));
+    }
+
+    #[test]
+    fn malformed_lines_are_rejected() {
+        assert!(parse_error(&cube("LUT_1D_SIZE 16")).contains("1D tables are not supported"));
+        assert!(parse_error(&cube("0.5 0.5")).contains("expected 3 numbers or a keyword"));
+        assert!(parse_error(&cube("0.5 0.5 0.5 0.5")).contains("expected 3 numbers or a keyword"));
+    }
+}
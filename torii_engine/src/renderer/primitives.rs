@@ -0,0 +1,311 @@
+// Built-in shapes for prototyping, with normals, tangents and uvs ; centered on the origin, +Y up, counter clockwise
+// front faces seen from outside. Subdivision counts are clamped to what the shape needs to stay closed.
+
+use std::collections::HashMap;
+use std::f32::consts::{FRAC_PI_2, PI, TAU};
+
+use glam::{Vec2, Vec3};
+
+use super::mesh::{MeshData, Vertex};
+
+// Axis aligned cube of side `size`, each face a grid of `subdivisions` x `subdivisions` quads with its own uvs
+pub fn cube(size: f32, subdivisions: u32) -> MeshData {
+    let subdivisions = subdivisions.max(1);
+    let half = size * 0.5;
+    let mut mesh = MeshData::default();
+    // normal, then the axes u (right) and v (down) follow seen from outside
+    let faces = [
+        (Vec3::X, Vec3::NEG_Z, Vec3::NEG_Y),
+        (Vec3::NEG_X, Vec3::Z, Vec3::NEG_Y),
+        (Vec3::Y, Vec3::X, Vec3::Z),
+        (Vec3::NEG_Y, Vec3::X, Vec3::NEG_Z),
+        (Vec3::Z, Vec3::X, Vec3::NEG_Y),
+        (Vec3::NEG_Z, Vec3::NEG_X, Vec3::NEG_Y),
+    ];
+    for (normal, u_axis, v_axis) in faces {
+        grid(&mut mesh, subdivisions, subdivisions, |_| false, |u, v| {
+            let position = normal * half + u_axis * ((u - 0.5) * size) + v_axis * ((v - 0.5) * size);
+            (position, normal)
+        });
+    }
+    mesh.compute_tangents();
+    mesh
+}
+
+// Flat grid on the XZ plane facing +Y, `width` along X and `depth` along Z ; uv (0, 0) at the -X -Z corner
+pub fn plane(width: f32, depth: f32, subdivisions_x: u32, subdivisions_z: u32) -> MeshData {
+    let mut mesh = MeshData::default();
+    grid(&mut mesh, subdivisions_x.max(1), subdivisions_z.max(1), |_| false, |u, v| {
+        (Vec3::new((u - 0.5) * width, 0.0, (v - 0.5) * depth), Vec3::Y)
+    });
+    mesh.compute_tangents();
+    mesh
+}
+
+// Sphere of `segments` meridians and `rings` parallels, u around the Y axis starting at +Z and v from the top pole
+pub fn uv_sphere(radius: f32, segments: u32, rings: u32) -> MeshData {
+    let rings = rings.max(2);
+    let profile = (0..=rings).map(|ring| {
+        let phi = ring as f32 / rings as f32 * PI;
+        let normal = Vec2::new(phi.sin(), phi.cos());
+        (normal * radius, normal)
+    });
+    let mut mesh = MeshData::default();
+    lathe(&mut mesh, segments.max(3), profile.collect());
+    mesh.compute_tangents();
+    mesh
+}
+
+// Sphere from an icosahedron whose triangles are split in 4 `subdivisions` times, evenly tessellated unlike the uv
+// sphere ; uvs are the uv sphere's, vertices on the seam are duplicated
+pub fn icosphere(radius: f32, subdivisions: u32) -> MeshData {
+    let t = (1.0 + 5f32.sqrt()) * 0.5;
+    let mut directions: Vec<Vec3> = [
+        (-1.0, t, 0.0), (1.0, t, 0.0), (-1.0, -t, 0.0), (1.0, -t, 0.0),
+        (0.0, -1.0, t), (0.0, 1.0, t), (0.0, -1.0, -t), (0.0, 1.0, -t),
+        (t, 0.0, -1.0), (t, 0.0, 1.0), (-t, 0.0, -1.0), (-t, 0.0, 1.0),
+    ]
+    .into_iter()
+    .map(|(x, y, z)| Vec3::new(x, y, z).normalize())
+    .collect();
+    let mut triangles: Vec<[u32; 3]> = vec![
+        [0, 11, 5], [0, 5, 1], [0, 1, 7], [0, 7, 10], [0, 10, 11],
+        [1, 5, 9], [5, 11, 4], [11, 10, 2], [10, 7, 6], [7, 1, 8],
+        [3, 9, 4], [3, 4, 2], [3, 2, 6], [3, 6, 8], [3, 8, 9],
+        [4, 9, 5], [2, 4, 11], [6, 2, 10], [8, 6, 7], [9, 8, 1],
+    ];
+    for _ in 0..subdivisions {
+        // edges shared by two triangles get a single midpoint
+        let mut midpoints: HashMap<(u32, u32), u32> = HashMap::new();
+        let mut midpoint = |a: u32, b: u32| -> u32 {
+            *midpoints.entry((a.min(b), a.max(b))).or_insert_with(|| {
+                directions.push((directions[a as usize] + directions[b as usize]).normalize());
+                directions.len() as u32 - 1
+            })
+        };
+        triangles = triangles
+            .iter()
+            .flat_map(|&[a, b, c]| {
+                let (ab, bc, ca) = (midpoint(a, b), midpoint(b, c), midpoint(c, a));
+                [[a, ab, ca], [b, bc, ab], [c, ca, bc], [ab, bc, ca]]
+            })
+            .collect();
+    }
+
+    let sphere_uv = |direction: Vec3| Vec2::new(direction.x.atan2(direction.z).rem_euclid(TAU) / TAU, direction.y.clamp(-1.0, 1.0).acos() / PI);
+    let vertices = directions
+        .iter()
+        .map(|&direction| Vertex::new((direction * radius).to_array(), direction.to_array(), sphere_uv(direction).to_array()))
+        .collect();
+    let mut mesh = MeshData::new(vertices, vec![]);
+    // the triangles straddling u = 0 / 1 would interpolate back across the whole texture, their vertices on the small
+    // u side get a copy moved past 1
+    let mut wrapped: HashMap<u32, u32> = HashMap::new();
+    for triangle in triangles.iter_mut() {
+        let us = triangle.map(|index| mesh.vertices[index as usize].uv[0]);
+        let max_u = us.iter().copied().fold(0.0, f32::max);
+        if max_u - us.iter().copied().fold(1.0, f32::min) <= 0.5 {
+            continue;
+        }
+        for (corner, u) in triangle.iter_mut().zip(us) {
+            if max_u - u > 0.5 {
+                *corner = *wrapped.entry(*corner).or_insert_with(|| {
+                    let mut vertex = mesh.vertices[*corner as usize];
+                    vertex.uv[0] += 1.0;
+                    mesh.vertices.push(vertex);
+                    mesh.vertices.len() as u32 - 1
+                });
+            }
+        }
+    }
+    mesh.indices = triangles.into_iter().flatten().collect();
+    mesh.compute_tangents();
+    mesh
+}
+
+// Cylinder along Y with `segments` around it, `height_segments` along it, and capped ends
+pub fn cylinder(radius: f32, height: f32, segments: u32, height_segments: u32) -> MeshData {
+    let segments = segments.max(3);
+    let height_segments = height_segments.max(1);
+    let half = height * 0.5;
+    let profile = (0..=height_segments).map(|row| (Vec2::new(radius, half - row as f32 / height_segments as f32 * height), Vec2::X));
+    let mut mesh = MeshData::default();
+    lathe(&mut mesh, segments, profile.collect());
+    disc(&mut mesh, radius, half, segments, true);
+    disc(&mut mesh, radius, -half, segments, false);
+    mesh.compute_tangents();
+    mesh
+}
+
+// Cone along Y, its tip at height / 2 and its capped base at -height / 2
+pub fn cone(radius: f32, height: f32, segments: u32, height_segments: u32) -> MeshData {
+    let segments = segments.max(3);
+    let height_segments = height_segments.max(1);
+    let half = height * 0.5;
+    let normal = Vec2::new(height, radius).normalize_or(Vec2::Y);
+    let profile = (0..=height_segments).map(|row| {
+        let along = row as f32 / height_segments as f32;
+        (Vec2::new(radius * along, half - along * height), normal)
+    });
+    let mut mesh = MeshData::default();
+    lathe(&mut mesh, segments, profile.collect());
+    disc(&mut mesh, radius, -half, segments, false);
+    mesh.compute_tangents();
+    mesh
+}
+
+// Cylinder of `height` between two hemispheres of `rings` parallels each, so `height + 2 * radius` tall ; v follows
+// the length of the profile, the texture isn't stretched on the caps
+pub fn capsule(radius: f32, height: f32, segments: u32, rings: u32) -> MeshData {
+    let rings = rings.max(1);
+    let half = height * 0.5;
+    let hemisphere = |top: bool| {
+        (0..=rings).map(move |ring| {
+            let phi = ring as f32 / rings as f32 * FRAC_PI_2 + if top { 0.0 } else { FRAC_PI_2 };
+            let normal = Vec2::new(phi.sin(), phi.cos());
+            (normal * radius + Vec2::new(0.0, if top { half } else { -half }), normal)
+        })
+    };
+    let profile = hemisphere(true).chain(hemisphere(false)).collect();
+    let mut mesh = MeshData::default();
+    lathe(&mut mesh, segments.max(3), profile);
+    mesh.compute_tangents();
+    mesh
+}
+
+// Torus around the Y axis ; `major_segments` around the ring, `minor_segments` around the tube
+pub fn torus(major_radius: f32, minor_radius: f32, major_segments: u32, minor_segments: u32) -> MeshData {
+    let mut mesh = MeshData::default();
+    grid(&mut mesh, major_segments.max(3), minor_segments.max(3), |_| false, |u, v| {
+        let (alpha, beta) = (u * TAU, v * TAU);
+        let around = Vec3::new(alpha.sin(), 0.0, alpha.cos());
+        // v starts on the outer equator and goes down first
+        let normal = around * beta.cos() - Vec3::Y * beta.sin();
+        (around * major_radius + normal * minor_radius, normal)
+    });
+    mesh.compute_tangents();
+    mesh
+}
+
+// Appends (columns + 1) x (rows + 1) vertices from `point(u, v) -> (position, normal)`, u going right and v down seen
+// from the front ; the degenerate triangles touching a `pinched` row (all its vertices at the same point) are skipped
+fn grid(mesh: &mut MeshData, columns: u32, rows: u32, pinched: impl Fn(u32) -> bool, point: impl Fn(f32, f32) -> (Vec3, Vec3)) {
+    let base = mesh.vertices.len() as u32;
+    for row in 0..=rows {
+        for column in 0..=columns {
+            let (u, v) = (column as f32 / columns as f32, row as f32 / rows as f32);
+            let (position, normal) = point(u, v);
+            // a point row only keeps one triangle per column, its vertices sit in the middle of their column
+            let uv_u = if pinched(row) { (column as f32 + 0.5) / columns as f32 } else { u };
+            mesh.vertices.push(Vertex::new(position.to_array(), normal.to_array(), [uv_u, v]));
+        }
+    }
+    let index = |column: u32, row: u32| base + row * (columns + 1) + column;
+    for row in 0..rows {
+        for column in 0..columns {
+            let (top_left, top_right) = (index(column, row), index(column + 1, row));
+            let (bottom_left, bottom_right) = (index(column, row + 1), index(column + 1, row + 1));
+            if !pinched(row) {
+                mesh.indices.extend([top_left, bottom_left, top_right]);
+            }
+            if !pinched(row + 1) {
+                mesh.indices.extend([if pinched(row) { top_left } else { top_right }, bottom_left, bottom_right]);
+            }
+        }
+    }
+}
+
+// Revolves `profile`, (radius, y) points from the top with their (radial, y) normals, around the Y axis ; v follows
+// the length of the profile
+fn lathe(mesh: &mut MeshData, segments: u32, profile: Vec<(Vec2, Vec2)>) {
+    let mut lengths = vec![0.0];
+    for pair in profile.windows(2) {
+        lengths.push(lengths[lengths.len() - 1] + pair[0].0.distance(pair[1].0));
+    }
+    let total = lengths[lengths.len() - 1].max(f32::EPSILON);
+    let rows = profile.len() as u32 - 1;
+    let base = mesh.vertices.len();
+    // rows on the axis (poles, tips) collapse to a point
+    let max_radius = profile.iter().fold(0.0f32, |max, (point, _)| max.max(point.x.abs()));
+    grid(mesh, segments, rows, |row| profile[row as usize].0.x.abs() <= max_radius * 1e-5, |u, v| {
+        let (point, normal) = profile[(v * rows as f32).round() as usize];
+        let around = Vec3::new((u * TAU).sin(), 0.0, (u * TAU).cos());
+        ((around * point.x).with_y(point.y), (around * normal.x).with_y(normal.y).normalize_or_zero())
+    });
+    // the grid spreads v evenly over the rows
+    for (offset, vertex) in mesh.vertices[base..].iter_mut().enumerate() {
+        vertex.uv[1] = lengths[offset / (segments as usize + 1)] / total;
+    }
+}
+
+// Flat cap at height `y` facing up or down, uvs mapped from above
+fn disc(mesh: &mut MeshData, radius: f32, y: f32, segments: u32, up: bool) {
+    let normal = if up { Vec3::Y } else { Vec3::NEG_Y };
+    let base = mesh.vertices.len() as u32;
+    mesh.vertices.push(Vertex::new([0.0, y, 0.0], normal.to_array(), [0.5, 0.5]));
+    for segment in 0..=segments {
+        let angle = segment as f32 / segments as f32 * TAU;
+        let (x, z) = (angle.sin(), angle.cos());
+        mesh.vertices.push(Vertex::new([x * radius, y, z * radius], normal.to_array(), [0.5 + x * 0.5, 0.5 + z * 0.5]));
+    }
+    for segment in 0..segments {
+        let (current, next) = (base + 1 + segment, base + 2 + segment);
+        // the rim goes counter clockwise seen from above
+        if up {
+            mesh.indices.extend([base, current, next]);
+        } else {
+            mesh.indices.extend([base, next, current]);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn shapes() -> Vec<(&'static str, MeshData)> {
+        vec![
+            ("cube", cube(2.0, 3)),
+            ("plane", plane(4.0, 2.0, 5, 3)),
+            ("uv sphere", uv_sphere(1.5, 24, 12)),
+            ("icosphere", icosphere(1.0, 3)),
+            ("cylinder", cylinder(0.5, 2.0, 16, 4)),
+            ("cone", cone(0.5, 1.0, 16, 3)),
+            ("capsule", capsule(0.5, 1.0, 16, 6)),
+            ("torus", torus(1.0, 0.25, 32, 12)),
+            // below the minimums, clamped
+            ("small sphere", uv_sphere(1.0, 0, 0)),
+            ("small cylinder", cylinder(1.0, 1.0, 0, 0)),
+        ]
+    }
+
+    #[test]
+    fn indices_are_in_range() {
+        for (name, mesh) in shapes() {
+            assert!(mesh.triangle_count() > 0, "{name} is empty");
+            assert!(mesh.indices.len().is_multiple_of(3), "{name} has a partial triangle");
+            assert!(mesh.indices.iter().all(|&index| (index as usize) < mesh.vertices.len()), "{name} indexes past its vertices");
+        }
+    }
+
+    #[test]
+    fn normals_are_unit_length() {
+        for (name, mesh) in shapes() {
+            for vertex in mesh.vertices.iter() {
+                let length = Vec3::from(vertex.normal).length();
+                assert!((length - 1.0).abs() < 1e-4, "{name} has a normal of length {length}");
+            }
+        }
+    }
+
+    #[test]
+    fn convex_shapes_face_outwards() {
+        for (name, mesh) in [("cube", cube(2.0, 2)), ("uv sphere", uv_sphere(1.0, 16, 8)), ("icosphere", icosphere(1.0, 2))] {
+            for triangle in mesh.indices.chunks_exact(3) {
+                let [a, b, c] = [0, 1, 2].map(|corner| Vec3::from(mesh.vertices[triangle[corner] as usize].position));
+                let centroid = (a + b + c) / 3.0;
+                assert!((b - a).cross(c - a).dot(centroid) > 0.0, "{name} has a triangle facing inwards");
+            }
+        }
+    }
+}
@@ -0,0 +1,33 @@
+#version 450
+// Texture times vertex color, premultiplied alpha blended. egui blends in gamma space, so the (sRGB) texture is
+// encoded back before the multiply ; with LINEAR_OUTPUT defined the result is decoded again for sRGB targets, which
+// encode on write.
+
+layout(set = 0, binding = 0) uniform texture2D ui_texture;
+layout(set = 0, binding = 1) uniform sampler ui_sampler;
+
+layout(location = 0) in vec2 uv;
+layout(location = 1) in vec4 color;
+
+layout(location = 0) out vec4 out_color;
+
+vec3 linear_to_gamma(vec3 linear) {
+    vec3 low = linear * 12.92;
+    vec3 high = 1.055 * pow(linear, vec3(1.0 / 2.4)) - 0.055;
+    return mix(high, low, vec3(lessThan(linear, vec3(0.0031308))));
+}
+
+vec3 gamma_to_linear(vec3 gamma) {
+    vec3 low = gamma / 12.92;
+    vec3 high = pow((gamma + 0.055) / 1.055, vec3(2.4));
+    return mix(high, low, vec3(lessThan(gamma, vec3(0.04045))));
+}
+
+void main() {
+    vec4 texel = texture(sampler2D(ui_texture, ui_sampler), uv);
+    vec4 gamma = color * vec4(linear_to_gamma(texel.rgb), texel.a);
+#ifdef LINEAR_OUTPUT
+    gamma.rgb = gamma_to_linear(gamma.rgb);
+#endif
+    out_color = gamma;
+}
@@ -0,0 +1,22 @@
+#version 450
+// egui meshes, positions in points from the top left corner of the window
+
+layout(push_constant) uniform Screen {
+    // in points
+    vec2 size;
+} screen;
+
+layout(location = 0) in vec2 in_position;
+layout(location = 1) in vec2 in_uv;
+// premultiplied, gamma encoded
+layout(location = 2) in vec4 in_color;
+
+layout(location = 0) out vec2 out_uv;
+layout(location = 1) out vec4 out_color;
+
+void main() {
+    // clip space has Y going down too
+    gl_Position = vec4(in_position / screen.size * 2.0 - 1.0, 0.0, 1.0);
+    out_uv = in_uv;
+    out_color = in_color;
+}
@@ -0,0 +1,13 @@
+#version 450
+
+// shared by the classic and the meshlet path, both output the normal and uv at the same locations
+layout(location = 0) in vec3 normal;
+layout(location = 1) in vec2 uv;
+
+layout(location = 0) out vec4 color;
+
+void main() {
+    vec3 light_direction = normalize(vec3(0.4, 1.0, 0.3));
+    float diffuse = max(dot(normalize(normal), light_direction), 0.0);
+    color = vec4(vec3(0.15 + 0.85 * diffuse), 1.0);
+}
@@ -0,0 +1,24 @@
+#version 450
+
+layout(set = 0, binding = 0) uniform Camera {
+    mat4 view_projection;
+    vec4 frustum[6];
+    vec4 position;
+} camera;
+
+layout(push_constant) uniform Draw {
+    mat4 model;
+} draw;
+
+layout(location = 0) in vec3 in_position;
+layout(location = 1) in vec3 in_normal;
+layout(location = 3) in vec2 in_uv;
+
+layout(location = 0) out vec3 normal;
+layout(location = 1) out vec2 uv;
+
+void main() {
+    gl_Position = camera.view_projection * draw.model * vec4(in_position, 1.0);
+    normal = mat3(draw.model) * in_normal;
+    uv = in_uv;
+}
@@ -0,0 +1,128 @@
+enable wgpu_mesh_shader;
+
+// Task shader: one invocation per meshlet, backfacing clusters (normal cone) and clusters outside the frustum are
+// dropped before any vertex is fetched ; the survivors are handed to the mesh shader through the payload.
+// Mesh shader: one workgroup per surviving meshlet.
+
+const TASK_GROUP_SIZE: u32 = 32u;
+const MAX_VERTICES: u32 = 64u;
+const MAX_TRIANGLES: u32 = 124u;
+// renderer::mesh::Vertex as floats: position (3), normal (3), tangent (4), uv (2)
+const VERTEX_STRIDE: u32 = 12u;
+
+struct Meshlet {
+    // xyz center, w radius, in mesh space
+    bounds: vec4<f32>,
+    // xyz axis, w sine of the cone's half angle ; w >= 1 disables cone culling
+    cone: vec4<f32>,
+    vertex_offset: u32,
+    triangle_offset: u32,
+    vertex_count: u32,
+    triangle_count: u32,
+}
+
+struct Camera {
+    view_projection: mat4x4<f32>,
+    // world space planes, normals pointing inside
+    frustum: array<vec4<f32>, 6>,
+    position: vec4<f32>,
+}
+
+struct DrawConstants {
+    model: mat4x4<f32>,
+    meshlet_count: u32,
+    // uniform scale of `model`, for the bounding spheres
+    scale: f32,
+}
+
+struct TaskPayload {
+    meshlets: array<u32, 32>,
+}
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) normal: vec3<f32>,
+    @location(1) uv: vec2<f32>,
+}
+
+struct PrimitiveOutput {
+    @builtin(triangle_indices) indices: vec3<u32>,
+}
+
+struct MeshOutput {
+    @builtin(vertices) vertices: array<VertexOutput, 64>,
+    @builtin(primitives) primitives: array<PrimitiveOutput, 124>,
+    @builtin(vertex_count) vertex_count: u32,
+    @builtin(primitive_count) primitive_count: u32,
+}
+
+@group(0) @binding(0) var<uniform> camera: Camera;
+@group(0) @binding(1) var<storage, read> meshlets: array<Meshlet>;
+@group(0) @binding(2) var<storage, read> vertices: array<f32>;
+@group(0) @binding(3) var<storage, read> meshlet_vertices: array<u32>;
+// one triangle per word, three 8 bit indices local to the meshlet
+@group(0) @binding(4) var<storage, read> meshlet_triangles: array<u32>;
+
+var<immediate> draw: DrawConstants;
+var<task_payload> payload: TaskPayload;
+var<workgroup> visible_count: atomic<u32>;
+var<workgroup> mesh_output: MeshOutput;
+
+fn is_visible(meshlet: Meshlet) -> bool {
+    let center = (draw.model * vec4<f32>(meshlet.bounds.xyz, 1.0)).xyz;
+    let radius = meshlet.bounds.w * draw.scale;
+    for (var plane = 0u; plane < 6u; plane++) {
+        if dot(camera.frustum[plane].xyz, center) + camera.frustum[plane].w < -radius {
+            return false;
+        }
+    }
+    if meshlet.cone.w < 1.0 {
+        let axis = normalize((draw.model * vec4<f32>(meshlet.cone.xyz, 0.0)).xyz);
+        let to_center = center - camera.position.xyz;
+        if dot(to_center, axis) >= meshlet.cone.w * length(to_center) + radius {
+            return false;
+        }
+    }
+    return true;
+}
+
+@task
+@payload(payload)
+@workgroup_size(32)
+fn task_main(@builtin(global_invocation_id) global_id: vec3<u32>, @builtin(local_invocation_index) local_index: u32) -> @builtin(mesh_task_size) vec3<u32> {
+    if local_index == 0u {
+        atomicStore(&visible_count, 0u);
+    }
+    workgroupBarrier();
+    let meshlet_index = global_id.x;
+    if meshlet_index < draw.meshlet_count && is_visible(meshlets[meshlet_index]) {
+        let slot = atomicAdd(&visible_count, 1u);
+        payload.meshlets[slot] = meshlet_index;
+    }
+    let count = workgroupUniformLoad(&visible_count);
+    return vec3<u32>(count, 1u, 1u);
+}
+
+@mesh(mesh_output)
+@payload(payload)
+@workgroup_size(32)
+fn mesh_main(@builtin(workgroup_id) workgroup_id: vec3<u32>, @builtin(local_invocation_index) local_index: u32) {
+    let meshlet = meshlets[payload.meshlets[workgroup_id.x]];
+    if local_index == 0u {
+        mesh_output.vertex_count = meshlet.vertex_count;
+        mesh_output.primitive_count = meshlet.triangle_count;
+    }
+    for (var vertex = local_index; vertex < meshlet.vertex_count; vertex += 32u) {
+        let base = meshlet_vertices[meshlet.vertex_offset + vertex] * VERTEX_STRIDE;
+        let position = vec3<f32>(vertices[base], vertices[base + 1u], vertices[base + 2u]);
+        let normal = vec3<f32>(vertices[base + 3u], vertices[base + 4u], vertices[base + 5u]);
+        let world = draw.model * vec4<f32>(position, 1.0);
+        mesh_output.vertices[vertex].position = camera.view_projection * world;
+        mesh_output.vertices[vertex].normal = (draw.model * vec4<f32>(normal, 0.0)).xyz;
+        mesh_output.vertices[vertex].uv = vec2<f32>(vertices[base + 10u], vertices[base + 11u]);
+    }
+    for (var triangle = local_index; triangle < meshlet.triangle_count; triangle += 32u) {
+        let packed = meshlet_triangles[meshlet.triangle_offset + triangle];
+        mesh_output.primitives[triangle].indices = vec3<u32>(packed & 0xffu, (packed >> 8u) & 0xffu, (packed >> 16u) & 0xffu);
+    }
+}
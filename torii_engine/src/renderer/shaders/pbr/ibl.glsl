@@ -0,0 +1,50 @@
+// Shared by the environment generation shaders, spliced in where they `#include "ibl.glsl"` (see renderer::pbr)
+
+const float PI = 3.14159265359;
+
+// world direction through texel `uv` (0..1) of cube face `face` (+X, -X, +Y, -Y, +Z, -Z)
+vec3 cube_direction(uint face, vec2 uv) {
+    vec2 st = uv * 2.0 - 1.0;
+    vec3 direction;
+    if (face == 0u) {
+        direction = vec3(1.0, -st.y, -st.x);
+    } else if (face == 1u) {
+        direction = vec3(-1.0, -st.y, st.x);
+    } else if (face == 2u) {
+        direction = vec3(st.x, 1.0, st.y);
+    } else if (face == 3u) {
+        direction = vec3(st.x, -1.0, -st.y);
+    } else if (face == 4u) {
+        direction = vec3(st.x, -st.y, 1.0);
+    } else {
+        direction = vec3(-st.x, -st.y, -1.0);
+    }
+    return normalize(direction);
+}
+
+float radical_inverse(uint bits) {
+    bits = (bits << 16u) | (bits >> 16u);
+    bits = ((bits & 0x55555555u) << 1u) | ((bits & 0xAAAAAAAAu) >> 1u);
+    bits = ((bits & 0x33333333u) << 2u) | ((bits & 0xCCCCCCCCu) >> 2u);
+    bits = ((bits & 0x0F0F0F0Fu) << 4u) | ((bits & 0xF0F0F0F0u) >> 4u);
+    bits = ((bits & 0x00FF00FFu) << 8u) | ((bits & 0xFF00FF00u) >> 8u);
+    return float(bits) * 2.3283064365386963e-10;
+}
+
+vec2 hammersley(uint index, uint count) {
+    return vec2(float(index) / float(count), radical_inverse(index));
+}
+
+// half vector around `normal` distributed along the GGX lobe of `roughness`
+vec3 importance_sample_ggx(vec2 xi, vec3 normal, float roughness) {
+    float alpha = roughness * roughness;
+    float phi = 2.0 * PI * xi.x;
+    float cos_theta = sqrt((1.0 - xi.y) / (1.0 + (alpha * alpha - 1.0) * xi.y));
+    float sin_theta = sqrt(1.0 - cos_theta * cos_theta);
+    vec3 half_vector = vec3(cos(phi) * sin_theta, sin(phi) * sin_theta, cos_theta);
+
+    vec3 up = abs(normal.z) < 0.999 ? vec3(0.0, 0.0, 1.0) : vec3(1.0, 0.0, 0.0);
+    vec3 tangent = normalize(cross(up, normal));
+    vec3 bitangent = cross(normal, tangent);
+    return normalize(tangent * half_vector.x + bitangent * half_vector.y + normal * half_vector.z);
+}
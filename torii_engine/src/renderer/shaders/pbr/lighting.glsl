@@ -0,0 +1,61 @@
+// Shared by the forward+ shaders, spliced in where they `#include "lighting.glsl"` (see renderer::pbr)
+
+// matches pbr::LightingData (std140)
+struct Lighting {
+    mat4 inverse_projection;
+    mat4 view;
+    // x, y, z cluster counts, w max lights per cluster
+    uvec4 cluster_grid;
+    // width, height, near, far
+    vec4 screen;
+    // depth slice scale and bias, tile width and height in pixels
+    vec4 slicing;
+    // directional lights, local lights, prefiltered environment mips, environment enabled
+    uvec4 counts;
+    // rgb ambient light used without environment, w environment intensity
+    vec4 ambient;
+    // x exposure
+    vec4 exposure;
+};
+
+// matches pbr::GpuLight ; directional lights come first in the light buffer
+struct Light {
+    // xyz position, w range
+    vec4 position_range;
+    // xyz direction the light travels in, w kind
+    vec4 direction_kind;
+    // rgb color, w intensity
+    vec4 color_intensity;
+    // x, y spot cone scale and offset
+    vec4 spot;
+    // x shadow slot (negative without shadow), y constant bias, z slope bias (world units), w normal bias (texels)
+    vec4 shadow;
+};
+
+const uint MAX_CASCADES = 4u;
+const uint MAX_DIRECTIONAL_SHADOWS = 4u;
+const uint MAX_POINT_SHADOWS = 8u;
+// matrices of the cube faces of point shadows come after the cascades of every directional shadow
+const uint POINT_SHADOW_MATRICES = MAX_CASCADES * MAX_DIRECTIONAL_SHADOWS;
+
+// matches pbr::ShadowData (std140)
+struct Shadows {
+    // view depth where each cascade ends
+    vec4 cascade_splits;
+    // world size of a texel of each cascade
+    vec4 cascade_texel_sizes;
+    // world depth covered by the projection of each cascade
+    vec4 cascade_depth_ranges;
+    // cascade count, pcf radius in texels
+    uvec4 params;
+    // x 1 / cascade resolution, y 1 / point shadow resolution
+    vec4 texel_size;
+    // position and range of the light of each point shadow
+    vec4 point_lights[MAX_POINT_SHADOWS];
+    // cascades at shadow slot * MAX_CASCADES + cascade, cube faces at POINT_SHADOW_MATRICES + slot * 6 + face
+    mat4 matrices[POINT_SHADOW_MATRICES + MAX_POINT_SHADOWS * 6u];
+};
+
+const uint LIGHT_DIRECTIONAL = 0u;
+const uint LIGHT_POINT = 1u;
+const uint LIGHT_SPOT = 2u;
@@ -0,0 +1,265 @@
+#version 450
+// glTF metallic-roughness shading: directional lights, the local lights of the fragment's cluster, and image based
+// ambient light from the environment. Outputs exposed linear radiance, tone mapping happens later ; with VELOCITY
+// defined, also the motion vectors for temporal effects.
+
+#include "lighting.glsl"
+
+const float PI = 3.14159265359;
+
+layout(set = 0, binding = 0) uniform Camera {
+    mat4 view_projection;
+    vec4 frustum[6];
+    vec4 position;
+    mat4 view;
+} camera;
+layout(set = 0, binding = 1) uniform LightingBlock { Lighting lighting; };
+layout(set = 0, binding = 2) readonly buffer LightBuffer { Light lights[]; };
+layout(set = 0, binding = 3) readonly buffer LightGridBuffer { uint light_counts[]; };
+layout(set = 0, binding = 4) readonly buffer LightIndexBuffer { uint light_indices[]; };
+layout(set = 0, binding = 5) uniform textureCube irradiance_map;
+layout(set = 0, binding = 6) uniform textureCube prefiltered_map;
+layout(set = 0, binding = 7) uniform texture2D brdf_lut;
+layout(set = 0, binding = 8) uniform sampler environment_sampler;
+layout(set = 0, binding = 9) uniform ShadowsBlock { Shadows shadows; };
+layout(set = 0, binding = 10) uniform texture2DArray directional_shadow_maps;
+layout(set = 0, binding = 11) uniform texture2DArray point_shadow_maps;
+layout(set = 0, binding = 12) uniform samplerShadow shadow_sampler;
+
+// matches pbr::MaterialData (std140)
+layout(set = 1, binding = 0) uniform Material {
+    vec4 base_color_factor;
+    // rgb emissive factor, w normal scale
+    vec4 emissive_normal_scale;
+    // metallic, roughness, occlusion strength, alpha cutoff
+    vec4 factors;
+    // alpha mode (0 opaque, 1 mask, 2 blend), double sided
+    uvec4 flags;
+} material;
+layout(set = 1, binding = 1) uniform texture2D base_color_texture;
+layout(set = 1, binding = 2) uniform texture2D metallic_roughness_texture;
+layout(set = 1, binding = 3) uniform texture2D normal_texture;
+layout(set = 1, binding = 4) uniform texture2D occlusion_texture;
+layout(set = 1, binding = 5) uniform texture2D emissive_texture;
+layout(set = 1, binding = 6) uniform sampler material_sampler;
+
+layout(location = 0) in vec3 world_position;
+layout(location = 1) in vec3 in_normal;
+layout(location = 2) in vec4 in_tangent;
+layout(location = 3) in vec2 uv;
+layout(location = 4) in float view_depth;
+layout(location = 5) in vec4 clip_position;
+layout(location = 6) in vec4 previous_clip_position;
+
+layout(location = 0) out vec4 out_color;
+#ifdef VELOCITY
+// uv units, from the previous frame's position to this one's ; alpha drives the blending of transparent surfaces
+layout(location = 1) out vec4 out_velocity;
+#endif
+
+float distribution_ggx(float n_dot_h, float roughness) {
+    float alpha = roughness * roughness;
+    float alpha2 = alpha * alpha;
+    float denominator = n_dot_h * n_dot_h * (alpha2 - 1.0) + 1.0;
+    return alpha2 / (PI * denominator * denominator);
+}
+
+// height correlated Smith term, already divided by 4 n.l n.v
+float visibility_smith_ggx(float n_dot_v, float n_dot_l, float roughness) {
+    float alpha = roughness * roughness;
+    float alpha2 = alpha * alpha;
+    float view_term = n_dot_l * sqrt(n_dot_v * n_dot_v * (1.0 - alpha2) + alpha2);
+    float light_term = n_dot_v * sqrt(n_dot_l * n_dot_l * (1.0 - alpha2) + alpha2);
+    return 0.5 / max(view_term + light_term, 1e-5);
+}
+
+vec3 fresnel_schlick(float cos_theta, vec3 f0) {
+    return f0 + (1.0 - f0) * pow(1.0 - cos_theta, 5.0);
+}
+
+vec3 fresnel_schlick_roughness(float cos_theta, vec3 f0, float roughness) {
+    return f0 + (max(vec3(1.0 - roughness), f0) - f0) * pow(1.0 - cos_theta, 5.0);
+}
+
+// fraction of the (2 radius + 1)^2 PCF taps around `uv` that are lit, each tap hardware filtered
+float filter_directional_shadow(vec2 uv, float layer, float reference) {
+    int radius = int(shadows.params.y);
+    float lit = 0.0;
+    for (int y = -radius; y <= radius; y++) {
+        for (int x = -radius; x <= radius; x++) {
+            vec2 tap = uv + vec2(x, y) * shadows.texel_size.x;
+            lit += texture(sampler2DArrayShadow(directional_shadow_maps, shadow_sampler), vec4(tap, layer, reference));
+        }
+    }
+    return lit / float((2 * radius + 1) * (2 * radius + 1));
+}
+
+float filter_point_shadow(vec2 uv, float layer, float reference) {
+    int radius = int(shadows.params.y);
+    float lit = 0.0;
+    for (int y = -radius; y <= radius; y++) {
+        for (int x = -radius; x <= radius; x++) {
+            vec2 tap = uv + vec2(x, y) * shadows.texel_size.y;
+            lit += texture(sampler2DArrayShadow(point_shadow_maps, shadow_sampler), vec4(tap, layer, reference));
+        }
+    }
+    return lit / float((2 * radius + 1) * (2 * radius + 1));
+}
+
+// receiver side bias in world units, growing with the slope of the surface relative to the light
+float shadow_bias(Light light, float n_dot_l) {
+    float tangent = sqrt(1.0 - n_dot_l * n_dot_l) / max(n_dot_l, 0.1);
+    return light.shadow.y + light.shadow.z * tangent;
+}
+
+float directional_shadow(Light light, vec3 normal, float n_dot_l) {
+    int slot = int(light.shadow.x);
+    uint cascade_count = shadows.params.x;
+    if (slot < 0 || view_depth > shadows.cascade_splits[cascade_count - 1u]) {
+        return 1.0;
+    }
+    uint cascade = 0u;
+    while (cascade + 1u < cascade_count && view_depth > shadows.cascade_splits[cascade]) {
+        cascade++;
+    }
+    vec3 offset_position = world_position + normalize(in_normal) * light.shadow.w * shadows.cascade_texel_sizes[cascade];
+    vec4 projected = shadows.matrices[uint(slot) * MAX_CASCADES + cascade] * vec4(offset_position, 1.0);
+    vec2 uv = projected.xy / projected.w * 0.5 + 0.5;
+    float reference = projected.z / projected.w - shadow_bias(light, n_dot_l) / shadows.cascade_depth_ranges[cascade];
+    return filter_directional_shadow(uv, float(uint(slot) * cascade_count + cascade), reference);
+}
+
+float point_shadow(Light light, vec3 normal, float n_dot_l) {
+    int slot = int(light.shadow.x);
+    if (slot < 0) {
+        return 1.0;
+    }
+    vec3 from_light = world_position - light.position_range.xyz;
+    vec3 axis = abs(from_light);
+    uint face;
+    if (axis.x >= axis.y && axis.x >= axis.z) {
+        face = from_light.x > 0.0 ? 0u : 1u;
+    } else if (axis.y >= axis.z) {
+        face = from_light.y > 0.0 ? 2u : 3u;
+    } else {
+        face = from_light.z > 0.0 ? 4u : 5u;
+    }
+    // a texel of a 90 degree face covers twice the distance along the major axis, over the resolution
+    float texel_size = 2.0 * max(axis.x, max(axis.y, axis.z)) * shadows.texel_size.y;
+    vec3 offset_position = world_position + normalize(in_normal) * light.shadow.w * texel_size;
+    vec4 projected = shadows.matrices[POINT_SHADOW_MATRICES + uint(slot) * 6u + face] * vec4(offset_position, 1.0);
+    vec2 uv = projected.xy / projected.w * 0.5 + 0.5;
+    float distance = length(offset_position - light.position_range.xyz) - shadow_bias(light, n_dot_l);
+    return filter_point_shadow(uv, float(uint(slot) * 6u + face), distance / light.position_range.w);
+}
+
+vec3 shade_light(Light light, vec3 normal, vec3 view_direction, vec3 diffuse_color, vec3 f0, float roughness) {
+    uint kind = uint(light.direction_kind.w);
+    vec3 light_direction = -light.direction_kind.xyz;
+    float attenuation = 1.0;
+    if (kind != LIGHT_DIRECTIONAL) {
+        vec3 to_light = light.position_range.xyz - world_position;
+        float distance2 = max(dot(to_light, to_light), 1e-4);
+        light_direction = to_light * inversesqrt(distance2);
+        // inverse square falloff, windowed to reach zero at the range
+        float range = light.position_range.w;
+        float window = clamp(1.0 - pow(distance2 / (range * range), 2.0), 0.0, 1.0);
+        attenuation = window * window / distance2;
+        if (kind == LIGHT_SPOT) {
+            float cone = clamp(dot(light.direction_kind.xyz, -light_direction) * light.spot.x + light.spot.y, 0.0, 1.0);
+            attenuation *= cone * cone;
+        }
+    }
+
+    float n_dot_l = clamp(dot(normal, light_direction), 0.0, 1.0);
+    if (n_dot_l <= 0.0 || attenuation <= 0.0) {
+        return vec3(0.0);
+    }
+    if (kind == LIGHT_DIRECTIONAL) {
+        attenuation *= directional_shadow(light, normal, n_dot_l);
+    } else if (kind == LIGHT_POINT) {
+        attenuation *= point_shadow(light, normal, n_dot_l);
+    }
+    if (attenuation <= 0.0) {
+        return vec3(0.0);
+    }
+    vec3 half_vector = normalize(light_direction + view_direction);
+    float n_dot_v = max(dot(normal, view_direction), 1e-4);
+    float n_dot_h = clamp(dot(normal, half_vector), 0.0, 1.0);
+    float v_dot_h = clamp(dot(view_direction, half_vector), 0.0, 1.0);
+
+    vec3 fresnel = fresnel_schlick(v_dot_h, f0);
+    vec3 specular = fresnel * distribution_ggx(n_dot_h, roughness) * visibility_smith_ggx(n_dot_v, n_dot_l, roughness);
+    vec3 diffuse = (1.0 - fresnel) * diffuse_color / PI;
+    return (diffuse + specular) * light.color_intensity.rgb * light.color_intensity.w * attenuation * n_dot_l;
+}
+
+uint cluster_index() {
+    uvec3 grid = lighting.cluster_grid.xyz;
+    uvec2 tile = uvec2(gl_FragCoord.xy / lighting.slicing.zw);
+    uint slice = uint(max(log(max(view_depth, 1e-4)) * lighting.slicing.x - lighting.slicing.y, 0.0));
+    uvec3 coords = min(uvec3(tile, slice), grid - 1u);
+    return coords.x + coords.y * grid.x + coords.z * grid.x * grid.y;
+}
+
+void main() {
+    vec4 base_color = material.base_color_factor * texture(sampler2D(base_color_texture, material_sampler), uv);
+    uint alpha_mode = material.flags.x;
+    if (alpha_mode == 1u && base_color.a < material.factors.w) {
+        discard;
+    }
+    vec2 metallic_roughness = texture(sampler2D(metallic_roughness_texture, material_sampler), uv).bg;
+    float metallic = clamp(material.factors.x * metallic_roughness.x, 0.0, 1.0);
+    float roughness = clamp(material.factors.y * metallic_roughness.y, 0.045, 1.0);
+    float occlusion = 1.0 + material.factors.z * (texture(sampler2D(occlusion_texture, material_sampler), uv).r - 1.0);
+    vec3 emissive = material.emissive_normal_scale.rgb * texture(sampler2D(emissive_texture, material_sampler), uv).rgb;
+
+    vec3 normal = normalize(in_normal);
+    if (material.flags.y != 0u && !gl_FrontFacing) {
+        normal = -normal;
+    }
+    if (dot(in_tangent.xyz, in_tangent.xyz) > 1e-8) {
+        vec3 tangent = normalize(in_tangent.xyz - normal * dot(normal, in_tangent.xyz));
+        vec3 bitangent = cross(normal, tangent) * in_tangent.w;
+        vec3 tangent_normal = texture(sampler2D(normal_texture, material_sampler), uv).xyz * 2.0 - 1.0;
+        tangent_normal.xy *= material.emissive_normal_scale.w;
+        normal = normalize(mat3(tangent, bitangent, normal) * tangent_normal);
+    }
+
+    vec3 view_direction = normalize(camera.position.xyz - world_position);
+    vec3 diffuse_color = base_color.rgb * (1.0 - metallic);
+    vec3 f0 = mix(vec3(0.04), base_color.rgb, metallic);
+
+    vec3 color = vec3(0.0);
+    for (uint index = 0u; index < lighting.counts.x; index++) {
+        color += shade_light(lights[index], normal, view_direction, diffuse_color, f0, roughness);
+    }
+    uint cluster = cluster_index();
+    uint max_lights = lighting.cluster_grid.w;
+    uint local_count = min(light_counts[cluster], max_lights);
+    for (uint slot = 0u; slot < local_count; slot++) {
+        color += shade_light(lights[light_indices[cluster * max_lights + slot]], normal, view_direction, diffuse_color, f0, roughness);
+    }
+
+    float n_dot_v = max(dot(normal, view_direction), 1e-4);
+    vec3 ambient;
+    if (lighting.counts.w != 0u) {
+        vec3 fresnel = fresnel_schlick_roughness(n_dot_v, f0, roughness);
+        vec3 irradiance = texture(samplerCube(irradiance_map, environment_sampler), normal).rgb;
+        float max_mip = float(lighting.counts.z - 1u);
+        vec3 reflected = reflect(-view_direction, normal);
+        vec3 prefiltered = textureLod(samplerCube(prefiltered_map, environment_sampler), reflected, roughness * max_mip).rgb;
+        vec2 brdf = texture(sampler2D(brdf_lut, environment_sampler), vec2(n_dot_v, roughness)).rg;
+        ambient = ((1.0 - fresnel) * diffuse_color * irradiance + prefiltered * (fresnel * brdf.x + brdf.y)) * lighting.ambient.w;
+    } else {
+        ambient = lighting.ambient.rgb * (diffuse_color + f0);
+    }
+    color += ambient * occlusion + emissive;
+
+    float alpha = alpha_mode == 2u ? base_color.a : 1.0;
+    out_color = vec4(color * lighting.exposure.x, alpha);
+#ifdef VELOCITY
+    vec2 velocity = (clip_position.xy / clip_position.w - previous_clip_position.xy / previous_clip_position.w) * 0.5;
+    out_velocity = vec4(velocity, 0.0, alpha);
+#endif
+}
@@ -0,0 +1,51 @@
+#version 450
+
+layout(set = 0, binding = 0) uniform Camera {
+    mat4 view_projection;
+    vec4 frustum[6];
+    vec4 position;
+    mat4 view;
+    // unjittered
+    mat4 previous_view_projection;
+    vec4 jitter;
+} camera;
+
+layout(push_constant) uniform Draw {
+    mat4 model;
+    // for the motion vectors, the model matrix itself for static meshes
+    mat4 previous_model;
+} draw;
+
+layout(location = 0) in vec3 in_position;
+layout(location = 1) in vec3 in_normal;
+layout(location = 2) in vec4 in_tangent;
+layout(location = 3) in vec2 in_uv;
+
+layout(location = 0) out vec3 out_world_position;
+layout(location = 1) out vec3 out_normal;
+layout(location = 2) out vec4 out_tangent;
+layout(location = 3) out vec2 out_uv;
+layout(location = 4) out float out_view_depth;
+// unjittered clip positions of this frame and the previous one, for the motion vectors
+layout(location = 5) out vec4 out_clip_position;
+layout(location = 6) out vec4 out_previous_clip_position;
+
+// cofactor matrix of the model's upper 3x3, the inverse transpose up to a scale the fragment shader normalizes away
+mat3 normal_matrix(mat4 model) {
+    vec3 x = model[0].xyz;
+    vec3 y = model[1].xyz;
+    vec3 z = model[2].xyz;
+    return mat3(cross(y, z), cross(z, x), cross(x, y));
+}
+
+void main() {
+    vec4 world_position = draw.model * vec4(in_position, 1.0);
+    out_world_position = world_position.xyz;
+    out_normal = normal_matrix(draw.model) * in_normal;
+    out_tangent = vec4(mat3(draw.model) * in_tangent.xyz, in_tangent.w);
+    out_uv = in_uv;
+    out_view_depth = -(camera.view * world_position).z;
+    gl_Position = camera.view_projection * world_position;
+    out_clip_position = vec4(gl_Position.xy - camera.jitter.xy * gl_Position.w, gl_Position.zw);
+    out_previous_clip_position = camera.previous_view_projection * draw.previous_model * vec4(in_position, 1.0);
+}
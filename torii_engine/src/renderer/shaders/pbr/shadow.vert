@@ -0,0 +1,23 @@
+#version 450
+// Depth only pass into one cascade or cube face of a shadow map
+
+#include "lighting.glsl"
+
+layout(set = 0, binding = 0) uniform ShadowsBlock { Shadows shadows; };
+
+layout(push_constant) uniform ShadowDraw {
+    mat4 model;
+    uint matrix_index;
+    // point shadow slot, unused by cascades
+    uint point_slot;
+} draw;
+
+layout(location = 0) in vec3 in_position;
+
+layout(location = 0) out vec3 out_world_position;
+
+void main() {
+    vec4 world_position = draw.model * vec4(in_position, 1.0);
+    out_world_position = world_position.xyz;
+    gl_Position = shadows.matrices[draw.matrix_index] * world_position;
+}
@@ -0,0 +1,20 @@
+#version 450
+// Point shadows store the linear distance to the light over its range, compared against the same distance when
+// shading
+
+#include "lighting.glsl"
+
+layout(set = 0, binding = 0) uniform ShadowsBlock { Shadows shadows; };
+
+layout(push_constant) uniform ShadowDraw {
+    mat4 model;
+    uint matrix_index;
+    uint point_slot;
+} draw;
+
+layout(location = 0) in vec3 world_position;
+
+void main() {
+    vec4 light = shadows.point_lights[draw.point_slot];
+    gl_FragDepth = clamp(length(world_position - light.xyz) / light.w, 0.0, 1.0);
+}
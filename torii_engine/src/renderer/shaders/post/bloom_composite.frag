@@ -0,0 +1,18 @@
+#version 450
+
+layout(set = 0, binding = 0) uniform texture2D scene;
+layout(set = 0, binding = 1) uniform texture2D bloom;
+layout(set = 0, binding = 2) uniform sampler linear_sampler;
+
+layout(push_constant) uniform Composite {
+    float intensity;
+} composite;
+
+layout(location = 0) in vec2 uv;
+layout(location = 0) out vec4 out_color;
+
+void main() {
+    vec3 scene_color = texture(sampler2D(scene, linear_sampler), uv).rgb;
+    vec3 bloom_color = texture(sampler2D(bloom, linear_sampler), uv).rgb;
+    out_color = vec4(mix(scene_color, bloom_color, composite.intensity), 1.0);
+}
@@ -0,0 +1,52 @@
+#version 450
+// 13 tap downsample of the bloom chain (Jimenez, Next Generation Post Processing in Call of Duty) ; the first one
+// weighs each group by its luminance (Karis average) to keep single bright pixels from flickering
+
+layout(set = 0, binding = 0) uniform texture2D source;
+layout(set = 0, binding = 1) uniform sampler source_sampler;
+
+layout(push_constant) uniform Downsample {
+    vec2 source_texel_size;
+    uint karis_average;
+} downsample;
+
+layout(location = 0) in vec2 uv;
+layout(location = 0) out vec4 out_color;
+
+vec3 tap(float x, float y) {
+    return texture(sampler2D(source, source_sampler), uv + vec2(x, y) * downsample.source_texel_size).rgb;
+}
+
+float karis_weight(vec3 color) {
+    return 1.0 / (1.0 + dot(color, vec3(0.2126, 0.7152, 0.0722)));
+}
+
+void main() {
+    vec3 a = tap(-2.0, -2.0);
+    vec3 b = tap(0.0, -2.0);
+    vec3 c = tap(2.0, -2.0);
+    vec3 d = tap(-2.0, 0.0);
+    vec3 e = tap(0.0, 0.0);
+    vec3 f = tap(2.0, 0.0);
+    vec3 g = tap(-2.0, 2.0);
+    vec3 h = tap(0.0, 2.0);
+    vec3 i = tap(2.0, 2.0);
+    vec3 j = tap(-1.0, -1.0);
+    vec3 k = tap(1.0, -1.0);
+    vec3 l = tap(-1.0, 1.0);
+    vec3 m = tap(1.0, 1.0);
+
+    vec3 groups[5] = vec3[5]((j + k + l + m) * 0.25, (a + b + d + e) * 0.25, (b + c + e + f) * 0.25, (d + e + g + h) * 0.25, (e + f + h + i) * 0.25);
+    float weights[5] = float[5](0.5, 0.125, 0.125, 0.125, 0.125);
+    vec3 color = vec3(0.0);
+    float total = 0.0;
+    for (int group = 0; group < 5; group++) {
+        float weight = weights[group];
+        if (downsample.karis_average != 0u) {
+            weight *= karis_weight(groups[group]);
+        }
+        color += groups[group] * weight;
+        total += weight;
+    }
+    out_color = vec4(color / total, 1.0);
+}
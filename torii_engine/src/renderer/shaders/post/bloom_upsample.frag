@@ -0,0 +1,24 @@
+#version 450
+// 3x3 tent upsample of the next smaller bloom mip, added onto the current one by the blend state
+
+layout(set = 0, binding = 0) uniform texture2D source;
+layout(set = 0, binding = 1) uniform sampler source_sampler;
+
+layout(push_constant) uniform Upsample {
+    // in uv units
+    vec2 filter_radius;
+} upsample;
+
+layout(location = 0) in vec2 uv;
+layout(location = 0) out vec4 out_color;
+
+vec3 tap(float x, float y) {
+    return texture(sampler2D(source, source_sampler), uv + vec2(x, y) * upsample.filter_radius).rgb;
+}
+
+void main() {
+    vec3 color = tap(0.0, 0.0) * 4.0;
+    color += (tap(-1.0, 0.0) + tap(1.0, 0.0) + tap(0.0, -1.0) + tap(0.0, 1.0)) * 2.0;
+    color += tap(-1.0, -1.0) + tap(1.0, -1.0) + tap(-1.0, 1.0) + tap(1.0, 1.0);
+    out_color = vec4(color / 16.0, 1.0);
+}
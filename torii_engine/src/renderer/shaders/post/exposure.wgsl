@@ -0,0 +1,49 @@
+// Averages the histogram into a luminance, eases the adapted luminance towards it and derives the exposure ; clears
+// the histogram for the next frame. Runs as a single workgroup, one invocation per bin.
+
+struct Adaptation {
+    min_log_luminance: f32,
+    log_luminance_range: f32,
+    // 1 - exp(-delta time * speed), 1 snaps straight to the target
+    rate: f32,
+    // exposure compensation in stops
+    compensation: f32,
+    pixel_count: u32,
+}
+
+@group(0) @binding(0) var<storage, read_write> bins: array<u32, 256>;
+// adapted luminance, exposure
+@group(0) @binding(1) var<storage, read_write> exposure: vec4<f32>;
+
+var<immediate> adaptation: Adaptation;
+var<workgroup> weighted: array<f32, 256>;
+
+@compute @workgroup_size(256)
+fn main(@builtin(local_invocation_index) index: u32) {
+    let count = bins[index];
+    weighted[index] = f32(count) * f32(index);
+    workgroupBarrier();
+    bins[index] = 0u;
+
+    for (var stride = 128u; stride > 0u; stride = stride >> 1u) {
+        if index < stride {
+            weighted[index] += weighted[index + stride];
+        }
+        workgroupBarrier();
+    }
+
+    if index == 0u {
+        // the black pixels of bin 0 don't count
+        let lit_pixels = max(f32(adaptation.pixel_count) - f32(count), 1.0);
+        let average_bin = weighted[0] / lit_pixels - 1.0;
+        let average_log_luminance = max(average_bin, 0.0) / 254.0 * adaptation.log_luminance_range + adaptation.min_log_luminance;
+        let target_luminance = exp2(average_log_luminance);
+        let previous = exposure.x;
+        var adapted = target_luminance;
+        if previous > 0.0 {
+            adapted = previous + (target_luminance - previous) * adaptation.rate;
+        }
+        // middle grey (0.18) at the adapted luminance
+        exposure = vec4<f32>(adapted, 0.18 / max(adapted, 1e-4) * exp2(adaptation.compensation), 0.0, 0.0);
+    }
+}
@@ -0,0 +1,122 @@
+#version 450
+// FXAA (after Timothy Lottes' FXAA 3.11): finds the edges from luma contrast, walks along them to their ends and
+// blends across by how far the pixel is from the closer end. Runs on display referred colors.
+
+layout(set = 0, binding = 0) uniform texture2D source;
+layout(set = 0, binding = 1) uniform sampler linear_sampler;
+
+layout(push_constant) uniform Fxaa {
+    vec2 texel_size;
+    // local contrast needed for an edge, relative to the brightest neighbor
+    float edge_threshold;
+    // and in absolute, to leave dark areas alone
+    float edge_threshold_min;
+} fxaa;
+
+layout(location = 0) in vec2 uv;
+layout(location = 0) out vec4 out_color;
+
+const int SEARCH_STEPS = 10;
+
+float luma(vec3 color) {
+    // perceptual enough for edge detection, the input may be linear (sRGB formats) or encoded
+    return sqrt(dot(color, vec3(0.299, 0.587, 0.114)));
+}
+
+float luma_at(vec2 position) {
+    return luma(textureLod(sampler2D(source, linear_sampler), position, 0.0).rgb);
+}
+
+void main() {
+    vec3 center_color = textureLod(sampler2D(source, linear_sampler), uv, 0.0).rgb;
+    float center = luma(center_color);
+    float north = luma_at(uv + vec2(0.0, -fxaa.texel_size.y));
+    float south = luma_at(uv + vec2(0.0, fxaa.texel_size.y));
+    float west = luma_at(uv + vec2(-fxaa.texel_size.x, 0.0));
+    float east = luma_at(uv + vec2(fxaa.texel_size.x, 0.0));
+    float luma_min = min(center, min(min(north, south), min(west, east)));
+    float luma_max = max(center, max(max(north, south), max(west, east)));
+    float contrast = luma_max - luma_min;
+    if (contrast < max(fxaa.edge_threshold_min, luma_max * fxaa.edge_threshold)) {
+        out_color = vec4(center_color, 1.0);
+        return;
+    }
+
+    float north_west = luma_at(uv - fxaa.texel_size);
+    float south_east = luma_at(uv + fxaa.texel_size);
+    float north_east = luma_at(uv + vec2(fxaa.texel_size.x, -fxaa.texel_size.y));
+    float south_west = luma_at(uv + vec2(-fxaa.texel_size.x, fxaa.texel_size.y));
+
+    float horizontal = abs(north_west + north_east - 2.0 * north) + 2.0 * abs(west + east - 2.0 * center) + abs(south_west + south_east - 2.0 * south);
+    float vertical = abs(north_west + south_west - 2.0 * west) + 2.0 * abs(north + south - 2.0 * center) + abs(north_east + south_east - 2.0 * east);
+    bool horizontal_edge = horizontal >= vertical;
+
+    // step across the edge, towards the side with the steeper gradient
+    float negative = horizontal_edge ? north : west;
+    float positive = horizontal_edge ? south : east;
+    float negative_gradient = abs(negative - center);
+    float positive_gradient = abs(positive - center);
+    float step_length = horizontal_edge ? fxaa.texel_size.y : fxaa.texel_size.x;
+    float edge_luma;
+    float gradient;
+    if (negative_gradient >= positive_gradient) {
+        step_length = -step_length;
+        edge_luma = 0.5 * (negative + center);
+        gradient = negative_gradient;
+    } else {
+        edge_luma = 0.5 * (positive + center);
+        gradient = positive_gradient;
+    }
+
+    vec2 edge_uv = uv;
+    vec2 along = horizontal_edge ? vec2(fxaa.texel_size.x, 0.0) : vec2(0.0, fxaa.texel_size.y);
+    if (horizontal_edge) {
+        edge_uv.y += step_length * 0.5;
+    } else {
+        edge_uv.x += step_length * 0.5;
+    }
+    float threshold = gradient * 0.25;
+    vec2 negative_uv = edge_uv - along;
+    vec2 positive_uv = edge_uv + along;
+    float negative_end = luma_at(negative_uv) - edge_luma;
+    float positive_end = luma_at(positive_uv) - edge_luma;
+    bool negative_done = abs(negative_end) >= threshold;
+    bool positive_done = abs(positive_end) >= threshold;
+    for (int i = 0; i < SEARCH_STEPS && !(negative_done && positive_done); i++) {
+        if (!negative_done) {
+            negative_uv -= along * 1.5;
+            negative_end = luma_at(negative_uv) - edge_luma;
+            negative_done = abs(negative_end) >= threshold;
+        }
+        if (!positive_done) {
+            positive_uv += along * 1.5;
+            positive_end = luma_at(positive_uv) - edge_luma;
+            positive_done = abs(positive_end) >= threshold;
+        }
+    }
+
+    float negative_distance = horizontal_edge ? uv.x - negative_uv.x : uv.y - negative_uv.y;
+    float positive_distance = horizontal_edge ? positive_uv.x - uv.x : positive_uv.y - uv.y;
+    bool negative_closer = negative_distance < positive_distance;
+    float closest_distance = min(negative_distance, positive_distance);
+    float edge_length = negative_distance + positive_distance;
+    // only blend when the luma at the closer end varies the other way than the center does
+    bool center_smaller = center < edge_luma;
+    bool correct_variation = ((negative_closer ? negative_end : positive_end) < 0.0) != center_smaller;
+    float edge_offset = correct_variation ? 0.5 - closest_distance / edge_length : 0.0;
+
+    // subpixel aliasing, from the average of the neighborhood
+    float average = (2.0 * (north + south + west + east) + north_west + north_east + south_west + south_east) / 12.0;
+    float subpixel = clamp(abs(average - center) / contrast, 0.0, 1.0);
+    subpixel = (-2.0 * subpixel + 3.0) * subpixel * subpixel;
+    float subpixel_offset = subpixel * subpixel * 0.75;
+
+    float offset = max(edge_offset, subpixel_offset);
+    vec2 final_uv = uv;
+    if (horizontal_edge) {
+        final_uv.y += offset * step_length;
+    } else {
+        final_uv.x += offset * step_length;
+    }
+    out_color = vec4(textureLod(sampler2D(source, linear_sampler), final_uv, 0.0).rgb, 1.0);
+}
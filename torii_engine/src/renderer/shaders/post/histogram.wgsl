@@ -0,0 +1,33 @@
+// Luminance histogram of the HDR image, 256 bins over a log2 luminance range ; bin 0 holds the (near) black pixels,
+// which auto exposure ignores
+
+struct Histogram {
+    min_log_luminance: f32,
+    inverse_log_luminance_range: f32,
+}
+
+@group(0) @binding(0) var hdr_image: texture_2d<f32>;
+@group(0) @binding(1) var<storage, read_write> bins: array<atomic<u32>, 256>;
+
+var<immediate> histogram: Histogram;
+var<workgroup> local_bins: array<atomic<u32>, 256>;
+
+@compute @workgroup_size(16, 16)
+fn main(@builtin(global_invocation_id) id: vec3<u32>, @builtin(local_invocation_index) index: u32) {
+    atomicStore(&local_bins[index], 0u);
+    workgroupBarrier();
+
+    let size = textureDimensions(hdr_image);
+    if id.x < size.x && id.y < size.y {
+        let color = textureLoad(hdr_image, vec2<i32>(id.xy), 0).rgb;
+        let luminance = dot(color, vec3<f32>(0.2126, 0.7152, 0.0722));
+        var bin = 0u;
+        if luminance > 1e-4 {
+            let position = clamp((log2(luminance) - histogram.min_log_luminance) * histogram.inverse_log_luminance_range, 0.0, 1.0);
+            bin = u32(position * 254.0 + 1.0);
+        }
+        atomicAdd(&local_bins[bin], 1u);
+    }
+    workgroupBarrier();
+    atomicAdd(&bins[index], atomicLoad(&local_bins[index]));
+}
@@ -0,0 +1,53 @@
+#version 450
+// Temporal anti-aliasing: blends the jittered current frame into the history reprojected along the motion vectors,
+// the history clamped to the current neighborhood's color range to reject what moved or appeared
+
+layout(set = 0, binding = 0) uniform texture2D current;
+layout(set = 0, binding = 1) uniform texture2D history;
+layout(set = 0, binding = 2) uniform texture2D velocity;
+layout(set = 0, binding = 3) uniform sampler linear_sampler;
+
+layout(push_constant) uniform Temporal {
+    vec2 texel_size;
+    // weight of the current frame
+    float blend;
+    uint history_valid;
+} temporal;
+
+layout(location = 0) in vec2 uv;
+layout(location = 0) out vec4 out_color;
+layout(location = 1) out vec4 out_history;
+
+// tone mapped weights keep bright pixels from dominating the blend
+vec3 compress(vec3 color) {
+    return color / (1.0 + max(color.r, max(color.g, color.b)));
+}
+
+vec3 uncompress(vec3 color) {
+    return color / max(1.0 - max(color.r, max(color.g, color.b)), 1e-4);
+}
+
+void main() {
+    vec3 center = compress(texture(sampler2D(current, linear_sampler), uv).rgb);
+    vec3 neighborhood_min = center;
+    vec3 neighborhood_max = center;
+    for (int y = -1; y <= 1; y++) {
+        for (int x = -1; x <= 1; x++) {
+            vec3 neighbor = compress(texture(sampler2D(current, linear_sampler), uv + vec2(x, y) * temporal.texel_size).rgb);
+            neighborhood_min = min(neighborhood_min, neighbor);
+            neighborhood_max = max(neighborhood_max, neighbor);
+        }
+    }
+
+    vec2 previous_uv = uv - texture(sampler2D(velocity, linear_sampler), uv).xy;
+    bool outside = any(lessThan(previous_uv, vec2(0.0))) || any(greaterThan(previous_uv, vec2(1.0)));
+    vec3 result = center;
+    if (temporal.history_valid != 0u && !outside) {
+        vec3 previous = compress(texture(sampler2D(history, linear_sampler), previous_uv).rgb);
+        previous = clamp(previous, neighborhood_min, neighborhood_max);
+        result = mix(previous, center, temporal.blend);
+    }
+    vec4 color = vec4(uncompress(result), 1.0);
+    out_color = color;
+    out_history = color;
+}
@@ -0,0 +1,114 @@
+#version 450
+// Exposure, tone mapping and color grading: linear HDR radiance in, display referred color out
+
+layout(set = 0, binding = 0) uniform texture2D hdr_image;
+layout(set = 0, binding = 1) uniform texture2D grading_lut;
+layout(set = 0, binding = 2) uniform sampler linear_sampler;
+// adapted luminance, exposure ; written by exposure.comp
+layout(set = 0, binding = 3) readonly buffer ExposureBuffer { vec4 exposure; };
+
+layout(push_constant) uniform Tonemap {
+    // used unless auto_exposure is set
+    float manual_exposure;
+    uint auto_exposure;
+    // 0 none (clamp), 1 ACES, 2 AgX
+    uint tonemapper;
+    // size of the grading lut cube, 0 without grading
+    uint lut_size;
+    // the output is a UNORM format, encode sRGB in the shader
+    uint encode_srgb;
+} tonemap;
+
+layout(location = 0) in vec2 uv;
+layout(location = 0) out vec4 out_color;
+
+// Stephen Hill's fit of the ACES reference rendering and output transforms
+vec3 aces(vec3 color) {
+    const mat3 input_matrix = mat3(
+        0.59719, 0.07600, 0.02840,
+        0.35458, 0.90834, 0.13383,
+        0.04823, 0.01566, 0.83777
+    );
+    const mat3 output_matrix = mat3(
+        1.60475, -0.10208, -0.00327,
+        -0.53108, 1.10813, -0.07276,
+        -0.07367, -0.00605, 1.07602
+    );
+    color = input_matrix * color;
+    vec3 a = color * (color + 0.0245786) - 0.000090537;
+    vec3 b = color * (0.983729 * color + 0.4329510) + 0.238081;
+    return clamp(output_matrix * (a / b), 0.0, 1.0);
+}
+
+// Troy Sobotka's AgX, with the polynomial fit of its default contrast curve
+vec3 agx_contrast(vec3 x) {
+    vec3 x2 = x * x;
+    vec3 x4 = x2 * x2;
+    return 15.5 * x4 * x2 - 40.14 * x4 * x + 31.96 * x4 - 6.868 * x2 * x + 0.4298 * x2 + 0.1191 * x - 0.00232;
+}
+
+vec3 agx(vec3 color) {
+    const mat3 inset = mat3(
+        0.842479062253094, 0.0423282422610123, 0.0423756549057051,
+        0.0784335999999992, 0.878468636469772, 0.0784336,
+        0.0792237451477643, 0.0791661274605434, 0.879142973793104
+    );
+    const mat3 outset = mat3(
+        1.19687900512017, -0.0528968517574562, -0.0529716355144438,
+        -0.0980208811401368, 1.15190312990417, -0.0980434501171241,
+        -0.0990297440797205, -0.0989611768448433, 1.15107367264116
+    );
+    const float min_ev = -12.47393;
+    const float max_ev = 4.026069;
+    color = inset * max(color, vec3(1e-10));
+    color = clamp((log2(color) - min_ev) / (max_ev - min_ev), 0.0, 1.0);
+    color = agx_contrast(color);
+    color = outset * color;
+    // the curve outputs display encoded values
+    return pow(max(color, vec3(0.0)), vec3(2.2));
+}
+
+vec3 linear_to_srgb(vec3 color) {
+    return mix(color * 12.92, 1.055 * pow(color, vec3(1.0 / 2.4)) - 0.055, step(vec3(0.0031308), color));
+}
+
+vec3 srgb_to_linear(vec3 color) {
+    return mix(color / 12.92, pow((color + 0.055) / 1.055, vec3(2.4)), step(vec3(0.04045), color));
+}
+
+// the lut is a strip of lut_size slices along x, one per blue value, each red along x and green along y ; it maps
+// sRGB encoded colors
+vec3 grade(vec3 color) {
+    float size = float(tonemap.lut_size);
+    vec3 encoded = clamp(linear_to_srgb(color), 0.0, 1.0) * (size - 1.0);
+    float blue_slice = floor(encoded.b);
+    float blue_blend = encoded.b - blue_slice;
+    vec2 texel = vec2(encoded.r + 0.5, encoded.g + 0.5) / vec2(size * size, size);
+    vec2 first = texel + vec2(blue_slice / size, 0.0);
+    vec2 second = texel + vec2(min(blue_slice + 1.0, size - 1.0) / size, 0.0);
+    vec3 graded = mix(
+        textureLod(sampler2D(grading_lut, linear_sampler), first, 0.0).rgb,
+        textureLod(sampler2D(grading_lut, linear_sampler), second, 0.0).rgb,
+        blue_blend
+    );
+    return srgb_to_linear(graded);
+}
+
+void main() {
+    float scale = tonemap.auto_exposure != 0u ? exposure.y : tonemap.manual_exposure;
+    vec3 color = texture(sampler2D(hdr_image, linear_sampler), uv).rgb * scale;
+    if (tonemap.tonemapper == 1u) {
+        color = aces(color);
+    } else if (tonemap.tonemapper == 2u) {
+        color = agx(color);
+    } else {
+        color = clamp(color, 0.0, 1.0);
+    }
+    if (tonemap.lut_size > 1u) {
+        color = grade(color);
+    }
+    if (tonemap.encode_srgb != 0u) {
+        color = linear_to_srgb(color);
+    }
+    out_color = vec4(color, 1.0);
+}
@@ -0,0 +1,132 @@
+#version 450
+// Physical sky: single Rayleigh and Mie scattering of the sunlight, ray marched through a spherical atmosphere of
+// exponentially decreasing density, and the sun disk seen through it ; with VELOCITY defined, also writes the motion
+// vectors of the camera rotation
+
+const float PI = 3.14159265359;
+const int VIEW_STEPS = 16;
+const int SUN_STEPS = 8;
+// radiance of the sun disk relative to the sun intensity
+const float SUN_DISK_RADIANCE = 100.0;
+// Mie extinction over scattering
+const float MIE_EXTINCTION = 1.1;
+
+layout(set = 0, binding = 0) uniform SkyView {
+    mat4 inverse_view_projection;
+    mat4 previous_view_projection;
+    vec4 jitter_exposure;
+} sky_view;
+
+// meters and inverse meters
+layout(push_constant) uniform Atmosphere {
+    // xyz towards the sun, w its intensity
+    vec4 sun;
+    // xyz scattering coefficients, w scale height
+    vec4 rayleigh;
+    // x scattering coefficient, y scale height, z anisotropy, w angular radius of the sun
+    vec4 mie;
+    // x planet radius, y atmosphere radius, z viewer altitude, w intensity of the whole sky
+    vec4 planet;
+} atmosphere;
+
+layout(location = 0) in vec3 direction;
+layout(location = 1) in vec2 ndc;
+
+layout(location = 0) out vec4 out_color;
+#ifdef VELOCITY
+layout(location = 1) out vec4 out_velocity;
+#endif
+
+// distances along the ray to where it enters and leaves a sphere centered on the planet, x > y when it misses
+vec2 ray_sphere(vec3 origin, vec3 ray, float radius) {
+    float b = dot(origin, ray);
+    float c = dot(origin, origin) - radius * radius;
+    float discriminant = b * b - c;
+    if (discriminant < 0.0) {
+        return vec2(1e10, -1e10);
+    }
+    float root = sqrt(discriminant);
+    return vec2(-b - root, -b + root);
+}
+
+vec3 extinction(float rayleigh_depth, float mie_depth) {
+    return exp(-(atmosphere.rayleigh.xyz * rayleigh_depth + atmosphere.mie.x * MIE_EXTINCTION * mie_depth));
+}
+
+vec3 sky_radiance(vec3 ray) {
+    float planet_radius = atmosphere.planet.x;
+    vec3 origin = vec3(0.0, planet_radius + atmosphere.planet.z, 0.0);
+    vec2 atmosphere_hit = ray_sphere(origin, ray, atmosphere.planet.y);
+    if (atmosphere_hit.x > atmosphere_hit.y || atmosphere_hit.y < 0.0) {
+        return vec3(0.0);
+    }
+    float start = max(atmosphere_hit.x, 0.0);
+    float end = atmosphere_hit.y;
+    vec2 ground_hit = ray_sphere(origin, ray, planet_radius);
+    bool hits_ground = ground_hit.x < ground_hit.y && ground_hit.x > 0.0;
+    if (hits_ground) {
+        end = min(end, ground_hit.x);
+    }
+
+    vec3 sun_direction = atmosphere.sun.xyz;
+    float mu = dot(ray, sun_direction);
+    float g = atmosphere.mie.z;
+    float rayleigh_phase = 3.0 / (16.0 * PI) * (1.0 + mu * mu);
+    float mie_phase = 3.0 / (8.0 * PI) * ((1.0 - g * g) * (1.0 + mu * mu)) / ((2.0 + g * g) * pow(1.0 + g * g - 2.0 * g * mu, 1.5));
+
+    float step_size = (end - start) / float(VIEW_STEPS);
+    float rayleigh_depth = 0.0;
+    float mie_depth = 0.0;
+    vec3 rayleigh_sum = vec3(0.0);
+    vec3 mie_sum = vec3(0.0);
+    for (int i = 0; i < VIEW_STEPS; i++) {
+        vec3 position = origin + ray * (start + (float(i) + 0.5) * step_size);
+        float height = length(position) - planet_radius;
+        float rayleigh_density = exp(-height / atmosphere.rayleigh.w) * step_size;
+        float mie_density = exp(-height / atmosphere.mie.y) * step_size;
+        rayleigh_depth += rayleigh_density;
+        mie_depth += mie_density;
+
+        // optical depth towards the sun, none of its light reaches the samples in the planet's shadow
+        float sun_step = ray_sphere(position, sun_direction, atmosphere.planet.y).y / float(SUN_STEPS);
+        float sun_rayleigh_depth = 0.0;
+        float sun_mie_depth = 0.0;
+        bool shadowed = false;
+        for (int j = 0; j < SUN_STEPS; j++) {
+            vec3 sun_position = position + sun_direction * ((float(j) + 0.5) * sun_step);
+            float sun_height = length(sun_position) - planet_radius;
+            if (sun_height < 0.0) {
+                shadowed = true;
+                break;
+            }
+            sun_rayleigh_depth += exp(-sun_height / atmosphere.rayleigh.w) * sun_step;
+            sun_mie_depth += exp(-sun_height / atmosphere.mie.y) * sun_step;
+        }
+        if (shadowed) {
+            continue;
+        }
+        vec3 attenuation = extinction(rayleigh_depth + sun_rayleigh_depth, mie_depth + sun_mie_depth);
+        rayleigh_sum += rayleigh_density * attenuation;
+        mie_sum += mie_density * attenuation;
+    }
+    vec3 radiance = atmosphere.sun.w * (rayleigh_phase * atmosphere.rayleigh.xyz * rayleigh_sum + mie_phase * atmosphere.mie.x * mie_sum);
+
+    // sun disk with a slightly soft edge, dimmed by the air it shines through
+    float cos_radius = cos(atmosphere.mie.w);
+    float disk = smoothstep(cos_radius - 1e-5, cos_radius, mu);
+    if (!hits_ground && disk > 0.0) {
+        radiance += disk * atmosphere.sun.w * SUN_DISK_RADIANCE * extinction(rayleigh_depth, mie_depth);
+    }
+    return radiance;
+}
+
+void main() {
+    vec3 ray = normalize(direction);
+    out_color = vec4(sky_radiance(ray) * atmosphere.planet.w * sky_view.jitter_exposure.z, 1.0);
+#ifdef VELOCITY
+    // the sky is infinitely far away, only the rotation moves it
+    vec4 previous = sky_view.previous_view_projection * vec4(ray, 0.0);
+    vec2 velocity = previous.w > 0.0 ? (ndc - previous.xy / previous.w) * 0.5 : vec2(0.0);
+    out_velocity = vec4(velocity, 0.0, 1.0);
+#endif
+}
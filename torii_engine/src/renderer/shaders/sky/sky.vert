@@ -0,0 +1,26 @@
+#version 450
+// One triangle covering the screen on the far plane, so with a LESS_OR_EQUAL test only the pixels no geometry was
+// drawn over are shaded ; outputs the world direction each pixel looks along
+
+layout(set = 0, binding = 0) uniform SkyView {
+    // inverse of the unjittered projection times the view rotation, the camera's position doesn't move the sky
+    mat4 inverse_view_projection;
+    mat4 previous_view_projection;
+    // xy the jitter of the frame, z the exposure
+    vec4 jitter_exposure;
+} sky_view;
+
+// far plane point, normalized in the fragment shader
+layout(location = 0) out vec3 out_direction;
+// unjittered NDC of the pixel, for the motion vectors
+layout(location = 1) out vec2 out_ndc;
+
+void main() {
+    vec2 uv = vec2(float((gl_VertexIndex << 1) & 2), float(gl_VertexIndex & 2));
+    vec2 ndc = uv * 2.0 - 1.0;
+    gl_Position = vec4(ndc, 1.0, 1.0);
+    // a pixel of the jittered frame shows what the unjittered camera sees at ndc - jitter
+    out_ndc = ndc - sky_view.jitter_exposure.xy;
+    vec4 far_point = sky_view.inverse_view_projection * vec4(out_ndc, 1.0, 1.0);
+    out_direction = far_point.xyz / far_point.w;
+}
@@ -0,0 +1,35 @@
+#version 450
+// Samples a cube map along the view direction ; with VELOCITY defined, also writes the motion vectors of the camera
+// rotation
+
+layout(set = 0, binding = 0) uniform SkyView {
+    mat4 inverse_view_projection;
+    mat4 previous_view_projection;
+    vec4 jitter_exposure;
+} sky_view;
+
+layout(set = 0, binding = 1) uniform textureCube sky_cube;
+layout(set = 0, binding = 2) uniform sampler sky_sampler;
+
+layout(push_constant) uniform Skybox {
+    float intensity;
+} skybox;
+
+layout(location = 0) in vec3 direction;
+layout(location = 1) in vec2 ndc;
+
+layout(location = 0) out vec4 out_color;
+#ifdef VELOCITY
+layout(location = 1) out vec4 out_velocity;
+#endif
+
+void main() {
+    vec3 radiance = texture(samplerCube(sky_cube, sky_sampler), normalize(direction)).rgb;
+    out_color = vec4(radiance * skybox.intensity * sky_view.jitter_exposure.z, 1.0);
+#ifdef VELOCITY
+    // the sky is infinitely far away, only the rotation moves it
+    vec4 previous = sky_view.previous_view_projection * vec4(normalize(direction), 0.0);
+    vec2 velocity = previous.w > 0.0 ? (ndc - previous.xy / previous.w) * 0.5 : vec2(0.0);
+    out_velocity = vec4(velocity, 0.0, 1.0);
+#endif
+}
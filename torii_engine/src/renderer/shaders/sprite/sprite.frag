@@ -0,0 +1,21 @@
+#version 450
+// Texture times tint, alpha blended ; with VELOCITY defined, also writes still motion vectors so sprites can go in a
+// scene target of the post processing stack
+
+layout(set = 0, binding = 1) uniform texture2D sprite_texture;
+layout(set = 0, binding = 2) uniform sampler sprite_sampler;
+
+layout(location = 0) in vec2 uv;
+layout(location = 1) in vec4 tint;
+
+layout(location = 0) out vec4 out_color;
+#ifdef VELOCITY
+layout(location = 1) out vec4 out_velocity;
+#endif
+
+void main() {
+    out_color = texture(sampler2D(sprite_texture, sprite_sampler), uv) * tint;
+#ifdef VELOCITY
+    out_velocity = vec4(0.0, 0.0, 0.0, out_color.a);
+#endif
+}
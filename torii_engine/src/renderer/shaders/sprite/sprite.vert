@@ -0,0 +1,30 @@
+#version 450
+// Instanced sprite quads, four strip vertices generated per instance
+
+layout(set = 0, binding = 0) uniform SpriteView {
+    mat4 view_projection;
+} sprite_view;
+
+// per instance
+layout(location = 0) in vec4 in_position_size;
+// xy pivot, z rotation
+layout(location = 1) in vec4 in_pivot_rotation;
+// uv min in xy, max in zw
+layout(location = 2) in vec4 in_uv_rect;
+layout(location = 3) in vec4 in_tint;
+
+layout(location = 0) out vec2 out_uv;
+layout(location = 1) out vec4 out_tint;
+
+void main() {
+    // (0, 0) bottom left to (1, 1) top right
+    vec2 corner = vec2(float(gl_VertexIndex & 1), float(gl_VertexIndex >> 1));
+    vec2 local = (corner - in_pivot_rotation.xy) * in_position_size.zw;
+    float s = sin(in_pivot_rotation.z);
+    float c = cos(in_pivot_rotation.z);
+    vec2 world = in_position_size.xy + vec2(local.x * c - local.y * s, local.x * s + local.y * c);
+    gl_Position = sprite_view.view_projection * vec4(world, 0.0, 1.0);
+    // image rows go down, the quad goes up
+    out_uv = mix(in_uv_rect.xy, in_uv_rect.zw, vec2(corner.x, 1.0 - corner.y));
+    out_tint = in_tint;
+}
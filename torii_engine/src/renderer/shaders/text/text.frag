@@ -0,0 +1,28 @@
+#version 450
+// Glyphs from their signed distance fields, antialiased over about a pixel whatever their size, alpha blended ; with
+// VELOCITY defined, also writes motion vectors so text can go in a scene target of the post processing stack
+
+layout(set = 0, binding = 1) uniform texture2D glyph_atlas;
+layout(set = 0, binding = 2) uniform sampler atlas_sampler;
+
+layout(location = 0) in vec2 uv;
+layout(location = 1) in vec4 color;
+layout(location = 2) in vec4 current_position;
+layout(location = 3) in vec4 previous_position;
+
+layout(location = 0) out vec4 out_color;
+#ifdef VELOCITY
+layout(location = 1) out vec4 out_velocity;
+#endif
+
+void main() {
+    // 0.5 on the outline
+    float distance = texture(sampler2D(glyph_atlas, atlas_sampler), uv).r;
+    float smoothing = max(fwidth(distance) * 0.7, 1e-4);
+    float coverage = smoothstep(0.5 - smoothing, 0.5 + smoothing, distance);
+    out_color = vec4(color.rgb, color.a * coverage);
+#ifdef VELOCITY
+    vec2 velocity = (current_position.xy / current_position.w - previous_position.xy / previous_position.w) * 0.5;
+    out_velocity = vec4(velocity, 0.0, out_color.a);
+#endif
+}
@@ -0,0 +1,35 @@
+#version 450
+// Instanced glyph quads, four strip vertices generated per instance ; the same for screen text (pixel projection)
+// and world text (the camera)
+
+layout(set = 0, binding = 0) uniform TextView {
+    mat4 view_projection;
+    // without jitter, for the motion vectors
+    mat4 current_view_projection;
+    mat4 previous_view_projection;
+} text_view;
+
+// per instance: bottom left corner, then the bottom and left edges of the quad
+layout(location = 0) in vec4 in_origin;
+layout(location = 1) in vec4 in_axis_x;
+layout(location = 2) in vec4 in_axis_y;
+// uv min in xy, max in zw
+layout(location = 3) in vec4 in_uv_rect;
+layout(location = 4) in vec4 in_color;
+
+layout(location = 0) out vec2 out_uv;
+layout(location = 1) out vec4 out_color;
+layout(location = 2) out vec4 out_current_position;
+layout(location = 3) out vec4 out_previous_position;
+
+void main() {
+    // (0, 0) bottom left to (1, 1) top right
+    vec2 corner = vec2(float(gl_VertexIndex & 1), float(gl_VertexIndex >> 1));
+    vec4 position = vec4(in_origin.xyz + in_axis_x.xyz * corner.x + in_axis_y.xyz * corner.y, 1.0);
+    gl_Position = text_view.view_projection * position;
+    out_current_position = text_view.current_view_projection * position;
+    out_previous_position = text_view.previous_view_projection * position;
+    // atlas rows go down, the quad goes up
+    out_uv = mix(in_uv_rect.xy, in_uv_rect.zw, vec2(corner.x, 1.0 - corner.y));
+    out_color = in_color;
+}
@@ -0,0 +1,340 @@
+// Sky rendering: skyboxes from cube maps or equirectangular HDR images, and a procedural physical sky lit by a
+// directional light. The sky is drawn after the opaque geometry, on the far plane, so the depth test keeps it to the
+// pixels nothing was drawn over.
+
+use ash::{vk, Device};
+use glam::{Mat4, Vec3, Vec4};
+use tracing::info;
+
+use crate::vulkan_api::VkApp;
+use crate::vulkan_api::descriptors::{DescriptorAllocator, DescriptorBinding, DescriptorBindings};
+use crate::vulkan_api::image::{GpuImage, ImageDesc};
+use crate::vulkan_api::memory::{as_bytes, GpuBuffer, MemoryLocation};
+use crate::vulkan_api::pipeline::{BlendMode, GraphicsPipeline, GraphicsPipelineBuilder, VertexLayout};
+use crate::vulkan_api::sampler::{Sampler, SamplerDesc};
+use crate::vulkan_api::shader::{Shader, ShaderStage};
+
+use super::camera::Camera;
+use super::hdr::HdrImage;
+use super::pbr::{equirect_to_cube, Light, LightKind, ENVIRONMENT_FORMAT};
+use super::texture::{f32_to_f16, Texture};
+use super::{RendererError, RendererResult, TargetFormats};
+
+const SKY_VERTEX_SHADER: &str = include_str!("shaders/sky/sky.vert");
+const SKYBOX_SHADER: &str = include_str!("shaders/sky/skybox.frag");
+const PROCEDURAL_SKY_SHADER: &str = include_str!("shaders/sky/procedural_sky.frag");
+// ray marching steps of the CPU side sun transmittance
+const TRANSMITTANCE_STEPS: u32 = 32;
+// Mie extinction over scattering, as in procedural_sky.frag
+const MIE_EXTINCTION: f32 = 1.1;
+
+// `SkyView` uniform block of the sky shaders (std140)
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+struct SkyViewData {
+    inverse_view_projection: Mat4,
+    previous_view_projection: Mat4,
+    jitter_exposure: [f32; 4],
+}
+
+// `Atmosphere` push constant block of procedural_sky.frag
+#[repr(C)]
+#[derive(Copy, Clone)]
+struct AtmosphereConstants {
+    sun: [f32; 4],
+    rayleigh: [f32; 4],
+    mie: [f32; 4],
+    planet: [f32; 4],
+}
+
+// A cube map of linear radiance, +Y up
+pub struct Skybox {
+    cube: GpuImage,
+    pub intensity: f32,
+}
+
+impl Skybox {
+    pub fn from_hdr(vk_app: &VkApp, image: &HdrImage, face_size: u32, name: &str) -> RendererResult<Self> {
+        let equirect = Texture::from_rgba_f32(vk_app, image.width, image.height, &image.pixels, &format!("{name} [equirect]"))?;
+        Self::from_equirect(vk_app, &equirect, face_size, name)
+    }
+
+    // `equirect` is a linear equirectangular (latitude-longitude) map, +Y up
+    pub fn from_equirect(vk_app: &VkApp, equirect: &Texture, face_size: u32, name: &str) -> RendererResult<Self> {
+        let cube = equirect_to_cube(vk_app, equirect, face_size, &format!("{name} [cube]"))?;
+        info!(target: "torii::renderer", name, face_size, "Created skybox from an equirectangular map");
+        Ok(Skybox { cube, intensity: 1.0 })
+    }
+
+    // +X, -X, +Y, -Y, +Z, -Z faces, square and all of the same size
+    pub fn from_faces(vk_app: &VkApp, faces: [&HdrImage; 6], name: &str) -> RendererResult<Self> {
+        let face_size = faces[0].width;
+        if face_size == 0 {
+            return Err(RendererError::SkyboxFacesError("empty face".to_owned()));
+        }
+        if let Some(face) = faces.iter().find(|face| face.width != face_size || face.height != face_size) {
+            return Err(RendererError::SkyboxFacesError(format!(
+                "faces must be square and of the same size, got {}x{} next to {face_size}x{face_size}",
+                face.width, face.height
+            )));
+        }
+        let half_pixels: Vec<u16> = faces
+            .iter()
+            .flat_map(|face| face.pixels.iter().flatten())
+            .map(|&value| f32_to_f16(value))
+            .collect();
+        let expected = 6 * face_size as usize * face_size as usize * 4;
+        if half_pixels.len() != expected {
+            return Err(RendererError::TextureDataSizeError { expected: expected * 2, given: half_pixels.len() * 2 });
+        }
+        let desc = ImageDesc::new_cube(ENVIRONMENT_FORMAT, face_size).with_full_mip_chain();
+        let cube = GpuImage::with_data(vk_app, desc, vk::ImageUsageFlags::SAMPLED, as_bytes(&half_pixels), true, &format!("{name} [cube]"))?;
+        info!(target: "torii::renderer", name, face_size, "Created skybox from cube faces");
+        Ok(Skybox { cube, intensity: 1.0 })
+    }
+
+    pub fn with_intensity(mut self, intensity: f32) -> Self {
+        self.intensity = intensity;
+        self
+    }
+
+    // GETTERS
+
+    pub fn cube(&self) -> &GpuImage {
+        &self.cube
+    }
+}
+
+// Single scattering atmosphere around a spherical planet, Earth's by default ; meters and inverse meters
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct ProceduralSky {
+    // towards the sun, see follow_light
+    pub sun_direction: Vec3,
+    pub sun_intensity: f32,
+    // radians, the real sun is about 0.0047
+    pub sun_angular_radius: f32,
+    // per wavelength (red, green, blue), what turns the sky blue and the sunsets red
+    pub rayleigh_scattering: Vec3,
+    pub rayleigh_scale_height: f32,
+    // haze and the glow around the sun
+    pub mie_scattering: f32,
+    pub mie_scale_height: f32,
+    // forward scattering of the haze, -1..1
+    pub mie_anisotropy: f32,
+    pub planet_radius: f32,
+    pub atmosphere_radius: f32,
+    pub viewer_altitude: f32,
+    // scales the whole sky, to match the units of the scene's lights
+    pub intensity: f32,
+}
+
+impl Default for ProceduralSky {
+    fn default() -> Self {
+        ProceduralSky {
+            sun_direction: Vec3::new(0.0, 0.3, -1.0).normalize(),
+            sun_intensity: 22.0,
+            sun_angular_radius: 0.01,
+            rayleigh_scattering: Vec3::new(5.5e-6, 13.0e-6, 22.4e-6),
+            rayleigh_scale_height: 8.0e3,
+            mie_scattering: 21.0e-6,
+            mie_scale_height: 1.2e3,
+            mie_anisotropy: 0.758,
+            planet_radius: 6371.0e3,
+            atmosphere_radius: 6471.0e3,
+            viewer_altitude: 1.0,
+            intensity: 1.0,
+        }
+    }
+}
+
+impl ProceduralSky {
+    // Puts the sun where `light` comes from ; call it every frame the light moves. Only directional lights are
+    // followed, false for the others.
+    pub fn follow_light(&mut self, light: &Light) -> bool {
+        match light.kind {
+            LightKind::Directional { direction } => {
+                self.sun_direction = -direction.normalize_or(Vec3::NEG_Y);
+                true
+            },
+            _ => false,
+        }
+    }
+
+    // Share of the sunlight per channel that reaches the viewer through the atmosphere, black once the sun is below
+    // the horizon ; the color to give the directional light the sky follows so it reddens at sunset
+    pub fn sun_transmittance(&self) -> Vec3 {
+        let origin = Vec3::new(0.0, self.planet_radius + self.viewer_altitude, 0.0);
+        let direction = self.sun_direction.normalize_or(Vec3::Y);
+        if let Some((entry, _)) = ray_sphere(origin, direction, self.planet_radius) {
+            if entry > 0.0 {
+                return Vec3::ZERO;
+            }
+        }
+        let Some((_, exit)) = ray_sphere(origin, direction, self.atmosphere_radius) else {
+            return Vec3::ONE;
+        };
+        let step = exit.max(0.0) / TRANSMITTANCE_STEPS as f32;
+        let (mut rayleigh_depth, mut mie_depth) = (0.0, 0.0);
+        for i in 0..TRANSMITTANCE_STEPS {
+            let height = (origin + direction * ((i as f32 + 0.5) * step)).length() - self.planet_radius;
+            rayleigh_depth += (-height / self.rayleigh_scale_height).exp() * step;
+            mie_depth += (-height / self.mie_scale_height).exp() * step;
+        }
+        let optical_depth = self.rayleigh_scattering * rayleigh_depth + Vec3::splat(self.mie_scattering * MIE_EXTINCTION * mie_depth);
+        (-optical_depth).exp()
+    }
+
+    fn gpu_data(&self) -> AtmosphereConstants {
+        AtmosphereConstants {
+            sun: self.sun_direction.normalize_or(Vec3::Y).extend(self.sun_intensity).to_array(),
+            rayleigh: self.rayleigh_scattering.extend(self.rayleigh_scale_height).to_array(),
+            mie: [self.mie_scattering, self.mie_scale_height, self.mie_anisotropy.clamp(-0.999, 0.999), self.sun_angular_radius],
+            planet: [self.planet_radius, self.atmosphere_radius, self.viewer_altitude, self.intensity],
+        }
+    }
+}
+
+// distances along `direction` (normalized) to where the ray enters and leaves the sphere of `radius` centered on the
+// origin, None when it misses
+fn ray_sphere(origin: Vec3, direction: Vec3, radius: f32) -> Option<(f32, f32)> {
+    let b = origin.dot(direction);
+    let c = origin.length_squared() - radius * radius;
+    let discriminant = b * b - c;
+    if discriminant < 0.0 {
+        return None;
+    }
+    let root = discriminant.sqrt();
+    Some((-b - root, -b + root))
+}
+
+struct FrameResources {
+    view_buffer: GpuBuffer,
+    descriptor_allocator: DescriptorAllocator,
+}
+
+// Draws a Skybox, any cube map (e.g. Environment::cube) or a ProceduralSky. Each frame: begin_frame uploads the
+// camera, then one of the draws is recorded after the opaque geometry in the pass the caller began, which must have
+// a depth attachment. Outputs linear radiance scaled by the exposure, and motion vectors when the target formats have
+// a velocity attachment.
+pub struct SkyRenderer {
+    device: Device,
+    skybox_pipeline: GraphicsPipeline,
+    procedural_pipeline: GraphicsPipeline,
+    sampler: Sampler,
+    // should match the ForwardRenderer's
+    pub exposure: f32,
+    frames: Vec<FrameResources>,
+    frame_index: usize,
+    // the last frame's camera, for the motion vectors
+    previous_view_projection: Option<Mat4>,
+}
+
+impl SkyRenderer {
+    pub fn new(vk_app: &VkApp, formats: TargetFormats) -> RendererResult<Self> {
+        let vertex = Shader::from_glsl(SKY_VERTEX_SHADER, ShaderStage::Vertex)?;
+        let with_velocity = formats.velocity != vk::Format::UNDEFINED;
+        let defines: &[(&str, &str)] = if with_velocity { &[("VELOCITY", "1")] } else { &[] };
+        let color_formats = if with_velocity { vec![formats.color, formats.velocity] } else { vec![formats.color] };
+        let pipeline = |source: &str, name: &str| -> RendererResult<GraphicsPipeline> {
+            let fragment = Shader::from_glsl_with_defines(source, ShaderStage::Fragment, defines)?;
+            // on the far plane, behind everything already drawn ; the depth buffer is left as it is
+            Ok(GraphicsPipelineBuilder::new()
+                .vertex(&vertex, VertexLayout::default())
+                .fragment(&fragment)
+                .color_formats(&color_formats)
+                .depth(formats.depth, true, false, vk::CompareOp::LESS_OR_EQUAL)
+                .samples(formats.samples)
+                .cull_mode(vk::CullModeFlags::NONE, vk::FrontFace::COUNTER_CLOCKWISE)
+                .blend(BlendMode::Opaque)
+                .build(vk_app, name)?)
+        };
+        let skybox_pipeline = pipeline(SKYBOX_SHADER, "skybox")?;
+        let procedural_pipeline = pipeline(PROCEDURAL_SKY_SHADER, "procedural sky")?;
+
+        let frames = (0..vk_app.vk_prop().renderer_config.frames_in_flight.max(1))
+            .map(|frame| {
+                Ok(FrameResources {
+                    view_buffer: GpuBuffer::new(
+                        vk_app,
+                        size_of::<SkyViewData>() as vk::DeviceSize,
+                        vk::BufferUsageFlags::UNIFORM_BUFFER,
+                        MemoryLocation::HostVisible,
+                        &format!("sky view [frame {frame}]"),
+                    )?,
+                    descriptor_allocator: DescriptorAllocator::new(vk_app.device())?,
+                })
+            })
+            .collect::<RendererResult<Vec<_>>>()?;
+
+        Ok(SkyRenderer {
+            device: vk_app.device().clone(),
+            skybox_pipeline,
+            procedural_pipeline,
+            sampler: Sampler::new(vk_app, SamplerDesc::linear_clamp(), "sky sampler")?,
+            exposure: 1.0,
+            frames,
+            frame_index: 0,
+            previous_view_projection: None,
+        })
+    }
+
+    // Starts recording frame `frame_index` (modulo the frames in flight) ; the previous frame that used the same slot
+    // must have completed on the GPU
+    pub fn begin_frame(&mut self, frame_index: usize, camera: &Camera) -> RendererResult<()> {
+        self.frame_index = frame_index % self.frames.len();
+        let frame = &mut self.frames[self.frame_index];
+        frame.descriptor_allocator.reset()?;
+        // the sky is infinitely far away, the camera only turns it
+        let mut rotation = camera.view;
+        rotation.w_axis = Vec4::W;
+        let view_projection = camera.view_projection();
+        let view_data = SkyViewData {
+            inverse_view_projection: (camera.projection * rotation).inverse(),
+            previous_view_projection: self.previous_view_projection.unwrap_or(view_projection),
+            jitter_exposure: [camera.jitter.x, camera.jitter.y, self.exposure, 0.0],
+        };
+        self.previous_view_projection = Some(view_projection);
+        frame.view_buffer.write(0, as_bytes(&[view_data]));
+        Ok(())
+    }
+
+    // Records `skybox` into a pass the caller began with dynamic rendering, viewport and scissor already set, after the
+    // opaque geometry
+    pub fn draw_skybox(&mut self, command_buffer: vk::CommandBuffer, skybox: &Skybox) -> RendererResult<()> {
+        self.draw_cube(command_buffer, skybox.cube(), skybox.intensity)
+    }
+
+    // Same as draw_skybox for any cube image readable by fragment shaders, such as an Environment's
+    pub fn draw_cube(&mut self, command_buffer: vk::CommandBuffer, cube: &GpuImage, intensity: f32) -> RendererResult<()> {
+        let frame = &self.frames[self.frame_index];
+        let bindings = DescriptorBindings::new()
+            .bind(0, 0, DescriptorBinding::whole_buffer(frame.view_buffer.buffer()))
+            .bind(0, 1, DescriptorBinding::sampled_image(cube.view()))
+            .bind(0, 2, DescriptorBinding::Sampler(self.sampler.sampler()))
+            .with_push_constants(as_bytes(&[intensity]));
+        self.draw(command_buffer, true, &bindings)
+    }
+
+    // Same as draw_skybox for the physical sky ; keep its sun on the scene's directional light with
+    // ProceduralSky::follow_light
+    pub fn draw_procedural(&mut self, command_buffer: vk::CommandBuffer, sky: &ProceduralSky) -> RendererResult<()> {
+        let frame = &self.frames[self.frame_index];
+        let bindings = DescriptorBindings::new()
+            .bind(0, 0, DescriptorBinding::whole_buffer(frame.view_buffer.buffer()))
+            .with_push_constants(as_bytes(&[sky.gpu_data()]));
+        self.draw(command_buffer, false, &bindings)
+    }
+
+    fn draw(&mut self, command_buffer: vk::CommandBuffer, skybox: bool, bindings: &DescriptorBindings) -> RendererResult<()> {
+        let pipeline = if skybox { &self.skybox_pipeline } else { &self.procedural_pipeline };
+        unsafe {
+            self.device.cmd_bind_pipeline(command_buffer, vk::PipelineBindPoint::GRAPHICS, pipeline.pipeline());
+        }
+        let frame = &mut self.frames[self.frame_index];
+        frame.descriptor_allocator.bind(command_buffer, vk::PipelineBindPoint::GRAPHICS, pipeline.layout(), bindings)?;
+        unsafe {
+            self.device.cmd_draw(command_buffer, 3, 1, 0, 0);
+        }
+        Ok(())
+    }
+}
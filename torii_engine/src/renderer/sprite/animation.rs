@@ -0,0 +1,187 @@
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum AnimationDirection {
+    #[default]
+    Forward,
+    Reverse,
+    // forward then back, the end frames shown once per cycle
+    PingPong,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct AnimationFrame {
+    // index in AtlasData::regions
+    pub region: usize,
+    // seconds
+    pub duration: f32,
+}
+
+// Frames of an atlas played one after the other
+#[derive(Clone, Debug, PartialEq)]
+pub struct SpriteAnimation {
+    pub frames: Vec<AnimationFrame>,
+    pub direction: AnimationDirection,
+    // stops on the last frame of the cycle otherwise
+    pub looping: bool,
+}
+
+impl SpriteAnimation {
+    pub fn new(frames: Vec<AnimationFrame>) -> Self {
+        SpriteAnimation {
+            frames,
+            direction: AnimationDirection::Forward,
+            looping: true,
+        }
+    }
+
+    pub fn with_direction(mut self, direction: AnimationDirection) -> Self {
+        self.direction = direction;
+        self
+    }
+
+    pub fn with_looping(mut self, looping: bool) -> Self {
+        self.looping = looping;
+        self
+    }
+
+    // seconds of one cycle
+    pub fn duration(&self) -> f32 {
+        self.sequence().map(|frame| self.frames[frame].duration).sum()
+    }
+
+    // Region shown `time` seconds after the start, None without frames
+    pub fn region_at(&self, time: f32) -> Option<usize> {
+        let duration = self.duration();
+        if self.frames.is_empty() {
+            return None;
+        }
+        if duration <= 0.0 {
+            return self.sequence().last().map(|frame| self.frames[frame].region);
+        }
+        let mut time = if self.looping { time.rem_euclid(duration) } else { time.clamp(0.0, duration) };
+        let mut last = 0;
+        for frame in self.sequence() {
+            last = frame;
+            if time < self.frames[frame].duration {
+                break;
+            }
+            time -= self.frames[frame].duration;
+        }
+        Some(self.frames[last].region)
+    }
+
+    // whether a non looping animation reached its end after `time` seconds
+    pub fn is_finished(&self, time: f32) -> bool {
+        !self.looping && time >= self.duration()
+    }
+
+    // indices in `frames` of one cycle
+    fn sequence(&self) -> impl Iterator<Item = usize> + '_ {
+        let count = self.frames.len();
+        let (forward, backward) = match self.direction {
+            AnimationDirection::Forward => (0..count, 0..0),
+            AnimationDirection::Reverse => (0..0, 0..count),
+            AnimationDirection::PingPong => (0..count, 1..count.saturating_sub(1)),
+        };
+        forward.chain(backward.rev())
+    }
+}
+
+// Playback state of an animation, advanced by the frame's delta time ; one per animated sprite
+#[derive(Clone, Debug, PartialEq)]
+pub struct AnimationPlayer {
+    animation: String,
+    time: f32,
+    pub speed: f32,
+    pub playing: bool,
+}
+
+impl AnimationPlayer {
+    pub fn new(animation: &str) -> Self {
+        AnimationPlayer {
+            animation: animation.to_owned(),
+            time: 0.0,
+            speed: 1.0,
+            playing: true,
+        }
+    }
+
+    // Switches to `animation` from its start, keeps going when it's the current one
+    pub fn play(&mut self, animation: &str) {
+        if self.animation != animation {
+            self.animation = animation.to_owned();
+            self.time = 0.0;
+        }
+        self.playing = true;
+    }
+
+    pub fn restart(&mut self) {
+        self.time = 0.0;
+        self.playing = true;
+    }
+
+    pub fn update(&mut self, delta_time: f32) {
+        if self.playing {
+            self.time += delta_time * self.speed;
+        }
+    }
+
+    // GETTERS
+
+    pub fn animation(&self) -> &str {
+        &self.animation
+    }
+
+    // seconds since the start of the animation, scaled by the speed
+    pub fn time(&self) -> f32 {
+        self.time
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // regions 10, 11, 12, 13 shown for a second each
+    fn animation(direction: AnimationDirection) -> SpriteAnimation {
+        let frames = (10..14).map(|region| AnimationFrame { region, duration: 1.0 }).collect();
+        SpriteAnimation::new(frames).with_direction(direction)
+    }
+
+    fn shown(animation: &SpriteAnimation, seconds: usize) -> Vec<usize> {
+        (0..seconds).map(|second| animation.region_at(second as f32 + 0.5).unwrap()).collect()
+    }
+
+    #[test]
+    fn ping_pong_shows_the_end_frames_once_per_cycle() {
+        let ping_pong = animation(AnimationDirection::PingPong);
+        assert_eq!(ping_pong.duration(), 6.0);
+        assert_eq!(shown(&ping_pong, 12), [10, 11, 12, 13, 12, 11, 10, 11, 12, 13, 12, 11]);
+    }
+
+    #[test]
+    fn forward_and_reverse_loop() {
+        assert_eq!(shown(&animation(AnimationDirection::Forward), 6), [10, 11, 12, 13, 10, 11]);
+        assert_eq!(shown(&animation(AnimationDirection::Reverse), 6), [13, 12, 11, 10, 13, 12]);
+        // before the start counts back from the end of the cycle
+        assert_eq!(animation(AnimationDirection::Forward).region_at(-0.5), Some(13));
+    }
+
+    #[test]
+    fn non_looping_animations_stop_on_their_last_frame() {
+        let once = animation(AnimationDirection::Forward).with_looping(false);
+        assert_eq!(shown(&once, 7), [10, 11, 12, 13, 13, 13, 13]);
+        assert_eq!(once.region_at(-1.0), Some(10));
+        assert!(!once.is_finished(3.9));
+        assert!(once.is_finished(4.0));
+
+        let ping_pong_once = animation(AnimationDirection::PingPong).with_looping(false);
+        assert_eq!(ping_pong_once.region_at(100.0), Some(11));
+    }
+
+    #[test]
+    fn degenerate_animations() {
+        assert_eq!(SpriteAnimation::new(vec![]).region_at(1.0), None);
+        let instant = SpriteAnimation::new(vec![AnimationFrame { region: 3, duration: 0.0 }, AnimationFrame { region: 4, duration: 0.0 }]);
+        assert_eq!(instant.region_at(1.0), Some(4));
+    }
+}
@@ -0,0 +1,395 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::path::Path;
+
+use glam::{UVec2, Vec2, Vec4};
+use serde::de::{MapAccess, Visitor};
+use serde::{Deserialize, Deserializer};
+
+use crate::vulkan_api::VkApp;
+
+use crate::renderer::texture::Texture;
+use crate::renderer::{RendererError, RendererResult};
+
+use super::animation::{AnimationDirection, AnimationFrame, AnimationPlayer, SpriteAnimation};
+use super::batch::Sprite;
+use super::packer::PackedAtlas;
+
+// frame duration of the animations that don't give one, seconds
+const DEFAULT_FRAME_DURATION: f32 = 0.1;
+
+// A named rectangle of an atlas image, pixels from its top left corner. Packers may trim the transparent border of
+// a frame: `source_size` is the frame before trimming and `trim_offset` where the kept rectangle sat in it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct AtlasRegion {
+    pub name: String,
+    pub position: UVec2,
+    pub size: UVec2,
+    pub trim_offset: UVec2,
+    pub source_size: UVec2,
+}
+
+// Layout of a texture atlas: its regions and the animations going through them
+#[derive(Clone, Debug, Default)]
+pub struct AtlasData {
+    pub width: u32,
+    pub height: u32,
+    pub regions: Vec<AtlasRegion>,
+    pub animations: HashMap<String, SpriteAnimation>,
+    region_indices: HashMap<String, usize>,
+}
+
+impl AtlasData {
+    pub fn new(width: u32, height: u32, regions: Vec<AtlasRegion>) -> Self {
+        let region_indices = regions.iter().enumerate().map(|(index, region)| (region.name.clone(), index)).collect();
+        AtlasData {
+            width,
+            height,
+            regions,
+            animations: HashMap::new(),
+            region_indices,
+        }
+    }
+
+    // Sprite sheet of `frame_count` frames of `frame_size` laid out in rows from the top left, named "{name} {index}"
+    // and played in order by the `name` animation
+    pub fn from_grid(name: &str, width: u32, height: u32, frame_size: UVec2, frame_count: u32, frame_duration: f32) -> RendererResult<Self> {
+        let columns = width / frame_size.x.max(1);
+        let rows = height / frame_size.y.max(1);
+        if frame_size.min_element() == 0 || frame_count > columns * rows {
+            return Err(RendererError::AtlasParseError(format!(
+                "{frame_count} frames of {}x{} don't fit a {width}x{height} sprite sheet",
+                frame_size.x, frame_size.y
+            )));
+        }
+        let regions = (0..frame_count)
+            .map(|index| AtlasRegion {
+                name: format!("{name} {index}"),
+                position: UVec2::new(index % columns, index / columns) * frame_size,
+                size: frame_size,
+                trim_offset: UVec2::ZERO,
+                source_size: frame_size,
+            })
+            .collect();
+        let mut atlas = Self::new(width, height, regions);
+        let frames = (0..frame_count as usize).map(|region| AnimationFrame { region, duration: frame_duration }).collect();
+        atlas.animations.insert(name.to_owned(), SpriteAnimation::new(frames));
+        Ok(atlas)
+    }
+
+    pub fn load_json(path: impl AsRef<Path>) -> RendererResult<Self> {
+        let path = path.as_ref();
+        let json = std::fs::read_to_string(path).map_err(|source| RendererError::IoError { path: path.to_path_buf(), source })?;
+        Self::parse_json(&json)
+    }
+
+    // TexturePacker "JSON (Hash)" / "JSON (Array)" exports and Aseprite sprite sheet exports, whose frame tags become
+    // animations (all the frames make the "default" one when there are no tags) ; rotated frames aren't supported
+    pub fn parse_json(json: &str) -> RendererResult<Self> {
+        let error = |message: String| RendererError::AtlasParseError(message);
+        let parsed: JsonAtlas = serde_json::from_str(json).map_err(|parse_error| error(parse_error.to_string()))?;
+        let frames = match parsed.frames {
+            JsonFrames::Hash(OrderedFrames(frames)) => frames,
+            JsonFrames::Array(frames) => frames.into_iter().map(|frame| (frame.filename, frame.frame)).collect(),
+        };
+
+        let mut regions = Vec::with_capacity(frames.len());
+        let mut durations = Vec::with_capacity(frames.len());
+        for (name, frame) in frames {
+            if frame.rotated {
+                return Err(error(format!("frame {name} is rotated, disable rotation in the packer")));
+            }
+            let size = UVec2::new(frame.frame.w, frame.frame.h);
+            let trim_offset = frame.sprite_source_size.map_or(UVec2::ZERO, |rect| UVec2::new(rect.x, rect.y));
+            regions.push(AtlasRegion {
+                name,
+                position: UVec2::new(frame.frame.x, frame.frame.y),
+                size,
+                trim_offset,
+                source_size: frame.source_size.map_or(size, |source| UVec2::new(source.w, source.h)),
+            });
+            durations.push(frame.duration.map_or(DEFAULT_FRAME_DURATION, |milliseconds| milliseconds / 1000.0));
+        }
+        let (width, height) = match parsed.meta.size {
+            Some(size) => (size.w, size.h),
+            None => regions.iter().fold((0, 0), |(width, height), region| {
+                let end = region.position + region.size;
+                (width.max(end.x), height.max(end.y))
+            }),
+        };
+        let mut atlas = Self::new(width, height, regions);
+
+        let frame = |region: usize| AnimationFrame { region, duration: durations[region] };
+        for tag in parsed.meta.frame_tags {
+            if tag.from > tag.to || tag.to >= atlas.regions.len() {
+                return Err(error(format!("tag {} goes through frames {}..={} out of {}", tag.name, tag.from, tag.to, atlas.regions.len())));
+            }
+            let direction = match tag.direction.as_str() {
+                "reverse" => AnimationDirection::Reverse,
+                "pingpong" => AnimationDirection::PingPong,
+                _ => AnimationDirection::Forward,
+            };
+            let animation = SpriteAnimation::new((tag.from..=tag.to).map(frame).collect()).with_direction(direction);
+            atlas.animations.insert(tag.name, animation);
+        }
+        if atlas.animations.is_empty() && !atlas.regions.is_empty() {
+            atlas.animations.insert("default".to_owned(), SpriteAnimation::new((0..atlas.regions.len()).map(frame).collect()));
+        }
+        Ok(atlas)
+    }
+
+    pub fn region_index(&self, name: &str) -> Option<usize> {
+        self.region_indices.get(name).copied()
+    }
+
+    pub fn region(&self, name: &str) -> Option<&AtlasRegion> {
+        self.region_index(name).map(|index| &self.regions[index])
+    }
+
+    // region shown by `player` right now, None when its animation isn't in the atlas
+    pub fn animated_region(&self, player: &AnimationPlayer) -> Option<usize> {
+        self.animations.get(player.animation())?.region_at(player.time())
+    }
+
+    // uv min in xy, max in zw
+    pub fn uv_rect(&self, region: usize) -> Vec4 {
+        let region = &self.regions[region];
+        let atlas_size = Vec2::new(self.width.max(1) as f32, self.height.max(1) as f32);
+        let min = region.position.as_vec2() / atlas_size;
+        let max = (region.position + region.size).as_vec2() / atlas_size;
+        Vec4::new(min.x, min.y, max.x, max.y)
+    }
+
+    // Sprite showing `region` at `pixels_per_unit` texels per world unit, centered on `position` as if it weren't
+    // trimmed
+    pub fn sprite(&self, region: usize, position: Vec2, pixels_per_unit: f32) -> Sprite {
+        self.sprite_with_pivot(region, position, Vec2::splat(0.5), pixels_per_unit)
+    }
+
+    // Same as sprite with `pivot` (0..1, (0, 0) the bottom left corner) of the untrimmed frame put on `position`,
+    // animation frames trimmed differently stay in place
+    pub fn sprite_with_pivot(&self, region: usize, position: Vec2, pivot: Vec2, pixels_per_unit: f32) -> Sprite {
+        let uv_rect = self.uv_rect(region);
+        let region = &self.regions[region];
+        let size = region.size.as_vec2().max(Vec2::ONE);
+        let source_size = region.source_size.as_vec2();
+        // trim offsets count from the top, the pivot from the bottom
+        let trim_from_bottom = Vec2::new(region.trim_offset.x as f32, source_size.y - region.trim_offset.y as f32 - size.y);
+        Sprite {
+            position,
+            size: size / pixels_per_unit,
+            pivot: (pivot * source_size - trim_from_bottom) / size,
+            uv_rect,
+            ..Sprite::default()
+        }
+    }
+}
+
+// An atlas image with its layout
+pub struct TextureAtlas {
+    texture: Texture,
+    data: AtlasData,
+}
+
+impl TextureAtlas {
+    pub fn new(texture: Texture, data: AtlasData) -> Self {
+        TextureAtlas { texture, data }
+    }
+
+    // Uploads the image of a runtime packed atlas
+    pub fn from_packed(vk_app: &VkApp, packed: PackedAtlas, name: &str) -> RendererResult<Self> {
+        let texture = Texture::from_rgba8(vk_app, packed.data.width, packed.data.height, &packed.pixels, true, name)?;
+        Ok(TextureAtlas { texture, data: packed.data })
+    }
+
+    // sprite of the region called `name`
+    pub fn sprite(&self, name: &str, position: Vec2, pixels_per_unit: f32) -> Option<Sprite> {
+        self.data.region_index(name).map(|region| self.data.sprite(region, position, pixels_per_unit))
+    }
+
+    // GETTERS
+
+    pub fn texture(&self) -> &Texture {
+        &self.texture
+    }
+
+    pub fn data(&self) -> &AtlasData {
+        &self.data
+    }
+}
+
+// The JSON of the packers, TexturePacker's keys being a subset of Aseprite's
+
+#[derive(Deserialize)]
+struct JsonAtlas {
+    frames: JsonFrames,
+    #[serde(default)]
+    meta: JsonMeta,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum JsonFrames {
+    Hash(OrderedFrames),
+    Array(Vec<JsonNamedFrame>),
+}
+
+// frames of a hash export in the file's order, Aseprite's tags refer to them by position
+struct OrderedFrames(Vec<(String, JsonFrame)>);
+
+impl<'de> Deserialize<'de> for OrderedFrames {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct FramesVisitor;
+
+        impl<'de> Visitor<'de> for FramesVisitor {
+            type Value = OrderedFrames;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("a map of frames")
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+                let mut frames = Vec::with_capacity(map.size_hint().unwrap_or(0));
+                while let Some(entry) = map.next_entry()? {
+                    frames.push(entry);
+                }
+                Ok(OrderedFrames(frames))
+            }
+        }
+
+        deserializer.deserialize_map(FramesVisitor)
+    }
+}
+
+#[derive(Deserialize)]
+struct JsonNamedFrame {
+    filename: String,
+    #[serde(flatten)]
+    frame: JsonFrame,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct JsonFrame {
+    frame: JsonRect,
+    #[serde(default)]
+    rotated: bool,
+    sprite_source_size: Option<JsonRect>,
+    source_size: Option<JsonSize>,
+    // milliseconds, Aseprite only
+    duration: Option<f32>,
+}
+
+#[derive(Deserialize)]
+struct JsonRect {
+    x: u32,
+    y: u32,
+    w: u32,
+    h: u32,
+}
+
+#[derive(Deserialize)]
+struct JsonSize {
+    w: u32,
+    h: u32,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+struct JsonMeta {
+    size: Option<JsonSize>,
+    #[serde(default)]
+    frame_tags: Vec<JsonTag>,
+}
+
+#[derive(Deserialize)]
+struct JsonTag {
+    name: String,
+    from: usize,
+    to: usize,
+    #[serde(default)]
+    direction: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Aseprite "Hash" export: frames keyed by name, not sorted, and frame tags in the meta
+    const ASEPRITE_HASH: &str = r#"{
+        "frames": {
+            "hero 2.aseprite": { "frame": { "x": 32, "y": 0, "w": 16, "h": 16 }, "rotated": false, "trimmed": false,
+                "spriteSourceSize": { "x": 0, "y": 0, "w": 16, "h": 16 }, "sourceSize": { "w": 16, "h": 16 }, "duration": 200 },
+            "hero 0.aseprite": { "frame": { "x": 0, "y": 0, "w": 16, "h": 16 }, "rotated": false, "trimmed": false,
+                "spriteSourceSize": { "x": 0, "y": 0, "w": 16, "h": 16 }, "sourceSize": { "w": 16, "h": 16 }, "duration": 100 },
+            "hero 1.aseprite": { "frame": { "x": 16, "y": 0, "w": 14, "h": 12 }, "rotated": false, "trimmed": true,
+                "spriteSourceSize": { "x": 1, "y": 4, "w": 14, "h": 12 }, "sourceSize": { "w": 16, "h": 16 }, "duration": 100 },
+            "hero 3.aseprite": { "frame": { "x": 48, "y": 0, "w": 16, "h": 16 }, "rotated": false, "trimmed": false,
+                "spriteSourceSize": { "x": 0, "y": 0, "w": 16, "h": 16 }, "sourceSize": { "w": 16, "h": 16 }, "duration": 100 }
+        },
+        "meta": {
+            "app": "https://www.aseprite.org/",
+            "size": { "w": 64, "h": 16 },
+            "frameTags": [
+                { "name": "idle", "from": 0, "to": 1, "direction": "forward" },
+                { "name": "run", "from": 1, "to": 3, "direction": "pingpong" },
+                { "name": "back", "from": 2, "to": 3, "direction": "reverse" }
+            ]
+        }
+    }"#;
+
+    #[test]
+    fn hash_frames_keep_the_file_order() {
+        let atlas = AtlasData::parse_json(ASEPRITE_HASH).unwrap();
+        let names: Vec<&str> = atlas.regions.iter().map(|region| region.name.as_str()).collect();
+        assert_eq!(names, ["hero 2.aseprite", "hero 0.aseprite", "hero 1.aseprite", "hero 3.aseprite"]);
+        assert_eq!((atlas.width, atlas.height), (64, 16));
+
+        let trimmed = atlas.region("hero 1.aseprite").unwrap();
+        assert_eq!(trimmed.position, UVec2::new(16, 0));
+        assert_eq!(trimmed.size, UVec2::new(14, 12));
+        assert_eq!(trimmed.trim_offset, UVec2::new(1, 4));
+        assert_eq!(trimmed.source_size, UVec2::new(16, 16));
+    }
+
+    #[test]
+    fn tags_become_animations() {
+        let atlas = AtlasData::parse_json(ASEPRITE_HASH).unwrap();
+        assert_eq!(atlas.animations.len(), 3);
+        assert!(!atlas.animations.contains_key("default"));
+
+        // tag ranges index the frames in file order
+        let idle = &atlas.animations["idle"];
+        assert_eq!(idle.direction, AnimationDirection::Forward);
+        assert_eq!(idle.frames, [AnimationFrame { region: 0, duration: 0.2 }, AnimationFrame { region: 1, duration: 0.1 }]);
+        let run = &atlas.animations["run"];
+        assert_eq!(run.direction, AnimationDirection::PingPong);
+        assert_eq!(run.frames.iter().map(|frame| frame.region).collect::<Vec<_>>(), [1, 2, 3]);
+        assert_eq!(atlas.animations["back"].direction, AnimationDirection::Reverse);
+    }
+
+    #[test]
+    fn array_frames_without_tags_play_as_default() {
+        let json = r#"{
+            "frames": [
+                { "filename": "a", "frame": { "x": 0, "y": 0, "w": 8, "h": 8 } },
+                { "filename": "b", "frame": { "x": 8, "y": 0, "w": 8, "h": 4 } }
+            ],
+            "meta": {}
+        }"#;
+        let atlas = AtlasData::parse_json(json).unwrap();
+        // without a size, the atlas bounds the frames
+        assert_eq!((atlas.width, atlas.height), (16, 8));
+        let default = &atlas.animations["default"];
+        assert_eq!(default.frames, [AnimationFrame { region: 0, duration: DEFAULT_FRAME_DURATION }, AnimationFrame { region: 1, duration: DEFAULT_FRAME_DURATION }]);
+    }
+
+    #[test]
+    fn tags_past_the_frames_and_rotated_frames_are_rejected() {
+        let out_of_range = ASEPRITE_HASH.replace(r#""from": 2, "to": 3"#, r#""from": 2, "to": 4"#);
+        assert!(matches!(AtlasData::parse_json(&out_of_range), Err(RendererError::AtlasParseError(_))));
+        let rotated = ASEPRITE_HASH.replacen(r#""rotated": false"#, r#""rotated": true"#, 1);
+        assert!(matches!(AtlasData::parse_json(&rotated), Err(RendererError::AtlasParseError(_))));
+        assert!(matches!(AtlasData::parse_json("{"), Err(RendererError::AtlasParseError(_))));
+    }
+}
@@ -0,0 +1,273 @@
+use ash::{vk, Device};
+use glam::{Mat4, Vec2, Vec4};
+use tracing::{info, warn};
+
+use crate::vulkan_api::VkApp;
+use crate::vulkan_api::descriptors::{DescriptorAllocator, DescriptorBinding, DescriptorBindings};
+use crate::vulkan_api::memory::{as_bytes, GpuBuffer, MemoryLocation};
+use crate::vulkan_api::pipeline::{BlendMode, GraphicsPipeline, GraphicsPipelineBuilder, VertexLayout};
+use crate::vulkan_api::sampler::{Sampler, SamplerDesc};
+use crate::vulkan_api::shader::{Shader, ShaderStage};
+
+use crate::renderer::camera::Camera;
+use crate::renderer::texture::Texture;
+use crate::renderer::{RendererResult, TargetFormats};
+
+const VERTEX_SHADER: &str = include_str!("../shaders/sprite/sprite.vert");
+const FRAGMENT_SHADER: &str = include_str!("../shaders/sprite/sprite.frag");
+
+// A textured quad on the XY plane
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Sprite {
+    pub position: Vec2,
+    // world units, negative sizes mirror the sprite
+    pub size: Vec2,
+    // radians, counter clockwise around the pivot
+    pub rotation: f32,
+    // point of the quad put on `position`, (0, 0) the bottom left corner and (1, 1) the top right one
+    pub pivot: Vec2,
+    // part of the texture shown, uv min in xy and max in zw
+    pub uv_rect: Vec4,
+    // multiplies the texture, linear RGBA
+    pub tint: Vec4,
+    // drawing order, higher on top
+    pub z: f32,
+}
+
+impl Default for Sprite {
+    fn default() -> Self {
+        Sprite {
+            position: Vec2::ZERO,
+            size: Vec2::ONE,
+            rotation: 0.0,
+            pivot: Vec2::splat(0.5),
+            uv_rect: Vec4::new(0.0, 0.0, 1.0, 1.0),
+            tint: Vec4::ONE,
+            z: 0.0,
+        }
+    }
+}
+
+impl Sprite {
+    // the whole texture
+    pub fn new(position: Vec2, size: Vec2) -> Self {
+        Sprite {
+            position,
+            size,
+            ..Self::default()
+        }
+    }
+
+    pub fn with_z(mut self, z: f32) -> Self {
+        self.z = z;
+        self
+    }
+
+    pub fn with_tint(mut self, tint: Vec4) -> Self {
+        self.tint = tint;
+        self
+    }
+
+    pub fn with_rotation(mut self, rotation: f32) -> Self {
+        self.rotation = rotation;
+        self
+    }
+
+    // mirrors the texture, the quad stays in place
+    pub fn flipped(mut self, horizontally: bool, vertically: bool) -> Self {
+        if horizontally {
+            self.uv_rect = Vec4::new(self.uv_rect.z, self.uv_rect.y, self.uv_rect.x, self.uv_rect.w);
+        }
+        if vertically {
+            self.uv_rect = Vec4::new(self.uv_rect.x, self.uv_rect.w, self.uv_rect.z, self.uv_rect.y);
+        }
+        self
+    }
+
+    fn instance(&self) -> SpriteInstance {
+        SpriteInstance {
+            position_size: [self.position.x, self.position.y, self.size.x, self.size.y],
+            pivot_rotation: [self.pivot.x, self.pivot.y, self.rotation, 0.0],
+            uv_rect: self.uv_rect.to_array(),
+            tint: self.tint.to_array(),
+        }
+    }
+}
+
+// per instance vertex data of sprite.vert
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+struct SpriteInstance {
+    position_size: [f32; 4],
+    pivot_rotation: [f32; 4],
+    uv_rect: [f32; 4],
+    tint: [f32; 4],
+}
+
+impl SpriteInstance {
+    fn layout() -> VertexLayout {
+        let attribute = |location: u32| {
+            vk::VertexInputAttributeDescription::default()
+                .location(location)
+                .binding(0)
+                .format(vk::Format::R32G32B32A32_SFLOAT)
+                .offset(location * 16)
+        };
+        VertexLayout {
+            bindings: vec![
+                vk::VertexInputBindingDescription::default()
+                    .binding(0)
+                    .stride(size_of::<SpriteInstance>() as u32)
+                    .input_rate(vk::VertexInputRate::INSTANCE),
+            ],
+            attributes: (0..4).map(attribute).collect(),
+        }
+    }
+}
+
+struct QueuedSprite {
+    z: f32,
+    texture: vk::ImageView,
+    instance: SpriteInstance,
+}
+
+struct FrameResources {
+    view_buffer: GpuBuffer,
+    instance_buffer: GpuBuffer,
+    descriptor_allocator: DescriptorAllocator,
+}
+
+// 2D renderer batching sprites: each frame, begin_frame takes the camera (see Camera2d), draw queues sprites, and
+// record sorts them by z then texture and issues one instanced draw per run of sprites sharing a texture, inside a
+// pass the caller began. Alpha blended without depth writes ; sprites with the same z and different textures have no
+// defined order between them.
+pub struct SpriteRenderer {
+    device: Device,
+    pipeline: GraphicsPipeline,
+    linear_sampler: Sampler,
+    nearest_sampler: Sampler,
+    // NEAREST for pixel art
+    pub filter: vk::Filter,
+    max_sprites: usize,
+    frames: Vec<FrameResources>,
+    frame_index: usize,
+    queue: Vec<QueuedSprite>,
+    batch_count: u32,
+}
+
+impl SpriteRenderer {
+    // Up to `max_sprites` per frame, the extra ones are dropped
+    pub fn new(vk_app: &VkApp, formats: TargetFormats, max_sprites: usize) -> RendererResult<Self> {
+        let vertex = Shader::from_glsl(VERTEX_SHADER, ShaderStage::Vertex)?;
+        let with_velocity = formats.velocity != vk::Format::UNDEFINED;
+        let defines: &[(&str, &str)] = if with_velocity { &[("VELOCITY", "1")] } else { &[] };
+        let fragment = Shader::from_glsl_with_defines(FRAGMENT_SHADER, ShaderStage::Fragment, defines)?;
+        let color_formats = if with_velocity { vec![formats.color, formats.velocity] } else { vec![formats.color] };
+        let pipeline = GraphicsPipelineBuilder::new()
+            .vertex(&vertex, SpriteInstance::layout())
+            .fragment(&fragment)
+            .color_formats(&color_formats)
+            .depth(formats.depth, false, false, vk::CompareOp::ALWAYS)
+            .samples(formats.samples)
+            .topology(vk::PrimitiveTopology::TRIANGLE_STRIP)
+            .cull_mode(vk::CullModeFlags::NONE, vk::FrontFace::COUNTER_CLOCKWISE)
+            .blend(BlendMode::Alpha)
+            .build(vk_app, "sprites")?;
+
+        let max_sprites = max_sprites.max(1);
+        let frames = (0..vk_app.vk_prop().renderer_config.frames_in_flight.max(1))
+            .map(|frame| {
+                let buffer = |size: usize, usage: vk::BufferUsageFlags, name: &str| {
+                    GpuBuffer::new(vk_app, size as vk::DeviceSize, usage, MemoryLocation::HostVisible, &format!("sprite {name} [frame {frame}]"))
+                };
+                Ok(FrameResources {
+                    view_buffer: buffer(size_of::<Mat4>(), vk::BufferUsageFlags::UNIFORM_BUFFER, "view")?,
+                    instance_buffer: buffer(max_sprites * size_of::<SpriteInstance>(), vk::BufferUsageFlags::VERTEX_BUFFER, "instances")?,
+                    descriptor_allocator: DescriptorAllocator::new(vk_app.device())?,
+                })
+            })
+            .collect::<RendererResult<Vec<_>>>()?;
+        info!(target: "torii::renderer", max_sprites, "Created sprite renderer");
+
+        Ok(SpriteRenderer {
+            device: vk_app.device().clone(),
+            pipeline,
+            linear_sampler: Sampler::new(vk_app, SamplerDesc::linear_clamp(), "sprite linear sampler")?,
+            nearest_sampler: Sampler::new(vk_app, SamplerDesc::nearest_clamp(), "sprite nearest sampler")?,
+            filter: vk::Filter::LINEAR,
+            max_sprites,
+            frames,
+            frame_index: 0,
+            queue: Vec::with_capacity(max_sprites),
+            batch_count: 0,
+        })
+    }
+
+    // Starts frame `frame_index` (modulo the frames in flight), dropping the sprites queued since the last record ;
+    // the previous frame that used the same slot must have completed on the GPU
+    pub fn begin_frame(&mut self, frame_index: usize, camera: &Camera) -> RendererResult<()> {
+        self.frame_index = frame_index % self.frames.len();
+        let frame = &mut self.frames[self.frame_index];
+        frame.descriptor_allocator.reset()?;
+        frame.view_buffer.write(0, as_bytes(&[camera.jittered_view_projection()]));
+        self.queue.clear();
+        Ok(())
+    }
+
+    // Queues `sprite` textured with `texture` ; the texture must stay alive until the frame completed on the GPU
+    pub fn draw(&mut self, texture: &Texture, sprite: &Sprite) {
+        self.queue.push(QueuedSprite {
+            z: sprite.z,
+            texture: texture.view(),
+            instance: sprite.instance(),
+        });
+    }
+
+    // Records the queued sprites into a pass the caller began with dynamic rendering, viewport and scissor already
+    // set, and empties the queue ; once per frame, the instances of the frame share one buffer
+    pub fn record(&mut self, command_buffer: vk::CommandBuffer) -> RendererResult<()> {
+        if self.queue.len() > self.max_sprites {
+            warn!(target: "torii::renderer", sprites = self.queue.len(), max_sprites = self.max_sprites, "Too many sprites, dropping the extra ones");
+            self.queue.truncate(self.max_sprites);
+        }
+        // stable, sprites sharing z and texture keep their queuing order
+        self.queue.sort_by(|a, b| a.z.total_cmp(&b.z).then_with(|| vk::Handle::as_raw(a.texture).cmp(&vk::Handle::as_raw(b.texture))));
+        let instances: Vec<SpriteInstance> = self.queue.iter().map(|sprite| sprite.instance).collect();
+        let frame = &mut self.frames[self.frame_index];
+        frame.instance_buffer.write(0, as_bytes(&instances));
+
+        let sampler = if self.filter == vk::Filter::NEAREST { &self.nearest_sampler } else { &self.linear_sampler };
+        self.batch_count = 0;
+        unsafe {
+            self.device.cmd_bind_pipeline(command_buffer, vk::PipelineBindPoint::GRAPHICS, self.pipeline.pipeline());
+            self.device.cmd_bind_vertex_buffers(command_buffer, 0, &[frame.instance_buffer.buffer()], &[0]);
+        }
+        let mut first_instance = 0;
+        for batch in self.queue.chunk_by(|a, b| a.texture == b.texture) {
+            let bindings = DescriptorBindings::new()
+                .bind(0, 0, DescriptorBinding::whole_buffer(frame.view_buffer.buffer()))
+                .bind(0, 1, DescriptorBinding::sampled_image(batch[0].texture))
+                .bind(0, 2, DescriptorBinding::Sampler(sampler.sampler()));
+            frame.descriptor_allocator.bind(command_buffer, vk::PipelineBindPoint::GRAPHICS, self.pipeline.layout(), &bindings)?;
+            unsafe {
+                self.device.cmd_draw(command_buffer, 4, batch.len() as u32, 0, first_instance);
+            }
+            first_instance += batch.len() as u32;
+            self.batch_count += 1;
+        }
+        self.queue.clear();
+        Ok(())
+    }
+
+    // GETTERS
+
+    // sprites waiting for record
+    pub fn queued_count(&self) -> usize {
+        self.queue.len()
+    }
+
+    // instanced draws issued by the last record
+    pub fn batch_count(&self) -> u32 {
+        self.batch_count
+    }
+}
@@ -0,0 +1,11 @@
+// 2D rendering: sprites batched per texture into instanced draws, texture atlases packed at runtime or read from
+// Aseprite / TexturePacker JSON, and frame animations through them. camera::Camera2d gives the (pixel perfect) views.
+
+mod animation;
+pub use animation::*;
+mod atlas;
+pub use atlas::*;
+mod batch;
+pub use batch::*;
+mod packer;
+pub use packer::*;
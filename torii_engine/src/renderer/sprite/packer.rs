@@ -0,0 +1,193 @@
+use glam::UVec2;
+use tracing::info;
+
+use crate::renderer::{RendererError, RendererResult};
+
+use super::atlas::{AtlasData, AtlasRegion};
+
+// An atlas packed at runtime, its RGBA8 image row by row ; upload it with TextureAtlas::from_packed
+#[derive(Clone, Debug)]
+pub struct PackedAtlas {
+    pub data: AtlasData,
+    pub pixels: Vec<u8>,
+}
+
+struct PackerImage {
+    name: String,
+    size: UVec2,
+    pixels: Vec<u8>,
+}
+
+// Packs images into one atlas, tallest first on shelves ; the border pixels of each image are repeated into its
+// padding, so linear filtering at the edges doesn't pick up the neighbours
+pub struct AtlasPacker {
+    images: Vec<PackerImage>,
+    max_size: u32,
+    padding: u32,
+}
+
+impl AtlasPacker {
+    // `max_size` bounds both sides of the atlas, e.g. the device's maxImageDimension2D
+    pub fn new(max_size: u32) -> Self {
+        AtlasPacker {
+            images: vec![],
+            max_size,
+            padding: 1,
+        }
+    }
+
+    pub fn with_padding(mut self, padding: u32) -> Self {
+        self.padding = padding;
+        self
+    }
+
+    // RGBA8 pixels row by row, the region is called `name`
+    pub fn add(&mut self, name: &str, width: u32, height: u32, pixels: &[u8]) -> RendererResult<()> {
+        let expected = width as usize * height as usize * 4;
+        if pixels.len() != expected || expected == 0 {
+            return Err(RendererError::TextureDataSizeError { expected, given: pixels.len() });
+        }
+        self.images.push(PackerImage {
+            name: name.to_owned(),
+            size: UVec2::new(width, height),
+            pixels: pixels.to_vec(),
+        });
+        Ok(())
+    }
+
+    // Tries widths from the smallest power of two holding the images' area up to `max_size`, keeping the first whose
+    // shelves are no taller than it is wide
+    pub fn pack(&self) -> RendererResult<PackedAtlas> {
+        if self.images.is_empty() {
+            return Err(RendererError::AtlasPackError("no images to pack".to_owned()));
+        }
+        let padded = |image: &PackerImage| image.size + UVec2::splat(self.padding * 2);
+        let area: u64 = self.images.iter().map(|image| padded(image).as_u64vec2().element_product()).sum();
+        let widest = self.images.iter().map(|image| padded(image).x).max().unwrap_or(1);
+        let mut order: Vec<usize> = (0..self.images.len()).collect();
+        order.sort_by_key(|&index| std::cmp::Reverse(self.images[index].size.y));
+
+        let too_large = || {
+            RendererError::AtlasPackError(format!("{} images don't fit in {}x{} texels", self.images.len(), self.max_size, self.max_size))
+        };
+        let mut width = ((area as f64).sqrt().ceil() as u32).max(widest).next_power_of_two().min(self.max_size);
+        if widest > width {
+            return Err(too_large());
+        }
+        let (positions, height) = loop {
+            let (positions, height) = self.shelves(&order, width);
+            if height <= width || width == self.max_size {
+                break (positions, height);
+            }
+            width = (width * 2).min(self.max_size);
+        };
+        if height > self.max_size {
+            return Err(too_large());
+        }
+
+        let mut pixels = vec![0; width as usize * height as usize * 4];
+        let mut regions = Vec::with_capacity(self.images.len());
+        for (image, &position) in self.images.iter().zip(positions.iter()) {
+            // the padded rectangle, reading the image clamped to its edges
+            let padding = self.padding as i64;
+            for y in -padding..image.size.y as i64 + padding {
+                for x in -padding..image.size.x as i64 + padding {
+                    let source_x = x.clamp(0, image.size.x as i64 - 1) as usize;
+                    let source_y = y.clamp(0, image.size.y as i64 - 1) as usize;
+                    let source = (source_y * image.size.x as usize + source_x) * 4;
+                    let target = ((position.y as i64 + y) as usize * width as usize + (position.x as i64 + x) as usize) * 4;
+                    pixels[target..target + 4].copy_from_slice(&image.pixels[source..source + 4]);
+                }
+            }
+            regions.push(AtlasRegion {
+                name: image.name.clone(),
+                position,
+                size: image.size,
+                trim_offset: UVec2::ZERO,
+                source_size: image.size,
+            });
+        }
+        info!(target: "torii::renderer", images = self.images.len(), width, height, "Packed sprite atlas");
+        Ok(PackedAtlas {
+            data: AtlasData::new(width, height, regions),
+            pixels,
+        })
+    }
+
+    // top left corner of each image (in `images` order, padding excluded) and the total height
+    fn shelves(&self, order: &[usize], width: u32) -> (Vec<UVec2>, u32) {
+        let mut positions = vec![UVec2::ZERO; self.images.len()];
+        let (mut x, mut y, mut shelf_height) = (0, 0, 0);
+        for &index in order {
+            let size = self.images[index].size + UVec2::splat(self.padding * 2);
+            if x + size.x > width && x > 0 {
+                y += shelf_height;
+                x = 0;
+                shelf_height = 0;
+            }
+            positions[index] = UVec2::new(x, y) + UVec2::splat(self.padding);
+            x += size.x;
+            shelf_height = shelf_height.max(size.y);
+        }
+        (positions, y + shelf_height)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn solid(width: u32, height: u32, value: u8) -> Vec<u8> {
+        vec![value; width as usize * height as usize * 4]
+    }
+
+    #[test]
+    fn packed_regions_never_overlap() {
+        let mut packer = AtlasPacker::new(1024).with_padding(2);
+        let sizes = [(30, 10), (8, 40), (16, 16), (64, 3), (5, 5), (33, 21), (1, 1), (12, 50), (40, 40), (7, 19)];
+        for (index, &(width, height)) in sizes.iter().enumerate() {
+            packer.add(&format!("image {index}"), width, height, &solid(width, height, index as u8 + 1)).unwrap();
+        }
+        let packed = packer.pack().unwrap();
+        let data = &packed.data;
+        assert_eq!(packed.pixels.len(), data.width as usize * data.height as usize * 4);
+
+        // padding included, every rectangle stays inside the atlas and away from the others
+        let padded = |region: &AtlasRegion| (region.position - UVec2::splat(2), region.position + region.size + UVec2::splat(2));
+        for (index, region) in data.regions.iter().enumerate() {
+            assert_eq!((region.size.x, region.size.y), sizes[index]);
+            let (min, max) = padded(region);
+            assert!(max.x <= data.width && max.y <= data.height, "{} is outside the atlas", region.name);
+            for other in data.regions[index + 1..].iter() {
+                let (other_min, other_max) = padded(other);
+                let overlaps = min.x < other_max.x && other_min.x < max.x && min.y < other_max.y && other_min.y < max.y;
+                assert!(!overlaps, "{} overlaps {}", region.name, other.name);
+            }
+            // the image landed where its region says, padding repeating its border
+            let pixel = |x: u32, y: u32| packed.pixels[(y as usize * data.width as usize + x as usize) * 4];
+            assert_eq!(pixel(region.position.x, region.position.y), index as u8 + 1);
+            assert_eq!(pixel(min.x, min.y), index as u8 + 1);
+        }
+    }
+
+    #[test]
+    fn images_larger_than_max_size_fail() {
+        let mut packer = AtlasPacker::new(64);
+        packer.add("wide", 70, 4, &solid(70, 4, 1)).unwrap();
+        assert!(matches!(packer.pack(), Err(RendererError::AtlasPackError(_))));
+
+        // each fits alone, not all together
+        let mut packer = AtlasPacker::new(64).with_padding(0);
+        for index in 0..5 {
+            packer.add(&format!("image {index}"), 40, 40, &solid(40, 40, 1)).unwrap();
+        }
+        assert!(matches!(packer.pack(), Err(RendererError::AtlasPackError(_))));
+    }
+
+    #[test]
+    fn empty_packers_and_mismatched_pixels_fail() {
+        assert!(matches!(AtlasPacker::new(64).pack(), Err(RendererError::AtlasPackError(_))));
+        let mut packer = AtlasPacker::new(64);
+        assert!(matches!(packer.add("short", 4, 4, &[0; 12]), Err(RendererError::TextureDataSizeError { expected: 64, given: 12 })));
+    }
+}
@@ -0,0 +1,192 @@
+use std::collections::HashMap;
+
+use ash::{vk, Device};
+use glam::{UVec2, Vec4};
+use tracing::{info, warn};
+
+use crate::vulkan_api::VkApp;
+use crate::vulkan_api::image::{GpuImage, ImageDesc, ImageState};
+use crate::vulkan_api::memory::GpuBuffer;
+
+use crate::renderer::RendererResult;
+
+use super::font::FontFace;
+use super::renderer::FontId;
+use super::sdf::rasterize_sdf;
+
+// empty texels kept around every glyph, so filtering at their edges reads nothing of the neighbours
+const GLYPH_GAP: u32 = 1;
+
+// Where a glyph sits in the atlas: uv min in xy and max in zw, and the quad showing it around the pen position,
+// ems (left, bottom, right, top)
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub(crate) struct AtlasGlyph {
+    pub uv_rect: Vec4,
+    pub plane: Vec4,
+}
+
+struct Shelf {
+    y: u32,
+    height: u32,
+    // free space starts there
+    x: u32,
+}
+
+// Distance fields of the glyphs drawn so far, rasterized the first time they're asked for and packed on shelves in
+// one R8 image. New glyphs go to a CPU copy first, cmd_upload copies the changed rectangle to the GPU.
+pub(crate) struct GlyphAtlas {
+    image: GpuImage,
+    size: u32,
+    pixels: Vec<u8>,
+    // None for glyphs without an outline, or that didn't fit
+    glyphs: HashMap<(FontId, u16), Option<AtlasGlyph>>,
+    shelves: Vec<Shelf>,
+    // texels changed since the last upload, min and max corners
+    dirty: Option<(UVec2, UVec2)>,
+    uploaded: bool,
+    full: bool,
+}
+
+impl GlyphAtlas {
+    pub(crate) fn new(vk_app: &VkApp, size: u32) -> RendererResult<Self> {
+        let desc = ImageDesc::new(vk::Format::R8_UNORM, vk::Extent2D { width: size, height: size });
+        let image = GpuImage::new(vk_app, desc, vk::ImageUsageFlags::SAMPLED | vk::ImageUsageFlags::TRANSFER_DST, "glyph atlas")?;
+        let mut atlas = GlyphAtlas {
+            image,
+            size,
+            pixels: vec![],
+            glyphs: HashMap::new(),
+            shelves: vec![],
+            dirty: None,
+            uploaded: false,
+            full: false,
+        };
+        atlas.clear();
+        info!(target: "torii::renderer", size, "Created glyph atlas");
+        Ok(atlas)
+    }
+
+    // Forgets every glyph, they're rasterized again when next asked for ; the whole image is uploaded again
+    pub(crate) fn clear(&mut self) {
+        self.pixels = vec![0; self.size as usize * self.size as usize];
+        self.glyphs.clear();
+        self.shelves.clear();
+        self.dirty = Some((UVec2::ZERO, UVec2::splat(self.size)));
+        self.full = false;
+    }
+
+    // The glyph of `font`, rasterized and packed when it's new ; None when it has no outline or the atlas is full
+    pub(crate) fn glyph(&mut self, font_id: FontId, face: &FontFace, glyph: ttf_parser::GlyphId) -> Option<AtlasGlyph> {
+        if let Some(&entry) = self.glyphs.get(&(font_id, glyph.0)) {
+            return entry;
+        }
+        let entry = rasterize_sdf(face, glyph).and_then(|sdf| {
+            let Some(position) = self.allocate(UVec2::new(sdf.width, sdf.height)) else {
+                if !self.full {
+                    warn!(target: "torii::renderer", size = self.size, "Glyph atlas is full, glyphs are missing until it's cleared");
+                    self.full = true;
+                }
+                return None;
+            };
+            for row in 0..sdf.height as usize {
+                let target = (position.y as usize + row) * self.size as usize + position.x as usize;
+                let source = row * sdf.width as usize;
+                self.pixels[target..target + sdf.width as usize].copy_from_slice(&sdf.pixels[source..source + sdf.width as usize]);
+            }
+            let end = position + UVec2::new(sdf.width, sdf.height);
+            self.dirty = Some(self.dirty.map_or((position, end), |(min, max)| (min.min(position), max.max(end))));
+            let size = self.size as f32;
+            Some(AtlasGlyph {
+                uv_rect: Vec4::new(position.x as f32, position.y as f32, end.x as f32, end.y as f32) / size,
+                plane: sdf.plane,
+            })
+        });
+        self.glyphs.insert((font_id, glyph.0), entry);
+        entry
+    }
+
+    // Top left corner for `size` texels: on the first shelf it fits without wasting more than a third of its height,
+    // or on a new shelf below the others
+    fn allocate(&mut self, size: UVec2) -> Option<UVec2> {
+        let padded = size + UVec2::splat(GLYPH_GAP);
+        let atlas_size = self.size;
+        let fitting = self.shelves.iter_mut().find(|shelf| {
+            shelf.height >= padded.y && shelf.height * 2 <= padded.y * 3 && shelf.x + padded.x <= atlas_size
+        });
+        let shelf = match fitting {
+            Some(shelf) => shelf,
+            None => {
+                let y = self.shelves.last().map_or(GLYPH_GAP, |shelf| shelf.y + shelf.height);
+                if y + padded.y > atlas_size || GLYPH_GAP + padded.x > atlas_size {
+                    return None;
+                }
+                self.shelves.push(Shelf { y, height: padded.y, x: GLYPH_GAP });
+                self.shelves.last_mut().expect("A shelf was just pushed!")
+            }
+        };
+        let position = UVec2::new(shelf.x, shelf.y);
+        shelf.x += padded.x;
+        Some(position)
+    }
+
+    // Copies the texels changed since the last upload through `staging` (host visible, as large as the atlas),
+    // outside of any pass ; the image ends up readable by fragment shaders
+    pub(crate) fn cmd_upload(&mut self, device: &Device, command_buffer: vk::CommandBuffer, staging: &mut GpuBuffer) {
+        let Some((min, max)) = self.dirty.take() else { return };
+        // the rows of the rectangle, the copy picks its columns
+        let start = min.y as usize * self.size as usize;
+        let end = max.y as usize * self.size as usize;
+        staging.write(start as vk::DeviceSize, &self.pixels[start..end]);
+        let region = vk::BufferImageCopy::default()
+            .buffer_offset((start + min.x as usize) as vk::DeviceSize)
+            .buffer_row_length(self.size)
+            .image_subresource(
+                vk::ImageSubresourceLayers::default()
+                    .aspect_mask(vk::ImageAspectFlags::COLOR)
+                    .mip_level(0)
+                    .base_array_layer(0)
+                    .layer_count(1),
+            )
+            .image_offset(vk::Offset3D { x: min.x as i32, y: min.y as i32, z: 0 })
+            .image_extent(vk::Extent3D { width: max.x - min.x, height: max.y - min.y, depth: 1 });
+
+        // earlier frames still sampling the atlas are ordered before the copy by the barrier
+        let from = if self.uploaded { ImageState::SHADER_READ } else { ImageState::UNDEFINED };
+        self.image.cmd_transition(device, command_buffer, from, ImageState::TRANSFER_DST);
+        unsafe {
+            device.cmd_copy_buffer_to_image(
+                command_buffer,
+                staging.buffer(),
+                self.image.image(),
+                vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                std::slice::from_ref(&region),
+            );
+        }
+        self.image.cmd_transition(device, command_buffer, ImageState::TRANSFER_DST, ImageState::SHADER_READ);
+        self.uploaded = true;
+    }
+
+    // GETTERS
+
+    pub(crate) fn view(&self) -> vk::ImageView {
+        self.image.view()
+    }
+
+    pub(crate) fn size(&self) -> u32 {
+        self.size
+    }
+
+    // glyphs asked for since the last clear, including the ones without outline
+    pub(crate) fn glyph_count(&self) -> usize {
+        self.glyphs.len()
+    }
+
+    pub(crate) fn is_dirty(&self) -> bool {
+        self.dirty.is_some()
+    }
+
+    pub(crate) fn is_full(&self) -> bool {
+        self.full
+    }
+}
+
@@ -0,0 +1,162 @@
+use std::path::Path;
+
+use tracing::info;
+use ttf_parser::gpos::{PairAdjustment, PositioningSubtable};
+use ttf_parser::{Face, GlyphId, Tag};
+
+use crate::renderer::{RendererError, RendererResult};
+
+// Vertical metrics of a font, in ems ; the descender is negative
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct FontMetrics {
+    pub ascender: f32,
+    pub descender: f32,
+    pub line_gap: f32,
+}
+
+impl FontMetrics {
+    // distance between the baselines of two lines
+    pub fn line_height(&self) -> f32 {
+        self.ascender - self.descender + self.line_gap
+    }
+}
+
+// A TrueType or OpenType font (glyf or CFF outlines), kept as its file's bytes and parsed again when laid out
+pub struct Font {
+    name: String,
+    data: Vec<u8>,
+    index: u32,
+    metrics: FontMetrics,
+}
+
+impl Font {
+    // .ttf / .otf, or the first font of a .ttc collection
+    pub fn load(path: impl AsRef<Path>) -> RendererResult<Self> {
+        let path = path.as_ref();
+        let data = std::fs::read(path).map_err(|source| RendererError::IoError { path: path.to_path_buf(), source })?;
+        let name = path.file_stem().map_or_else(|| "font".to_owned(), |stem| stem.to_string_lossy().into_owned());
+        Self::from_bytes(data, 0, &name)
+    }
+
+    // font `index` of a collection, 0 for plain font files
+    pub fn from_bytes(data: Vec<u8>, index: u32, name: &str) -> RendererResult<Self> {
+        let face = Face::parse(&data, index).map_err(|parse_error| RendererError::FontParseError(format!("{name}: {parse_error}")))?;
+        let units_per_em = face.units_per_em() as f32;
+        let metrics = FontMetrics {
+            ascender: face.ascender() as f32 / units_per_em,
+            descender: face.descender() as f32 / units_per_em,
+            line_gap: face.line_gap() as f32 / units_per_em,
+        };
+        info!(target: "torii::renderer", name, glyphs = face.number_of_glyphs(), "Loaded font");
+        Ok(Font {
+            name: name.to_owned(),
+            data,
+            index,
+            metrics,
+        })
+    }
+
+    pub(crate) fn face(&self) -> FontFace<'_> {
+        let face = Face::parse(&self.data, self.index).expect("Font data was validated when loaded!");
+        FontFace::new(face)
+    }
+
+    // GETTERS
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn metrics(&self) -> FontMetrics {
+        self.metrics
+    }
+}
+
+// A parsed font with the lookups of its kerning, for the time of one layout ; distances in ems
+pub(crate) struct FontFace<'a> {
+    face: Face<'a>,
+    units_per_em: f32,
+    // pair adjustment subtables of the GPOS "kern" feature, per lookup
+    kerning_lookups: Vec<Vec<PairAdjustment<'a>>>,
+}
+
+impl<'a> FontFace<'a> {
+    fn new(face: Face<'a>) -> Self {
+        let mut kerning_lookups = vec![];
+        if let Some(gpos) = face.tables().gpos {
+            let kern = Tag::from_bytes(b"kern");
+            let mut indices: Vec<u16> = gpos.features.into_iter().filter(|feature| feature.tag == kern).flat_map(|feature| feature.lookup_indices).collect();
+            indices.sort_unstable();
+            indices.dedup();
+            for index in indices {
+                let Some(lookup) = gpos.lookups.get(index) else { continue };
+                let subtables: Vec<PairAdjustment> = lookup
+                    .subtables
+                    .into_iter::<PositioningSubtable>()
+                    .filter_map(|subtable| match subtable {
+                        PositioningSubtable::Pair(pairs) => Some(pairs),
+                        _ => None,
+                    })
+                    .collect();
+                if !subtables.is_empty() {
+                    kerning_lookups.push(subtables);
+                }
+            }
+        }
+        FontFace {
+            units_per_em: face.units_per_em() as f32,
+            face,
+            kerning_lookups,
+        }
+    }
+
+    // the .notdef glyph for characters the font doesn't have
+    pub(crate) fn glyph(&self, character: char) -> GlyphId {
+        self.face.glyph_index(character).unwrap_or(GlyphId(0))
+    }
+
+    pub(crate) fn advance(&self, glyph: GlyphId) -> f32 {
+        self.face.glyph_hor_advance(glyph).unwrap_or(0) as f32 / self.units_per_em
+    }
+
+    // Advance adjustment between two consecutive glyphs, from the GPOS pair adjustments when the font has any and its
+    // legacy kern table otherwise ; within a lookup the first subtable covering the pair wins
+    pub(crate) fn kerning(&self, left: GlyphId, right: GlyphId) -> f32 {
+        let units = if self.kerning_lookups.is_empty() {
+            self.face
+                .tables()
+                .kern
+                .map(|kern| {
+                    kern.subtables
+                        .into_iter()
+                        .filter(|subtable| subtable.horizontal && !subtable.variable && !subtable.has_cross_stream && !subtable.has_state_machine)
+                        .filter_map(|subtable| subtable.glyphs_kerning(left, right))
+                        .map(i32::from)
+                        .sum()
+                })
+                .unwrap_or(0)
+        } else {
+            self.kerning_lookups.iter().filter_map(|subtables| subtables.iter().find_map(|pairs| pair_kerning(pairs, left, right))).sum()
+        };
+        units as f32 / self.units_per_em
+    }
+
+    pub(crate) fn face(&self) -> &Face<'a> {
+        &self.face
+    }
+}
+
+// x advance of the first glyph when `pairs` covers the pair
+fn pair_kerning(pairs: &PairAdjustment, left: GlyphId, right: GlyphId) -> Option<i32> {
+    match pairs {
+        PairAdjustment::Format1 { coverage, sets } => {
+            let (first, _) = sets.get(coverage.get(left)?)?.get(right)?;
+            Some(first.x_advance as i32)
+        }
+        PairAdjustment::Format2 { coverage, classes, matrix } => {
+            coverage.get(left)?;
+            let (first, _) = matrix.get((classes.0.get(left), classes.1.get(right)))?;
+            Some(first.x_advance as i32)
+        }
+    }
+}
@@ -0,0 +1,281 @@
+use glam::{Vec2, Vec4};
+use ttf_parser::GlyphId;
+
+use super::font::{Font, FontFace, FontMetrics};
+
+// spaces a tab advances by
+const TAB_WIDTH: f32 = 4.0;
+
+// Horizontal placement of the lines in the text's box
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum TextAlign {
+    #[default]
+    Left,
+    Center,
+    Right,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct TextStyle {
+    // em size: pixels for screen text, world units for world text
+    pub size: f32,
+    // multiplies the font's line height
+    pub line_spacing: f32,
+    pub align: TextAlign,
+    // lines wrap between words to stay narrower, words too long for a line alone are cut ; None to only break lines
+    // at '\n'
+    pub max_width: Option<f32>,
+    // linear RGBA
+    pub color: Vec4,
+}
+
+impl Default for TextStyle {
+    fn default() -> Self {
+        TextStyle {
+            size: 16.0,
+            line_spacing: 1.0,
+            align: TextAlign::Left,
+            max_width: None,
+            color: Vec4::ONE,
+        }
+    }
+}
+
+impl TextStyle {
+    pub fn new(size: f32) -> Self {
+        TextStyle { size, ..Self::default() }
+    }
+
+    pub fn with_align(mut self, align: TextAlign) -> Self {
+        self.align = align;
+        self
+    }
+
+    pub fn with_max_width(mut self, max_width: f32) -> Self {
+        self.max_width = Some(max_width);
+        self
+    }
+
+    pub fn with_line_spacing(mut self, line_spacing: f32) -> Self {
+        self.line_spacing = line_spacing;
+        self
+    }
+
+    pub fn with_color(mut self, color: Vec4) -> Self {
+        self.color = color;
+        self
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct LaidOutGlyph {
+    // glyph index in the font
+    pub glyph: u16,
+    // pen position on the baseline, from the top left corner of the text's box with Y going down
+    pub position: Vec2,
+    // byte offset of its character in the text
+    pub text_index: usize,
+}
+
+#[derive(Copy, Clone, Debug)]
+struct LineGlyph {
+    glyph: GlyphId,
+    x: f32,
+    advance: f32,
+    whitespace: bool,
+    text_index: usize,
+}
+
+// Glyphs of a text placed line by line: one glyph per character (ligatures and complex scripts aren't shaped),
+// moved by the font's kerning, wrapped at the style's max width and aligned in the box. Whitespace takes room but
+// has no glyphs.
+#[derive(Clone, Debug, Default)]
+pub struct TextLayout {
+    glyphs: Vec<LaidOutGlyph>,
+    size: Vec2,
+    line_count: usize,
+}
+
+impl TextLayout {
+    pub fn new(font: &Font, text: &str, style: &TextStyle) -> Self {
+        Self::with_face(&font.face(), font.metrics(), text, style)
+    }
+
+    pub(crate) fn with_face(face: &FontFace, metrics: FontMetrics, text: &str, style: &TextStyle) -> Self {
+        let size = style.size;
+        let space = face.glyph(' ');
+        let mut lines: Vec<(Vec<LineGlyph>, f32)> = vec![];
+        let mut finish = |line: Vec<LineGlyph>| {
+            // trailing whitespace doesn't count in the width
+            let width = line.iter().rev().find(|glyph| !glyph.whitespace).map_or(0.0, |glyph| glyph.x + glyph.advance);
+            lines.push((line, width));
+        };
+
+        let mut paragraph_start = 0;
+        for paragraph in text.split('\n') {
+            let mut line: Vec<LineGlyph> = vec![];
+            let mut pen = 0.0;
+            let mut previous = None;
+            for (index, character) in paragraph.char_indices() {
+                let (glyph, advance) = match character {
+                    '\t' => (space, face.advance(space) * TAB_WIDTH * size),
+                    _ if character.is_control() => continue,
+                    _ => {
+                        let glyph = face.glyph(character);
+                        (glyph, face.advance(glyph) * size)
+                    }
+                };
+                let kerning = previous.map_or(0.0, |previous| face.kerning(previous, glyph) * size);
+                let whitespace = character.is_whitespace();
+                let overflows = style.max_width.is_some_and(|max_width| pen + kerning + advance > max_width);
+                if overflows && !whitespace && !line.is_empty() {
+                    // after the last whitespace of the line, before this character when it's all one word
+                    let split = line.iter().rposition(|glyph| glyph.whitespace).map_or(line.len(), |index| index + 1);
+                    let mut carried = line.split_off(split);
+                    finish(std::mem::take(&mut line));
+                    let shift = carried.first().map_or(0.0, |glyph| glyph.x);
+                    for glyph in &mut carried {
+                        glyph.x -= shift;
+                    }
+                    pen = carried.last().map_or(0.0, |glyph| glyph.x + glyph.advance);
+                    line = carried;
+                }
+                let x = if line.is_empty() { 0.0 } else { pen + kerning };
+                line.push(LineGlyph {
+                    glyph,
+                    x,
+                    advance,
+                    whitespace,
+                    text_index: paragraph_start + index,
+                });
+                pen = x + advance;
+                previous = Some(glyph);
+            }
+            finish(line);
+            paragraph_start += paragraph.len() + 1;
+        }
+
+        let box_width = style.max_width.unwrap_or_else(|| lines.iter().map(|(_, width)| *width).fold(0.0, f32::max));
+        let line_advance = metrics.line_height() * style.line_spacing * size;
+        let mut glyphs = vec![];
+        for (line_index, (line, width)) in lines.iter().enumerate() {
+            let offset = match style.align {
+                TextAlign::Left => 0.0,
+                TextAlign::Center => (box_width - width) * 0.5,
+                TextAlign::Right => box_width - width,
+            };
+            let baseline = metrics.ascender * size + line_index as f32 * line_advance;
+            glyphs.extend(line.iter().filter(|glyph| !glyph.whitespace).map(|glyph| LaidOutGlyph {
+                glyph: glyph.glyph.0,
+                position: Vec2::new(offset + glyph.x, baseline),
+                text_index: glyph.text_index,
+            }));
+        }
+        let height = (metrics.ascender - metrics.descender) * size + lines.len().saturating_sub(1) as f32 * line_advance;
+        TextLayout {
+            glyphs,
+            size: Vec2::new(box_width, height),
+            line_count: lines.len(),
+        }
+    }
+
+    // GETTERS
+
+    pub fn glyphs(&self) -> &[LaidOutGlyph] {
+        &self.glyphs
+    }
+
+    // the box of the text, the max width when the style has one
+    pub fn size(&self) -> Vec2 {
+        self.size
+    }
+
+    pub fn line_count(&self) -> usize {
+        self.line_count
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // egui's monospace font, every glyph advances by the same width
+    fn font() -> Font {
+        let definitions = crate::debug_ui::egui::FontDefinitions::default();
+        Font::from_bytes(definitions.font_data["Hack"].font.to_vec(), 0, "Hack").unwrap()
+    }
+
+    fn advance(font: &Font) -> f32 {
+        let face = font.face();
+        face.advance(face.glyph('a'))
+    }
+
+    // (line, column) of each glyph, from its position
+    fn grid(layout: &TextLayout, font: &Font, style: &TextStyle) -> Vec<(usize, f32)> {
+        let line_advance = font.metrics().line_height() * style.line_spacing * style.size;
+        let first_baseline = font.metrics().ascender * style.size;
+        layout
+            .glyphs()
+            .iter()
+            .map(|glyph| {
+                let line = (glyph.position.y - first_baseline) / line_advance;
+                assert!((line - line.round()).abs() < 1e-4, "glyph off its baseline");
+                (line.round() as usize, glyph.position.x / (advance(font) * style.size))
+            })
+            .collect()
+    }
+
+    fn assert_columns(actual: &[(usize, f32)], expected: &[(usize, f32)]) {
+        assert_eq!(actual.len(), expected.len(), "{actual:?}");
+        for (&(line, column), &(expected_line, expected_column)) in actual.iter().zip(expected) {
+            assert_eq!(line, expected_line, "{actual:?}");
+            assert!((column - expected_column).abs() < 1e-3, "{actual:?}");
+        }
+    }
+
+    #[test]
+    fn lines_wrap_between_words() {
+        let font = font();
+        let style = TextStyle::new(10.0).with_max_width(7.5 * advance(&font) * 10.0);
+        let layout = TextLayout::new(&font, "aaa bbb ccc", &style);
+        assert_eq!(layout.line_count(), 2);
+        assert_columns(&grid(&layout, &font, &style), &[(0, 0.0), (0, 1.0), (0, 2.0), (0, 4.0), (0, 5.0), (0, 6.0), (1, 0.0), (1, 1.0), (1, 2.0)]);
+        // the glyphs remember their characters, whitespace has none
+        let indices: Vec<usize> = layout.glyphs().iter().map(|glyph| glyph.text_index).collect();
+        assert_eq!(indices, [0, 1, 2, 4, 5, 6, 8, 9, 10]);
+        assert_eq!(layout.size().x, style.max_width.unwrap());
+    }
+
+    #[test]
+    fn words_too_long_for_a_line_are_cut() {
+        let font = font();
+        let style = TextStyle::new(1.0).with_max_width(4.5 * advance(&font));
+        let layout = TextLayout::new(&font, "abcdefghij", &style);
+        assert_eq!(layout.line_count(), 3);
+        let lines: Vec<usize> = grid(&layout, &font, &style).iter().map(|&(line, _)| line).collect();
+        assert_eq!(lines, [0, 0, 0, 0, 1, 1, 1, 1, 2, 2]);
+        assert_columns(&grid(&layout, &font, &style)[4..5], &[(1, 0.0)]);
+    }
+
+    #[test]
+    fn new_lines_always_break() {
+        let font = font();
+        let layout = TextLayout::new(&font, "a\n\nb", &TextStyle::new(1.0));
+        assert_eq!(layout.line_count(), 3);
+        assert_columns(&grid(&layout, &font, &TextStyle::new(1.0)), &[(0, 0.0), (2, 0.0)]);
+    }
+
+    #[test]
+    fn lines_align_in_the_box() {
+        let font = font();
+        let right = TextStyle::new(1.0).with_align(TextAlign::Right);
+        let layout = TextLayout::new(&font, "ab\nabcd ", &right);
+        // the widest line sets the box, trailing whitespace doesn't count
+        assert!((layout.size().x - 4.0 * advance(&font)).abs() < 1e-4);
+        assert_columns(&grid(&layout, &font, &right), &[(0, 2.0), (0, 3.0), (1, 0.0), (1, 1.0), (1, 2.0), (1, 3.0)]);
+
+        let center = TextStyle::new(1.0).with_align(TextAlign::Center).with_max_width(6.0 * advance(&font));
+        let layout = TextLayout::new(&font, "ab\nabcd", &center);
+        assert_columns(&grid(&layout, &font, &center), &[(0, 2.0), (0, 3.0), (1, 1.0), (1, 2.0), (1, 3.0), (1, 4.0)]);
+    }
+}
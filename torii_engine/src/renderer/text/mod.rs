@@ -0,0 +1,12 @@
+// Text: TrueType / OpenType fonts, glyphs rasterized on the CPU into signed distance fields packed in an atlas that
+// grows with the glyphs drawn, Latin layout with kerning, wrapping and alignment, and the renderer drawing it over
+// the screen or in the scene.
+
+mod atlas;
+mod font;
+pub use font::*;
+mod layout;
+pub use layout::*;
+mod renderer;
+pub use renderer::*;
+mod sdf;
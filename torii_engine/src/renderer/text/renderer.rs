@@ -0,0 +1,407 @@
+use ash::{vk, Device};
+use glam::{Mat4, Vec2, Vec3, Vec4};
+use tracing::{info, warn};
+use ttf_parser::GlyphId;
+
+use crate::vulkan_api::VkApp;
+use crate::vulkan_api::descriptors::{DescriptorAllocator, DescriptorBinding, DescriptorBindings};
+use crate::vulkan_api::memory::{as_bytes, GpuBuffer, MemoryLocation};
+use crate::vulkan_api::pipeline::{BlendMode, GraphicsPipeline, GraphicsPipelineBuilder, VertexLayout};
+use crate::vulkan_api::sampler::{Sampler, SamplerDesc};
+use crate::vulkan_api::shader::{Shader, ShaderStage};
+
+use crate::renderer::camera::Camera;
+use crate::renderer::{RendererResult, TargetFormats};
+
+use super::atlas::GlyphAtlas;
+use super::font::Font;
+use super::layout::{TextLayout, TextStyle};
+
+const VERTEX_SHADER: &str = include_str!("../shaders/text/text.vert");
+const FRAGMENT_SHADER: &str = include_str!("../shaders/text/text.frag");
+
+// texels on each side of the glyph atlas
+const GLYPH_ATLAS_SIZE: u32 = 1024;
+
+// A font added to a TextRenderer
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct FontId(usize);
+
+// Text over the whole target, `position` in pixels from its top left corner
+#[derive(Clone, Debug, PartialEq)]
+pub struct ScreenText {
+    pub text: String,
+    pub font: FontId,
+    pub style: TextStyle,
+    pub position: Vec2,
+    // point of the text's box put on `position`, (0, 0) its top left corner and (1, 1) the bottom right one
+    pub anchor: Vec2,
+}
+
+impl ScreenText {
+    pub fn new(text: impl Into<String>, font: FontId, style: TextStyle, position: Vec2) -> Self {
+        ScreenText {
+            text: text.into(),
+            font,
+            style,
+            position,
+            anchor: Vec2::ZERO,
+        }
+    }
+
+    pub fn with_anchor(mut self, anchor: Vec2) -> Self {
+        self.anchor = anchor;
+        self
+    }
+}
+
+// Text in the scene on the XY plane of `transform`, reading along +X with +Y up, depth tested against the opaque
+// geometry ; the style's size is in world units
+#[derive(Clone, Debug, PartialEq)]
+pub struct WorldText {
+    pub text: String,
+    pub font: FontId,
+    pub style: TextStyle,
+    pub transform: Mat4,
+    // point of the text's box put on the transform's origin, (0, 0) its top left corner and (1, 1) the bottom right one
+    pub anchor: Vec2,
+    // faces the camera, keeping only the position and scale of the transform
+    pub billboard: bool,
+}
+
+impl WorldText {
+    // centered on `position`
+    pub fn new(text: impl Into<String>, font: FontId, style: TextStyle, position: Vec3) -> Self {
+        WorldText {
+            text: text.into(),
+            font,
+            style,
+            transform: Mat4::from_translation(position),
+            anchor: Vec2::splat(0.5),
+            billboard: false,
+        }
+    }
+
+    pub fn with_transform(mut self, transform: Mat4) -> Self {
+        self.transform = transform;
+        self
+    }
+
+    pub fn with_anchor(mut self, anchor: Vec2) -> Self {
+        self.anchor = anchor;
+        self
+    }
+
+    pub fn with_billboard(mut self, billboard: bool) -> Self {
+        self.billboard = billboard;
+        self
+    }
+}
+
+// view of text.vert
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+struct TextView {
+    view_projection: Mat4,
+    current_view_projection: Mat4,
+    previous_view_projection: Mat4,
+}
+
+// per instance vertex data of text.vert
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+struct GlyphInstance {
+    origin: [f32; 4],
+    axis_x: [f32; 4],
+    axis_y: [f32; 4],
+    uv_rect: [f32; 4],
+    color: [f32; 4],
+}
+
+impl GlyphInstance {
+    fn layout() -> VertexLayout {
+        let attribute = |location: u32| {
+            vk::VertexInputAttributeDescription::default()
+                .location(location)
+                .binding(0)
+                .format(vk::Format::R32G32B32A32_SFLOAT)
+                .offset(location * 16)
+        };
+        VertexLayout {
+            bindings: vec![
+                vk::VertexInputBindingDescription::default()
+                    .binding(0)
+                    .stride(size_of::<GlyphInstance>() as u32)
+                    .input_rate(vk::VertexInputRate::INSTANCE),
+            ],
+            attributes: (0..5).map(attribute).collect(),
+        }
+    }
+}
+
+// A glyph's quad in the text's box (Y down): left, top, right, bottom ; and its uv rect
+struct GlyphQuad {
+    rect: Vec4,
+    uv_rect: Vec4,
+}
+
+struct FrameResources {
+    world_view_buffer: GpuBuffer,
+    screen_view_buffer: GpuBuffer,
+    instance_buffer: GpuBuffer,
+    staging_buffer: GpuBuffer,
+    descriptor_allocator: DescriptorAllocator,
+}
+
+// Draws text from the fonts added to it, glyphs rendered from signed distance fields so any size stays sharp. Each
+// frame: begin_frame, draw_screen / draw_world lay out and queue text (rasterizing the glyphs seen for the first
+// time), upload_glyphs copies them to the atlas outside of any pass, and record draws everything inside a pass the
+// caller began, world text first.
+pub struct TextRenderer {
+    device: Device,
+    world_pipeline: GraphicsPipeline,
+    screen_pipeline: GraphicsPipeline,
+    sampler: Sampler,
+    atlas: GlyphAtlas,
+    fonts: Vec<Font>,
+    max_glyphs: usize,
+    frames: Vec<FrameResources>,
+    frame_index: usize,
+    world_glyphs: Vec<GlyphInstance>,
+    screen_glyphs: Vec<GlyphInstance>,
+    // world space right, up and back of the camera, for billboards
+    camera_axes: [Vec3; 3],
+    previous_view_projection: Option<Mat4>,
+}
+
+impl TextRenderer {
+    // Up to `max_glyphs` per frame, the extra ones are dropped
+    pub fn new(vk_app: &VkApp, formats: TargetFormats, max_glyphs: usize) -> RendererResult<Self> {
+        let vertex = Shader::from_glsl(VERTEX_SHADER, ShaderStage::Vertex)?;
+        let with_velocity = formats.velocity != vk::Format::UNDEFINED;
+        let defines: &[(&str, &str)] = if with_velocity { &[("VELOCITY", "1")] } else { &[] };
+        let fragment = Shader::from_glsl_with_defines(FRAGMENT_SHADER, ShaderStage::Fragment, defines)?;
+        let color_formats = if with_velocity { vec![formats.color, formats.velocity] } else { vec![formats.color] };
+        let pipeline = |depth_test: bool, name: &str| {
+            GraphicsPipelineBuilder::new()
+                .vertex(&vertex, GlyphInstance::layout())
+                .fragment(&fragment)
+                .color_formats(&color_formats)
+                .depth(formats.depth, depth_test, false, if depth_test { vk::CompareOp::LESS_OR_EQUAL } else { vk::CompareOp::ALWAYS })
+                .samples(formats.samples)
+                .topology(vk::PrimitiveTopology::TRIANGLE_STRIP)
+                .cull_mode(vk::CullModeFlags::NONE, vk::FrontFace::COUNTER_CLOCKWISE)
+                .blend(BlendMode::Alpha)
+                .build(vk_app, name)
+        };
+        let world_pipeline = pipeline(true, "world text")?;
+        let screen_pipeline = pipeline(false, "screen text")?;
+
+        let max_glyphs = max_glyphs.max(1);
+        let frames = (0..vk_app.vk_prop().renderer_config.frames_in_flight.max(1))
+            .map(|frame| {
+                let buffer = |size: usize, usage: vk::BufferUsageFlags, name: &str| {
+                    GpuBuffer::new(vk_app, size as vk::DeviceSize, usage, MemoryLocation::HostVisible, &format!("text {name} [frame {frame}]"))
+                };
+                Ok(FrameResources {
+                    world_view_buffer: buffer(size_of::<TextView>(), vk::BufferUsageFlags::UNIFORM_BUFFER, "world view")?,
+                    screen_view_buffer: buffer(size_of::<TextView>(), vk::BufferUsageFlags::UNIFORM_BUFFER, "screen view")?,
+                    instance_buffer: buffer(max_glyphs * size_of::<GlyphInstance>(), vk::BufferUsageFlags::VERTEX_BUFFER, "instances")?,
+                    staging_buffer: buffer((GLYPH_ATLAS_SIZE * GLYPH_ATLAS_SIZE) as usize, vk::BufferUsageFlags::TRANSFER_SRC, "atlas staging")?,
+                    descriptor_allocator: DescriptorAllocator::new(vk_app.device())?,
+                })
+            })
+            .collect::<RendererResult<Vec<_>>>()?;
+        info!(target: "torii::renderer", max_glyphs, "Created text renderer");
+
+        Ok(TextRenderer {
+            device: vk_app.device().clone(),
+            world_pipeline,
+            screen_pipeline,
+            sampler: Sampler::new(vk_app, SamplerDesc::linear_clamp(), "glyph atlas sampler")?,
+            atlas: GlyphAtlas::new(vk_app, GLYPH_ATLAS_SIZE)?,
+            fonts: vec![],
+            max_glyphs,
+            frames,
+            frame_index: 0,
+            world_glyphs: vec![],
+            screen_glyphs: vec![],
+            camera_axes: [Vec3::X, Vec3::Y, Vec3::Z],
+            previous_view_projection: None,
+        })
+    }
+
+    pub fn add_font(&mut self, font: Font) -> FontId {
+        self.fonts.push(font);
+        FontId(self.fonts.len() - 1)
+    }
+
+    pub fn font(&self, font: FontId) -> &Font {
+        &self.fonts[font.0]
+    }
+
+    // Lays `text` out without drawing it, e.g. to measure it
+    pub fn layout(&self, font: FontId, text: &str, style: &TextStyle) -> TextLayout {
+        TextLayout::new(&self.fonts[font.0], text, style)
+    }
+
+    // Starts frame `frame_index` (modulo the frames in flight) drawn from `camera` into a target of `extent`,
+    // dropping the text queued since the last record ; the previous frame that used the same slot must have completed
+    // on the GPU. A glyph atlas that filled up during the last frame is cleared.
+    pub fn begin_frame(&mut self, frame_index: usize, camera: &Camera, extent: vk::Extent2D) -> RendererResult<()> {
+        self.frame_index = frame_index % self.frames.len();
+        if self.atlas.is_full() {
+            info!(target: "torii::renderer", glyphs = self.atlas.glyph_count(), "Clearing the full glyph atlas");
+            self.atlas.clear();
+        }
+        let frame = &mut self.frames[self.frame_index];
+        frame.descriptor_allocator.reset()?;
+
+        let view_projection = camera.view_projection();
+        let world_view = TextView {
+            view_projection: camera.jittered_view_projection(),
+            current_view_projection: view_projection,
+            previous_view_projection: self.previous_view_projection.unwrap_or(view_projection),
+        };
+        self.previous_view_projection = Some(view_projection);
+        // pixels from the top left corner to clip space, which has Y going down too
+        let pixels = Vec3::new(2.0 / extent.width.max(1) as f32, 2.0 / extent.height.max(1) as f32, 1.0);
+        let screen_projection = Mat4::from_translation(Vec3::new(-1.0, -1.0, 0.0)) * Mat4::from_scale(pixels);
+        let screen_view = TextView {
+            view_projection: screen_projection,
+            current_view_projection: screen_projection,
+            previous_view_projection: screen_projection,
+        };
+        frame.world_view_buffer.write(0, as_bytes(&[world_view]));
+        frame.screen_view_buffer.write(0, as_bytes(&[screen_view]));
+
+        let inverse_view = camera.view.inverse();
+        self.camera_axes = [inverse_view.x_axis.truncate(), inverse_view.y_axis.truncate(), inverse_view.z_axis.truncate()];
+        self.world_glyphs.clear();
+        self.screen_glyphs.clear();
+        Ok(())
+    }
+
+    // Queues `text` ; returns the size of its box in pixels
+    pub fn draw_screen(&mut self, text: &ScreenText) -> Vec2 {
+        let (size, quads) = self.glyph_quads(text.font, &text.text, &text.style);
+        // whole pixels, for crisp small text
+        let offset = (text.position - text.anchor * size).round();
+        let color = text.style.color.to_array();
+        self.screen_glyphs.extend(quads.iter().map(|quad| GlyphInstance {
+            origin: [offset.x + quad.rect.x, offset.y + quad.rect.w, 0.0, 1.0],
+            axis_x: [quad.rect.z - quad.rect.x, 0.0, 0.0, 0.0],
+            axis_y: [0.0, quad.rect.y - quad.rect.w, 0.0, 0.0],
+            uv_rect: quad.uv_rect.to_array(),
+            color,
+        }));
+        size
+    }
+
+    // Queues `text` ; returns the size of its box in world units, before the transform
+    pub fn draw_world(&mut self, text: &WorldText) -> Vec2 {
+        let (size, quads) = self.glyph_quads(text.font, &text.text, &text.style);
+        let transform = if text.billboard {
+            let (scale, _, translation) = text.transform.to_scale_rotation_translation();
+            let [right, up, back] = self.camera_axes;
+            Mat4::from_cols((right * scale.x).extend(0.0), (up * scale.y).extend(0.0), (back * scale.z).extend(0.0), translation.extend(1.0))
+        } else {
+            text.transform
+        };
+        // the box goes down from its top left corner, the plane goes up
+        let anchor = text.anchor * size;
+        let color = text.style.color.to_array();
+        self.world_glyphs.extend(quads.iter().map(|quad| {
+            let origin = transform.transform_point3(Vec3::new(quad.rect.x - anchor.x, anchor.y - quad.rect.w, 0.0));
+            GlyphInstance {
+                origin: origin.extend(1.0).to_array(),
+                axis_x: transform.transform_vector3(Vec3::X * (quad.rect.z - quad.rect.x)).extend(0.0).to_array(),
+                axis_y: transform.transform_vector3(Vec3::Y * (quad.rect.w - quad.rect.y)).extend(0.0).to_array(),
+                uv_rect: quad.uv_rect.to_array(),
+                color,
+            }
+        }));
+        size
+    }
+
+    // layout of `text` and the quads of its glyphs that have an outline
+    fn glyph_quads(&mut self, font_id: FontId, text: &str, style: &TextStyle) -> (Vec2, Vec<GlyphQuad>) {
+        let font = &self.fonts[font_id.0];
+        let face = font.face();
+        let layout = TextLayout::with_face(&face, font.metrics(), text, style);
+        let quads = layout
+            .glyphs()
+            .iter()
+            .filter_map(|glyph| {
+                let atlas_glyph = self.atlas.glyph(font_id, &face, GlyphId(glyph.glyph))?;
+                let plane = atlas_glyph.plane * style.size;
+                Some(GlyphQuad {
+                    rect: Vec4::new(glyph.position.x + plane.x, glyph.position.y - plane.w, glyph.position.x + plane.z, glyph.position.y - plane.y),
+                    uv_rect: atlas_glyph.uv_rect,
+                })
+            })
+            .collect();
+        (layout.size(), quads)
+    }
+
+    // Copies the glyphs rasterized since the last upload into the atlas, outside of any pass ; after the draws of the
+    // frame and before record
+    pub fn upload_glyphs(&mut self, command_buffer: vk::CommandBuffer) {
+        let frame = &mut self.frames[self.frame_index];
+        self.atlas.cmd_upload(&self.device, command_buffer, &mut frame.staging_buffer);
+    }
+
+    // Records the queued text into a pass the caller began with dynamic rendering, viewport and scissor already set,
+    // and empties the queue ; once per frame, the glyphs of the frame share one buffer
+    pub fn record(&mut self, command_buffer: vk::CommandBuffer) -> RendererResult<()> {
+        if self.atlas.is_dirty() {
+            warn!(target: "torii::renderer", "Recording text before upload_glyphs, new glyphs are missing");
+        }
+        let queued = self.world_glyphs.len() + self.screen_glyphs.len();
+        if queued > self.max_glyphs {
+            warn!(target: "torii::renderer", glyphs = queued, max_glyphs = self.max_glyphs, "Too many glyphs, dropping the extra ones");
+            self.world_glyphs.truncate(self.max_glyphs);
+            self.screen_glyphs.truncate(self.max_glyphs - self.world_glyphs.len());
+        }
+        let frame = &mut self.frames[self.frame_index];
+        frame.instance_buffer.write(0, as_bytes(&self.world_glyphs));
+        frame.instance_buffer.write((self.world_glyphs.len() * size_of::<GlyphInstance>()) as vk::DeviceSize, as_bytes(&self.screen_glyphs));
+
+        let draws = [
+            (&self.world_pipeline, &frame.world_view_buffer, 0, self.world_glyphs.len()),
+            (&self.screen_pipeline, &frame.screen_view_buffer, self.world_glyphs.len(), self.screen_glyphs.len()),
+        ];
+        for (pipeline, view_buffer, first_instance, count) in draws {
+            if count == 0 {
+                continue;
+            }
+            let bindings = DescriptorBindings::new()
+                .bind(0, 0, DescriptorBinding::whole_buffer(view_buffer.buffer()))
+                .bind(0, 1, DescriptorBinding::sampled_image(self.atlas.view()))
+                .bind(0, 2, DescriptorBinding::Sampler(self.sampler.sampler()));
+            unsafe {
+                self.device.cmd_bind_pipeline(command_buffer, vk::PipelineBindPoint::GRAPHICS, pipeline.pipeline());
+                self.device.cmd_bind_vertex_buffers(command_buffer, 0, &[frame.instance_buffer.buffer()], &[0]);
+            }
+            frame.descriptor_allocator.bind(command_buffer, vk::PipelineBindPoint::GRAPHICS, pipeline.layout(), &bindings)?;
+            unsafe {
+                self.device.cmd_draw(command_buffer, 4, count as u32, 0, first_instance as u32);
+            }
+        }
+        self.world_glyphs.clear();
+        self.screen_glyphs.clear();
+        Ok(())
+    }
+
+    // GETTERS
+
+    // glyphs waiting for record
+    pub fn queued_count(&self) -> usize {
+        self.world_glyphs.len() + self.screen_glyphs.len()
+    }
+
+    // glyphs in the atlas, and its size in texels
+    pub fn atlas_usage(&self) -> (usize, u32) {
+        (self.atlas.glyph_count(), self.atlas.size())
+    }
+}
@@ -0,0 +1,147 @@
+use glam::{Vec2, Vec4};
+use ttf_parser::{GlyphId, OutlineBuilder};
+
+use super::font::FontFace;
+
+// texels per em of the distance fields, text drawn much larger than this loses its sharp corners
+pub(crate) const SDF_EM_SIZE: f32 = 40.0;
+// texels of distance encoded on each side of the outline
+pub(crate) const SDF_SPREAD: f32 = 5.0;
+
+// Signed distance field of a glyph: 0.5 on the outline, growing inside, one byte per texel row by row. `plane`
+// is the rectangle the texels cover around the pen position, ems: left, bottom, right, top.
+pub(crate) struct GlyphSdf {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<u8>,
+    pub plane: Vec4,
+}
+
+// None for glyphs without an outline, like spaces
+pub(crate) fn rasterize_sdf(face: &FontFace, glyph: GlyphId) -> Option<GlyphSdf> {
+    let face = face.face();
+    let scale = SDF_EM_SIZE / face.units_per_em() as f32;
+    let mut outline = Outline {
+        scale,
+        segments: vec![],
+        start: Vec2::ZERO,
+        current: Vec2::ZERO,
+    };
+    let bounds = face.outline_glyph(glyph, &mut outline)?;
+    if outline.segments.is_empty() || bounds.width() == 0 || bounds.height() == 0 {
+        return None;
+    }
+
+    // texel grid, its origin in the bottom left corner
+    let padding = SDF_SPREAD.ceil() as i32;
+    let left = (bounds.x_min as f32 * scale).floor() as i32 - padding;
+    let bottom = (bounds.y_min as f32 * scale).floor() as i32 - padding;
+    let right = (bounds.x_max as f32 * scale).ceil() as i32 + padding;
+    let top = (bounds.y_max as f32 * scale).ceil() as i32 + padding;
+    let (width, height) = ((right - left) as u32, (top - bottom) as u32);
+
+    let mut pixels = Vec::with_capacity(width as usize * height as usize);
+    for row in 0..height {
+        for column in 0..width {
+            let point = Vec2::new(left as f32 + column as f32 + 0.5, top as f32 - row as f32 - 0.5);
+            let distance = outline.signed_distance(point);
+            let value = (0.5 + distance / (2.0 * SDF_SPREAD)).clamp(0.0, 1.0);
+            pixels.push((value * 255.0).round() as u8);
+        }
+    }
+    Some(GlyphSdf {
+        width,
+        height,
+        pixels,
+        plane: Vec4::new(left as f32, bottom as f32, right as f32, top as f32) / SDF_EM_SIZE,
+    })
+}
+
+// A glyph's contours flattened into line segments, texels
+struct Outline {
+    scale: f32,
+    segments: Vec<(Vec2, Vec2)>,
+    start: Vec2,
+    current: Vec2,
+}
+
+impl Outline {
+    fn point(&self, x: f32, y: f32) -> Vec2 {
+        Vec2::new(x, y) * self.scale
+    }
+
+    fn push(&mut self, to: Vec2) {
+        if to != self.current {
+            self.segments.push((self.current, to));
+        }
+        self.current = to;
+    }
+
+    // segments for a curve whose control polygon is `length` texels long, about one per texel
+    fn steps(length: f32) -> u32 {
+        (length.ceil() as u32).clamp(1, 16)
+    }
+
+    // Distance to the closest segment up to the spread, positive inside ; inside by the non zero winding rule, as
+    // fonts fill their overlapping contours
+    fn signed_distance(&self, point: Vec2) -> f32 {
+        let mut closest = SDF_SPREAD * SDF_SPREAD;
+        let mut winding = 0;
+        for &(a, b) in &self.segments {
+            let edge = b - a;
+            // segments whose bounds are already too far are skipped
+            let outside = (a.min(b) - point).max(point - a.max(b)).max(Vec2::ZERO);
+            if outside.length_squared() < closest {
+                let t = ((point - a).dot(edge) / edge.length_squared()).clamp(0.0, 1.0);
+                closest = closest.min((a + edge * t).distance_squared(point));
+            }
+
+            let side = edge.perp_dot(point - a);
+            if a.y <= point.y && b.y > point.y && side > 0.0 {
+                winding += 1;
+            } else if b.y <= point.y && a.y > point.y && side < 0.0 {
+                winding -= 1;
+            }
+        }
+        let distance = closest.sqrt();
+        if winding != 0 { distance } else { -distance }
+    }
+}
+
+impl OutlineBuilder for Outline {
+    fn move_to(&mut self, x: f32, y: f32) {
+        self.start = self.point(x, y);
+        self.current = self.start;
+    }
+
+    fn line_to(&mut self, x: f32, y: f32) {
+        let to = self.point(x, y);
+        self.push(to);
+    }
+
+    fn quad_to(&mut self, x1: f32, y1: f32, x: f32, y: f32) {
+        let (from, control, to) = (self.current, self.point(x1, y1), self.point(x, y));
+        let steps = Self::steps(from.distance(control) + control.distance(to));
+        for step in 1..=steps {
+            let t = step as f32 / steps as f32;
+            let point = from.lerp(control, t).lerp(control.lerp(to, t), t);
+            self.push(point);
+        }
+    }
+
+    fn curve_to(&mut self, x1: f32, y1: f32, x2: f32, y2: f32, x: f32, y: f32) {
+        let (from, control1, control2, to) = (self.current, self.point(x1, y1), self.point(x2, y2), self.point(x, y));
+        let steps = Self::steps(from.distance(control1) + control1.distance(control2) + control2.distance(to));
+        for step in 1..=steps {
+            let t = step as f32 / steps as f32;
+            let s = 1.0 - t;
+            let point = from * (s * s * s) + control1 * (3.0 * s * s * t) + control2 * (3.0 * s * t * t) + to * (t * t * t);
+            self.push(point);
+        }
+    }
+
+    fn close(&mut self) {
+        let start = self.start;
+        self.push(start);
+    }
+}
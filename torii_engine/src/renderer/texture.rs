@@ -0,0 +1,84 @@
+use ash::vk;
+
+use crate::vulkan_api::VkApp;
+use crate::vulkan_api::image::{GpuImage, ImageDesc};
+use crate::vulkan_api::memory::as_bytes;
+
+use super::{RendererError, RendererResult};
+
+// A sampled 2D image with its full mip chain, read only once uploaded
+pub struct Texture {
+    image: GpuImage,
+}
+
+impl Texture {
+    // 8 bit RGBA pixels, row by row ; `srgb` for colors (base color, emissive), linear for data (normals,
+    // metallic-roughness, occlusion)
+    pub fn from_rgba8(vk_app: &VkApp, width: u32, height: u32, pixels: &[u8], srgb: bool, name: &str) -> RendererResult<Self> {
+        let format = if srgb { vk::Format::R8G8B8A8_SRGB } else { vk::Format::R8G8B8A8_UNORM };
+        Self::from_bytes(vk_app, format, width, height, pixels, 4, name)
+    }
+
+    // Linear HDR pixels, stored as half floats (full floats can't be filtered everywhere)
+    pub fn from_rgba_f32(vk_app: &VkApp, width: u32, height: u32, pixels: &[[f32; 4]], name: &str) -> RendererResult<Self> {
+        let half_pixels: Vec<u16> = pixels.iter().flatten().map(|&value| f32_to_f16(value)).collect();
+        Self::from_bytes(vk_app, vk::Format::R16G16B16A16_SFLOAT, width, height, as_bytes(&half_pixels), 8, name)
+    }
+
+    // 1x1 texture of a single color, the stand in for a material's missing textures
+    pub fn solid(vk_app: &VkApp, color: [u8; 4], srgb: bool, name: &str) -> RendererResult<Self> {
+        Self::from_rgba8(vk_app, 1, 1, &color, srgb, name)
+    }
+
+    fn from_bytes(vk_app: &VkApp, format: vk::Format, width: u32, height: u32, data: &[u8], pixel_size: usize, name: &str) -> RendererResult<Self> {
+        let expected = width as usize * height as usize * pixel_size;
+        if data.len() != expected || expected == 0 {
+            return Err(RendererError::TextureDataSizeError { expected, given: data.len() });
+        }
+        let desc = ImageDesc::new(format, vk::Extent2D { width, height }).with_full_mip_chain();
+        let image = GpuImage::with_data(vk_app, desc, vk::ImageUsageFlags::SAMPLED, data, true, name)?;
+        Ok(Texture { image })
+    }
+
+    // GETTERS
+
+    pub fn image(&self) -> &GpuImage {
+        &self.image
+    }
+
+    pub fn view(&self) -> vk::ImageView {
+        self.image.view()
+    }
+
+    pub fn extent(&self) -> vk::Extent2D {
+        self.image.extent()
+    }
+}
+
+// IEEE half float bits of `value`, rounding to nearest ; out of range values become infinities, tiny ones subnormals
+pub(crate) fn f32_to_f16(value: f32) -> u16 {
+    let bits = value.to_bits();
+    let sign = ((bits >> 16) & 0x8000) as u16;
+    let exponent = ((bits >> 23) & 0xff) as i32;
+    let mantissa = bits & 0x007f_ffff;
+    if exponent == 0xff {
+        let nan = if mantissa != 0 { 0x0200 } else { 0 };
+        return sign | 0x7c00 | nan;
+    }
+    let half_exponent = exponent - 127 + 15;
+    if half_exponent >= 0x1f {
+        return sign | 0x7c00;
+    }
+    if half_exponent <= 0 {
+        if half_exponent < -10 {
+            return sign;
+        }
+        let mantissa = mantissa | 0x0080_0000;
+        let shift = (14 - half_exponent) as u32;
+        let rounded = (mantissa + (1 << (shift - 1))) >> shift;
+        return sign | rounded as u16;
+    }
+    // a carry out of the mantissa correctly bumps the exponent
+    let rounded = ((half_exponent as u32) << 10) + ((mantissa + 0x0000_1000) >> 13);
+    sign | rounded.min(0x7c00) as u16
+}
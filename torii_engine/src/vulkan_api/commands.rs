@@ -0,0 +1,42 @@
+use ash::vk;
+
+use super::VkApp;
+
+// Records `record` into a throwaway command buffer, submits it and blocks until the GPU is done ; meant for uploads
+// and other load time work, not for anything per frame. Runs on the graphics queue, or the compute queue in
+// compute-only mode.
+pub fn one_time_submit(vk_app: &VkApp, record: impl FnOnce(vk::CommandBuffer)) -> Result<(), vk::Result> {
+    let (queue_family, queue) = match vk_app.is_compute_only() {
+        true => (vk_app.compute_queue_family(), vk_app.compute_queue()),
+        false => (vk_app.graphics_queue_family(), vk_app.graphics_queue()),
+    };
+    let device = vk_app.device();
+
+    let pool_create_info = vk::CommandPoolCreateInfo::default()
+        .flags(vk::CommandPoolCreateFlags::TRANSIENT)
+        .queue_family_index(queue_family);
+    let command_pool = unsafe { device.create_command_pool(&pool_create_info, None)? };
+    let result = (|| unsafe {
+        let allocate_info = vk::CommandBufferAllocateInfo::default()
+            .command_pool(command_pool)
+            .level(vk::CommandBufferLevel::PRIMARY)
+            .command_buffer_count(1);
+        let command_buffer = device.allocate_command_buffers(&allocate_info)?[0];
+        let begin_info = vk::CommandBufferBeginInfo::default().flags(vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT);
+        device.begin_command_buffer(command_buffer, &begin_info)?;
+        record(command_buffer);
+        device.end_command_buffer(command_buffer)?;
+
+        let fence = device.create_fence(&vk::FenceCreateInfo::default(), None)?;
+        let submit_info = vk::SubmitInfo::default().command_buffers(std::slice::from_ref(&command_buffer));
+        let submitted = device
+            .queue_submit(queue, std::slice::from_ref(&submit_info), fence)
+            .and_then(|_| device.wait_for_fences(std::slice::from_ref(&fence), true, u64::MAX));
+        device.destroy_fence(fence, None);
+        submitted
+    })();
+    unsafe {
+        device.destroy_command_pool(command_pool, None);
+    }
+    result
+}
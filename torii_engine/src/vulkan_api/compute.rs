@@ -0,0 +1,302 @@
+// Compute pipelines built from a reflected compute shader, and a context recording dispatches on the compute queue.
+//
+//     let pipeline = ComputePipeline::new(vk_app, &Shader::from_glsl(source, ShaderStage::Compute)?, "blur")?;
+//     let mut compute = ComputeContext::new(vk_app)?;
+//     let bindings = DescriptorBindings::new()
+//         .bind_named("input", DescriptorBinding::buffer(input, 0, vk::WHOLE_SIZE))
+//         .bind_named("output", DescriptorBinding::storage_image(view));
+//     compute.dispatch(&pipeline, &bindings, pipeline.group_count([width, height, 1]))?;
+//     compute.submit_and_wait()?;
+//
+// With async compute (VkApp::has_async_compute) the compute queue family differs from the graphics one: resources
+// used by both should be created with SharingMode::CONCURRENT over both families, and submissions ordered against
+// graphics with semaphores (ComputeContext::submit waits and signals).
+
+use ash::{vk, Device};
+use tracing::debug;
+
+use super::descriptors::{DescriptorAllocator, DescriptorBindings};
+use super::pipeline::PipelineLayout;
+use super::shader::{Shader, ShaderReflection, ShaderStage};
+use super::{ComputeError, ComputeResult, VkApp};
+
+pub struct ComputePipeline {
+    device: Device,
+    reflection: ShaderReflection,
+    layout: PipelineLayout,
+    pipeline: vk::Pipeline,
+}
+
+impl ComputePipeline {
+    pub fn new(vk_app: &VkApp, shader: &Shader, name: &str) -> ComputeResult<Self> {
+        if shader.stage() != ShaderStage::Compute {
+            return Err(ComputeError::NotComputeShaderError(shader.stage()));
+        }
+        let device = vk_app.device();
+        let layout = PipelineLayout::new(device, &[shader.reflection()])?;
+
+        let module = shader.create_module(device)?;
+        let entry_point = std::ffi::CString::new(shader.entry_point()).unwrap();
+        let stage = vk::PipelineShaderStageCreateInfo::default()
+            .stage(vk::ShaderStageFlags::COMPUTE)
+            .module(module)
+            .name(&entry_point);
+        let create_info = vk::ComputePipelineCreateInfo::default()
+            .stage(stage)
+            .layout(layout.layout());
+        let created = unsafe {
+            device.create_compute_pipelines(vk::PipelineCache::null(), std::slice::from_ref(&create_info), None)
+        };
+        unsafe {
+            device.destroy_shader_module(module, None);
+        }
+        let pipeline = created.map_err(|(_, error)| error)?[0];
+
+        let debug_utils = vk_app.debug_utils();
+        debug_utils.set_object_name(pipeline, name);
+        debug_utils.set_object_name(layout.layout(), &format!("{name} layout"));
+        debug!(
+            target: "torii::vulkan",
+            name,
+            workgroup_size = ?shader.reflection().workgroup_size,
+            bindings = layout.bindings().len(),
+            push_constant_size = layout.push_constant_size(),
+            "Created compute pipeline"
+        );
+        Ok(ComputePipeline {
+            device: device.clone(),
+            reflection: shader.reflection().clone(),
+            layout,
+            pipeline,
+        })
+    }
+
+    // workgroups needed to cover `invocations` threads with the shader's local size
+    pub fn group_count(&self, invocations: [u32; 3]) -> [u32; 3] {
+        let workgroup_size = self.reflection.workgroup_size;
+        [0, 1, 2].map(|axis| invocations[axis].div_ceil(workgroup_size[axis].max(1)))
+    }
+
+    // GETTERS
+
+    pub fn reflection(&self) -> &ShaderReflection {
+        &self.reflection
+    }
+
+    pub fn layout(&self) -> &PipelineLayout {
+        &self.layout
+    }
+
+    pub fn pipeline(&self) -> vk::Pipeline {
+        self.pipeline
+    }
+}
+
+impl Drop for ComputePipeline {
+    fn drop(&mut self) {
+        unsafe {
+            self.device.destroy_pipeline(self.pipeline, None);
+        }
+    }
+}
+
+// A semaphore the submission waits on before its commands reach `stage`
+#[derive(Copy, Clone, Debug)]
+pub struct SemaphoreWait {
+    pub semaphore: vk::Semaphore,
+    pub stage: vk::PipelineStageFlags,
+}
+
+// Records dispatches into one command buffer of the compute queue until submitted ; once the submission completed
+// (ComputeContext::wait) its descriptor sets are recycled and recording starts over
+pub struct ComputeContext {
+    device: Device,
+    queue: vk::Queue,
+    command_pool: vk::CommandPool,
+    command_buffer: vk::CommandBuffer,
+    fence: vk::Fence,
+    descriptor_allocator: DescriptorAllocator,
+    recording: bool,
+    in_flight: bool,
+    dispatch_count: u32,
+}
+
+impl ComputeContext {
+    pub fn new(vk_app: &VkApp) -> ComputeResult<Self> {
+        let device = vk_app.device();
+        let descriptor_allocator = DescriptorAllocator::new(device)?;
+        let pool_create_info = vk::CommandPoolCreateInfo::default()
+            .flags(vk::CommandPoolCreateFlags::TRANSIENT)
+            .queue_family_index(vk_app.compute_queue_family());
+        let command_pool = unsafe { device.create_command_pool(&pool_create_info, None)? };
+        let mut context = ComputeContext {
+            device: device.clone(),
+            queue: vk_app.compute_queue(),
+            command_pool,
+            command_buffer: vk::CommandBuffer::null(),
+            fence: vk::Fence::null(),
+            descriptor_allocator,
+            recording: false,
+            in_flight: false,
+            dispatch_count: 0,
+        };
+
+        let allocate_info = vk::CommandBufferAllocateInfo::default()
+            .command_pool(command_pool)
+            .level(vk::CommandBufferLevel::PRIMARY)
+            .command_buffer_count(1);
+        context.command_buffer = unsafe { device.allocate_command_buffers(&allocate_info)? }[0];
+        context.fence = unsafe { device.create_fence(&vk::FenceCreateInfo::default(), None)? };
+
+        let debug_utils = vk_app.debug_utils();
+        debug_utils.set_object_name(context.command_buffer, "compute command buffer");
+        debug_utils.set_object_name(context.fence, "compute fence");
+        Ok(context)
+    }
+
+    pub fn dispatch(&mut self, pipeline: &ComputePipeline, bindings: &DescriptorBindings, group_count: [u32; 3]) -> ComputeResult<()> {
+        self.bind(pipeline, bindings)?;
+        unsafe {
+            self.device.cmd_dispatch(self.command_buffer, group_count[0], group_count[1], group_count[2]);
+        }
+        Ok(())
+    }
+
+    // group counts read from a vk::DispatchIndirectCommand in `buffer`, e.g. written by a previous dispatch
+    pub fn dispatch_indirect(&mut self, pipeline: &ComputePipeline, bindings: &DescriptorBindings, buffer: vk::Buffer, offset: vk::DeviceSize) -> ComputeResult<()> {
+        self.bind(pipeline, bindings)?;
+        unsafe {
+            self.device.cmd_dispatch_indirect(self.command_buffer, buffer, offset);
+        }
+        Ok(())
+    }
+
+    // Submits everything recorded since the last submission ; `signals` lets the graphics queue wait on the results
+    pub fn submit(&mut self, waits: &[SemaphoreWait], signals: &[vk::Semaphore]) -> ComputeResult<()> {
+        if self.in_flight {
+            self.wait()?;
+        }
+        self.begin()?;
+        // results also become visible to the host once the fence is signaled
+        let to_host = vk::MemoryBarrier::default()
+            .src_access_mask(vk::AccessFlags::SHADER_WRITE)
+            .dst_access_mask(vk::AccessFlags::HOST_READ);
+        unsafe {
+            self.device.cmd_pipeline_barrier(
+                self.command_buffer,
+                vk::PipelineStageFlags::COMPUTE_SHADER,
+                vk::PipelineStageFlags::HOST,
+                vk::DependencyFlags::empty(),
+                std::slice::from_ref(&to_host),
+                &[],
+                &[],
+            );
+            self.device.end_command_buffer(self.command_buffer)?;
+        }
+
+        let wait_semaphores: Vec<vk::Semaphore> = waits.iter().map(|wait| wait.semaphore).collect();
+        let wait_stages: Vec<vk::PipelineStageFlags> = waits.iter().map(|wait| wait.stage).collect();
+        let submit_info = vk::SubmitInfo::default()
+            .wait_semaphores(&wait_semaphores)
+            .wait_dst_stage_mask(&wait_stages)
+            .command_buffers(std::slice::from_ref(&self.command_buffer))
+            .signal_semaphores(signals);
+        unsafe {
+            self.device.queue_submit(self.queue, std::slice::from_ref(&submit_info), self.fence)?;
+        }
+        self.recording = false;
+        self.in_flight = true;
+        Ok(())
+    }
+
+    // blocks until the last submission completed, then recycles its command buffer and descriptor sets
+    pub fn wait(&mut self) -> ComputeResult<()> {
+        if !self.in_flight {
+            return Ok(());
+        }
+        unsafe {
+            self.device.wait_for_fences(std::slice::from_ref(&self.fence), true, u64::MAX)?;
+            self.device.reset_fences(std::slice::from_ref(&self.fence))?;
+            self.device.reset_command_pool(self.command_pool, vk::CommandPoolResetFlags::empty())?;
+        }
+        self.descriptor_allocator.reset()?;
+        self.in_flight = false;
+        Ok(())
+    }
+
+    pub fn submit_and_wait(&mut self) -> ComputeResult<()> {
+        self.submit(&[], &[])?;
+        self.wait()
+    }
+
+    pub fn is_in_flight(&self) -> bool {
+        self.in_flight
+    }
+}
+
+impl ComputeContext {
+    fn begin(&mut self) -> ComputeResult<()> {
+        if self.recording {
+            return Ok(());
+        }
+        if self.in_flight {
+            self.wait()?;
+        }
+        let begin_info = vk::CommandBufferBeginInfo::default().flags(vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT);
+        unsafe {
+            self.device.begin_command_buffer(self.command_buffer, &begin_info)?;
+        }
+        self.recording = true;
+        self.dispatch_count = 0;
+        Ok(())
+    }
+
+    fn bind(&mut self, pipeline: &ComputePipeline, bindings: &DescriptorBindings) -> ComputeResult<()> {
+        self.begin()?;
+
+        // dispatches of a submission run in order: each one sees what the previous ones wrote, including
+        // indirect arguments
+        if self.dispatch_count > 0 {
+            let barrier = vk::MemoryBarrier::default()
+                .src_access_mask(vk::AccessFlags::SHADER_WRITE)
+                .dst_access_mask(vk::AccessFlags::SHADER_READ | vk::AccessFlags::SHADER_WRITE | vk::AccessFlags::INDIRECT_COMMAND_READ);
+            unsafe {
+                self.device.cmd_pipeline_barrier(
+                    self.command_buffer,
+                    vk::PipelineStageFlags::COMPUTE_SHADER,
+                    vk::PipelineStageFlags::COMPUTE_SHADER | vk::PipelineStageFlags::DRAW_INDIRECT,
+                    vk::DependencyFlags::empty(),
+                    std::slice::from_ref(&barrier),
+                    &[],
+                    &[],
+                );
+            }
+        }
+        self.dispatch_count += 1;
+
+        unsafe {
+            self.device.cmd_bind_pipeline(self.command_buffer, vk::PipelineBindPoint::COMPUTE, pipeline.pipeline);
+        }
+        self.descriptor_allocator.bind(self.command_buffer, vk::PipelineBindPoint::COMPUTE, &pipeline.layout, bindings)?;
+        Ok(())
+    }
+}
+
+impl Drop for ComputeContext {
+    fn drop(&mut self) {
+        unsafe {
+            if self.in_flight {
+                let _ = self.device.wait_for_fences(std::slice::from_ref(&self.fence), true, u64::MAX);
+            }
+            self.device.destroy_fence(self.fence, None);
+            self.device.destroy_command_pool(self.command_pool, None);
+        }
+    }
+}
+
+// Binary semaphore for ordering compute submissions against graphics ones (and back)
+pub fn create_semaphore(vk_app: &VkApp, name: &str) -> ComputeResult<vk::Semaphore> {
+    let semaphore = unsafe { vk_app.device().create_semaphore(&vk::SemaphoreCreateInfo::default(), None)? };
+    vk_app.debug_utils().set_object_name(semaphore, name);
+    Ok(semaphore)
+}
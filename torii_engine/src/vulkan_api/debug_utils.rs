@@ -0,0 +1,173 @@
+use std::ffi::CString;
+use std::path::Path;
+
+use ash::{ext, vk, Device, Instance};
+use ash::vk::Handle;
+
+// Device level half of VK_EXT_debug_utils: object names and command buffer / queue labels, as shown by validation
+// messages, RenderDoc and other capture tools. Every function is a no-op when the extension is not enabled, so
+// callers never need to check.
+#[derive(Clone)]
+pub struct DebugUtils {
+    debug_utils_device: Option<ext::debug_utils::Device>,
+}
+
+impl DebugUtils {
+    pub fn new(instance: &Instance, device: &Device, enabled: bool) -> Self {
+        DebugUtils {
+            debug_utils_device: enabled.then(|| ext::debug_utils::Device::new(instance, device)),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.debug_utils_device.is_some()
+    }
+
+    // OBJECT NAMING
+
+    pub fn set_object_name<H: Handle>(&self, handle: H, name: &str) {
+        let Some(debug_utils_device) = self.debug_utils_device.as_ref() else {
+            return;
+        };
+        let Ok(name) = CString::new(name) else {
+            return;
+        };
+        let name_info = vk::DebugUtilsObjectNameInfoEXT::default()
+            .object_handle(handle)
+            .object_name(&name);
+        unsafe {
+            // naming is best effort, a failure must never take the renderer down
+            let _ = debug_utils_device.set_debug_utils_object_name(&name_info);
+        }
+    }
+
+    // COMMAND BUFFER LABELS
+
+    pub fn cmd_begin_label(&self, command_buffer: vk::CommandBuffer, label: &str, color: [f32; 4]) {
+        if let Some(debug_utils_device) = self.debug_utils_device.as_ref() {
+            let label = CString::new(label).unwrap_or_default();
+            unsafe {
+                debug_utils_device.cmd_begin_debug_utils_label(command_buffer, &Self::label(&label, color));
+            }
+        }
+    }
+
+    pub fn cmd_end_label(&self, command_buffer: vk::CommandBuffer) {
+        if let Some(debug_utils_device) = self.debug_utils_device.as_ref() {
+            unsafe {
+                debug_utils_device.cmd_end_debug_utils_label(command_buffer);
+            }
+        }
+    }
+
+    pub fn cmd_insert_label(&self, command_buffer: vk::CommandBuffer, label: &str, color: [f32; 4]) {
+        if let Some(debug_utils_device) = self.debug_utils_device.as_ref() {
+            let label = CString::new(label).unwrap_or_default();
+            unsafe {
+                debug_utils_device.cmd_insert_debug_utils_label(command_buffer, &Self::label(&label, color));
+            }
+        }
+    }
+
+    // begins a label that ends when the returned guard is dropped
+    pub fn cmd_scoped_label(&self, command_buffer: vk::CommandBuffer, label: &str, color: [f32; 4]) -> CommandBufferLabel<'_> {
+        self.cmd_begin_label(command_buffer, label, color);
+        CommandBufferLabel {
+            debug_utils: self,
+            command_buffer,
+        }
+    }
+
+    // QUEUE LABELS
+
+    pub fn queue_begin_label(&self, queue: vk::Queue, label: &str, color: [f32; 4]) {
+        if let Some(debug_utils_device) = self.debug_utils_device.as_ref() {
+            let label = CString::new(label).unwrap_or_default();
+            unsafe {
+                debug_utils_device.queue_begin_debug_utils_label(queue, &Self::label(&label, color));
+            }
+        }
+    }
+
+    pub fn queue_end_label(&self, queue: vk::Queue) {
+        if let Some(debug_utils_device) = self.debug_utils_device.as_ref() {
+            unsafe {
+                debug_utils_device.queue_end_debug_utils_label(queue);
+            }
+        }
+    }
+
+    pub fn queue_insert_label(&self, queue: vk::Queue, label: &str, color: [f32; 4]) {
+        if let Some(debug_utils_device) = self.debug_utils_device.as_ref() {
+            let label = CString::new(label).unwrap_or_default();
+            unsafe {
+                debug_utils_device.queue_insert_debug_utils_label(queue, &Self::label(&label, color));
+            }
+        }
+    }
+
+    pub fn queue_scoped_label(&self, queue: vk::Queue, label: &str, color: [f32; 4]) -> QueueLabel<'_> {
+        self.queue_begin_label(queue, label, color);
+        QueueLabel {
+            debug_utils: self,
+            queue,
+        }
+    }
+}
+
+impl DebugUtils {
+    fn label(label: &CString, color: [f32; 4]) -> vk::DebugUtilsLabelEXT<'_> {
+        vk::DebugUtilsLabelEXT::default()
+            .label_name(label)
+            .color(color)
+    }
+}
+
+// Ends its command buffer label when dropped ; must be dropped while the command buffer is still recording
+pub struct CommandBufferLabel<'a> {
+    debug_utils: &'a DebugUtils,
+    command_buffer: vk::CommandBuffer,
+}
+
+impl Drop for CommandBufferLabel<'_> {
+    fn drop(&mut self) {
+        self.debug_utils.cmd_end_label(self.command_buffer);
+    }
+}
+
+pub struct QueueLabel<'a> {
+    debug_utils: &'a DebugUtils,
+    queue: vk::Queue,
+}
+
+impl Drop for QueueLabel<'_> {
+    fn drop(&mut self) {
+        self.debug_utils.queue_end_label(self.queue);
+    }
+}
+
+// NAME DERIVATION
+// Names follow `<source> [<role>]`, e.g. `models/tree.gltf [vertices]` or `Player#12 [vertices]` ; whatever creates an
+// object appends its role to the name it was given, so every object made for the same asset or entity groups together
+// in capture tools. Loaders creating GPU objects straight from a file name them after the path ; the engine keeps no
+// entities, objects owned by the game's entities are named by the game when it uploads them
+
+// keeps the file name and its parent directory, which is usually enough to identify an asset
+pub fn name_from_asset_path(path: impl AsRef<Path>) -> String {
+    let path = path.as_ref();
+    let file_name = path.file_name().map(|file_name| file_name.to_string_lossy());
+    let parent_name = path.parent().and_then(|parent| parent.file_name()).map(|parent| parent.to_string_lossy());
+    match (parent_name, file_name) {
+        (Some(parent_name), Some(file_name)) => format!("{parent_name}/{file_name}"),
+        (None, Some(file_name)) => file_name.into_owned(),
+        _ => path.to_string_lossy().into_owned(),
+    }
+}
+
+// common label colors, so passes are recognisable at a glance in captures
+pub mod label_colors {
+    pub const GRAPHICS: [f32; 4] = [0.2, 0.6, 1.0, 1.0];
+    pub const COMPUTE: [f32; 4] = [1.0, 0.6, 0.2, 1.0];
+    pub const TRANSFER: [f32; 4] = [0.6, 0.6, 0.6, 1.0];
+    pub const PRESENT: [f32; 4] = [0.4, 1.0, 0.4, 1.0];
+}
@@ -0,0 +1,284 @@
+use std::collections::BTreeMap;
+
+use ash::{vk, Device};
+
+use super::pipeline::PipelineLayout;
+use super::shader::ReflectedBinding;
+use super::{DescriptorError, DescriptorResult};
+
+// One descriptor ; buffers and images take the uniform/storage or sampled/storage type the shader declares
+#[derive(Copy, Clone, Debug)]
+pub enum DescriptorBinding {
+    Buffer { buffer: vk::Buffer, offset: vk::DeviceSize, range: vk::DeviceSize },
+    Image { view: vk::ImageView, layout: vk::ImageLayout },
+    Sampler(vk::Sampler),
+    CombinedImageSampler { view: vk::ImageView, layout: vk::ImageLayout, sampler: vk::Sampler },
+}
+
+impl DescriptorBinding {
+    pub fn buffer(buffer: vk::Buffer, offset: vk::DeviceSize, range: vk::DeviceSize) -> Self {
+        DescriptorBinding::Buffer { buffer, offset, range }
+    }
+
+    pub fn whole_buffer(buffer: vk::Buffer) -> Self {
+        DescriptorBinding::Buffer { buffer, offset: 0, range: vk::WHOLE_SIZE }
+    }
+
+    pub fn storage_image(view: vk::ImageView) -> Self {
+        DescriptorBinding::Image { view, layout: vk::ImageLayout::GENERAL }
+    }
+
+    pub fn sampled_image(view: vk::ImageView) -> Self {
+        DescriptorBinding::Image { view, layout: vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL }
+    }
+
+    pub fn combined_image_sampler(view: vk::ImageView, sampler: vk::Sampler) -> Self {
+        DescriptorBinding::CombinedImageSampler { view, layout: vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL, sampler }
+    }
+
+    fn matches(&self, descriptor_type: vk::DescriptorType) -> bool {
+        match self {
+            DescriptorBinding::Buffer { .. } => {
+                matches!(descriptor_type, vk::DescriptorType::UNIFORM_BUFFER | vk::DescriptorType::STORAGE_BUFFER)
+            },
+            DescriptorBinding::Image { .. } => {
+                matches!(descriptor_type, vk::DescriptorType::STORAGE_IMAGE | vk::DescriptorType::SAMPLED_IMAGE)
+            },
+            DescriptorBinding::Sampler(_) => descriptor_type == vk::DescriptorType::SAMPLER,
+            DescriptorBinding::CombinedImageSampler { .. } => descriptor_type == vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+enum BindingSlot {
+    Location(u32, u32),
+    Name(String),
+}
+
+// What a draw or dispatch binds, checked against the pipeline's reflected layout when it is bound
+#[derive(Clone, Debug, Default)]
+pub struct DescriptorBindings {
+    bindings: Vec<(BindingSlot, Vec<DescriptorBinding>)>,
+    push_constants: Vec<u8>,
+}
+
+impl DescriptorBindings {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn bind(mut self, set: u32, binding: u32, value: DescriptorBinding) -> Self {
+        self.bindings.push((BindingSlot::Location(set, binding), vec![value]));
+        self
+    }
+
+    // by the variable name in the shader
+    pub fn bind_named(mut self, name: impl Into<String>, value: DescriptorBinding) -> Self {
+        self.bindings.push((BindingSlot::Name(name.into()), vec![value]));
+        self
+    }
+
+    // for descriptor arrays, starting at element 0
+    pub fn bind_array(mut self, set: u32, binding: u32, values: Vec<DescriptorBinding>) -> Self {
+        self.bindings.push((BindingSlot::Location(set, binding), values));
+        self
+    }
+
+    pub fn with_push_constants(mut self, bytes: &[u8]) -> Self {
+        self.push_constants = bytes.to_vec();
+        self
+    }
+
+    pub fn push_constants(&self) -> &[u8] {
+        &self.push_constants
+    }
+
+    // (set, binding) -> values, every reflected binding must be covered
+    fn resolve<'b>(&'b self, reflected_bindings: &'b [ReflectedBinding], push_constant_size: u32) -> DescriptorResult<ResolvedBindings<'b>> {
+        let find = |set: u32, binding: u32| reflected_bindings.iter().find(|reflected| reflected.set == set && reflected.binding == binding);
+        let mut resolved = BTreeMap::new();
+        for (slot, values) in self.bindings.iter() {
+            let reflected = match slot {
+                BindingSlot::Location(set, binding) => find(*set, *binding)
+                    .ok_or(DescriptorError::UnknownBindingError { set: *set, binding: *binding })?,
+                BindingSlot::Name(name) => reflected_bindings
+                    .iter()
+                    .find(|reflected| reflected.name.as_deref() == Some(name.as_str()))
+                    .ok_or_else(|| DescriptorError::UnknownBindingNameError(name.clone()))?,
+            };
+            if !values.iter().all(|value| value.matches(reflected.descriptor_type)) {
+                return Err(DescriptorError::BindingTypeMismatchError {
+                    set: reflected.set,
+                    binding: reflected.binding,
+                    expected: reflected.descriptor_type,
+                });
+            }
+            resolved.insert((reflected.set, reflected.binding), (reflected, values.as_slice()));
+        }
+        if let Some(missing) = reflected_bindings.iter().find(|reflected| !resolved.contains_key(&(reflected.set, reflected.binding))) {
+            return Err(DescriptorError::MissingBindingError { set: missing.set, binding: missing.binding });
+        }
+        if self.push_constants.len() as u32 > push_constant_size {
+            return Err(DescriptorError::PushConstantSizeError {
+                given: self.push_constants.len(),
+                declared: push_constant_size,
+            });
+        }
+        Ok(resolved)
+    }
+}
+
+// (set, binding) -> the reflected binding and the values bound to it
+type ResolvedBindings<'b> = BTreeMap<(u32, u32), (&'b ReflectedBinding, &'b [DescriptorBinding])>;
+
+const DESCRIPTOR_POOL_SETS: u32 = 64;
+const DESCRIPTOR_POOL_TYPES: [vk::DescriptorType; 6] = [
+    vk::DescriptorType::UNIFORM_BUFFER,
+    vk::DescriptorType::STORAGE_BUFFER,
+    vk::DescriptorType::STORAGE_IMAGE,
+    vk::DescriptorType::SAMPLED_IMAGE,
+    vk::DescriptorType::SAMPLER,
+    vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+];
+
+// Hands out descriptor sets for the bindings of one batch of work (a submission, a frame) ; reset once that work
+// completed. A new pool is added whenever the current ones run out.
+pub struct DescriptorAllocator {
+    device: Device,
+    pools: Vec<vk::DescriptorPool>,
+}
+
+impl DescriptorAllocator {
+    pub fn new(device: &Device) -> DescriptorResult<Self> {
+        let mut allocator = DescriptorAllocator {
+            device: device.clone(),
+            pools: vec![],
+        };
+        allocator.pools.push(allocator.create_pool()?);
+        Ok(allocator)
+    }
+
+    // Writes `bindings` into fresh sets for `layout`, then binds them and the push constants
+    pub fn bind(
+        &mut self,
+        command_buffer: vk::CommandBuffer,
+        bind_point: vk::PipelineBindPoint,
+        layout: &PipelineLayout,
+        bindings: &DescriptorBindings,
+    ) -> DescriptorResult<()> {
+        let descriptor_sets = self.write_sets(layout, bindings)?;
+        unsafe {
+            if !descriptor_sets.is_empty() {
+                self.device.cmd_bind_descriptor_sets(command_buffer, bind_point, layout.layout(), 0, &descriptor_sets, &[]);
+            }
+            if !bindings.push_constants.is_empty() {
+                self.device.cmd_push_constants(command_buffer, layout.layout(), layout.push_constant_stages(), 0, &bindings.push_constants);
+            }
+        }
+        Ok(())
+    }
+
+    // Writes `bindings` (but not their push constants) into fresh sets for `layout` without binding them, for
+    // commands recorded later such as render graph passes
+    pub fn write_sets(&mut self, layout: &PipelineLayout, bindings: &DescriptorBindings) -> DescriptorResult<Vec<vk::DescriptorSet>> {
+        let resolved = bindings.resolve(layout.bindings(), layout.push_constant_size())?;
+        let descriptor_sets = self.allocate(layout.set_layouts())?;
+
+        // infos of one binding are contiguous, the writes only refer to them once both lists are complete
+        let mut buffer_infos: Vec<vk::DescriptorBufferInfo> = vec![];
+        let mut image_infos: Vec<vk::DescriptorImageInfo> = vec![];
+        let mut ranges = vec![];
+        for (&(set, binding), &(reflected, values)) in resolved.iter() {
+            let (buffer_start, image_start) = (buffer_infos.len(), image_infos.len());
+            for value in values.iter() {
+                match *value {
+                    DescriptorBinding::Buffer { buffer, offset, range } => {
+                        buffer_infos.push(vk::DescriptorBufferInfo { buffer, offset, range });
+                    },
+                    DescriptorBinding::Image { view, layout } => {
+                        image_infos.push(vk::DescriptorImageInfo { sampler: vk::Sampler::null(), image_view: view, image_layout: layout });
+                    },
+                    DescriptorBinding::Sampler(sampler) => {
+                        image_infos.push(vk::DescriptorImageInfo { sampler, ..Default::default() });
+                    },
+                    DescriptorBinding::CombinedImageSampler { view, layout, sampler } => {
+                        image_infos.push(vk::DescriptorImageInfo { sampler, image_view: view, image_layout: layout });
+                    },
+                }
+            }
+            ranges.push((set, binding, reflected.descriptor_type, buffer_start..buffer_infos.len(), image_start..image_infos.len()));
+        }
+        let descriptor_writes: Vec<vk::WriteDescriptorSet> = ranges
+            .into_iter()
+            .filter(|(_, _, _, buffer_range, image_range)| !buffer_range.is_empty() || !image_range.is_empty())
+            .map(|(set, binding, descriptor_type, buffer_range, image_range)| {
+                let write = vk::WriteDescriptorSet::default()
+                    .dst_set(descriptor_sets[set as usize])
+                    .dst_binding(binding)
+                    .dst_array_element(0)
+                    .descriptor_type(descriptor_type);
+                match buffer_range.is_empty() {
+                    false => write.buffer_info(&buffer_infos[buffer_range]),
+                    true => write.image_info(&image_infos[image_range]),
+                }
+            })
+            .collect();
+
+        unsafe {
+            self.device.update_descriptor_sets(&descriptor_writes, &[]);
+        }
+        Ok(descriptor_sets)
+    }
+
+    pub fn allocate(&mut self, set_layouts: &[vk::DescriptorSetLayout]) -> DescriptorResult<Vec<vk::DescriptorSet>> {
+        if set_layouts.is_empty() {
+            return Ok(vec![]);
+        }
+        let pool = *self.pools.last().unwrap();
+        let allocate_info = vk::DescriptorSetAllocateInfo::default()
+            .descriptor_pool(pool)
+            .set_layouts(set_layouts);
+        match unsafe { self.device.allocate_descriptor_sets(&allocate_info) } {
+            Err(vk::Result::ERROR_OUT_OF_POOL_MEMORY | vk::Result::ERROR_FRAGMENTED_POOL) => {
+                let pool = self.create_pool()?;
+                self.pools.push(pool);
+                let allocate_info = allocate_info.descriptor_pool(pool);
+                Ok(unsafe { self.device.allocate_descriptor_sets(&allocate_info)? })
+            },
+            result => Ok(result?),
+        }
+    }
+
+    // every set handed out so far becomes invalid
+    pub fn reset(&mut self) -> DescriptorResult<()> {
+        for &pool in self.pools.iter() {
+            unsafe {
+                self.device.reset_descriptor_pool(pool, vk::DescriptorPoolResetFlags::empty())?;
+            }
+        }
+        Ok(())
+    }
+
+    fn create_pool(&self) -> DescriptorResult<vk::DescriptorPool> {
+        let pool_sizes = DESCRIPTOR_POOL_TYPES.map(|descriptor_type| {
+            vk::DescriptorPoolSize::default()
+                .ty(descriptor_type)
+                .descriptor_count(DESCRIPTOR_POOL_SETS * 4)
+        });
+        let create_info = vk::DescriptorPoolCreateInfo::default()
+            .max_sets(DESCRIPTOR_POOL_SETS)
+            .pool_sizes(&pool_sizes);
+        Ok(unsafe { self.device.create_descriptor_pool(&create_info, None)? })
+    }
+}
+
+impl Drop for DescriptorAllocator {
+    fn drop(&mut self) {
+        unsafe {
+            for &pool in self.pools.iter() {
+                self.device.destroy_descriptor_pool(pool, None);
+            }
+        }
+    }
+}
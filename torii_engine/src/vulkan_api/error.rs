@@ -0,0 +1,125 @@
+#[cfg(not(feature = "linked"))]
+use std::path::PathBuf;
+
+use thiserror::Error;
+
+use super::shader::ShaderStage;
+use super::versioning::version_string;
+
+#[derive(Error, Debug)]
+pub enum VkError {
+    #[cfg(not(feature = "linked"))]
+    #[error(
+        "Vulkan loader not found{} ; install a vulkan driver or pass the path of the loader library",
+        .path.as_ref().map(|path| format!(" at {}", path.display())).unwrap_or_default()
+    )]
+    LoaderNotFoundError {
+        path: Option<PathBuf>,
+        #[source]
+        source: ash::LoadingError,
+    },
+    #[error("Invalid vulkan api version range: minimum {} is above maximum {}", version_string(*min), version_string(*max))]
+    InvalidApiVersionRangeError { min: u32, max: u32 },
+    #[error("Vulkan instance version {} is below the minimum required {}", version_string(*available), version_string(*required))]
+    UnsupportedInstanceVersionError { required: u32, available: u32 },
+    #[error("Validation layers requested, but not available")]
+    ValidationLayersUnavailableError,
+    #[error("Required instance extensions not available: {}", .0.join(", "))]
+    MissingInstanceExtensionsError(Vec<String>),
+    #[error("Failed to create vulkan instance")]
+    InstanceCreationError(#[source] ash::vk::Result),
+    #[error("Failed to enumerate the GPUs: {0}")]
+    PhysicalDeviceEnumerationError(#[source] ash::vk::Result),
+    #[error("No GPU with vulkan support found")]
+    NoPhysicalDeviceError,
+    #[error("No suitable GPU found ; {}", .0.join(" ; "))]
+    NoSuitablePhysicalDeviceError(Vec<String>),
+}
+
+pub type VkResult<T> = std::result::Result<T, VkError>;
+
+#[derive(Error, Debug)]
+pub enum ShaderError {
+    #[error("Failed to parse shader: {0}")]
+    ParseError(String),
+    #[error("Invalid shader: {0}")]
+    ValidationError(String),
+    #[error("Failed to write SPIR-V: {0}")]
+    SpirvWriteError(String),
+    #[error("Shader has no {0:?} entry point")]
+    MissingEntryPointError(ShaderStage),
+    #[error("Unsupported shader stage {0}")]
+    UnsupportedStageError(String),
+    #[error("Unsupported shader binding {0}")]
+    UnsupportedBindingError(String),
+}
+
+#[derive(Error, Debug)]
+pub enum MemoryError {
+    #[error("No memory type suits {0}")]
+    NoCompatibleMemoryTypeError(String),
+    #[error("Vulkan call failed while creating or filling a GPU resource")]
+    VulkanCallError(#[from] ash::vk::Result),
+}
+
+pub type MemoryResult<T> = std::result::Result<T, MemoryError>;
+
+#[derive(Error, Debug)]
+pub enum DescriptorError {
+    #[error("Pipeline has no binding named {0}")]
+    UnknownBindingNameError(String),
+    #[error("Pipeline has no binding at set {set} binding {binding}")]
+    UnknownBindingError { set: u32, binding: u32 },
+    #[error("Nothing bound at set {set} binding {binding}")]
+    MissingBindingError { set: u32, binding: u32 },
+    #[error("Set {set} binding {binding} expects a {expected:?} descriptor")]
+    BindingTypeMismatchError { set: u32, binding: u32, expected: ash::vk::DescriptorType },
+    #[error("Push constants are {given} bytes, the pipeline declares {declared}")]
+    PushConstantSizeError { given: usize, declared: u32 },
+    #[error("Vulkan call failed while writing descriptors")]
+    VulkanCallError(#[from] ash::vk::Result),
+}
+
+pub type DescriptorResult<T> = std::result::Result<T, DescriptorError>;
+
+#[derive(Error, Debug)]
+pub enum PipelineError {
+    #[error("Graphics pipeline needs a {0}")]
+    MissingStageError(&'static str),
+    #[error("{0:?} shader given where a {1} shader was expected")]
+    WrongStageError(ShaderStage, &'static str),
+    #[error("Graphics pipelines render with dynamic rendering, which the device does not have enabled")]
+    DynamicRenderingUnavailableError,
+    #[error("Task/mesh pipeline requested, but VK_EXT_mesh_shader is not enabled on the device")]
+    MeshShaderUnavailableError,
+    #[error("Vulkan call failed while creating a pipeline")]
+    VulkanCallError(#[from] ash::vk::Result),
+}
+
+pub type PipelineResult<T> = std::result::Result<T, PipelineError>;
+
+#[derive(Error, Debug)]
+pub enum ComputeError {
+    #[error("Compute pipelines need a compute shader, got {0:?}")]
+    NotComputeShaderError(ShaderStage),
+    #[error(transparent)]
+    DescriptorError(#[from] DescriptorError),
+    #[error("Vulkan call failed while recording compute work")]
+    VulkanCallError(#[from] ash::vk::Result),
+}
+
+pub type ComputeResult<T> = std::result::Result<T, ComputeError>;
+
+#[derive(Error, Debug)]
+pub enum RenderTargetError {
+    #[error("No depth format{} usable as an attachment on this device", if *.0 { " with stencil" } else { "" })]
+    NoDepthFormatError(bool),
+    #[error("Format {format:?} does not support {usage:?} on this device")]
+    UnsupportedFormatError { format: ash::vk::Format, usage: ash::vk::ImageUsageFlags },
+    #[error(transparent)]
+    MemoryError(#[from] MemoryError),
+    #[error("Vulkan call failed while recreating render targets")]
+    VulkanCallError(#[from] ash::vk::Result),
+}
+
+pub type RenderTargetResult<T> = std::result::Result<T, RenderTargetError>;
@@ -0,0 +1,411 @@
+use ash::{vk, Device};
+
+use super::commands::one_time_submit;
+use super::memory::{allocate_memory, GpuBuffer, MemoryLocation};
+use super::render_graph::ImportedImage;
+use super::{MemoryResult, VkApp};
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct ImageDesc {
+    pub format: vk::Format,
+    pub extent: vk::Extent2D,
+    pub samples: vk::SampleCountFlags,
+    pub mip_levels: u32,
+    pub array_layers: u32,
+    // the layers are cube faces, six per cube
+    pub cube: bool,
+}
+
+impl ImageDesc {
+    pub fn new(format: vk::Format, extent: vk::Extent2D) -> Self {
+        ImageDesc {
+            format,
+            extent,
+            samples: vk::SampleCountFlags::TYPE_1,
+            mip_levels: 1,
+            array_layers: 1,
+            cube: false,
+        }
+    }
+
+    // six square faces, sampled as a cube
+    pub fn new_cube(format: vk::Format, size: u32) -> Self {
+        ImageDesc {
+            array_layers: 6,
+            cube: true,
+            ..Self::new(format, vk::Extent2D { width: size, height: size })
+        }
+    }
+
+    // number of mips down to 1x1
+    pub fn full_mip_chain(extent: vk::Extent2D) -> u32 {
+        u32::BITS - extent.width.max(extent.height).max(1).leading_zeros()
+    }
+
+    pub fn with_samples(mut self, samples: vk::SampleCountFlags) -> Self {
+        self.samples = samples;
+        self
+    }
+
+    pub fn with_mip_levels(mut self, mip_levels: u32) -> Self {
+        self.mip_levels = mip_levels;
+        self
+    }
+
+    pub fn with_array_layers(mut self, array_layers: u32) -> Self {
+        self.array_layers = array_layers;
+        self
+    }
+
+    pub fn with_full_mip_chain(mut self) -> Self {
+        self.mip_levels = Self::full_mip_chain(self.extent);
+        self
+    }
+
+    pub fn mip_extent(&self, mip_level: u32) -> vk::Extent2D {
+        vk::Extent2D {
+            width: (self.extent.width >> mip_level).max(1),
+            height: (self.extent.height >> mip_level).max(1),
+        }
+    }
+
+    pub(crate) fn create_flags(&self) -> vk::ImageCreateFlags {
+        match self.cube {
+            true => vk::ImageCreateFlags::CUBE_COMPATIBLE,
+            false => vk::ImageCreateFlags::empty(),
+        }
+    }
+
+    // cube (array) for cube images, 2D array for other layered ones
+    pub(crate) fn view_type(&self) -> vk::ImageViewType {
+        match (self.cube, self.array_layers) {
+            (true, 6) => vk::ImageViewType::CUBE,
+            (true, _) => vk::ImageViewType::CUBE_ARRAY,
+            (false, 1) => vk::ImageViewType::TYPE_2D,
+            (false, _) => vk::ImageViewType::TYPE_2D_ARRAY,
+        }
+    }
+
+    pub(crate) fn aspect_mask(&self) -> vk::ImageAspectFlags {
+        match self.format {
+            vk::Format::D16_UNORM | vk::Format::X8_D24_UNORM_PACK32 | vk::Format::D32_SFLOAT => vk::ImageAspectFlags::DEPTH,
+            vk::Format::D16_UNORM_S8_UINT | vk::Format::D24_UNORM_S8_UINT | vk::Format::D32_SFLOAT_S8_UINT => {
+                vk::ImageAspectFlags::DEPTH | vk::ImageAspectFlags::STENCIL
+            },
+            vk::Format::S8_UINT => vk::ImageAspectFlags::STENCIL,
+            _ => vk::ImageAspectFlags::COLOR,
+        }
+    }
+
+    pub(crate) fn subresource_range(&self) -> vk::ImageSubresourceRange {
+        vk::ImageSubresourceRange::default()
+            .aspect_mask(self.aspect_mask())
+            .base_mip_level(0)
+            .level_count(self.mip_levels)
+            .base_array_layer(0)
+            .layer_count(self.array_layers)
+    }
+}
+
+// First of `candidates` (in order of preference) whose optimal tiling supports every `features` flag
+pub fn find_supported_format(vk_app: &VkApp, candidates: &[vk::Format], features: vk::FormatFeatureFlags) -> Option<vk::Format> {
+    candidates.iter().copied().find(|&format| {
+        let properties = unsafe {
+            vk_app.instance().get_physical_device_format_properties(vk_app.physical_device(), format)
+        };
+        properties.optimal_tiling_features.contains(features)
+    })
+}
+
+// Where an image is in its life, for the barriers of one-off transitions (uploads, load time generation) ; frames
+// go through the render graph instead
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct ImageState {
+    pub stage: vk::PipelineStageFlags,
+    pub access: vk::AccessFlags,
+    pub layout: vk::ImageLayout,
+}
+
+impl ImageState {
+    pub const UNDEFINED: Self = Self::new(vk::PipelineStageFlags::TOP_OF_PIPE, vk::AccessFlags::empty(), vk::ImageLayout::UNDEFINED);
+    pub const TRANSFER_SRC: Self = Self::new(vk::PipelineStageFlags::TRANSFER, vk::AccessFlags::TRANSFER_READ, vk::ImageLayout::TRANSFER_SRC_OPTIMAL);
+    pub const TRANSFER_DST: Self = Self::new(vk::PipelineStageFlags::TRANSFER, vk::AccessFlags::TRANSFER_WRITE, vk::ImageLayout::TRANSFER_DST_OPTIMAL);
+    pub const DEPTH_ATTACHMENT: Self = Self::new(
+        vk::PipelineStageFlags::from_raw(vk::PipelineStageFlags::EARLY_FRAGMENT_TESTS.as_raw() | vk::PipelineStageFlags::LATE_FRAGMENT_TESTS.as_raw()),
+        vk::AccessFlags::from_raw(vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_READ.as_raw() | vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE.as_raw()),
+        vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL,
+    );
+    pub const COLOR_ATTACHMENT: Self = Self::new(
+        vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
+        vk::AccessFlags::from_raw(vk::AccessFlags::COLOR_ATTACHMENT_READ.as_raw() | vk::AccessFlags::COLOR_ATTACHMENT_WRITE.as_raw()),
+        vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
+    );
+    pub const STORAGE_WRITE_COMPUTE: Self = Self::new(vk::PipelineStageFlags::COMPUTE_SHADER, vk::AccessFlags::SHADER_WRITE, vk::ImageLayout::GENERAL);
+    // read by compute and fragment shaders
+    pub const SHADER_READ: Self = Self::new(
+        vk::PipelineStageFlags::from_raw(vk::PipelineStageFlags::COMPUTE_SHADER.as_raw() | vk::PipelineStageFlags::FRAGMENT_SHADER.as_raw()),
+        vk::AccessFlags::SHADER_READ,
+        vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+    );
+
+    pub const fn new(stage: vk::PipelineStageFlags, access: vk::AccessFlags, layout: vk::ImageLayout) -> Self {
+        ImageState { stage, access, layout }
+    }
+}
+
+// A view created on top of a GpuImage, e.g. over a single mip for compute writes ; must not outlive the image
+pub struct GpuImageView {
+    device: Device,
+    view: vk::ImageView,
+}
+
+impl GpuImageView {
+    pub fn view(&self) -> vk::ImageView {
+        self.view
+    }
+}
+
+impl Drop for GpuImageView {
+    fn drop(&mut self) {
+        unsafe {
+            self.device.destroy_image_view(self.view, None);
+        }
+    }
+}
+
+// A 2D image (or array / cube of them) with its own dedicated device local allocation and a view over every mip and
+// layer
+pub struct GpuImage {
+    device: Device,
+    image: vk::Image,
+    memory: vk::DeviceMemory,
+    view: vk::ImageView,
+    desc: ImageDesc,
+    usage: vk::ImageUsageFlags,
+}
+
+impl GpuImage {
+    pub fn new(vk_app: &VkApp, desc: ImageDesc, usage: vk::ImageUsageFlags, name: &str) -> MemoryResult<Self> {
+        let device = vk_app.device();
+        let create_info = vk::ImageCreateInfo::default()
+            .flags(desc.create_flags())
+            .image_type(vk::ImageType::TYPE_2D)
+            .format(desc.format)
+            .extent(vk::Extent3D { width: desc.extent.width, height: desc.extent.height, depth: 1 })
+            .mip_levels(desc.mip_levels)
+            .array_layers(desc.array_layers)
+            .samples(desc.samples)
+            .tiling(vk::ImageTiling::OPTIMAL)
+            .usage(usage)
+            .sharing_mode(vk::SharingMode::EXCLUSIVE)
+            .initial_layout(vk::ImageLayout::UNDEFINED);
+        let image = unsafe { device.create_image(&create_info, None)? };
+        let mut gpu_image = GpuImage {
+            device: device.clone(),
+            image,
+            memory: vk::DeviceMemory::null(),
+            view: vk::ImageView::null(),
+            desc,
+            usage,
+        };
+
+        let requirements = unsafe { device.get_image_memory_requirements(image) };
+        gpu_image.memory = allocate_memory(vk_app, requirements, MemoryLocation::DeviceLocal, name)?;
+        unsafe {
+            device.bind_image_memory(image, gpu_image.memory, 0)?;
+        }
+        let view_create_info = vk::ImageViewCreateInfo::default()
+            .image(image)
+            .view_type(desc.view_type())
+            .format(desc.format)
+            .subresource_range(desc.subresource_range());
+        gpu_image.view = unsafe { device.create_image_view(&view_create_info, None)? };
+
+        let debug_utils = vk_app.debug_utils();
+        debug_utils.set_object_name(image, name);
+        debug_utils.set_object_name(gpu_image.view, &format!("{name} [view]"));
+        Ok(gpu_image)
+    }
+
+    // Device local image holding `data` (mip 0 of every layer, tightly packed one layer after the other), left
+    // readable by shaders ; the other mips are downsampled from mip 0 when `generate_mips` is set
+    pub fn with_data(vk_app: &VkApp, desc: ImageDesc, usage: vk::ImageUsageFlags, data: &[u8], generate_mips: bool, name: &str) -> MemoryResult<Self> {
+        let usage = usage | vk::ImageUsageFlags::TRANSFER_DST | vk::ImageUsageFlags::SAMPLED;
+        let usage = if generate_mips { usage | vk::ImageUsageFlags::TRANSFER_SRC } else { usage };
+        let gpu_image = Self::new(vk_app, desc, usage, name)?;
+        gpu_image.upload(vk_app, data, generate_mips)?;
+        Ok(gpu_image)
+    }
+
+    // Replaces mip 0 of every layer with `data` through a staging buffer and waits for the copy ; the image ends up
+    // in SHADER_READ_ONLY_OPTIMAL
+    pub fn upload(&self, vk_app: &VkApp, data: &[u8], generate_mips: bool) -> MemoryResult<()> {
+        let mut staging = GpuBuffer::new(vk_app, data.len() as vk::DeviceSize, vk::BufferUsageFlags::TRANSFER_SRC, MemoryLocation::HostVisible, "image staging buffer")?;
+        staging.write(0, data);
+        let region = vk::BufferImageCopy::default()
+            .image_subresource(self.subresource_layers(0))
+            .image_extent(vk::Extent3D { width: self.desc.extent.width, height: self.desc.extent.height, depth: 1 });
+        let device = vk_app.device();
+        one_time_submit(vk_app, |command_buffer| unsafe {
+            self.cmd_transition(device, command_buffer, ImageState::UNDEFINED, ImageState::TRANSFER_DST);
+            device.cmd_copy_buffer_to_image(
+                command_buffer,
+                staging.buffer(),
+                self.image,
+                vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                std::slice::from_ref(&region),
+            );
+            if generate_mips && self.desc.mip_levels > 1 {
+                self.cmd_generate_mips(device, command_buffer, ImageState::TRANSFER_DST);
+            } else {
+                self.cmd_transition(device, command_buffer, ImageState::TRANSFER_DST, ImageState::SHADER_READ);
+            }
+        })?;
+        Ok(())
+    }
+
+    // barrier moving every mip and layer from `from` to `to`
+    pub fn cmd_transition(&self, device: &Device, command_buffer: vk::CommandBuffer, from: ImageState, to: ImageState) {
+        self.cmd_transition_mips(device, command_buffer, 0, self.desc.mip_levels, from, to);
+    }
+
+    pub fn cmd_transition_mips(&self, device: &Device, command_buffer: vk::CommandBuffer, base_mip: u32, mip_count: u32, from: ImageState, to: ImageState) {
+        let barrier = vk::ImageMemoryBarrier::default()
+            .src_access_mask(from.access)
+            .dst_access_mask(to.access)
+            .old_layout(from.layout)
+            .new_layout(to.layout)
+            .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+            .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+            .image(self.image)
+            .subresource_range(self.desc.subresource_range().base_mip_level(base_mip).level_count(mip_count));
+        unsafe {
+            device.cmd_pipeline_barrier(
+                command_buffer,
+                from.stage,
+                to.stage,
+                vk::DependencyFlags::empty(),
+                &[],
+                &[],
+                std::slice::from_ref(&barrier),
+            );
+        }
+    }
+
+    // Fills mips 1.. by successive linear blits from mip 0, which must be in `from` ; every mip ends up in
+    // ImageState::SHADER_READ. The image needs TRANSFER_SRC and TRANSFER_DST usage.
+    pub fn cmd_generate_mips(&self, device: &Device, command_buffer: vk::CommandBuffer, from: ImageState) {
+        if self.desc.mip_levels <= 1 {
+            self.cmd_transition(device, command_buffer, from, ImageState::SHADER_READ);
+            return;
+        }
+        self.cmd_transition_mips(device, command_buffer, 0, 1, from, ImageState::TRANSFER_SRC);
+        self.cmd_transition_mips(device, command_buffer, 1, self.desc.mip_levels - 1, ImageState::UNDEFINED, ImageState::TRANSFER_DST);
+        let corner = |extent: vk::Extent2D| vk::Offset3D { x: extent.width as i32, y: extent.height as i32, z: 1 };
+        for mip in 1..self.desc.mip_levels {
+            let blit = vk::ImageBlit::default()
+                .src_subresource(self.subresource_layers(mip - 1))
+                .src_offsets([vk::Offset3D::default(), corner(self.desc.mip_extent(mip - 1))])
+                .dst_subresource(self.subresource_layers(mip))
+                .dst_offsets([vk::Offset3D::default(), corner(self.desc.mip_extent(mip))]);
+            unsafe {
+                device.cmd_blit_image(
+                    command_buffer,
+                    self.image,
+                    vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+                    self.image,
+                    vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                    std::slice::from_ref(&blit),
+                    vk::Filter::LINEAR,
+                );
+            }
+            // the mip just written is the source of the next blit
+            self.cmd_transition_mips(device, command_buffer, mip, 1, ImageState::TRANSFER_DST, ImageState::TRANSFER_SRC);
+        }
+        self.cmd_transition(device, command_buffer, ImageState::TRANSFER_SRC, ImageState::SHADER_READ);
+    }
+
+    // View over `mip_count` mips starting at `base_mip`, every layer ; cube images can be viewed as 2D arrays for
+    // storage writes
+    pub fn create_view(&self, vk_app: &VkApp, view_type: vk::ImageViewType, base_mip: u32, mip_count: u32, name: &str) -> MemoryResult<GpuImageView> {
+        let device = vk_app.device();
+        let view_create_info = vk::ImageViewCreateInfo::default()
+            .image(self.image)
+            .view_type(view_type)
+            .format(self.desc.format)
+            .subresource_range(self.desc.subresource_range().base_mip_level(base_mip).level_count(mip_count));
+        let view = unsafe { device.create_image_view(&view_create_info, None)? };
+        vk_app.debug_utils().set_object_name(view, name);
+        Ok(GpuImageView { device: device.clone(), view })
+    }
+
+    // 2D view of mip 0 of array layer `layer`, e.g. to render into one layer
+    pub fn create_layer_view(&self, vk_app: &VkApp, layer: u32, name: &str) -> MemoryResult<GpuImageView> {
+        let device = vk_app.device();
+        let view_create_info = vk::ImageViewCreateInfo::default()
+            .image(self.image)
+            .view_type(vk::ImageViewType::TYPE_2D)
+            .format(self.desc.format)
+            .subresource_range(self.desc.subresource_range().level_count(1).base_array_layer(layer).layer_count(1));
+        let view = unsafe { device.create_image_view(&view_create_info, None)? };
+        vk_app.debug_utils().set_object_name(view, name);
+        Ok(GpuImageView { device: device.clone(), view })
+    }
+
+    // for use in a render graph, starting from (and left in) whatever state the caller tracks
+    pub fn imported(&self) -> ImportedImage {
+        ImportedImage::new(self.image, self.view, self.desc)
+    }
+
+    // GETTERS
+
+    pub fn image(&self) -> vk::Image {
+        self.image
+    }
+
+    pub fn view(&self) -> vk::ImageView {
+        self.view
+    }
+
+    pub fn desc(&self) -> &ImageDesc {
+        &self.desc
+    }
+
+    pub fn format(&self) -> vk::Format {
+        self.desc.format
+    }
+
+    pub fn extent(&self) -> vk::Extent2D {
+        self.desc.extent
+    }
+
+    pub fn samples(&self) -> vk::SampleCountFlags {
+        self.desc.samples
+    }
+
+    pub fn usage(&self) -> vk::ImageUsageFlags {
+        self.usage
+    }
+}
+
+impl GpuImage {
+    fn subresource_layers(&self, mip_level: u32) -> vk::ImageSubresourceLayers {
+        vk::ImageSubresourceLayers::default()
+            .aspect_mask(self.desc.aspect_mask())
+            .mip_level(mip_level)
+            .base_array_layer(0)
+            .layer_count(self.desc.array_layers)
+    }
+}
+
+impl Drop for GpuImage {
+    fn drop(&mut self) {
+        unsafe {
+            self.device.destroy_image_view(self.view, None);
+            self.device.destroy_image(self.image, None);
+            if self.memory != vk::DeviceMemory::null() {
+                self.device.free_memory(self.memory, None);
+            }
+        }
+    }
+}
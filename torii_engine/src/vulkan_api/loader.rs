@@ -0,0 +1,43 @@
+use std::path::Path;
+
+use ash::Entry;
+#[cfg(not(feature = "linked"))]
+use tracing::info;
+#[cfg(feature = "linked")]
+use tracing::warn;
+
+#[cfg(not(feature = "linked"))]
+use super::VkError;
+use super::VkResult;
+
+#[cfg(not(any(feature = "linked", feature = "loaded")))]
+compile_error!("torii_engine needs either the `loaded` or the `linked` feature to reach the vulkan loader");
+
+// Linked at build time, so the loader is known to be there and an explicit path cannot apply
+#[cfg(feature = "linked")]
+pub fn load_entry(loader_path: Option<&Path>) -> VkResult<Entry> {
+    if let Some(loader_path) = loader_path {
+        warn!(target: "torii::vulkan", loader_path = %loader_path.display(), "Vulkan loader path ignored, the loader is linked");
+    }
+    Ok(Entry::linked())
+}
+
+// Loads the system loader (libvulkan.so.1, vulkan-1.dll, libvulkan.dylib ...) or the library at `loader_path`
+#[cfg(not(feature = "linked"))]
+pub fn load_entry(loader_path: Option<&Path>) -> VkResult<Entry> {
+    let entry = unsafe {
+        match loader_path {
+            Some(loader_path) => Entry::load_from(loader_path),
+            None => Entry::load(),
+        }
+    }
+        .map_err(|source| VkError::LoaderNotFoundError {
+            path: loader_path.map(Path::to_path_buf),
+            source,
+        })?;
+    match loader_path {
+        Some(loader_path) => info!(target: "torii::vulkan", loader_path = %loader_path.display(), "Loaded vulkan loader"),
+        None => info!(target: "torii::vulkan", "Loaded system vulkan loader"),
+    }
+    Ok(entry)
+}
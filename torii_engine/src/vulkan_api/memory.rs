@@ -0,0 +1,157 @@
+use std::ptr::NonNull;
+
+use ash::{vk, Device};
+
+use super::commands::one_time_submit;
+use super::{MemoryError, MemoryResult, VkApp};
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum MemoryLocation {
+    // only the GPU reads and writes it, filled through staging uploads
+    DeviceLocal,
+    // persistently mapped and coherent, for data the CPU rewrites every frame
+    HostVisible,
+}
+
+impl MemoryLocation {
+    fn required_flags(&self) -> vk::MemoryPropertyFlags {
+        match self {
+            MemoryLocation::DeviceLocal => vk::MemoryPropertyFlags::empty(),
+            MemoryLocation::HostVisible => vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT,
+        }
+    }
+}
+
+// First memory type allowed by `memory_type_bits` with every `required` flag, preferring DEVICE_LOCAL ones
+pub fn find_memory_type(
+    memory_properties: &vk::PhysicalDeviceMemoryProperties,
+    memory_type_bits: u32,
+    required: vk::MemoryPropertyFlags,
+) -> Option<u32> {
+    let memory_types = memory_properties.memory_types_as_slice();
+    let candidates = || {
+        (0..memory_types.len() as u32).filter(move |&index| {
+            memory_type_bits & (1 << index) != 0 && memory_types[index as usize].property_flags.contains(required)
+        })
+    };
+    candidates()
+        .find(|&index| memory_types[index as usize].property_flags.contains(vk::MemoryPropertyFlags::DEVICE_LOCAL))
+        .or_else(|| candidates().next())
+}
+
+pub(crate) fn allocate_memory(vk_app: &VkApp, requirements: vk::MemoryRequirements, location: MemoryLocation, what: &str) -> MemoryResult<vk::DeviceMemory> {
+    let memory_properties = unsafe {
+        vk_app.instance().get_physical_device_memory_properties(vk_app.physical_device())
+    };
+    let memory_type_index = find_memory_type(&memory_properties, requirements.memory_type_bits, location.required_flags())
+        .ok_or_else(|| MemoryError::NoCompatibleMemoryTypeError(what.to_owned()))?;
+    let allocate_info = vk::MemoryAllocateInfo::default()
+        .allocation_size(requirements.size)
+        .memory_type_index(memory_type_index);
+    Ok(unsafe { vk_app.device().allocate_memory(&allocate_info, None)? })
+}
+
+// A buffer with its own dedicated allocation
+pub struct GpuBuffer {
+    device: Device,
+    buffer: vk::Buffer,
+    memory: vk::DeviceMemory,
+    size: vk::DeviceSize,
+    location: MemoryLocation,
+    mapped: Option<NonNull<u8>>,
+}
+
+impl GpuBuffer {
+    pub fn new(vk_app: &VkApp, size: vk::DeviceSize, usage: vk::BufferUsageFlags, location: MemoryLocation, name: &str) -> MemoryResult<Self> {
+        let device = vk_app.device();
+        let create_info = vk::BufferCreateInfo::default()
+            // vulkan rejects empty buffers, empty meshes still get a valid handle
+            .size(size.max(4))
+            .usage(usage)
+            .sharing_mode(vk::SharingMode::EXCLUSIVE);
+        let buffer = unsafe { device.create_buffer(&create_info, None)? };
+        let mut gpu_buffer = GpuBuffer {
+            device: device.clone(),
+            buffer,
+            memory: vk::DeviceMemory::null(),
+            size,
+            location,
+            mapped: None,
+        };
+
+        let requirements = unsafe { device.get_buffer_memory_requirements(buffer) };
+        gpu_buffer.memory = allocate_memory(vk_app, requirements, location, name)?;
+        unsafe {
+            device.bind_buffer_memory(buffer, gpu_buffer.memory, 0)?;
+            if location == MemoryLocation::HostVisible {
+                let pointer = device.map_memory(gpu_buffer.memory, 0, vk::WHOLE_SIZE, vk::MemoryMapFlags::empty())?;
+                gpu_buffer.mapped = NonNull::new(pointer.cast());
+            }
+        }
+        vk_app.debug_utils().set_object_name(buffer, name);
+        Ok(gpu_buffer)
+    }
+
+    // device local buffer holding `data`, uploaded through a staging buffer before returning
+    pub fn with_data(vk_app: &VkApp, data: &[u8], usage: vk::BufferUsageFlags, name: &str) -> MemoryResult<Self> {
+        let gpu_buffer = Self::new(vk_app, data.len() as vk::DeviceSize, usage | vk::BufferUsageFlags::TRANSFER_DST, MemoryLocation::DeviceLocal, name)?;
+        gpu_buffer.upload(vk_app, 0, data)?;
+        Ok(gpu_buffer)
+    }
+
+    // Copies `data` at `offset` through a staging buffer and waits for the copy ; the buffer needs TRANSFER_DST
+    pub fn upload(&self, vk_app: &VkApp, offset: vk::DeviceSize, data: &[u8]) -> MemoryResult<()> {
+        if data.is_empty() {
+            return Ok(());
+        }
+        let mut staging = Self::new(vk_app, data.len() as vk::DeviceSize, vk::BufferUsageFlags::TRANSFER_SRC, MemoryLocation::HostVisible, "staging buffer")?;
+        staging.write(0, data);
+        let region = vk::BufferCopy::default()
+            .src_offset(0)
+            .dst_offset(offset)
+            .size(data.len() as vk::DeviceSize);
+        one_time_submit(vk_app, |command_buffer| unsafe {
+            vk_app.device().cmd_copy_buffer(command_buffer, staging.buffer, self.buffer, std::slice::from_ref(&region));
+        })?;
+        Ok(())
+    }
+
+    // host visible buffers only
+    pub fn write(&mut self, offset: vk::DeviceSize, data: &[u8]) {
+        let mapped = self.mapped.expect("Buffer is not host visible!");
+        assert!(offset + data.len() as vk::DeviceSize <= self.size, "Write past the end of the buffer!");
+        unsafe {
+            std::ptr::copy_nonoverlapping(data.as_ptr(), mapped.as_ptr().add(offset as usize), data.len());
+        }
+    }
+
+    // GETTERS
+
+    pub fn buffer(&self) -> vk::Buffer {
+        self.buffer
+    }
+
+    pub fn size(&self) -> vk::DeviceSize {
+        self.size
+    }
+
+    pub fn location(&self) -> MemoryLocation {
+        self.location
+    }
+}
+
+impl Drop for GpuBuffer {
+    fn drop(&mut self) {
+        unsafe {
+            self.device.destroy_buffer(self.buffer, None);
+            if self.memory != vk::DeviceMemory::null() {
+                self.device.free_memory(self.memory, None);
+            }
+        }
+    }
+}
+
+// Raw bytes of plain #[repr(C)] data, for buffer uploads and push constants
+pub fn as_bytes<T: Copy>(data: &[T]) -> &[u8] {
+    unsafe { std::slice::from_raw_parts(data.as_ptr().cast(), std::mem::size_of_val(data)) }
+}
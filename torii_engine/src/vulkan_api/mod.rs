@@ -1,9 +1,28 @@
 ﻿pub mod validation;
 pub mod utility;
 pub mod versioning;
+pub mod swapchain;
+pub mod debug_utils;
+pub mod requirements;
+mod error;
+pub use error::*;
+pub mod loader;
+pub mod report;
+pub mod render_graph;
+pub mod shader;
+pub mod memory;
+pub mod image;
+pub mod sampler;
+pub mod render_target;
+pub mod commands;
+pub mod descriptors;
+pub mod pipeline;
+pub mod compute;
 
-use std::ffi::{c_void, CString};
-use std::ptr;
+use std::collections::BTreeSet;
+use std::ffi::{CStr, CString};
+use std::os::raw::c_char;
+use std::path::PathBuf;
 
 use ash::Entry;
 use ash::Instance;
@@ -11,12 +30,19 @@ use ash::vk;
 use ash::Device;
 
 use ash_window;
+use tracing::{debug, info, warn};
+use crate::config::{EngineConfig, RendererConfig};
 use winit::window::Window;
 use winit::raw_window_handle::{HasDisplayHandle, HasWindowHandle};
 
 pub struct VkProp {
     pub vk_app_info: versioning::VkAppInfo,
     pub debug_module_info: Option<validation::DebugModuleProp>,
+    pub renderer_config: RendererConfig,
+    // explicit vulkan loader library, the system one when None (see loader::load_entry)
+    pub loader_path: Option<PathBuf>,
+    // extensions and features asked for by the app, on top of what the engine itself needs
+    pub requirements: requirements::VkRequirements,
 }
 
 impl Default for VkProp {
@@ -24,6 +50,22 @@ impl Default for VkProp {
         VkProp {
             vk_app_info: versioning::VkAppInfo::default(),
             debug_module_info: Some(validation::DebugModuleProp::default()),
+            renderer_config: RendererConfig::default(),
+            loader_path: None,
+            requirements: requirements::VkRequirements::default(),
+        }
+    }
+}
+
+impl From<&EngineConfig> for VkProp {
+    fn from(config: &EngineConfig) -> Self {
+        let (major, minor, patch) = config.app.version;
+        VkProp {
+            vk_app_info: versioning::VkAppInfo::new(versioning::make_api_version(0, major, minor, patch), config.app.name.as_str()),
+            debug_module_info: config.renderer.validation.then(validation::DebugModuleProp::default),
+            renderer_config: config.renderer.clone(),
+            loader_path: config.renderer.loader_path.clone(),
+            requirements: requirements::VkRequirements::default(),
         }
     }
 }
@@ -34,287 +76,710 @@ pub struct VkApp {
     instance: Option<Instance>,
     physical_device: Option<vk::PhysicalDevice>,
     
+    enabled_instance_extensions: BTreeSet<CString>,
+    enabled_device_extensions: BTreeSet<CString>,
+    enabled_features: requirements::DeviceFeatures,
+    // negotiated within VkAppInfo's range ; the device version never exceeds the instance one and decides which
+    // feature structs are valid to chain
+    instance_api_version: u32,
+    device_api_version: u32,
+    device_report: Option<report::DeviceReport>,
+    
+    graphics_queue_family: Option<u32>,
+    present_queue_family: Option<u32>,
     graphics_queue: Option<vk::Queue>,
     present_queue: Option<vk::Queue>,
+    // a dedicated compute family when the device has one (async compute), the graphics family otherwise
+    compute_queue_family: Option<u32>,
+    compute_queue: Option<vk::Queue>,
+    // no surface, no swapchain, no graphics or present queue (see VkApp::new_compute_only)
+    compute_only: bool,
     device: Option<Device>,
     
     surface_loader: Option<ash::khr::surface::Instance>,
-    surface: Option<vk::SurfaceKHR>,
     
     debug_module: Option<validation::DebugModule>,
+    debug_utils_enabled: bool,
+    debug_utils: Option<debug_utils::DebugUtils>,
+    // read by the debug callback chained into instance creation, which also fires during instance destruction
+    instance_messenger_state: Option<Box<validation::DebugMessengerState>>,
 }
 
 impl VkApp {
-    pub fn new(vk_api_prop: Option<VkProp>, window: &Window) -> Self {
+    pub fn from_config(config: &EngineConfig, window: &Window) -> VkResult<Self> {
+        Self::new(Some(VkProp::from(config)), window)
+    }
+    
+    #[tracing::instrument(target = "torii::vulkan", name = "VkApp::new", skip_all)]
+    pub fn new(vk_api_prop: Option<VkProp>, window: &Window) -> VkResult<Self> {
+        Self::build(vk_api_prop, Some(window))
+    }
+    
+    // Headless device for compute work only: no window, no surface extensions and no graphics queue required, so it
+    // also runs on compute-only devices. The graphics and present getters panic in this mode.
+    #[tracing::instrument(target = "torii::vulkan", name = "VkApp::new_compute_only", skip_all)]
+    pub fn new_compute_only(vk_api_prop: Option<VkProp>) -> VkResult<Self> {
+        Self::build(vk_api_prop, None)
+    }
+    
+    fn build(vk_api_prop: Option<VkProp>, window: Option<&Window>) -> VkResult<Self> {
         let vk_prop = vk_api_prop.unwrap_or_default();
 
-        let entry = Entry::linked();
+        let entry = loader::load_entry(vk_prop.loader_path.as_deref())?;
         
         let mut api = VkApp {
             vk_prop,
             entry,
             instance: None,
             physical_device: None,
+            enabled_instance_extensions: BTreeSet::new(),
+            enabled_device_extensions: BTreeSet::new(),
+            enabled_features: requirements::DeviceFeatures::default(),
+            instance_api_version: vk::API_VERSION_1_0,
+            device_api_version: vk::API_VERSION_1_0,
+            device_report: None,
             device: None,
+            graphics_queue_family: None,
+            present_queue_family: None,
             graphics_queue: None,
             present_queue: None,
-            surface: None,
+            compute_queue_family: None,
+            compute_queue: None,
+            compute_only: window.is_none(),
             surface_loader: None,
             debug_module: None,
+            debug_utils_enabled: false,
+            debug_utils: None,
+            instance_messenger_state: None,
         };
         
-        api.attach_instance(window);
-        api.create_surface(window);
-        api.pick_physical_device();
-        api.create_logical_device();
+        api.attach_instance(window)?;
+        
+        // the device is chosen against a probe surface of the first window ; every window then owns its own
+        // surface and swapchain (see swapchain::Swapchain)
+        let probe_surface = window.map(|window| api.create_surface(window));
+        let picked = api.pick_physical_device(probe_surface);
+        if picked.is_ok() {
+            api.create_logical_device(probe_surface);
+        }
+        if let Some(probe_surface) = probe_surface {
+            unsafe {
+                api.surface_loader().destroy_surface(probe_surface, None);
+            }
+        }
+        picked?;
         
-        api
+        Ok(api)
     }
     
-    pub fn attach_instance(&mut self, window: &Window) {
-        if self.vk_prop.debug_module_info.is_some() && validation::check_validation_layer_support(self.vk_prop.debug_module_info.as_ref().unwrap(), &self.entry) == false {
-            panic!("Validation layers requested, but not available!");
+    // without a window no surface extension is enabled (compute-only mode)
+    pub fn attach_instance(&mut self, window: Option<&Window>) -> VkResult<()> {
+        if self.vk_prop.debug_module_info.is_some() && !validation::check_validation_layer_support(self.vk_prop.debug_module_info.as_ref().unwrap(), &self.entry) {
+            return Err(VkError::ValidationLayersUnavailableError);
         }
+        let instance_api_version = self.negotiate_instance_version()?;
 
         let app_name = CString::new(self.vk_prop.vk_app_info.app_name()).unwrap();
         let engine_name = CString::new(self.vk_prop.vk_app_info.engine_name()).unwrap();
         let mut app_info = vk::ApplicationInfo::default()
             .application_version(self.vk_prop.vk_app_info.application_version())
             .engine_version(self.vk_prop.vk_app_info.engine_version())
-            .api_version(self.vk_prop.vk_app_info.api_version());
+            .api_version(instance_api_version);
         app_info.p_application_name = app_name.as_ptr();
         app_info.p_engine_name = engine_name.as_ptr();
 
-        let debug_utils_create_info = validation::DebugModule::populate_debug_messenger_create_info();
-        let required_validation_layer_raw_names: Vec<CString> = 
-            self.vk_prop.debug_module_info.as_ref().unwrap_or(&validation::DebugModuleProp::default())
-            .required_validation_layers
+        let debug_module_info = self.vk_prop.debug_module_info.as_ref();
+        // a separate messenger state covers messages emitted while creating and destroying the instance itself
+        let instance_messenger_state = debug_module_info.map(|debug_module_prop| debug_module_prop.messenger_state());
+        let mut debug_utils_create_info = match (debug_module_info, instance_messenger_state.as_deref()) {
+            (Some(debug_module_prop), Some(messenger_state)) => 
+                Some(validation::DebugModule::populate_debug_messenger_create_info(debug_module_prop, messenger_state)),
+            _ => None,
+        };
+        let required_validation_layer_raw_names: Vec<CString> = debug_module_info
+            .map(|debug_module_prop| debug_module_prop.required_validation_layers.as_slice())
+            .unwrap_or_default()
             .iter()
-            .map(|layer_name| CString::new(*layer_name).unwrap())
+            .map(|layer_name| CString::new(layer_name.as_str()).unwrap())
             .collect();
-        let enable_layer_names: Vec<*const i8> = required_validation_layer_raw_names
+        let enable_layer_names: Vec<*const c_char> = required_validation_layer_raw_names
             .iter()
             .map(|layer_name| layer_name.as_ptr())
             .collect();
         
-        let extension_names = ash_window::enumerate_required_extensions(window.display_handle().unwrap().as_raw()).unwrap();
+        let mut instance_requirements = requirements::VkRequirements::new();
+        if let Some(window) = window {
+            for &extension_name in ash_window::enumerate_required_extensions(window.display_handle().unwrap().as_raw()).unwrap() {
+                instance_requirements.require_instance_extension(unsafe { CStr::from_ptr(extension_name) });
+            }
+        }
+        // debug utils also makes object names and labels show up in capture tools, so it is enabled whenever present
+        if debug_module_info.is_some() {
+            instance_requirements.require_instance_extension(ash::ext::debug_utils::NAME);
+        } else {
+            instance_requirements.request_instance_extension(ash::ext::debug_utils::NAME);
+        }
+        // lists portability implementations (e.g. MoltenVK) among the physical devices ; on a 1.0 instance it also
+        // needs get_physical_device_properties2, which is core from 1.1 on
+        instance_requirements.request_instance_extension(ash::khr::portability_enumeration::NAME);
+        if instance_api_version < versioning::API_VERSION_1_1 {
+            instance_requirements.request_instance_extension(ash::khr::get_physical_device_properties2::NAME);
+        }
+        
+        let validation_features = debug_module_info
+            .map(|debug_module_prop| debug_module_prop.validation_features)
+            .filter(|validation_features| validation_features.any());
+        let validation_feature_mechanism = match (debug_module_info, validation_features) {
+            (Some(debug_module_prop), Some(_)) => validation::validation_feature_mechanism(debug_module_prop, &self.entry),
+            _ => validation::ValidationFeatureMechanism::Unsupported,
+        };
+        let validation_layer_settings = validation_features.as_ref().map(validation::ValidationLayerSettings::new);
+        let mut layer_settings_create_info = None;
+        let mut validation_features_create_info = None;
+        match (validation_feature_mechanism, validation_layer_settings.as_ref()) {
+            (validation::ValidationFeatureMechanism::LayerSettings, Some(settings)) => {
+                instance_requirements.require_instance_extension(ash::ext::layer_settings::NAME);
+                layer_settings_create_info = Some(settings.layer_settings_create_info());
+            },
+            (validation::ValidationFeatureMechanism::ValidationFeatures, Some(settings)) => {
+                instance_requirements.require_instance_extension(ash::ext::validation_features::NAME);
+                validation_features_create_info = Some(settings.validation_features_create_info());
+            },
+            (validation::ValidationFeatureMechanism::Unsupported, Some(_)) => {
+                warn!(target: "torii::vulkan", "Validation features requested, but the validation layer supports neither VK_EXT_layer_settings nor VK_EXT_validation_features");
+            },
+            _ => (),
+        }
+        instance_requirements.merge(&self.vk_prop.requirements);
+        
+        // extensions provided by the enabled layers count as available too
+        let mut available_extensions = requirements::extension_names(&unsafe {
+            self.entry.enumerate_instance_extension_properties(None).unwrap_or_default()
+        });
+        for layer_name in required_validation_layer_raw_names.iter() {
+            available_extensions.extend(requirements::extension_names(&unsafe {
+                self.entry.enumerate_instance_extension_properties(Some(layer_name)).unwrap_or_default()
+            }));
+        }
+        let enabled_instance_extensions = match instance_requirements.negotiate_instance_extensions(&available_extensions) {
+            Ok(enabled_instance_extensions) => enabled_instance_extensions,
+            Err(missing) => return Err(VkError::MissingInstanceExtensionsError(missing)),
+        };
+        for extension_name in enabled_instance_extensions.iter() {
+            debug!(target: "torii::vulkan", extension_name = %extension_name.to_string_lossy(), "Enabling instance extension");
+        }
+        self.debug_utils_enabled = enabled_instance_extensions.contains(ash::ext::debug_utils::NAME);
+        let extension_names: Vec<*const c_char> = enabled_instance_extensions
+            .iter()
+            .map(|extension_name| extension_name.as_ptr())
+            .collect();
+        let instance_create_flags = if enabled_instance_extensions.contains(ash::khr::portability_enumeration::NAME) {
+            vk::InstanceCreateFlags::ENUMERATE_PORTABILITY_KHR
+        } else {
+            vk::InstanceCreateFlags::empty()
+        };
         
-        let mut create_info = vk::InstanceCreateInfo::default().flags(vk::InstanceCreateFlags::empty());
-        create_info.p_next = if self.vk_prop.debug_module_info.is_some() { &debug_utils_create_info as *const vk::DebugUtilsMessengerCreateInfoEXT as *const c_void} else { ptr::null() };
-        create_info.p_application_info = &app_info;
-        create_info.pp_enabled_layer_names = if self.vk_prop.debug_module_info.is_some() { enable_layer_names.as_ptr() } else { ptr::null() };
-        create_info.enabled_layer_count = if self.vk_prop.debug_module_info.is_some() { enable_layer_names.len() } else { 0 } as u32;
-        create_info.pp_enabled_extension_names = extension_names.as_ptr();
-        create_info.enabled_extension_count = extension_names.len() as u32;
+        let mut create_info = vk::InstanceCreateInfo::default()
+            .flags(instance_create_flags)
+            .application_info(&app_info)
+            .enabled_layer_names(&enable_layer_names)
+            .enabled_extension_names(&extension_names);
+        if let Some(debug_utils_create_info) = debug_utils_create_info.as_mut() {
+            create_info = create_info.push_next(debug_utils_create_info);
+        }
+        if let Some(layer_settings_create_info) = layer_settings_create_info.as_mut() {
+            create_info = create_info.push_next(layer_settings_create_info);
+        }
+        if let Some(validation_features_create_info) = validation_features_create_info.as_mut() {
+            create_info = create_info.push_next(validation_features_create_info);
+        }
 
         let instance = unsafe {
-            self.entry.create_instance(&create_info, None).expect("Failed to create instance!")
+            self.entry.create_instance(&create_info, None).map_err(VkError::InstanceCreationError)?
         };
+        info!(target: "torii::vulkan", api_version = versioning::version_string(instance_api_version), "Created instance");
         
+        self.surface_loader = Some(ash::khr::surface::Instance::new(&self.entry, &instance));
+        self.debug_module = debug_module_info
+            .map(|debug_module_prop| validation::DebugModule::new(&self.entry, &instance, debug_module_prop));
+        self.instance_messenger_state = instance_messenger_state;
+        self.enabled_instance_extensions = enabled_instance_extensions;
+        self.instance_api_version = instance_api_version;
         self.instance = Some(instance);
-        self.debug_module = Some(validation::DebugModule::new(&self.entry, self.instance.as_ref().unwrap()));
+        Ok(())
     }
     
-    pub fn create_surface(&mut self, window: &Window) {
-        let surface = unsafe {
+    pub fn create_surface(&self, window: &Window) -> vk::SurfaceKHR {
+        unsafe {
             ash_window::create_surface(
                 &self.entry, 
-                self.instance.as_ref().unwrap(),
+                self.instance(),
                 window.display_handle().unwrap().as_raw(),
                 window.window_handle().unwrap().as_raw(),
                 None
             )
-        }.unwrap();
-        let surface_loader = ash::khr::surface::Instance::new(&self.entry, self.instance.as_ref().unwrap());
-        
-        self.surface = Some(surface);
-        self.surface_loader = Some(surface_loader);
+        }.expect("Failed to create window surface!")
     }
     
-    pub fn pick_physical_device(&mut self) {
-        unsafe {
-            let physical_devices = self.instance.as_ref().unwrap().enumerate_physical_devices().unwrap();
+    // `surface` is None in compute-only mode, where neither graphics nor present support is required
+    pub fn pick_physical_device(&mut self, surface: Option<vk::SurfaceKHR>) -> VkResult<()> {
+        let device_requirements = self.device_requirements();
+        let physical_devices = unsafe { self.instance().enumerate_physical_devices().unwrap() };
+        
+        info!(target: "torii::vulkan", device_count = physical_devices.len(), "Enumerated devices (GPU) with Vulkan support");
+        
+        if physical_devices.is_empty() {
+            return Err(VkError::NoPhysicalDeviceError);
+        }
+        let min_api_version = versioning::major_minor(self.vk_prop.vk_app_info.min_api_version());
+
+        // devices are ranked by type first, then by how many optional extensions and features they support
+        let mut chosen_device: Option<(vk::PhysicalDevice, vk::PhysicalDeviceProperties, u32, requirements::NegotiatedDevice)> = None;
+        let mut chosen_rank = (0, 0);
+        let mut rejected_devices = vec![];
+        
+        for device in physical_devices {
+            let (device_properties, device_queue_families) = self.get_physical_device_properties(device);
+            Self::log_physical_device(&device_properties, &device_queue_families);
             
-            println!("{} devices (GPU) found with Vulkan Support", physical_devices.len());
+            let device_api_version = versioning::major_minor(device_properties.api_version).min(self.instance_api_version);
+            let available_extensions = requirements::extension_names(&unsafe {
+                self.instance().enumerate_device_extension_properties(device).unwrap_or_default()
+            });
+            let available_features = self.get_physical_device_features(device, device_api_version, &available_extensions);
             
-            if physical_devices.len() == 0 {
-                panic!("Failed to find GPUs (discrete or integrated) with Vulkan support!");
+            let mut rejection_reasons = vec![];
+            if device_api_version < min_api_version {
+                rejection_reasons.push(format!(
+                    "api version {} below the minimum {}",
+                    versioning::version_string(device_properties.api_version),
+                    versioning::version_string(min_api_version),
+                ));
             }
-
-            let mut chosen_device: Option<vk::PhysicalDevice>= None;
-            let mut chosen_device_id = 0;
-            
-            for device in physical_devices {
-                let (
-                    device_properties, 
-                    device_features, 
-                    device_queue_families
-                ) = self.get_physical_device_properties(device);
-                
-                if Self::check_physical_device(device_properties, device_features, device_queue_families) {
-                    if chosen_device.is_none() {
-                        chosen_device = Some(device);
-                        chosen_device_id = device_properties.device_id;
-                    }
+            if let Some(surface) = surface {
+                if Self::find_graphics_queue_family(&device_queue_families).is_none() {
+                    rejection_reasons.push(String::from("no graphics queue family"));
+                }
+                if self.find_present_queue_family(device, surface, &device_queue_families).is_none() {
+                    rejection_reasons.push(String::from("no present queue family"));
                 }
             }
-            
-            match chosen_device {
-                None => panic!("Failed to find a suitable GPU!"),
-                Some(_) => {
-                    println!("Chose physical device with id: {}", chosen_device_id);
-                    self.physical_device = chosen_device;
+            if Self::find_compute_queue_family(&device_queue_families).is_none() {
+                rejection_reasons.push(String::from("no compute queue family"));
+            }
+            let negotiated_device = match device_requirements.negotiate_device(&available_extensions, &available_features) {
+                Ok(negotiated_device) => Some(negotiated_device),
+                Err(missing) => {
+                    rejection_reasons.extend(missing);
+                    None
                 },
+            };
+            
+            let Some(mut negotiated_device) = negotiated_device.filter(|_| rejection_reasons.is_empty()) else {
+                let device_name = utility::vk_to_string(&device_properties.device_name);
+                info!(
+                    target: "torii::vulkan",
+                    device_id = device_properties.device_id,
+                    reasons = rejection_reasons.join(", "),
+                    "Rejected physical device"
+                );
+                rejected_devices.push(format!("{device_name}: {}", rejection_reasons.join(", ")));
+                continue;
+            };
+            // portability implementations must have the subset extension enabled whenever they expose it
+            if available_extensions.contains(ash::khr::portability_subset::NAME) {
+                negotiated_device.enabled_extensions.insert(ash::khr::portability_subset::NAME.to_owned());
+            }
+            
+            let rank = (Self::device_type_rank(device_properties.device_type), negotiated_device.optional_count);
+            if chosen_device.is_none() || rank > chosen_rank {
+                chosen_rank = rank;
+                chosen_device = Some((device, device_properties, device_api_version, negotiated_device));
             }
         }
+        
+        let Some((device, device_properties, device_api_version, negotiated_device)) = chosen_device else {
+            return Err(VkError::NoSuitablePhysicalDeviceError(rejected_devices));
+        };
+        info!(
+            target: "torii::vulkan",
+            device_id = device_properties.device_id,
+            device_name = utility::vk_to_string(&device_properties.device_name),
+            api_version = versioning::version_string(device_api_version),
+            portability_subset = negotiated_device.enabled_extensions.contains(ash::khr::portability_subset::NAME),
+            optional_supported = negotiated_device.optional_count,
+            "Chose physical device"
+        );
+        self.device_report = Some(report::DeviceReport::new(self.instance(), device, self.instance_api_version));
+        self.physical_device = Some(device);
+        self.device_api_version = device_api_version;
+        self.enabled_device_extensions = negotiated_device.enabled_extensions;
+        self.enabled_features = negotiated_device.enabled_features;
+        Ok(())
     }
     
-    fn get_physical_device_properties(&mut self, physical_device: vk::PhysicalDevice) -> (vk::PhysicalDeviceProperties, vk::PhysicalDeviceFeatures, Vec<vk::QueueFamilyProperties>) {
-        unsafe {
-            (
-                self.instance.as_ref().unwrap().get_physical_device_properties(physical_device),
-                self.instance.as_ref().unwrap().get_physical_device_features(physical_device),
-                self.instance.as_ref().unwrap().get_physical_device_queue_family_properties(physical_device),
-            )
+    // Panics with the first validation error reported since the last call when the debug module was set up with
+    // ValidationErrorAction::Panic ; does nothing otherwise
+    pub fn check_validation_errors(&self) {
+        let instance_error = self.instance_messenger_state.as_ref().and_then(|messenger_state| messenger_state.take_error());
+        let error = instance_error.or_else(|| self.debug_module.as_ref().and_then(validation::DebugModule::take_error));
+        if let Some(error) = error {
+            panic!("{error}");
+        }
+    }
+
+    // GETTERS (panic if called before the corresponding object was created)
+    
+    pub fn vk_prop(&self) -> &VkProp {
+        &self.vk_prop
+    }
+    
+    pub fn entry(&self) -> &Entry {
+        &self.entry
+    }
+    
+    pub fn instance(&self) -> &Instance {
+        self.instance.as_ref().expect("Vulkan instance has not been created!")
+    }
+    
+    pub fn physical_device(&self) -> vk::PhysicalDevice {
+        self.physical_device.expect("Physical device has not been picked!")
+    }
+    
+    pub fn device(&self) -> &Device {
+        self.device.as_ref().expect("Logical device has not been created!")
+    }
+    
+    pub fn surface_loader(&self) -> &ash::khr::surface::Instance {
+        self.surface_loader.as_ref().expect("Surface loader has not been created!")
+    }
+    
+    // always usable, naming and labels silently do nothing when VK_EXT_debug_utils is not enabled
+    pub fn debug_utils(&self) -> &debug_utils::DebugUtils {
+        self.debug_utils.as_ref().expect("Logical device has not been created!")
+    }
+    
+    // the graphics and present getters panic in compute-only mode
+    pub fn graphics_queue_family(&self) -> u32 {
+        self.graphics_queue_family.expect("Logical device has not been created!")
+    }
+    
+    pub fn present_queue_family(&self) -> u32 {
+        self.present_queue_family.expect("Logical device has not been created!")
+    }
+    
+    pub fn graphics_queue(&self) -> vk::Queue {
+        self.graphics_queue.expect("Logical device has not been created!")
+    }
+    
+    pub fn present_queue(&self) -> vk::Queue {
+        self.present_queue.expect("Logical device has not been created!")
+    }
+    
+    pub fn compute_queue_family(&self) -> u32 {
+        self.compute_queue_family.expect("Logical device has not been created!")
+    }
+    
+    pub fn compute_queue(&self) -> vk::Queue {
+        self.compute_queue.expect("Logical device has not been created!")
+    }
+    
+    // compute submissions then overlap graphics work and must be synchronized with semaphores
+    pub fn has_async_compute(&self) -> bool {
+        self.compute_queue_family.is_some() && self.compute_queue_family != self.graphics_queue_family && !self.compute_only
+    }
+    
+    pub fn is_compute_only(&self) -> bool {
+        self.compute_only
+    }
+    
+    pub fn enabled_instance_extensions(&self) -> &BTreeSet<CString> {
+        &self.enabled_instance_extensions
+    }
+    
+    pub fn enabled_device_extensions(&self) -> &BTreeSet<CString> {
+        &self.enabled_device_extensions
+    }
+    
+    pub fn is_device_extension_enabled(&self, name: &CStr) -> bool {
+        self.enabled_device_extensions.contains(name)
+    }
+    
+    pub fn enabled_features(&self) -> &requirements::DeviceFeatures {
+        &self.enabled_features
+    }
+    
+    // e.g. vk_app.is_feature_enabled(device_feature!(vulkan13.dynamic_rendering))
+    pub fn is_feature_enabled(&self, feature: requirements::FeatureRequest) -> bool {
+        self.enabled_features.is_enabled(feature)
+    }
+    
+    // capabilities of the chosen device, e.g. to attach to a crash or bug report
+    pub fn device_report(&self) -> &report::DeviceReport {
+        self.device_report.as_ref().expect("Physical device has not been picked!")
+    }
+    
+    pub fn instance_api_version(&self) -> u32 {
+        self.instance_api_version
+    }
+    
+    pub fn device_api_version(&self) -> u32 {
+        self.device_api_version
+    }
+    
+    // highest version both the loader and VkAppInfo's range allow ; a 1.0 loader has no vkEnumerateInstanceVersion
+    fn negotiate_instance_version(&self) -> VkResult<u32> {
+        let min_api_version = versioning::major_minor(self.vk_prop.vk_app_info.min_api_version());
+        let max_api_version = versioning::major_minor(self.vk_prop.vk_app_info.max_api_version());
+        if min_api_version > max_api_version {
+            return Err(VkError::InvalidApiVersionRangeError { min: min_api_version, max: max_api_version });
+        }
+        
+        let loader_version = unsafe { self.entry.try_enumerate_instance_version() }
+            .ok()
+            .flatten()
+            .unwrap_or(versioning::API_VERSION_1_0);
+        debug!(target: "torii::vulkan", loader_version = versioning::version_string(loader_version), "Queried instance version");
+        if versioning::major_minor(loader_version) < min_api_version {
+            return Err(VkError::UnsupportedInstanceVersionError { required: min_api_version, available: loader_version });
+        }
+        Ok(versioning::major_minor(loader_version).min(max_api_version))
+    }
+    
+    // the engine's own needs, merged with what the app asked for in VkProp::requirements
+    fn device_requirements(&self) -> requirements::VkRequirements {
+        let mut device_requirements = requirements::VkRequirements::new();
+        if !self.compute_only {
+            device_requirements.require_device_extension(ash::khr::swapchain::NAME);
+        }
+        // the render graph records its barriers with vkCmdPipelineBarrier2
+        device_requirements.request_feature(crate::device_feature!(vulkan13.synchronization2));
+        // graphics pipelines render without render pass objects
+        device_requirements.request_feature(crate::device_feature!(vulkan13.dynamic_rendering));
+        // material textures are filtered anisotropically when the device allows it
+        device_requirements.request_feature(crate::device_feature!(core.sampler_anisotropy));
+        // shadow cascades clamp the casters in front of their near plane instead of clipping them
+        device_requirements.request_feature(crate::device_feature!(core.depth_clamp));
+        // meshlet rendering when available, the classic vertex pipeline otherwise (see renderer::MeshRenderer) ;
+        // the mesh stages are SPIR-V 1.4, core from vulkan 1.2 on
+        if self.vk_prop.renderer_config.mesh_shading && !self.compute_only {
+            device_requirements
+                .request_device_extension(ash::ext::mesh_shader::NAME)
+                .request_device_extension(ash::khr::spirv_1_4::NAME)
+                .request_device_extension(ash::khr::shader_float_controls::NAME)
+                .request_feature(crate::device_feature!(mesh_shader.task_shader))
+                .request_feature(crate::device_feature!(mesh_shader.mesh_shader));
+        }
+        
+        let validation_features = self.vk_prop.debug_module_info
+            .as_ref()
+            .map(|debug_module_prop| debug_module_prop.validation_features)
+            .unwrap_or_default();
+        // GPU-assisted validation instruments shaders with storage writes
+        if validation_features.gpu_assisted {
+            device_requirements
+                .request_feature(crate::device_feature!(core.fragment_stores_and_atomics))
+                .request_feature(crate::device_feature!(core.vertex_pipeline_stores_and_atomics));
         }
+        if validation_features.debug_printf {
+            device_requirements.request_device_extension(ash::khr::shader_non_semantic_info::NAME);
+        }
+        
+        device_requirements.merge(&self.vk_prop.requirements);
+        device_requirements
     }
     
-    fn get_current_physical_device_properties(&mut self) -> (vk::PhysicalDeviceProperties, vk::PhysicalDeviceFeatures, Vec<vk::QueueFamilyProperties>) {
+    fn get_physical_device_properties(&self, physical_device: vk::PhysicalDevice) -> (vk::PhysicalDeviceProperties, Vec<vk::QueueFamilyProperties>) {
         unsafe {
             (
-                self.instance.as_ref().unwrap().get_physical_device_properties(self.physical_device.unwrap()),
-                self.instance.as_ref().unwrap().get_physical_device_features(self.physical_device.unwrap()),
-                self.instance.as_ref().unwrap().get_physical_device_queue_family_properties(self.physical_device.unwrap()),
+                self.instance().get_physical_device_properties(physical_device),
+                self.instance().get_physical_device_queue_family_properties(physical_device),
             )
         }
     }
     
-    fn check_physical_device(device_properties: vk::PhysicalDeviceProperties, device_features: vk::PhysicalDeviceFeatures, device_queue_families: Vec<vk::QueueFamilyProperties>) -> bool {
+    // vulkan 1.0 devices only expose the core features, the others go through the features2 chain
+    fn get_physical_device_features(&self, physical_device: vk::PhysicalDevice, device_api_version: u32, device_extensions: &BTreeSet<CString>) -> requirements::DeviceFeatures {
+        let mut device_features = requirements::DeviceFeatures::default();
+        if device_api_version >= vk::API_VERSION_1_1 {
+            device_features.with_chain(device_api_version, device_extensions, |features2| unsafe {
+                self.instance().get_physical_device_features2(physical_device, features2);
+            });
+        } else {
+            device_features.core = unsafe { self.instance().get_physical_device_features(physical_device) };
+        }
+        device_features
+    }
+    
+    fn device_type_rank(device_type: vk::PhysicalDeviceType) -> u32 {
+        match device_type {
+            vk::PhysicalDeviceType::DISCRETE_GPU => 4,
+            vk::PhysicalDeviceType::INTEGRATED_GPU => 3,
+            vk::PhysicalDeviceType::VIRTUAL_GPU => 2,
+            vk::PhysicalDeviceType::CPU => 1,
+            _ => 0,
+        }
+    }
+    
+    fn log_physical_device(device_properties: &vk::PhysicalDeviceProperties, device_queue_families: &[vk::QueueFamilyProperties]) {
         let device_type = match device_properties.device_type {
             vk::PhysicalDeviceType::CPU => "Cpu",
             vk::PhysicalDeviceType::INTEGRATED_GPU => "Integrated GPU",
             vk::PhysicalDeviceType::DISCRETE_GPU => "Discrete GPU",
             vk::PhysicalDeviceType::VIRTUAL_GPU => "Virtual GPU",
-            vk::PhysicalDeviceType::OTHER => "Unknown",
-            _ => panic!(),
+            _ => "Unknown",
         };
         let device_name = utility::vk_to_string(&device_properties.device_name);
-        let device_id = device_properties.device_id;
-        
-        println!("\tDevice Name: {}, id: {}, type: {}", device_name, device_id, device_type);
-        
-        let api_major = vk::api_version_major(device_properties.api_version);
-        let api_minor = vk::api_version_minor(device_properties.api_version);
-        let api_patch = vk::api_version_patch(device_properties.api_version);
+        let api_version = versioning::version_string(device_properties.api_version);
         
-        println!("\tAPI Version: {}.{}.{}", api_major, api_minor, api_patch);
-        
-        let support_checker = |is: bool| -> &str {match is {true => "Supported", false => "Unsupported"}};
-        println!("\tSupport Queue Family Count: {}", device_queue_families.len());
-        println!("\t\tQueue Count\t | Graphics, Compute, Transfer, Sparse Binding");
-        for queue_family in device_queue_families.iter() {
-            let is_graphics = queue_family.queue_flags.contains(vk::QueueFlags::GRAPHICS);
-            let is_compute = queue_family.queue_flags.contains(vk::QueueFlags::COMPUTE);
-            let is_transfer = queue_family.queue_flags.contains(vk::QueueFlags::TRANSFER);
-            let is_sparse = queue_family.queue_flags.contains(vk::QueueFlags::SPARSE_BINDING);
-            println!("\t\t{}\t         | {}, {}, {}, {}", queue_family.queue_count, 
-                     support_checker(is_graphics), support_checker(is_compute),
-                     support_checker(is_transfer), support_checker(is_sparse),
-            );
-        }
-        println!("\t\tADDITIONAL FEATURES ---------------------------------------------------------------");
-        println!("\t\tQueue Count\t | Protected Binding, Video Encode, Video Decode, Optical Flow (Nvidia)");
-        for &queue_family in device_queue_families.iter() {
-            let is_protected = queue_family.queue_flags.contains(vk::QueueFlags::PROTECTED);
-            let is_encode = queue_family.queue_flags.contains(vk::QueueFlags::VIDEO_ENCODE_KHR);
-            let is_decode = queue_family.queue_flags.contains(vk::QueueFlags::VIDEO_DECODE_KHR);
-            let is_optical = queue_family.queue_flags.contains(vk::QueueFlags::OPTICAL_FLOW_NV);
-            println!("\t\t{}\t         | {}, {}, {}, {}", queue_family.queue_count,
-                     support_checker(is_protected), support_checker(is_encode),
-                     support_checker(is_decode), support_checker(is_optical)
+        info!(
+            target: "torii::vulkan",
+            device_name, device_id = device_properties.device_id, device_type, api_version,
+            queue_family_count = device_queue_families.len(),
+            "Found physical device"
+        );
+        for (queue_family_index, queue_family) in device_queue_families.iter().enumerate() {
+            debug!(
+                target: "torii::vulkan",
+                device_id = device_properties.device_id,
+                queue_family_index,
+                queue_count = queue_family.queue_count,
+                graphics = queue_family.queue_flags.contains(vk::QueueFlags::GRAPHICS),
+                compute = queue_family.queue_flags.contains(vk::QueueFlags::COMPUTE),
+                transfer = queue_family.queue_flags.contains(vk::QueueFlags::TRANSFER),
+                sparse_binding = queue_family.queue_flags.contains(vk::QueueFlags::SPARSE_BINDING),
+                protected = queue_family.queue_flags.contains(vk::QueueFlags::PROTECTED),
+                video_encode = queue_family.queue_flags.contains(vk::QueueFlags::VIDEO_ENCODE_KHR),
+                video_decode = queue_family.queue_flags.contains(vk::QueueFlags::VIDEO_DECODE_KHR),
+                optical_flow = queue_family.queue_flags.contains(vk::QueueFlags::OPTICAL_FLOW_NV),
+                "Queue family"
             );
         }
-        
-        let geometry_shader_support = device_features.geometry_shader == 1;
-        let tesselation_shader_support = device_features.tessellation_shader == 1;
-        // etc....
-        println!("\tGeometry Shader Support: {}", support_checker(geometry_shader_support));
-        println!("\tTesselation Shader Support: {}", support_checker(tesselation_shader_support));
-
-        device_properties.device_type == vk::PhysicalDeviceType::DISCRETE_GPU && geometry_shader_support
     }
     
-    fn create_logical_device(&mut self) {
-        let (_, _, device_queue_families) = self.get_current_physical_device_properties();
+    fn create_logical_device(&mut self, surface: Option<vk::SurfaceKHR>) {
+        let (_, device_queue_families) = self.get_physical_device_properties(self.physical_device());
         
-        let mut queue_create_infos: Vec<vk::DeviceQueueCreateInfo> = Vec::new();
-
-        let graphics_queue_family_index = Self::find_graphics_queue_family(&device_queue_families);
-        let mut graphics_queue_info = vk::DeviceQueueCreateInfo::default()
-            .queue_family_index(graphics_queue_family_index.unwrap())
-            .queue_priorities(&[1.0f32]);
-        graphics_queue_info.queue_count = 1;
-        queue_create_infos.push(graphics_queue_info);
+        let graphics_queue_family_index = surface.map(|_| {
+            Self::find_graphics_queue_family(&device_queue_families).expect("Chosen device has no graphics queue family!")
+        });
+        let present_queue_family_index = surface.map(|surface| {
+            self.find_present_queue_family(self.physical_device(), surface, &device_queue_families)
+                .expect("Chosen device has no present queue family!")
+        });
+        let compute_queue_family_index = Self::find_compute_queue_family(&device_queue_families)
+            .expect("Chosen device has no compute queue family!");
         
-        let present_queue_family_index = self.find_present_queue_family(&device_queue_families);
-        let mut present_queue_info = vk::DeviceQueueCreateInfo::default()
-            .queue_family_index(present_queue_family_index.unwrap())
-            .queue_priorities(&[1.0f32]);
-        present_queue_info.queue_count = 1;
-        queue_create_infos.push(present_queue_info);
+        // a queue family may only be requested once, graphics and present are usually the same family
+        let mut unique_queue_families: Vec<u32> = vec![];
+        for queue_family_index in [graphics_queue_family_index, present_queue_family_index, Some(compute_queue_family_index)].into_iter().flatten() {
+            if !unique_queue_families.contains(&queue_family_index) {
+                unique_queue_families.push(queue_family_index);
+            }
+        }
         
+        let queue_priorities = [1.0f32];
+        let queue_create_infos: Vec<vk::DeviceQueueCreateInfo> = unique_queue_families
+            .iter()
+            .map(|&queue_family_index| {
+                vk::DeviceQueueCreateInfo::default()
+                    .queue_family_index(queue_family_index)
+                    .queue_priorities(&queue_priorities)
+            })
+            .collect();
         
-        let physical_device_features_to_use = vk::PhysicalDeviceFeatures::default();
+        let enabled_extension_names: Vec<*const c_char> = self.enabled_device_extensions
+            .iter()
+            .map(|extension_name| extension_name.as_ptr())
+            .collect();
+        for extension_name in self.enabled_device_extensions.iter() {
+            debug!(target: "torii::vulkan", extension_name = %extension_name.to_string_lossy(), "Enabling device extension");
+        }
         
-        let mut device_create_info = vk::DeviceCreateInfo::default()
+        let device_create_info = vk::DeviceCreateInfo::default()
             .queue_create_infos(&queue_create_infos)
-            .enabled_features(&physical_device_features_to_use);
-        device_create_info.queue_create_info_count = queue_create_infos.len() as u32;
-        device_create_info.enabled_extension_count = 0;
+            .enabled_extension_names(&enabled_extension_names);
         
-        let logical_device = unsafe {
-            self.instance.as_ref().unwrap()
-                .create_device(self.physical_device.unwrap(), &device_create_info, None)
-                .expect("Failed to create logical device!")
-        };
+        let mut enabled_features = self.enabled_features.clone();
+        let logical_device = if self.device_api_version >= vk::API_VERSION_1_1 {
+            enabled_features.with_chain(self.device_api_version, &self.enabled_device_extensions, |features2| unsafe {
+                self.instance()
+                    .create_device(self.physical_device(), &device_create_info.push_next(features2), None)
+            })
+        } else {
+            unsafe {
+                self.instance()
+                    .create_device(self.physical_device(), &device_create_info.enabled_features(&enabled_features.core), None)
+            }
+        }.expect("Failed to create logical device!");
         
-        let graphics_queue = unsafe {
-            logical_device
-                .get_device_queue(graphics_queue_family_index.unwrap(), 0)
+        let (graphics_queue, present_queue, compute_queue) = unsafe {
+            (
+                graphics_queue_family_index.map(|index| logical_device.get_device_queue(index, 0)),
+                present_queue_family_index.map(|index| logical_device.get_device_queue(index, 0)),
+                logical_device.get_device_queue(compute_queue_family_index, 0),
+            )
         };
         
+        let debug_utils = debug_utils::DebugUtils::new(self.instance(), &logical_device, self.debug_utils_enabled);
+        debug_utils.set_object_name(logical_device.handle(), "torii device");
+        if let Some(graphics_queue) = graphics_queue {
+            debug_utils.set_object_name(graphics_queue, "graphics queue");
+        }
+        if let Some(present_queue) = present_queue.filter(|&present_queue| Some(present_queue) != graphics_queue) {
+            debug_utils.set_object_name(present_queue, "present queue");
+        }
+        if Some(compute_queue) != graphics_queue {
+            debug_utils.set_object_name(compute_queue, "compute queue");
+        }
+        info!(
+            target: "torii::vulkan",
+            compute_queue_family = compute_queue_family_index,
+            async_compute = Some(compute_queue_family_index) != graphics_queue_family_index,
+            compute_only = self.compute_only,
+            "Created logical device"
+        );
+        
+        self.debug_utils = Some(debug_utils);
         self.device = Some(logical_device);
-        self.graphics_queue = Some(graphics_queue);
+        self.graphics_queue_family = graphics_queue_family_index;
+        self.present_queue_family = present_queue_family_index;
+        self.compute_queue_family = Some(compute_queue_family_index);
+        self.graphics_queue = graphics_queue;
+        self.present_queue = present_queue;
+        self.compute_queue = Some(compute_queue);
     }
     
-    fn find_graphics_queue_family(device_queue_families: &Vec<vk::QueueFamilyProperties>) -> Option<u32> {
-        let mut graphics_family_index = None;
-        let mut index = 0;
-        for &queue_family in device_queue_families {
-            if queue_family.queue_count > 0 && queue_family.queue_flags.contains(vk::QueueFlags::GRAPHICS) {
-                graphics_family_index = Some(index);
-                break;
-            }
-            index += 1;
-        }
-        graphics_family_index
+    fn find_graphics_queue_family(device_queue_families: &[vk::QueueFamilyProperties]) -> Option<u32> {
+        device_queue_families
+            .iter()
+            .position(|queue_family| queue_family.queue_count > 0 && queue_family.queue_flags.contains(vk::QueueFlags::GRAPHICS))
+            .map(|index| index as u32)
     }
 
-    fn find_present_queue_family(&self, device_queue_families: &Vec<vk::QueueFamilyProperties>) -> Option<u32> {
-        let mut family_index = None;
-        let mut index = 0;
-        for &queue_family in device_queue_families {
-            let is_present_support = unsafe {
-                self.surface_loader.as_ref().unwrap().get_physical_device_surface_support(self.physical_device.unwrap(), index, self.surface.unwrap()).unwrap()
-            };
-            
-            if queue_family.queue_count > 0 && is_present_support {
-                family_index = Some(index);
-                break;
-            }
-            index += 1;
-        }
-        family_index
+    // a family with compute but no graphics runs independently of the graphics queue (async compute)
+    fn find_compute_queue_family(device_queue_families: &[vk::QueueFamilyProperties]) -> Option<u32> {
+        let has_compute = |queue_family: &vk::QueueFamilyProperties| queue_family.queue_count > 0 && queue_family.queue_flags.contains(vk::QueueFlags::COMPUTE);
+        device_queue_families
+            .iter()
+            .position(|queue_family| has_compute(queue_family) && !queue_family.queue_flags.contains(vk::QueueFlags::GRAPHICS))
+            .or_else(|| device_queue_families.iter().position(has_compute))
+            .map(|index| index as u32)
+    }
+
+    fn find_present_queue_family(&self, physical_device: vk::PhysicalDevice, surface: vk::SurfaceKHR, device_queue_families: &[vk::QueueFamilyProperties]) -> Option<u32> {
+        device_queue_families
+            .iter()
+            .enumerate()
+            .position(|(index, queue_family)| {
+                let is_present_support = unsafe {
+                    self.surface_loader()
+                        .get_physical_device_surface_support(physical_device, index as u32, surface)
+                        .unwrap_or(false)
+                };
+                queue_family.queue_count > 0 && is_present_support
+            })
+            .map(|index| index as u32)
     }
 }
 
@@ -322,6 +787,7 @@ impl Drop for VkApp {
     fn drop(&mut self) {
         unsafe {
             if let Some(logical_device) = self.device.take() {
+                let _ = logical_device.device_wait_idle();
                 logical_device.destroy_device(None);
             }
             
@@ -329,13 +795,9 @@ impl Drop for VkApp {
                 drop(debug);
             }
             
-            if let Some(surface) = self.surface.take() {
-                self.surface_loader.take().unwrap().destroy_surface(surface, None);
-            }
-            
             if let Some(instance) = self.instance.take() {
                 instance.destroy_instance(None);
             }
         }
     }
-}
\ No newline at end of file
+}
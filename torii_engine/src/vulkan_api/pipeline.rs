@@ -0,0 +1,459 @@
+use std::ffi::CString;
+
+use ash::{vk, Device};
+use tracing::debug;
+
+use super::shader::{ReflectedBinding, Shader, ShaderReflection, ShaderStage};
+use super::{PipelineError, PipelineResult, VkApp};
+
+// Descriptor set layouts and pipeline layout built from the reflection of every stage of a pipeline ; a binding
+// used by several stages is visible to all of them
+pub struct PipelineLayout {
+    device: Device,
+    bindings: Vec<ReflectedBinding>,
+    push_constant_size: u32,
+    push_constant_stages: vk::ShaderStageFlags,
+    // indexed by set, sets no stage uses get an empty layout
+    set_layouts: Vec<vk::DescriptorSetLayout>,
+    layout: vk::PipelineLayout,
+}
+
+impl PipelineLayout {
+    pub fn new(device: &Device, reflections: &[&ShaderReflection]) -> Result<Self, vk::Result> {
+        let mut bindings: Vec<ReflectedBinding> = vec![];
+        let mut binding_stages: Vec<vk::ShaderStageFlags> = vec![];
+        let mut push_constant_size = 0;
+        let mut push_constant_stages = vk::ShaderStageFlags::empty();
+        for reflection in reflections.iter() {
+            let stage = reflection.stage.vk_stage();
+            for reflected in reflection.bindings.iter() {
+                match bindings.iter().position(|existing| existing.set == reflected.set && existing.binding == reflected.binding) {
+                    Some(index) => {
+                        bindings[index].writable |= reflected.writable;
+                        binding_stages[index] |= stage;
+                    },
+                    None => {
+                        bindings.push(reflected.clone());
+                        binding_stages.push(stage);
+                    },
+                }
+            }
+            if reflection.push_constant_size > 0 {
+                push_constant_size = push_constant_size.max(reflection.push_constant_size);
+                push_constant_stages |= stage;
+            }
+        }
+
+        let mut pipeline_layout = PipelineLayout {
+            device: device.clone(),
+            bindings,
+            push_constant_size,
+            push_constant_stages,
+            set_layouts: vec![],
+            layout: vk::PipelineLayout::null(),
+        };
+        // on error the handles created so far are destroyed by Drop
+        let set_count = pipeline_layout.bindings.iter().map(|reflected| reflected.set + 1).max().unwrap_or(0);
+        for set in 0..set_count {
+            let layout_bindings: Vec<vk::DescriptorSetLayoutBinding> = pipeline_layout.bindings
+                .iter()
+                .zip(binding_stages.iter())
+                .filter(|(reflected, _)| reflected.set == set)
+                .map(|(reflected, &stages)| {
+                    vk::DescriptorSetLayoutBinding::default()
+                        .binding(reflected.binding)
+                        .descriptor_type(reflected.descriptor_type)
+                        .descriptor_count(reflected.count)
+                        .stage_flags(stages)
+                })
+                .collect();
+            let create_info = vk::DescriptorSetLayoutCreateInfo::default().bindings(&layout_bindings);
+            pipeline_layout.set_layouts.push(unsafe { device.create_descriptor_set_layout(&create_info, None)? });
+        }
+
+        let push_constant_ranges: Vec<vk::PushConstantRange> = (push_constant_size > 0)
+            .then(|| {
+                vk::PushConstantRange::default()
+                    .stage_flags(push_constant_stages)
+                    .offset(0)
+                    .size(push_constant_size)
+            })
+            .into_iter()
+            .collect();
+        let create_info = vk::PipelineLayoutCreateInfo::default()
+            .set_layouts(&pipeline_layout.set_layouts)
+            .push_constant_ranges(&push_constant_ranges);
+        pipeline_layout.layout = unsafe { device.create_pipeline_layout(&create_info, None)? };
+        Ok(pipeline_layout)
+    }
+
+    // GETTERS
+
+    pub fn bindings(&self) -> &[ReflectedBinding] {
+        &self.bindings
+    }
+
+    pub fn push_constant_size(&self) -> u32 {
+        self.push_constant_size
+    }
+
+    pub fn push_constant_stages(&self) -> vk::ShaderStageFlags {
+        self.push_constant_stages
+    }
+
+    pub fn set_layouts(&self) -> &[vk::DescriptorSetLayout] {
+        &self.set_layouts
+    }
+
+    pub fn layout(&self) -> vk::PipelineLayout {
+        self.layout
+    }
+}
+
+impl Drop for PipelineLayout {
+    fn drop(&mut self) {
+        unsafe {
+            self.device.destroy_pipeline_layout(self.layout, None);
+            for &set_layout in self.set_layouts.iter() {
+                self.device.destroy_descriptor_set_layout(set_layout, None);
+            }
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum BlendMode {
+    Opaque,
+    Alpha,
+    PremultipliedAlpha,
+    Additive,
+}
+
+impl BlendMode {
+    fn attachment_state(&self) -> vk::PipelineColorBlendAttachmentState {
+        let state = vk::PipelineColorBlendAttachmentState::default().color_write_mask(vk::ColorComponentFlags::RGBA);
+        let (src_color, dst_color) = match self {
+            BlendMode::Opaque => return state.blend_enable(false),
+            BlendMode::Alpha => (vk::BlendFactor::SRC_ALPHA, vk::BlendFactor::ONE_MINUS_SRC_ALPHA),
+            BlendMode::PremultipliedAlpha => (vk::BlendFactor::ONE, vk::BlendFactor::ONE_MINUS_SRC_ALPHA),
+            BlendMode::Additive => (vk::BlendFactor::ONE, vk::BlendFactor::ONE),
+        };
+        state
+            .blend_enable(true)
+            .src_color_blend_factor(src_color)
+            .dst_color_blend_factor(dst_color)
+            .color_blend_op(vk::BlendOp::ADD)
+            .src_alpha_blend_factor(vk::BlendFactor::ONE)
+            .dst_alpha_blend_factor(vk::BlendFactor::ONE_MINUS_SRC_ALPHA)
+            .alpha_blend_op(vk::BlendOp::ADD)
+    }
+}
+
+// Vertex buffer layout of the classic vertex pipeline
+#[derive(Clone, Debug, Default)]
+pub struct VertexLayout {
+    pub bindings: Vec<vk::VertexInputBindingDescription>,
+    pub attributes: Vec<vk::VertexInputAttributeDescription>,
+}
+
+// Graphics pipeline rendering through dynamic rendering (no render pass objects), either with the classic
+// vertex stage or with task/mesh stages ; viewport and scissor are dynamic
+pub struct GraphicsPipelineBuilder<'s> {
+    vertex: Option<&'s Shader>,
+    task: Option<&'s Shader>,
+    mesh: Option<&'s Shader>,
+    fragment: Option<&'s Shader>,
+    vertex_layout: VertexLayout,
+    topology: vk::PrimitiveTopology,
+    polygon_mode: vk::PolygonMode,
+    cull_mode: vk::CullModeFlags,
+    front_face: vk::FrontFace,
+    depth_test: bool,
+    depth_write: bool,
+    depth_compare: vk::CompareOp,
+    // constant factor, slope factor
+    depth_bias: Option<(f32, f32)>,
+    depth_clamp: bool,
+    color_formats: Vec<vk::Format>,
+    depth_format: vk::Format,
+    samples: vk::SampleCountFlags,
+    blend: BlendMode,
+}
+
+impl Default for GraphicsPipelineBuilder<'_> {
+    fn default() -> Self {
+        GraphicsPipelineBuilder {
+            vertex: None,
+            task: None,
+            mesh: None,
+            fragment: None,
+            vertex_layout: VertexLayout::default(),
+            topology: vk::PrimitiveTopology::TRIANGLE_LIST,
+            polygon_mode: vk::PolygonMode::FILL,
+            cull_mode: vk::CullModeFlags::BACK,
+            front_face: vk::FrontFace::COUNTER_CLOCKWISE,
+            depth_test: false,
+            depth_write: false,
+            depth_compare: vk::CompareOp::LESS_OR_EQUAL,
+            depth_bias: None,
+            depth_clamp: false,
+            color_formats: vec![],
+            depth_format: vk::Format::UNDEFINED,
+            samples: vk::SampleCountFlags::TYPE_1,
+            blend: BlendMode::Opaque,
+        }
+    }
+}
+
+impl<'s> GraphicsPipelineBuilder<'s> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn vertex(mut self, shader: &'s Shader, vertex_layout: VertexLayout) -> Self {
+        self.vertex = Some(shader);
+        self.vertex_layout = vertex_layout;
+        self
+    }
+
+    // optional, amplifies or culls work ahead of the mesh stage
+    pub fn task(mut self, shader: &'s Shader) -> Self {
+        self.task = Some(shader);
+        self
+    }
+
+    pub fn mesh(mut self, shader: &'s Shader) -> Self {
+        self.mesh = Some(shader);
+        self
+    }
+
+    // optional, depth only passes go without
+    pub fn fragment(mut self, shader: &'s Shader) -> Self {
+        self.fragment = Some(shader);
+        self
+    }
+
+    pub fn topology(mut self, topology: vk::PrimitiveTopology) -> Self {
+        self.topology = topology;
+        self
+    }
+
+    pub fn polygon_mode(mut self, polygon_mode: vk::PolygonMode) -> Self {
+        self.polygon_mode = polygon_mode;
+        self
+    }
+
+    pub fn cull_mode(mut self, cull_mode: vk::CullModeFlags, front_face: vk::FrontFace) -> Self {
+        self.cull_mode = cull_mode;
+        self.front_face = front_face;
+        self
+    }
+
+    pub fn depth(mut self, format: vk::Format, test: bool, write: bool, compare: vk::CompareOp) -> Self {
+        self.depth_format = format;
+        self.depth_test = test;
+        self.depth_write = write;
+        self.depth_compare = compare;
+        self
+    }
+
+    pub fn depth_bias(mut self, constant_factor: f32, slope_factor: f32) -> Self {
+        self.depth_bias = Some((constant_factor, slope_factor));
+        self
+    }
+
+    pub fn depth_clamp(mut self, depth_clamp: bool) -> Self {
+        self.depth_clamp = depth_clamp;
+        self
+    }
+
+    pub fn color_formats(mut self, color_formats: &[vk::Format]) -> Self {
+        self.color_formats = color_formats.to_vec();
+        self
+    }
+
+    pub fn samples(mut self, samples: vk::SampleCountFlags) -> Self {
+        self.samples = samples;
+        self
+    }
+
+    pub fn blend(mut self, blend: BlendMode) -> Self {
+        self.blend = blend;
+        self
+    }
+
+    pub fn build(self, vk_app: &VkApp, name: &str) -> PipelineResult<GraphicsPipeline> {
+        if !vk_app.is_feature_enabled(crate::device_feature!(vulkan13.dynamic_rendering)) {
+            return Err(PipelineError::DynamicRenderingUnavailableError);
+        }
+        let stages = self.stages()?;
+        let uses_mesh_shading = self.mesh.is_some();
+        if uses_mesh_shading && !is_mesh_shading_enabled(vk_app, self.task.is_some()) {
+            return Err(PipelineError::MeshShaderUnavailableError);
+        }
+
+        let device = vk_app.device();
+        let reflections: Vec<&ShaderReflection> = stages.iter().map(|shader| shader.reflection()).collect();
+        let layout = PipelineLayout::new(device, &reflections)?;
+
+        let mut modules = vec![];
+        for shader in stages.iter() {
+            match shader.create_module(device) {
+                Ok(module) => modules.push(module),
+                Err(error) => {
+                    destroy_modules(device, &modules);
+                    return Err(error.into());
+                },
+            }
+        }
+        let entry_points: Vec<CString> = stages.iter().map(|shader| CString::new(shader.entry_point()).unwrap()).collect();
+        let stage_create_infos: Vec<vk::PipelineShaderStageCreateInfo> = stages
+            .iter()
+            .zip(modules.iter())
+            .zip(entry_points.iter())
+            .map(|((shader, &module), entry_point)| {
+                vk::PipelineShaderStageCreateInfo::default()
+                    .stage(shader.stage().vk_stage())
+                    .module(module)
+                    .name(entry_point)
+            })
+            .collect();
+
+        let vertex_input_state = vk::PipelineVertexInputStateCreateInfo::default()
+            .vertex_binding_descriptions(&self.vertex_layout.bindings)
+            .vertex_attribute_descriptions(&self.vertex_layout.attributes);
+        let input_assembly_state = vk::PipelineInputAssemblyStateCreateInfo::default().topology(self.topology);
+        // counts only, the actual viewport and scissor are set while recording
+        let viewport_state = vk::PipelineViewportStateCreateInfo::default()
+            .viewport_count(1)
+            .scissor_count(1);
+        let rasterization_state = vk::PipelineRasterizationStateCreateInfo::default()
+            .polygon_mode(self.polygon_mode)
+            .cull_mode(self.cull_mode)
+            .front_face(self.front_face)
+            .depth_clamp_enable(self.depth_clamp)
+            .depth_bias_enable(self.depth_bias.is_some())
+            .depth_bias_constant_factor(self.depth_bias.map_or(0.0, |(constant_factor, _)| constant_factor))
+            .depth_bias_slope_factor(self.depth_bias.map_or(0.0, |(_, slope_factor)| slope_factor))
+            .line_width(1.0);
+        let multisample_state = vk::PipelineMultisampleStateCreateInfo::default().rasterization_samples(self.samples);
+        let depth_stencil_state = vk::PipelineDepthStencilStateCreateInfo::default()
+            .depth_test_enable(self.depth_test)
+            .depth_write_enable(self.depth_write)
+            .depth_compare_op(self.depth_compare);
+        let blend_attachments: Vec<vk::PipelineColorBlendAttachmentState> = self.color_formats
+            .iter()
+            .map(|_| self.blend.attachment_state())
+            .collect();
+        let color_blend_state = vk::PipelineColorBlendStateCreateInfo::default().attachments(&blend_attachments);
+        let dynamic_states = [vk::DynamicState::VIEWPORT, vk::DynamicState::SCISSOR];
+        let dynamic_state = vk::PipelineDynamicStateCreateInfo::default().dynamic_states(&dynamic_states);
+        let mut rendering_create_info = vk::PipelineRenderingCreateInfo::default()
+            .color_attachment_formats(&self.color_formats)
+            .depth_attachment_format(self.depth_format);
+
+        let mut create_info = vk::GraphicsPipelineCreateInfo::default()
+            .stages(&stage_create_infos)
+            .viewport_state(&viewport_state)
+            .rasterization_state(&rasterization_state)
+            .multisample_state(&multisample_state)
+            .depth_stencil_state(&depth_stencil_state)
+            .color_blend_state(&color_blend_state)
+            .dynamic_state(&dynamic_state)
+            .layout(layout.layout())
+            .push_next(&mut rendering_create_info);
+        // mesh pipelines have no vertex input, their vertices come out of the mesh stage
+        if !uses_mesh_shading {
+            create_info = create_info
+                .vertex_input_state(&vertex_input_state)
+                .input_assembly_state(&input_assembly_state);
+        }
+        let created = unsafe {
+            device.create_graphics_pipelines(vk::PipelineCache::null(), std::slice::from_ref(&create_info), None)
+        };
+        destroy_modules(device, &modules);
+        let pipeline = created.map_err(|(_, error)| error)?[0];
+
+        let debug_utils = vk_app.debug_utils();
+        debug_utils.set_object_name(pipeline, name);
+        debug_utils.set_object_name(layout.layout(), &format!("{name} layout"));
+        debug!(
+            target: "torii::vulkan",
+            name,
+            stages = ?stages.iter().map(|shader| shader.stage()).collect::<Vec<_>>(),
+            bindings = layout.bindings().len(),
+            push_constant_size = layout.push_constant_size(),
+            "Created graphics pipeline"
+        );
+        Ok(GraphicsPipeline {
+            device: device.clone(),
+            layout,
+            pipeline,
+            uses_mesh_shading,
+        })
+    }
+
+    fn stages(&self) -> PipelineResult<Vec<&'s Shader>> {
+        let expect = |shader: Option<&'s Shader>, stage: ShaderStage, name: &'static str| match shader {
+            Some(shader) if shader.stage() != stage => Err(PipelineError::WrongStageError(shader.stage(), name)),
+            shader => Ok(shader),
+        };
+        let vertex = expect(self.vertex, ShaderStage::Vertex, "vertex")?;
+        let task = expect(self.task, ShaderStage::Task, "task")?;
+        let mesh = expect(self.mesh, ShaderStage::Mesh, "mesh")?;
+        let fragment = expect(self.fragment, ShaderStage::Fragment, "fragment")?;
+        match (vertex, mesh) {
+            (None, None) => return Err(PipelineError::MissingStageError("vertex or mesh shader")),
+            (Some(_), Some(_)) => return Err(PipelineError::WrongStageError(ShaderStage::Mesh, "vertex")),
+            (Some(_), None) if task.is_some() => return Err(PipelineError::MissingStageError("mesh shader after the task shader")),
+            _ => (),
+        }
+        Ok([vertex, task, mesh, fragment].into_iter().flatten().collect())
+    }
+}
+
+pub struct GraphicsPipeline {
+    device: Device,
+    layout: PipelineLayout,
+    pipeline: vk::Pipeline,
+    uses_mesh_shading: bool,
+}
+
+impl GraphicsPipeline {
+    // GETTERS
+
+    pub fn layout(&self) -> &PipelineLayout {
+        &self.layout
+    }
+
+    pub fn pipeline(&self) -> vk::Pipeline {
+        self.pipeline
+    }
+
+    pub fn uses_mesh_shading(&self) -> bool {
+        self.uses_mesh_shading
+    }
+}
+
+impl Drop for GraphicsPipeline {
+    fn drop(&mut self) {
+        unsafe {
+            self.device.destroy_pipeline(self.pipeline, None);
+        }
+    }
+}
+
+// VK_EXT_mesh_shader with its mesh (and, if asked for, task) stage enabled on the device
+pub fn is_mesh_shading_enabled(vk_app: &VkApp, with_task_shader: bool) -> bool {
+    vk_app.is_device_extension_enabled(ash::ext::mesh_shader::NAME)
+        && vk_app.is_feature_enabled(crate::device_feature!(mesh_shader.mesh_shader))
+        && (!with_task_shader || vk_app.is_feature_enabled(crate::device_feature!(mesh_shader.task_shader)))
+}
+
+fn destroy_modules(device: &Device, modules: &[vk::ShaderModule]) {
+    unsafe {
+        for &module in modules.iter() {
+            device.destroy_shader_module(module, None);
+        }
+    }
+}
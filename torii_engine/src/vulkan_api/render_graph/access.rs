@@ -0,0 +1,163 @@
+use ash::vk;
+
+// Synchronization2 view of one use of a resource ; `layout` is ignored for buffers
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct AccessInfo {
+    pub stage: vk::PipelineStageFlags2,
+    pub access: vk::AccessFlags2,
+    pub layout: vk::ImageLayout,
+}
+
+impl AccessInfo {
+    pub const fn new(stage: vk::PipelineStageFlags2, access: vk::AccessFlags2, layout: vk::ImageLayout) -> Self {
+        AccessInfo { stage, access, layout }
+    }
+
+    // nothing happened to the resource yet
+    pub const fn none() -> Self {
+        Self::new(vk::PipelineStageFlags2::NONE, vk::AccessFlags2::NONE, vk::ImageLayout::UNDEFINED)
+    }
+
+    pub fn is_write(&self) -> bool {
+        self.access.intersects(
+            vk::AccessFlags2::SHADER_WRITE
+                | vk::AccessFlags2::SHADER_STORAGE_WRITE
+                | vk::AccessFlags2::COLOR_ATTACHMENT_WRITE
+                | vk::AccessFlags2::DEPTH_STENCIL_ATTACHMENT_WRITE
+                | vk::AccessFlags2::TRANSFER_WRITE
+                | vk::AccessFlags2::HOST_WRITE
+                | vk::AccessFlags2::MEMORY_WRITE
+        )
+    }
+}
+
+// The ways a pass can use an image ; Custom covers anything not listed
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ImageAccess {
+    ColorAttachmentWrite,
+    // blending reads the attachment before writing it
+    ColorAttachmentReadWrite,
+    DepthAttachmentWrite,
+    DepthAttachmentRead,
+    SampledVertex,
+    SampledFragment,
+    SampledCompute,
+    StorageReadCompute,
+    StorageWriteCompute,
+    StorageReadWriteCompute,
+    TransferSrc,
+    TransferDst,
+    // only meaningful as the final access of an imported swapchain image
+    Present,
+    Custom(AccessInfo),
+}
+
+impl ImageAccess {
+    pub fn info(&self) -> AccessInfo {
+        use vk::{AccessFlags2 as A, ImageLayout as L, PipelineStageFlags2 as S};
+        let depth_tests = S::EARLY_FRAGMENT_TESTS | S::LATE_FRAGMENT_TESTS;
+        match *self {
+            ImageAccess::ColorAttachmentWrite => AccessInfo::new(S::COLOR_ATTACHMENT_OUTPUT, A::COLOR_ATTACHMENT_WRITE, L::COLOR_ATTACHMENT_OPTIMAL),
+            ImageAccess::ColorAttachmentReadWrite => AccessInfo::new(
+                S::COLOR_ATTACHMENT_OUTPUT,
+                A::COLOR_ATTACHMENT_READ | A::COLOR_ATTACHMENT_WRITE,
+                L::COLOR_ATTACHMENT_OPTIMAL,
+            ),
+            ImageAccess::DepthAttachmentWrite => AccessInfo::new(
+                depth_tests,
+                A::DEPTH_STENCIL_ATTACHMENT_READ | A::DEPTH_STENCIL_ATTACHMENT_WRITE,
+                L::DEPTH_STENCIL_ATTACHMENT_OPTIMAL,
+            ),
+            ImageAccess::DepthAttachmentRead => AccessInfo::new(depth_tests, A::DEPTH_STENCIL_ATTACHMENT_READ, L::DEPTH_STENCIL_READ_ONLY_OPTIMAL),
+            ImageAccess::SampledVertex => AccessInfo::new(S::VERTEX_SHADER, A::SHADER_SAMPLED_READ, L::SHADER_READ_ONLY_OPTIMAL),
+            ImageAccess::SampledFragment => AccessInfo::new(S::FRAGMENT_SHADER, A::SHADER_SAMPLED_READ, L::SHADER_READ_ONLY_OPTIMAL),
+            ImageAccess::SampledCompute => AccessInfo::new(S::COMPUTE_SHADER, A::SHADER_SAMPLED_READ, L::SHADER_READ_ONLY_OPTIMAL),
+            ImageAccess::StorageReadCompute => AccessInfo::new(S::COMPUTE_SHADER, A::SHADER_STORAGE_READ, L::GENERAL),
+            ImageAccess::StorageWriteCompute => AccessInfo::new(S::COMPUTE_SHADER, A::SHADER_STORAGE_WRITE, L::GENERAL),
+            ImageAccess::StorageReadWriteCompute => AccessInfo::new(S::COMPUTE_SHADER, A::SHADER_STORAGE_READ | A::SHADER_STORAGE_WRITE, L::GENERAL),
+            ImageAccess::TransferSrc => AccessInfo::new(S::ALL_TRANSFER, A::TRANSFER_READ, L::TRANSFER_SRC_OPTIMAL),
+            ImageAccess::TransferDst => AccessInfo::new(S::ALL_TRANSFER, A::TRANSFER_WRITE, L::TRANSFER_DST_OPTIMAL),
+            ImageAccess::Present => AccessInfo::new(S::NONE, A::NONE, L::PRESENT_SRC_KHR),
+            ImageAccess::Custom(info) => info,
+        }
+    }
+
+    // usage flags a transient image needs to support this access
+    pub(crate) fn usage(&self) -> vk::ImageUsageFlags {
+        match *self {
+            ImageAccess::ColorAttachmentWrite | ImageAccess::ColorAttachmentReadWrite => vk::ImageUsageFlags::COLOR_ATTACHMENT,
+            ImageAccess::DepthAttachmentWrite | ImageAccess::DepthAttachmentRead => vk::ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT,
+            ImageAccess::SampledVertex | ImageAccess::SampledFragment | ImageAccess::SampledCompute => vk::ImageUsageFlags::SAMPLED,
+            ImageAccess::StorageReadCompute | ImageAccess::StorageWriteCompute | ImageAccess::StorageReadWriteCompute => vk::ImageUsageFlags::STORAGE,
+            ImageAccess::TransferSrc => vk::ImageUsageFlags::TRANSFER_SRC,
+            ImageAccess::TransferDst => vk::ImageUsageFlags::TRANSFER_DST,
+            ImageAccess::Present => vk::ImageUsageFlags::empty(),
+            ImageAccess::Custom(info) => Self::usage_from_layout(info.layout),
+        }
+    }
+
+    fn usage_from_layout(layout: vk::ImageLayout) -> vk::ImageUsageFlags {
+        match layout {
+            vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL => vk::ImageUsageFlags::COLOR_ATTACHMENT,
+            vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL | vk::ImageLayout::DEPTH_STENCIL_READ_ONLY_OPTIMAL
+                | vk::ImageLayout::DEPTH_ATTACHMENT_OPTIMAL | vk::ImageLayout::DEPTH_READ_ONLY_OPTIMAL => vk::ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT,
+            vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL => vk::ImageUsageFlags::SAMPLED,
+            vk::ImageLayout::GENERAL => vk::ImageUsageFlags::STORAGE,
+            vk::ImageLayout::TRANSFER_SRC_OPTIMAL => vk::ImageUsageFlags::TRANSFER_SRC,
+            vk::ImageLayout::TRANSFER_DST_OPTIMAL => vk::ImageUsageFlags::TRANSFER_DST,
+            _ => vk::ImageUsageFlags::empty(),
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum BufferAccess {
+    VertexBuffer,
+    IndexBuffer,
+    IndirectBuffer,
+    UniformGraphics,
+    UniformCompute,
+    StorageReadGraphics,
+    StorageReadCompute,
+    StorageWriteCompute,
+    StorageReadWriteCompute,
+    TransferSrc,
+    TransferDst,
+    Custom(AccessInfo, vk::BufferUsageFlags),
+}
+
+impl BufferAccess {
+    pub fn info(&self) -> AccessInfo {
+        use vk::{AccessFlags2 as A, ImageLayout as L, PipelineStageFlags2 as S};
+        let graphics_shaders = S::VERTEX_SHADER | S::FRAGMENT_SHADER;
+        let (stage, access) = match *self {
+            BufferAccess::VertexBuffer => (S::VERTEX_ATTRIBUTE_INPUT, A::VERTEX_ATTRIBUTE_READ),
+            BufferAccess::IndexBuffer => (S::INDEX_INPUT, A::INDEX_READ),
+            BufferAccess::IndirectBuffer => (S::DRAW_INDIRECT, A::INDIRECT_COMMAND_READ),
+            BufferAccess::UniformGraphics => (graphics_shaders, A::UNIFORM_READ),
+            BufferAccess::UniformCompute => (S::COMPUTE_SHADER, A::UNIFORM_READ),
+            BufferAccess::StorageReadGraphics => (graphics_shaders, A::SHADER_STORAGE_READ),
+            BufferAccess::StorageReadCompute => (S::COMPUTE_SHADER, A::SHADER_STORAGE_READ),
+            BufferAccess::StorageWriteCompute => (S::COMPUTE_SHADER, A::SHADER_STORAGE_WRITE),
+            BufferAccess::StorageReadWriteCompute => (S::COMPUTE_SHADER, A::SHADER_STORAGE_READ | A::SHADER_STORAGE_WRITE),
+            BufferAccess::TransferSrc => (S::ALL_TRANSFER, A::TRANSFER_READ),
+            BufferAccess::TransferDst => (S::ALL_TRANSFER, A::TRANSFER_WRITE),
+            BufferAccess::Custom(info, _) => (info.stage, info.access),
+        };
+        AccessInfo::new(stage, access, L::UNDEFINED)
+    }
+
+    pub(crate) fn usage(&self) -> vk::BufferUsageFlags {
+        match *self {
+            BufferAccess::VertexBuffer => vk::BufferUsageFlags::VERTEX_BUFFER,
+            BufferAccess::IndexBuffer => vk::BufferUsageFlags::INDEX_BUFFER,
+            BufferAccess::IndirectBuffer => vk::BufferUsageFlags::INDIRECT_BUFFER,
+            BufferAccess::UniformGraphics | BufferAccess::UniformCompute => vk::BufferUsageFlags::UNIFORM_BUFFER,
+            BufferAccess::StorageReadGraphics | BufferAccess::StorageReadCompute
+                | BufferAccess::StorageWriteCompute | BufferAccess::StorageReadWriteCompute => vk::BufferUsageFlags::STORAGE_BUFFER,
+            BufferAccess::TransferSrc => vk::BufferUsageFlags::TRANSFER_SRC,
+            BufferAccess::TransferDst => vk::BufferUsageFlags::TRANSFER_DST,
+            BufferAccess::Custom(_, usage) => usage,
+        }
+    }
+}
@@ -0,0 +1,126 @@
+use ash::vk;
+
+use super::compile::Lifetime;
+
+// One transient resource to place in memory
+#[derive(Copy, Clone, Debug)]
+pub(crate) struct AliasCandidate {
+    pub(crate) lifetime: Lifetime,
+    pub(crate) requirements: vk::MemoryRequirements,
+}
+
+// Memory shared by resources with disjoint lifetimes ; every occupant is bound at offset 0
+#[derive(Clone, Debug)]
+pub(crate) struct MemoryBlock {
+    pub(crate) size: vk::DeviceSize,
+    pub(crate) memory_type_bits: u32,
+    // candidate indices, sorted by lifetime
+    pub(crate) occupants: Vec<usize>,
+}
+
+// Greedy first fit, largest resources first: each resource joins the first block whose occupants it never overlaps
+// and which shares a memory type with it, otherwise opens a new block. Returns the blocks and, per candidate, the
+// block it landed in.
+pub(crate) fn assign_blocks(candidates: &[AliasCandidate]) -> (Vec<MemoryBlock>, Vec<usize>) {
+    let mut by_size: Vec<usize> = (0..candidates.len()).collect();
+    by_size.sort_by_key(|&candidate| std::cmp::Reverse(candidates[candidate].requirements.size));
+
+    let mut blocks: Vec<MemoryBlock> = vec![];
+    let mut assignment = vec![0; candidates.len()];
+    for candidate_index in by_size {
+        let candidate = &candidates[candidate_index];
+        let fitting_block = blocks.iter().position(|block| {
+            block.memory_type_bits & candidate.requirements.memory_type_bits != 0
+                && block.occupants.iter().all(|&occupant| !candidates[occupant].lifetime.overlaps(&candidate.lifetime))
+        });
+        let block_index = match fitting_block {
+            Some(block_index) => block_index,
+            None => {
+                blocks.push(MemoryBlock {
+                    size: 0,
+                    memory_type_bits: candidate.requirements.memory_type_bits,
+                    occupants: vec![],
+                });
+                blocks.len() - 1
+            },
+        };
+        let block = &mut blocks[block_index];
+        block.size = block.size.max(candidate.requirements.size);
+        block.memory_type_bits &= candidate.requirements.memory_type_bits;
+        block.occupants.push(candidate_index);
+        assignment[candidate_index] = block_index;
+    }
+
+    for block in blocks.iter_mut() {
+        block.occupants.sort_by_key(|&occupant| candidates[occupant].lifetime.first);
+    }
+    (blocks, assignment)
+}
+
+// The occupant using the block's memory right before `candidate_index`, which its first barrier has to wait for
+pub(crate) fn previous_occupant(block: &MemoryBlock, candidate_index: usize) -> Option<usize> {
+    let position = block.occupants.iter().position(|&occupant| occupant == candidate_index)?;
+    position.checked_sub(1).map(|previous| block.occupants[previous])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn candidate(first: usize, last: usize, size: vk::DeviceSize, memory_type_bits: u32) -> AliasCandidate {
+        AliasCandidate {
+            lifetime: Lifetime {
+                first,
+                last,
+                stages: vk::PipelineStageFlags2::FRAGMENT_SHADER,
+                writes: vk::AccessFlags2::NONE,
+            },
+            requirements: vk::MemoryRequirements { size, alignment: 256, memory_type_bits },
+        }
+    }
+
+    #[test]
+    fn overlapping_lifetimes_never_share_a_block() {
+        let candidates = [
+            candidate(0, 1, 1024, 0b11),
+            candidate(1, 2, 512, 0b11),
+            candidate(2, 3, 2048, 0b11),
+            candidate(3, 3, 256, 0b11),
+            candidate(0, 3, 128, 0b11),
+            candidate(4, 5, 4096, 0b11),
+        ];
+        let (blocks, assignment) = assign_blocks(&candidates);
+
+        for block in &blocks {
+            for (position, &occupant) in block.occupants.iter().enumerate() {
+                for &other in &block.occupants[position + 1..] {
+                    assert!(!candidates[occupant].lifetime.overlaps(&candidates[other].lifetime), "{occupant} and {other} overlap");
+                }
+                assert!(block.size >= candidates[occupant].requirements.size);
+            }
+        }
+        for (candidate_index, &block_index) in assignment.iter().enumerate() {
+            assert!(blocks[block_index].occupants.contains(&candidate_index));
+        }
+        // 0, 2 and 5 follow each other, so do 1 and 3
+        assert_eq!(blocks.len(), 3);
+    }
+
+    #[test]
+    fn incompatible_memory_types_never_share_a_block() {
+        let candidates = [candidate(0, 0, 1024, 0b01), candidate(1, 1, 1024, 0b10)];
+        let (blocks, assignment) = assign_blocks(&candidates);
+        assert_eq!(blocks.len(), 2);
+        assert_ne!(assignment[0], assignment[1]);
+    }
+
+    #[test]
+    fn previous_occupant_follows_lifetimes() {
+        let candidates = [candidate(2, 3, 512, 1), candidate(0, 1, 1024, 1)];
+        let (blocks, _) = assign_blocks(&candidates);
+        assert_eq!(blocks.len(), 1);
+        assert_eq!(blocks[0].occupants, [1, 0]);
+        assert_eq!(previous_occupant(&blocks[0], 0), Some(1));
+        assert_eq!(previous_occupant(&blocks[0], 1), None);
+    }
+}
@@ -0,0 +1,558 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use ash::vk;
+
+use super::{
+    execute, AccessInfo, BufferHandle, BufferResource, BufferSource, ImageHandle, ImageResource, ImageSource, Pass,
+    PassKind, RenderGraphError, RenderGraphResult,
+};
+
+// Positions (in execution order) of the first and last live pass using a resource
+#[derive(Copy, Clone, Debug)]
+pub(crate) struct Lifetime {
+    pub(crate) first: usize,
+    pub(crate) last: usize,
+    // every stage that touched the resource and every write made to it, which the next resource aliasing its memory
+    // has to wait for
+    pub(crate) stages: vk::PipelineStageFlags2,
+    pub(crate) writes: vk::AccessFlags2,
+}
+
+impl Lifetime {
+    pub(crate) fn overlaps(&self, other: &Lifetime) -> bool {
+        self.first <= other.last && other.first <= self.last
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+pub(crate) struct ImageBarrier {
+    pub(crate) image: ImageHandle,
+    pub(crate) src_stage: vk::PipelineStageFlags2,
+    pub(crate) src_access: vk::AccessFlags2,
+    pub(crate) dst_stage: vk::PipelineStageFlags2,
+    pub(crate) dst_access: vk::AccessFlags2,
+    pub(crate) old_layout: vk::ImageLayout,
+    pub(crate) new_layout: vk::ImageLayout,
+    // first use of a transient image, whose source is the previous user of its memory once aliasing is known
+    pub(crate) first_use: bool,
+}
+
+#[derive(Copy, Clone, Debug)]
+pub(crate) struct BufferBarrier {
+    pub(crate) buffer: BufferHandle,
+    pub(crate) src_stage: vk::PipelineStageFlags2,
+    pub(crate) src_access: vk::AccessFlags2,
+    pub(crate) dst_stage: vk::PipelineStageFlags2,
+    pub(crate) dst_access: vk::AccessFlags2,
+    pub(crate) first_use: bool,
+}
+
+#[derive(Clone, Debug, Default)]
+pub(crate) struct Barriers {
+    pub(crate) images: Vec<ImageBarrier>,
+    pub(crate) buffers: Vec<BufferBarrier>,
+}
+
+impl Barriers {
+    pub(crate) fn len(&self) -> usize {
+        self.images.len() + self.buffers.len()
+    }
+}
+
+// What has happened to a resource so far while walking the passes in order
+#[derive(Copy, Clone)]
+struct SyncState {
+    touched: bool,
+    layout: vk::ImageLayout,
+    write_stage: vk::PipelineStageFlags2,
+    write_access: vk::AccessFlags2,
+    read_stage: vk::PipelineStageFlags2,
+    // stages and accesses the last write has already been made visible to
+    visible_stage: vk::PipelineStageFlags2,
+    visible_access: vk::AccessFlags2,
+}
+
+impl SyncState {
+    fn untouched() -> Self {
+        SyncState {
+            touched: false,
+            layout: vk::ImageLayout::UNDEFINED,
+            write_stage: vk::PipelineStageFlags2::NONE,
+            write_access: vk::AccessFlags2::NONE,
+            read_stage: vk::PipelineStageFlags2::NONE,
+            visible_stage: vk::PipelineStageFlags2::NONE,
+            visible_access: vk::AccessFlags2::NONE,
+        }
+    }
+
+    fn imported(initial_access: AccessInfo) -> Self {
+        SyncState {
+            touched: true,
+            layout: initial_access.layout,
+            write_stage: initial_access.stage,
+            write_access: initial_access.access,
+            ..Self::untouched()
+        }
+    }
+
+    // (src stage, src access, old layout, first use) of the barrier needed before `access`, if any
+    fn barrier_before(&self, access: AccessInfo, is_write: bool, is_image: bool) -> Option<(vk::PipelineStageFlags2, vk::AccessFlags2, vk::ImageLayout, bool)> {
+        if !self.touched {
+            return Some((vk::PipelineStageFlags2::NONE, vk::AccessFlags2::NONE, vk::ImageLayout::UNDEFINED, true));
+        }
+        let has_write = !self.write_stage.is_empty() || !self.write_access.is_empty();
+        let needed = (is_image && self.layout != access.layout)
+            || (is_write && (has_write || !self.read_stage.is_empty()))
+            || (!is_write && has_write && !(self.visible_stage.contains(access.stage) && self.visible_access.contains(access.access)));
+        needed.then_some((self.write_stage | self.read_stage, self.write_access, self.layout, false))
+    }
+
+    fn apply(&mut self, access: AccessInfo, is_write: bool, barrier_emitted: bool) {
+        if barrier_emitted {
+            self.visible_stage |= access.stage;
+            self.visible_access |= access.access;
+        }
+        if is_write {
+            self.write_stage = access.stage;
+            self.write_access = access.access;
+            self.read_stage = vk::PipelineStageFlags2::NONE;
+            self.visible_stage = vk::PipelineStageFlags2::NONE;
+            self.visible_access = vk::AccessFlags2::NONE;
+        } else {
+            self.read_stage |= access.stage;
+        }
+        self.touched = true;
+        self.layout = access.layout;
+    }
+}
+
+// Graph with its passes culled and synchronized ; execute it with CompiledGraph::execute, and drop it only once the
+// GPU is done with the last execution
+pub struct CompiledGraph<'a> {
+    pub(crate) images: Vec<ImageResource>,
+    pub(crate) buffers: Vec<BufferResource>,
+    pub(crate) passes: Vec<Pass<'a>>,
+    // indices into `passes` of the live passes, in execution order
+    pub(crate) order: Vec<usize>,
+    pub(crate) culled: Vec<usize>,
+    // barriers recorded before the pass at the same position in `order`
+    pub(crate) pass_barriers: Vec<Barriers>,
+    // transitions of imported resources to their final access
+    pub(crate) final_barriers: Barriers,
+    pub(crate) image_lifetimes: Vec<Option<Lifetime>>,
+    pub(crate) buffer_lifetimes: Vec<Option<Lifetime>>,
+    pub(crate) realized: Option<execute::Realized>,
+}
+
+impl CompiledGraph<'_> {
+    pub fn pass_order(&self) -> Vec<&str> {
+        self.order.iter().map(|&pass_index| self.passes[pass_index].name.as_str()).collect()
+    }
+
+    pub fn culled_passes(&self) -> Vec<&str> {
+        self.culled.iter().map(|&pass_index| self.passes[pass_index].name.as_str()).collect()
+    }
+
+    pub fn barrier_count(&self) -> usize {
+        self.pass_barriers.iter().map(Barriers::len).sum::<usize>() + self.final_barriers.len()
+    }
+
+    // The live passes in execution order then the culled ones, e.g. for the debug overlay
+    pub fn pass_summaries(&self) -> Vec<PassSummary> {
+        let summary = |pass_index: usize, barriers: Option<usize>| {
+            let pass = &self.passes[pass_index];
+            let images = pass.image_accesses.iter().map(|&(image, access, write)| (format!("{} ({access:?})", self.images[image.0].name), write));
+            let buffers = pass.buffer_accesses.iter().map(|&(buffer, access, write)| (format!("{} ({access:?})", self.buffers[buffer.0].name), write));
+            let (writes, reads): (Vec<_>, Vec<_>) = images.chain(buffers).partition(|(_, write)| *write);
+            PassSummary {
+                name: pass.name.clone(),
+                kind: pass.kind,
+                reads: reads.into_iter().map(|(resource, _)| resource).collect(),
+                writes: writes.into_iter().map(|(resource, _)| resource).collect(),
+                barriers,
+            }
+        };
+        let live = self.order.iter().enumerate().map(|(position, &pass_index)| summary(pass_index, Some(self.pass_barriers[position].len())));
+        let culled = self.culled.iter().map(|&pass_index| summary(pass_index, None));
+        live.chain(culled).collect()
+    }
+}
+
+// A pass of a compiled graph with the resources it uses, by name and access
+#[derive(Clone, Debug, PartialEq)]
+pub struct PassSummary {
+    pub name: String,
+    pub kind: PassKind,
+    pub reads: Vec<String>,
+    pub writes: Vec<String>,
+    // recorded right before the pass, None when it was culled
+    pub barriers: Option<usize>,
+}
+
+pub(crate) fn compile(images: Vec<ImageResource>, buffers: Vec<BufferResource>, passes: Vec<Pass<'_>>) -> RenderGraphResult<CompiledGraph<'_>> {
+    validate_handles(&images, &buffers, &passes)?;
+
+    // passes run in declaration order, which already puts every access after those it depends on
+    let data_dependencies = dependencies(&images, &buffers, &passes);
+    let live = live_passes(&images, &buffers, &passes, &data_dependencies);
+    let order: Vec<usize> = (0..passes.len()).filter(|pass_index| live.contains(pass_index)).collect();
+    let culled: Vec<usize> = (0..passes.len()).filter(|pass_index| !live.contains(pass_index)).collect();
+
+    let mut image_states: Vec<SyncState> = images
+        .iter()
+        .map(|image| match image.source {
+            ImageSource::Transient(_) => SyncState::untouched(),
+            ImageSource::Imported(imported) => SyncState::imported(imported.initial_access),
+        })
+        .collect();
+    let mut buffer_states: Vec<SyncState> = buffers
+        .iter()
+        .map(|buffer| match buffer.source {
+            BufferSource::Transient(_) => SyncState::untouched(),
+            BufferSource::Imported(imported) => SyncState::imported(imported.initial_access),
+        })
+        .collect();
+    let mut image_lifetimes: Vec<Option<Lifetime>> = vec![None; images.len()];
+    let mut buffer_lifetimes: Vec<Option<Lifetime>> = vec![None; buffers.len()];
+
+    let mut pass_barriers = Vec::with_capacity(order.len());
+    for (position, &pass_index) in order.iter().enumerate() {
+        let pass = &passes[pass_index];
+        let mut barriers = Barriers::default();
+
+        for (image_index, (access, is_write)) in merged_image_accesses(pass) {
+            let state = &mut image_states[image_index];
+            let barrier = state.barrier_before(access, is_write, true);
+            if let Some((src_stage, src_access, old_layout, first_use)) = barrier {
+                barriers.images.push(ImageBarrier {
+                    image: ImageHandle(image_index),
+                    src_stage,
+                    src_access,
+                    dst_stage: access.stage,
+                    dst_access: access.access,
+                    old_layout,
+                    new_layout: access.layout,
+                    first_use,
+                });
+            }
+            state.apply(access, is_write, barrier.is_some());
+            extend_lifetime(&mut image_lifetimes[image_index], position, access, is_write);
+        }
+        for (buffer_index, (access, is_write)) in merged_buffer_accesses(pass) {
+            let state = &mut buffer_states[buffer_index];
+            let barrier = state.barrier_before(access, is_write, false);
+            if let Some((src_stage, src_access, _, first_use)) = barrier {
+                barriers.buffers.push(BufferBarrier {
+                    buffer: BufferHandle(buffer_index),
+                    src_stage,
+                    src_access,
+                    dst_stage: access.stage,
+                    dst_access: access.access,
+                    first_use,
+                });
+            }
+            state.apply(access, is_write, barrier.is_some());
+            extend_lifetime(&mut buffer_lifetimes[buffer_index], position, access, is_write);
+        }
+        pass_barriers.push(barriers);
+    }
+
+    let mut final_barriers = Barriers::default();
+    for (image_index, image) in images.iter().enumerate() {
+        let ImageSource::Imported(imported) = image.source else { continue };
+        let (Some(final_access), state) = (imported.final_access, &image_states[image_index]) else { continue };
+        final_barriers.images.push(ImageBarrier {
+            image: ImageHandle(image_index),
+            src_stage: state.write_stage | state.read_stage,
+            src_access: state.write_access,
+            dst_stage: final_access.stage,
+            dst_access: final_access.access,
+            old_layout: state.layout,
+            new_layout: final_access.layout,
+            first_use: false,
+        });
+    }
+    for (buffer_index, buffer) in buffers.iter().enumerate() {
+        let BufferSource::Imported(imported) = buffer.source else { continue };
+        let (Some(final_access), state) = (imported.final_access, &buffer_states[buffer_index]) else { continue };
+        final_barriers.buffers.push(BufferBarrier {
+            buffer: BufferHandle(buffer_index),
+            src_stage: state.write_stage | state.read_stage,
+            src_access: state.write_access,
+            dst_stage: final_access.stage,
+            dst_access: final_access.access,
+            first_use: false,
+        });
+    }
+
+    Ok(CompiledGraph {
+        images,
+        buffers,
+        passes,
+        order,
+        culled,
+        pass_barriers,
+        final_barriers,
+        image_lifetimes,
+        buffer_lifetimes,
+        realized: None,
+    })
+}
+
+fn validate_handles(images: &[ImageResource], buffers: &[BufferResource], passes: &[Pass<'_>]) -> RenderGraphResult<()> {
+    for pass in passes.iter() {
+        if let Some(&(image, _, _)) = pass.image_accesses.iter().find(|(image, _, _)| image.0 >= images.len()) {
+            return Err(RenderGraphError::InvalidHandleError { pass: pass.name.clone(), resource: "image", index: image.0 });
+        }
+        if let Some(&(buffer, _, _)) = pass.buffer_accesses.iter().find(|(buffer, _, _)| buffer.0 >= buffers.len()) {
+            return Err(RenderGraphError::InvalidHandleError { pass: pass.name.clone(), resource: "buffer", index: buffer.0 });
+        }
+    }
+    Ok(())
+}
+
+// A resource used several times by one pass gets a single combined access ; differing layouts fall back to GENERAL
+fn merge_access(merged: &mut BTreeMap<usize, (AccessInfo, bool)>, index: usize, access: AccessInfo, is_write: bool) {
+    merged
+        .entry(index)
+        .and_modify(|(existing, existing_write)| {
+            existing.stage |= access.stage;
+            existing.access |= access.access;
+            if existing.layout != access.layout {
+                existing.layout = vk::ImageLayout::GENERAL;
+            }
+            *existing_write |= is_write;
+        })
+        .or_insert((access, is_write));
+}
+
+fn merged_image_accesses(pass: &Pass<'_>) -> BTreeMap<usize, (AccessInfo, bool)> {
+    let mut merged = BTreeMap::new();
+    for &(image, access, is_write) in pass.image_accesses.iter() {
+        merge_access(&mut merged, image.0, access.info(), is_write);
+    }
+    merged
+}
+
+fn merged_buffer_accesses(pass: &Pass<'_>) -> BTreeMap<usize, (AccessInfo, bool)> {
+    let mut merged = BTreeMap::new();
+    for &(buffer, access, is_write) in pass.buffer_accesses.iter() {
+        merge_access(&mut merged, buffer.0, access.info(), is_write);
+    }
+    merged
+}
+
+fn extend_lifetime(lifetime: &mut Option<Lifetime>, position: usize, access: AccessInfo, is_write: bool) {
+    let writes = if is_write { access.access } else { vk::AccessFlags2::NONE };
+    match lifetime {
+        Some(lifetime) => {
+            lifetime.last = position;
+            lifetime.stages |= access.stage;
+            lifetime.writes |= writes;
+        },
+        None => *lifetime = Some(Lifetime { first: position, last: position, stages: access.stage, writes }),
+    }
+}
+
+// The writers each pass consumes, which keep them alive: a reader consumes the last writer declared before it, a writer
+// the previous writer (it may only overwrite part of the resource). Write after read hazards need no edge, the barriers
+// derived in declaration order cover them
+fn dependencies(images: &[ImageResource], buffers: &[BufferResource], passes: &[Pass<'_>]) -> Vec<BTreeSet<usize>> {
+    let mut image_writers: Vec<Option<usize>> = vec![None; images.len()];
+    let mut buffer_writers: Vec<Option<usize>> = vec![None; buffers.len()];
+    let mut data = vec![BTreeSet::new(); passes.len()];
+
+    let mut access = |pass_index: usize, last_writer: &mut Option<usize>, is_write: bool| {
+        if let Some(writer) = *last_writer {
+            data[pass_index].insert(writer);
+        }
+        if is_write {
+            *last_writer = Some(pass_index);
+        }
+    };
+    for (pass_index, pass) in passes.iter().enumerate() {
+        for (image_index, (_, is_write)) in merged_image_accesses(pass) {
+            access(pass_index, &mut image_writers[image_index], is_write);
+        }
+        for (buffer_index, (_, is_write)) in merged_buffer_accesses(pass) {
+            access(pass_index, &mut buffer_writers[buffer_index], is_write);
+        }
+    }
+
+    for (pass_index, pass_data) in data.iter_mut().enumerate() {
+        pass_data.remove(&pass_index);
+    }
+    data
+}
+
+// Passes with side effects or writing an imported resource are kept, along with the writers they consume
+fn live_passes(images: &[ImageResource], buffers: &[BufferResource], passes: &[Pass<'_>], dependencies: &[BTreeSet<usize>]) -> BTreeSet<usize> {
+    let mut pending: Vec<usize> = passes
+        .iter()
+        .enumerate()
+        .filter(|(_, pass)| {
+            pass.side_effects
+                || pass.image_accesses.iter().any(|&(image, _, is_write)| is_write && matches!(images[image.0].source, ImageSource::Imported(_)))
+                || pass.buffer_accesses.iter().any(|&(buffer, _, is_write)| is_write && matches!(buffers[buffer.0].source, BufferSource::Imported(_)))
+        })
+        .map(|(pass_index, _)| pass_index)
+        .collect();
+
+    let mut live = BTreeSet::new();
+    while let Some(pass_index) = pending.pop() {
+        if live.insert(pass_index) {
+            pending.extend(dependencies[pass_index].iter().copied());
+        }
+    }
+    live
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vulkan_api::render_graph::{ImageAccess, ImageDesc, ImportedImage, RenderGraph};
+
+    const EXTENT: vk::Extent2D = vk::Extent2D { width: 64, height: 64 };
+
+    fn color_image(graph: &mut RenderGraph<'_>, name: &str) -> ImageHandle {
+        graph.create_image(name, ImageDesc::new(vk::Format::R8G8B8A8_UNORM, EXTENT))
+    }
+
+    fn backbuffer(graph: &mut RenderGraph<'_>) -> ImageHandle {
+        let imported = ImportedImage::swapchain(vk::Image::null(), vk::ImageView::null(), vk::Format::B8G8R8A8_SRGB, EXTENT);
+        graph.import_image("backbuffer", imported)
+    }
+
+    fn graph_dependencies(graph: &RenderGraph<'_>) -> Vec<BTreeSet<usize>> {
+        dependencies(&graph.images, &graph.buffers, &graph.passes)
+    }
+
+    #[test]
+    fn reader_depends_on_the_writer_before_it() {
+        let mut graph = RenderGraph::new();
+        let image = color_image(&mut graph, "image");
+        graph.add_pass("write", PassKind::Graphics).write_image(image, ImageAccess::ColorAttachmentWrite).execute(|_| ());
+        graph.add_pass("read", PassKind::Graphics).read_image(image, ImageAccess::SampledFragment).side_effects().execute(|_| ());
+
+        let data = graph_dependencies(&graph);
+        assert_eq!(data[1], BTreeSet::from([0]));
+        assert_eq!(graph.compile().unwrap().pass_order(), ["write", "read"]);
+    }
+
+    #[test]
+    fn writer_waits_for_earlier_readers_and_readers_only_consume_their_writer() {
+        let mut graph = RenderGraph::new();
+        let image = color_image(&mut graph, "image");
+        graph.add_pass("a", PassKind::Graphics).write_image(image, ImageAccess::ColorAttachmentWrite).execute(|_| ());
+        graph.add_pass("b", PassKind::Graphics).read_image(image, ImageAccess::SampledFragment).side_effects().execute(|_| ());
+        graph.add_pass("c", PassKind::Graphics).write_image(image, ImageAccess::ColorAttachmentWrite).execute(|_| ());
+        graph.add_pass("d", PassKind::Graphics).read_image(image, ImageAccess::SampledFragment).side_effects().execute(|_| ());
+
+        let data = graph_dependencies(&graph);
+        // b reads a's output, d reads c's, never the other way around ; c consumes a's write, not b's read
+        assert_eq!(data[1], BTreeSet::from([0]));
+        assert_eq!(data[2], BTreeSet::from([0]));
+        assert_eq!(data[3], BTreeSet::from([2]));
+        let compiled = graph.compile().unwrap();
+        assert_eq!(compiled.pass_order(), ["a", "b", "c", "d"]);
+        // c overwrites what b reads: its barrier waits for b's read (write after read)
+        let barrier = &compiled.pass_barriers[2].images[0];
+        assert!(barrier.src_stage.contains(vk::PipelineStageFlags2::FRAGMENT_SHADER));
+    }
+
+    #[test]
+    fn writers_stay_in_declaration_order() {
+        let mut graph = RenderGraph::new();
+        let backbuffer = backbuffer(&mut graph);
+        graph.add_pass("clear", PassKind::Graphics).write_image(backbuffer, ImageAccess::ColorAttachmentWrite).execute(|_| ());
+        graph.add_pass("draw", PassKind::Graphics).write_image(backbuffer, ImageAccess::ColorAttachmentReadWrite).execute(|_| ());
+
+        assert_eq!(graph_dependencies(&graph)[1], BTreeSet::from([0]));
+        let compiled = graph.compile().unwrap();
+        assert_eq!(compiled.pass_order(), ["clear", "draw"]);
+        // the second write waits for the first one
+        let barrier = &compiled.pass_barriers[1].images[0];
+        assert_eq!(barrier.src_access, vk::AccessFlags2::COLOR_ATTACHMENT_WRITE);
+        assert!(!barrier.first_use);
+    }
+
+    #[test]
+    fn passes_without_consumers_are_culled() {
+        let mut graph = RenderGraph::new();
+        let unused = color_image(&mut graph, "unused");
+        let readback = color_image(&mut graph, "readback");
+        let backbuffer = backbuffer(&mut graph);
+        graph.add_pass("unused", PassKind::Graphics).write_image(unused, ImageAccess::ColorAttachmentWrite).execute(|_| ());
+        graph.add_pass("readback", PassKind::Transfer).write_image(readback, ImageAccess::TransferDst).side_effects().execute(|_| ());
+        graph.add_pass("present", PassKind::Graphics).write_image(backbuffer, ImageAccess::ColorAttachmentWrite).execute(|_| ());
+
+        let compiled = graph.compile().unwrap();
+        assert_eq!(compiled.pass_order(), ["readback", "present"]);
+        assert_eq!(compiled.culled_passes(), ["unused"]);
+        assert!(compiled.image_lifetimes[unused.0].is_none());
+    }
+
+    #[test]
+    fn writers_consumed_by_live_passes_are_kept() {
+        let mut graph = RenderGraph::new();
+        let scene = color_image(&mut graph, "scene");
+        let histogram = color_image(&mut graph, "histogram");
+        let backbuffer = backbuffer(&mut graph);
+        graph.add_pass("scene", PassKind::Graphics).write_image(scene, ImageAccess::ColorAttachmentWrite).execute(|_| ());
+        graph.add_pass("histogram", PassKind::Compute)
+            .read_image(scene, ImageAccess::SampledCompute)
+            .write_image(histogram, ImageAccess::StorageWriteCompute)
+            .execute(|_| ());
+        graph.add_pass("blit", PassKind::Graphics)
+            .read_image(scene, ImageAccess::SampledFragment)
+            .write_image(backbuffer, ImageAccess::ColorAttachmentWrite)
+            .execute(|_| ());
+
+        let compiled = graph.compile().unwrap();
+        assert_eq!(compiled.pass_order(), ["scene", "blit"]);
+        assert_eq!(compiled.culled_passes(), ["histogram"]);
+    }
+
+    #[test]
+    fn depth_color_present_barriers() {
+        let mut graph = RenderGraph::new();
+        let depth = graph.create_image("depth", ImageDesc::new(vk::Format::D32_SFLOAT, EXTENT));
+        let backbuffer = backbuffer(&mut graph);
+        graph.add_pass("depth prepass", PassKind::Graphics).write_image(depth, ImageAccess::DepthAttachmentWrite).execute(|_| ());
+        graph.add_pass("color", PassKind::Graphics)
+            .read_image(depth, ImageAccess::DepthAttachmentRead)
+            .write_image(backbuffer, ImageAccess::ColorAttachmentWrite)
+            .execute(|_| ());
+
+        let compiled = graph.compile().unwrap();
+        assert_eq!(compiled.pass_order(), ["depth prepass", "color"]);
+        assert_eq!(compiled.barrier_count(), 4);
+
+        let layouts = |barriers: &Barriers| -> Vec<(usize, vk::ImageLayout, vk::ImageLayout)> {
+            barriers.images.iter().map(|barrier| (barrier.image.0, barrier.old_layout, barrier.new_layout)).collect()
+        };
+        use vk::ImageLayout as L;
+        assert_eq!(layouts(&compiled.pass_barriers[0]), [(depth.0, L::UNDEFINED, L::DEPTH_STENCIL_ATTACHMENT_OPTIMAL)]);
+        assert_eq!(
+            layouts(&compiled.pass_barriers[1]),
+            [
+                (depth.0, L::DEPTH_STENCIL_ATTACHMENT_OPTIMAL, L::DEPTH_STENCIL_READ_ONLY_OPTIMAL),
+                (backbuffer.0, L::UNDEFINED, L::COLOR_ATTACHMENT_OPTIMAL),
+            ]
+        );
+        assert_eq!(layouts(&compiled.final_barriers), [(backbuffer.0, L::COLOR_ATTACHMENT_OPTIMAL, L::PRESENT_SRC_KHR)]);
+
+        // the transient depth image starts from nothing, the swapchain image from its acquire
+        assert!(compiled.pass_barriers[0].images[0].first_use);
+        let acquire = &compiled.pass_barriers[1].images[1];
+        assert_eq!(acquire.src_stage, vk::PipelineStageFlags2::COLOR_ATTACHMENT_OUTPUT);
+        assert!(!acquire.first_use);
+    }
+
+    #[test]
+    fn unknown_handles_are_rejected() {
+        let mut graph = RenderGraph::new();
+        graph.add_pass("dangling", PassKind::Graphics).write_image(ImageHandle(3), ImageAccess::ColorAttachmentWrite).execute(|_| ());
+        assert!(graph.compile().is_err());
+    }
+}
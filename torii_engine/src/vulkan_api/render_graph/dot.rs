@@ -0,0 +1,85 @@
+use std::fmt::Write;
+
+use super::{BufferSource, CompiledGraph, ImageSource, PassKind};
+
+impl CompiledGraph<'_> {
+    // Graphviz view of the compiled graph: passes in execution order with their barrier count, culled passes
+    // dashed, resources with their lifetime and, once executed, the memory block they alias
+    //     dot -Tsvg graph.dot -o graph.svg
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::from("digraph render_graph {\n    rankdir=LR;\n    node [fontname=\"monospace\"];\n");
+
+        for (position, &pass_index) in self.order.iter().enumerate() {
+            let pass = &self.passes[pass_index];
+            let color = match pass.kind {
+                PassKind::Graphics => "lightblue",
+                PassKind::Compute => "orange",
+                PassKind::Transfer => "lightgray",
+            };
+            let _ = writeln!(
+                dot,
+                "    pass{pass_index} [shape=box, style=filled, fillcolor={color}, label=\"#{position} {}\\n{} barriers\"];",
+                escape(&pass.name),
+                self.pass_barriers[position].len(),
+            );
+        }
+        for &pass_index in self.culled.iter() {
+            let _ = writeln!(
+                dot,
+                "    pass{pass_index} [shape=box, style=dashed, fontcolor=gray, label=\"{}\\n(culled)\"];",
+                escape(&self.passes[pass_index].name),
+            );
+        }
+
+        for (image_index, image) in self.images.iter().enumerate() {
+            let (shape, details) = match image.source {
+                ImageSource::Imported(_) => ("doubleoctagon", String::from("imported")),
+                ImageSource::Transient(desc) => ("ellipse", format!(
+                    "{:?} {}x{}{}{}",
+                    desc.format,
+                    desc.extent.width,
+                    desc.extent.height,
+                    self.image_lifetimes[image_index].map(|lifetime| format!("\\nlive #{}..#{}", lifetime.first, lifetime.last)).unwrap_or_default(),
+                    self.realized.as_ref().and_then(|realized| realized.image_blocks[image_index]).map(|block| format!("\\nmemory block {block}")).unwrap_or_default(),
+                )),
+            };
+            let _ = writeln!(dot, "    image{image_index} [shape={shape}, label=\"{}\\n{details}\"];", escape(&image.name));
+        }
+        for (buffer_index, buffer) in self.buffers.iter().enumerate() {
+            let (shape, details) = match buffer.source {
+                BufferSource::Imported(_) => ("doubleoctagon", String::from("imported")),
+                BufferSource::Transient(desc) => ("ellipse", format!(
+                    "{} bytes{}{}",
+                    desc.size,
+                    self.buffer_lifetimes[buffer_index].map(|lifetime| format!("\\nlive #{}..#{}", lifetime.first, lifetime.last)).unwrap_or_default(),
+                    self.realized.as_ref().and_then(|realized| realized.buffer_blocks[buffer_index]).map(|block| format!("\\nmemory block {block}")).unwrap_or_default(),
+                )),
+            };
+            let _ = writeln!(dot, "    buffer{buffer_index} [shape={shape}, style=rounded, label=\"{}\\n{details}\"];", escape(&buffer.name));
+        }
+
+        // reads point from the resource to the pass, writes from the pass to the resource
+        for (pass_index, pass) in self.passes.iter().enumerate() {
+            for &(image, access, is_write) in pass.image_accesses.iter() {
+                let label = format!("{:?}", access.info().layout);
+                match is_write {
+                    true => { let _ = writeln!(dot, "    pass{pass_index} -> image{} [label=\"{label}\"];", image.0); },
+                    false => { let _ = writeln!(dot, "    image{} -> pass{pass_index} [label=\"{label}\"];", image.0); },
+                }
+            }
+            for &(buffer, _, is_write) in pass.buffer_accesses.iter() {
+                match is_write {
+                    true => { let _ = writeln!(dot, "    pass{pass_index} -> buffer{};", buffer.0); },
+                    false => { let _ = writeln!(dot, "    buffer{} -> pass{pass_index};", buffer.0); },
+                }
+            }
+        }
+
+        dot.push_str("}\n");
+        dot
+    }
+}
+
+fn escape(name: &str) -> String {
+    name.replace('\\', "\\\\").replace('"', "\\\"")
+}
@@ -0,0 +1,18 @@
+use ash::vk;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum RenderGraphError {
+    #[error("Pass {pass} uses {resource} {index}, which does not belong to this graph")]
+    InvalidHandleError { pass: String, resource: &'static str, index: usize },
+    #[error("{0} is not an imported resource")]
+    NotImportedError(String),
+    #[error("Render graph needs the synchronization2 feature, which the device does not have enabled")]
+    Synchronization2UnavailableError,
+    #[error("No memory type suits transient resources sharing memory block {0}")]
+    NoCompatibleMemoryTypeError(usize),
+    #[error("Vulkan call failed while realizing the render graph")]
+    VulkanCallError(#[from] vk::Result),
+}
+
+pub type RenderGraphResult<T> = std::result::Result<T, RenderGraphError>;
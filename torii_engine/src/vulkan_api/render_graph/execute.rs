@@ -0,0 +1,380 @@
+use ash::{vk, Device};
+use tracing::debug;
+
+use super::aliasing::{self, AliasCandidate, MemoryBlock};
+use super::compile::{Barriers, Lifetime};
+use super::{
+    BufferHandle, BufferSource, CompiledGraph, ImageDesc, ImageHandle, ImageSource, PassKind, RenderGraphError,
+    RenderGraphResult,
+};
+use crate::vulkan_api::debug_utils::label_colors;
+use crate::vulkan_api::VkApp;
+
+// Handed to every pass callback while its commands are recorded
+pub struct PassContext<'r> {
+    pub command_buffer: vk::CommandBuffer,
+    pub device: &'r Device,
+    images: &'r [ResolvedImage],
+    buffers: &'r [vk::Buffer],
+}
+
+#[derive(Copy, Clone)]
+struct ResolvedImage {
+    image: vk::Image,
+    view: vk::ImageView,
+    desc: ImageDesc,
+}
+
+impl PassContext<'_> {
+    pub fn image(&self, image: ImageHandle) -> vk::Image {
+        self.images[image.0].image
+    }
+
+    pub fn image_view(&self, image: ImageHandle) -> vk::ImageView {
+        self.images[image.0].view
+    }
+
+    pub fn image_desc(&self, image: ImageHandle) -> &ImageDesc {
+        &self.images[image.0].desc
+    }
+
+    pub fn buffer(&self, buffer: BufferHandle) -> vk::Buffer {
+        self.buffers[buffer.0]
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+enum Transient {
+    Image(usize),
+    Buffer(usize),
+}
+
+// Transient resources of a compiled graph and the memory they share ; destroyed on drop without waiting, so the
+// compiled graph must only be dropped once the GPU is done with its last execution (e.g. its frame fence signaled)
+pub(crate) struct Realized {
+    device: Device,
+    images: Vec<Option<(vk::Image, vk::ImageView)>>,
+    buffers: Vec<Option<vk::Buffer>>,
+    memory: Vec<vk::DeviceMemory>,
+    pub(crate) image_blocks: Vec<Option<usize>>,
+    pub(crate) buffer_blocks: Vec<Option<usize>>,
+    // lifetime of the resource that used the same memory before, waited on by the first barrier
+    image_predecessors: Vec<Option<Lifetime>>,
+    buffer_predecessors: Vec<Option<Lifetime>>,
+    // lifetime of the last resource in the same memory, which the first barrier of the next execution waits on: the
+    // previous frame may still be using the memory
+    image_last_occupants: Vec<Option<Lifetime>>,
+    buffer_last_occupants: Vec<Option<Lifetime>>,
+    executed: bool,
+}
+
+impl Realized {
+    // what the first use of a transient image waits for, if anything
+    fn image_predecessor(&self, image_index: usize) -> Option<Lifetime> {
+        self.image_predecessors[image_index].or(if self.executed { self.image_last_occupants[image_index] } else { None })
+    }
+
+    fn buffer_predecessor(&self, buffer_index: usize) -> Option<Lifetime> {
+        self.buffer_predecessors[buffer_index].or(if self.executed { self.buffer_last_occupants[buffer_index] } else { None })
+    }
+}
+
+impl Drop for Realized {
+    fn drop(&mut self) {
+        unsafe {
+            for &(image, view) in self.images.iter().flatten() {
+                self.device.destroy_image_view(view, None);
+                self.device.destroy_image(image, None);
+            }
+            for &buffer in self.buffers.iter().flatten() {
+                self.device.destroy_buffer(buffer, None);
+            }
+            for &memory in self.memory.iter() {
+                self.device.free_memory(memory, None);
+            }
+        }
+    }
+}
+
+impl CompiledGraph<'_> {
+    // Records every live pass with its barriers into `command_buffer` ; the transient resources are created on the
+    // first call and reused afterwards, each execution waiting on the previous one's last use of their memory
+    pub fn execute(&mut self, vk_app: &VkApp, command_buffer: vk::CommandBuffer) -> RenderGraphResult<()> {
+        if !vk_app.is_feature_enabled(crate::device_feature!(vulkan13.synchronization2)) {
+            return Err(RenderGraphError::Synchronization2UnavailableError);
+        }
+        if self.realized.is_none() {
+            self.realized = Some(self.realize(vk_app)?);
+        }
+
+        let device = vk_app.device();
+        let debug_utils = vk_app.debug_utils();
+        let (images, buffers) = self.resolve();
+        for (position, &pass_index) in self.order.iter().enumerate() {
+            self.record_barriers(device, command_buffer, &self.pass_barriers[position], &images, &buffers);
+
+            let pass = &mut self.passes[pass_index];
+            let color = match pass.kind {
+                PassKind::Graphics => label_colors::GRAPHICS,
+                PassKind::Compute => label_colors::COMPUTE,
+                PassKind::Transfer => label_colors::TRANSFER,
+            };
+            let _label = debug_utils.cmd_scoped_label(command_buffer, &pass.name, color);
+            (pass.callback)(&PassContext {
+                command_buffer,
+                device,
+                images: &images,
+                buffers: &buffers,
+            });
+        }
+        self.record_barriers(device, command_buffer, &self.final_barriers, &images, &buffers);
+        if let Some(realized) = self.realized.as_mut() {
+            realized.executed = true;
+        }
+        Ok(())
+    }
+
+    // swaps the handles of an imported image, e.g. for this frame's swapchain image ; its description and
+    // accesses are those given at import
+    pub fn set_imported_image(&mut self, image: ImageHandle, new_image: vk::Image, new_view: vk::ImageView) -> RenderGraphResult<()> {
+        let resource = self.images.get_mut(image.0).ok_or_else(|| RenderGraphError::NotImportedError(format!("image {}", image.0)))?;
+        match &mut resource.source {
+            ImageSource::Imported(imported) => {
+                imported.image = new_image;
+                imported.view = new_view;
+                Ok(())
+            },
+            ImageSource::Transient(_) => Err(RenderGraphError::NotImportedError(resource.name.clone())),
+        }
+    }
+
+    pub fn set_imported_buffer(&mut self, buffer: BufferHandle, new_buffer: vk::Buffer) -> RenderGraphResult<()> {
+        let resource = self.buffers.get_mut(buffer.0).ok_or_else(|| RenderGraphError::NotImportedError(format!("buffer {}", buffer.0)))?;
+        match &mut resource.source {
+            BufferSource::Imported(imported) => {
+                imported.buffer = new_buffer;
+                Ok(())
+            },
+            BufferSource::Transient(_) => Err(RenderGraphError::NotImportedError(resource.name.clone())),
+        }
+    }
+}
+
+impl CompiledGraph<'_> {
+    fn realize(&self, vk_app: &VkApp) -> RenderGraphResult<Realized> {
+        let device = vk_app.device();
+        let mut realized = Realized {
+            device: device.clone(),
+            images: vec![None; self.images.len()],
+            buffers: vec![None; self.buffers.len()],
+            memory: vec![],
+            image_blocks: vec![None; self.images.len()],
+            buffer_blocks: vec![None; self.buffers.len()],
+            image_predecessors: vec![None; self.images.len()],
+            buffer_predecessors: vec![None; self.buffers.len()],
+            image_last_occupants: vec![None; self.images.len()],
+            buffer_last_occupants: vec![None; self.buffers.len()],
+            executed: false,
+        };
+
+        // resources never used by a live pass are not created at all
+        let mut transients = vec![];
+        let mut candidates = vec![];
+        for (image_index, image) in self.images.iter().enumerate() {
+            let (ImageSource::Transient(desc), Some(lifetime)) = (image.source, self.image_lifetimes[image_index]) else { continue };
+            let usage = self.order
+                .iter()
+                .flat_map(|&pass_index| self.passes[pass_index].image_accesses.iter())
+                .filter(|(handle, _, _)| handle.0 == image_index)
+                .fold(vk::ImageUsageFlags::empty(), |usage, (_, access, _)| usage | access.usage());
+            let image_create_info = vk::ImageCreateInfo::default()
+                .flags(desc.create_flags())
+                .image_type(vk::ImageType::TYPE_2D)
+                .format(desc.format)
+                .extent(vk::Extent3D { width: desc.extent.width, height: desc.extent.height, depth: 1 })
+                .mip_levels(desc.mip_levels)
+                .array_layers(desc.array_layers)
+                .samples(desc.samples)
+                .tiling(vk::ImageTiling::OPTIMAL)
+                .usage(usage)
+                .sharing_mode(vk::SharingMode::EXCLUSIVE)
+                .initial_layout(vk::ImageLayout::UNDEFINED);
+            let vk_image = unsafe { device.create_image(&image_create_info, None)? };
+            realized.images[image_index] = Some((vk_image, vk::ImageView::null()));
+            vk_app.debug_utils().set_object_name(vk_image, &format!("{} [render graph image]", image.name));
+            transients.push(Transient::Image(image_index));
+            candidates.push(AliasCandidate {
+                lifetime,
+                requirements: unsafe { device.get_image_memory_requirements(vk_image) },
+            });
+        }
+        for (buffer_index, buffer) in self.buffers.iter().enumerate() {
+            let (BufferSource::Transient(desc), Some(lifetime)) = (buffer.source, self.buffer_lifetimes[buffer_index]) else { continue };
+            let usage = self.order
+                .iter()
+                .flat_map(|&pass_index| self.passes[pass_index].buffer_accesses.iter())
+                .filter(|(handle, _, _)| handle.0 == buffer_index)
+                .fold(vk::BufferUsageFlags::empty(), |usage, (_, access, _)| usage | access.usage());
+            let buffer_create_info = vk::BufferCreateInfo::default()
+                .size(desc.size)
+                .usage(usage)
+                .sharing_mode(vk::SharingMode::EXCLUSIVE);
+            let vk_buffer = unsafe { device.create_buffer(&buffer_create_info, None)? };
+            realized.buffers[buffer_index] = Some(vk_buffer);
+            vk_app.debug_utils().set_object_name(vk_buffer, &format!("{} [render graph buffer]", buffer.name));
+            transients.push(Transient::Buffer(buffer_index));
+            candidates.push(AliasCandidate {
+                lifetime,
+                requirements: unsafe { device.get_buffer_memory_requirements(vk_buffer) },
+            });
+        }
+
+        let (blocks, assignment) = aliasing::assign_blocks(&candidates);
+        let memory_properties = unsafe {
+            vk_app.instance().get_physical_device_memory_properties(vk_app.physical_device())
+        };
+        for (block_index, block) in blocks.iter().enumerate() {
+            let memory_type_index = Self::memory_type_index(&memory_properties, block)
+                .ok_or(RenderGraphError::NoCompatibleMemoryTypeError(block_index))?;
+            let allocate_info = vk::MemoryAllocateInfo::default()
+                .allocation_size(block.size)
+                .memory_type_index(memory_type_index);
+            let memory = unsafe { device.allocate_memory(&allocate_info, None)? };
+            vk_app.debug_utils().set_object_name(memory, &format!("render graph memory block {block_index}"));
+            realized.memory.push(memory);
+        }
+        debug!(
+            target: "torii::vulkan::render_graph",
+            transient_resources = candidates.len(),
+            memory_blocks = blocks.len(),
+            unaliased_bytes = candidates.iter().map(|candidate| candidate.requirements.size).sum::<u64>(),
+            aliased_bytes = blocks.iter().map(|block| block.size).sum::<u64>(),
+            "Realized render graph"
+        );
+
+        for (candidate_index, &transient) in transients.iter().enumerate() {
+            let block_index = assignment[candidate_index];
+            let memory = realized.memory[block_index];
+            let predecessor = aliasing::previous_occupant(&blocks[block_index], candidate_index)
+                .map(|previous| candidates[previous].lifetime);
+            let last_occupant = blocks[block_index].occupants.last().map(|&last| candidates[last].lifetime);
+            match transient {
+                Transient::Image(image_index) => {
+                    let (vk_image, _) = realized.images[image_index].unwrap();
+                    let desc = self.images[image_index].source.desc();
+                    unsafe { device.bind_image_memory(vk_image, memory, 0)? };
+                    let view_create_info = vk::ImageViewCreateInfo::default()
+                        .image(vk_image)
+                        .view_type(desc.view_type())
+                        .format(desc.format)
+                        .subresource_range(desc.subresource_range());
+                    let view = unsafe { device.create_image_view(&view_create_info, None)? };
+                    realized.images[image_index] = Some((vk_image, view));
+                    realized.image_blocks[image_index] = Some(block_index);
+                    realized.image_predecessors[image_index] = predecessor;
+                    realized.image_last_occupants[image_index] = last_occupant;
+                },
+                Transient::Buffer(buffer_index) => {
+                    unsafe { device.bind_buffer_memory(realized.buffers[buffer_index].unwrap(), memory, 0)? };
+                    realized.buffer_blocks[buffer_index] = Some(block_index);
+                    realized.buffer_predecessors[buffer_index] = predecessor;
+                    realized.buffer_last_occupants[buffer_index] = last_occupant;
+                },
+            }
+        }
+        Ok(realized)
+    }
+
+    // device local memory when possible
+    fn memory_type_index(memory_properties: &vk::PhysicalDeviceMemoryProperties, block: &MemoryBlock) -> Option<u32> {
+        let allowed = |index: &u32| block.memory_type_bits & (1 << index) != 0;
+        let memory_types = memory_properties.memory_types_as_slice();
+        (0..memory_types.len() as u32)
+            .filter(allowed)
+            .find(|&index| memory_types[index as usize].property_flags.contains(vk::MemoryPropertyFlags::DEVICE_LOCAL))
+            .or_else(|| (0..memory_types.len() as u32).find(allowed))
+    }
+
+    fn resolve(&self) -> (Vec<ResolvedImage>, Vec<vk::Buffer>) {
+        let realized = self.realized.as_ref().expect("Render graph has not been realized!");
+        let images = self.images
+            .iter()
+            .enumerate()
+            .map(|(image_index, image)| match image.source {
+                ImageSource::Transient(desc) => {
+                    let (image, view) = realized.images[image_index].unwrap_or_default();
+                    ResolvedImage { image, view, desc }
+                },
+                ImageSource::Imported(imported) => ResolvedImage { image: imported.image, view: imported.view, desc: imported.desc },
+            })
+            .collect();
+        let buffers = self.buffers
+            .iter()
+            .enumerate()
+            .map(|(buffer_index, buffer)| match buffer.source {
+                BufferSource::Transient(_) => realized.buffers[buffer_index].unwrap_or_default(),
+                BufferSource::Imported(imported) => imported.buffer,
+            })
+            .collect();
+        (images, buffers)
+    }
+
+    fn record_barriers(&self, device: &Device, command_buffer: vk::CommandBuffer, barriers: &Barriers, images: &[ResolvedImage], buffers: &[vk::Buffer]) {
+        let realized = self.realized.as_ref().expect("Render graph has not been realized!");
+        let image_barriers: Vec<vk::ImageMemoryBarrier2> = barriers.images
+            .iter()
+            .map(|barrier| {
+                let predecessor = barrier.first_use.then(|| realized.image_predecessor(barrier.image.0)).flatten();
+                let (src_stage, src_access) = match predecessor {
+                    Some(predecessor) => (predecessor.stages, predecessor.writes),
+                    None => (barrier.src_stage, barrier.src_access),
+                };
+                let resolved = &images[barrier.image.0];
+                vk::ImageMemoryBarrier2::default()
+                    .src_stage_mask(src_stage)
+                    .src_access_mask(src_access)
+                    .dst_stage_mask(barrier.dst_stage)
+                    .dst_access_mask(barrier.dst_access)
+                    .old_layout(barrier.old_layout)
+                    .new_layout(barrier.new_layout)
+                    .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+                    .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+                    .image(resolved.image)
+                    .subresource_range(resolved.desc.subresource_range())
+            })
+            .collect();
+        // a buffer's first use only needs a barrier when its memory was used before, by another resource or the
+        // previous execution
+        let buffer_barriers: Vec<vk::BufferMemoryBarrier2> = barriers.buffers
+            .iter()
+            .filter_map(|barrier| {
+                let (src_stage, src_access) = if barrier.first_use {
+                    let predecessor = realized.buffer_predecessor(barrier.buffer.0)?;
+                    (predecessor.stages, predecessor.writes)
+                } else {
+                    (barrier.src_stage, barrier.src_access)
+                };
+                Some(vk::BufferMemoryBarrier2::default()
+                    .src_stage_mask(src_stage)
+                    .src_access_mask(src_access)
+                    .dst_stage_mask(barrier.dst_stage)
+                    .dst_access_mask(barrier.dst_access)
+                    .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+                    .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+                    .buffer(buffers[barrier.buffer.0])
+                    .offset(0)
+                    .size(vk::WHOLE_SIZE))
+            })
+            .collect();
+        if image_barriers.is_empty() && buffer_barriers.is_empty() {
+            return;
+        }
+
+        let dependency_info = vk::DependencyInfo::default()
+            .image_memory_barriers(&image_barriers)
+            .buffer_memory_barriers(&buffer_barriers);
+        unsafe {
+            device.cmd_pipeline_barrier2(command_buffer, &dependency_info);
+        }
+    }
+}
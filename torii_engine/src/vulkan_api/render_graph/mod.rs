@@ -0,0 +1,285 @@
+// Frame described as passes declaring which images and buffers they read and write. Compiling the graph keeps the
+// passes in declaration order, culls the ones nothing depends on and derives every synchronization2 barrier and
+// layout transition ; executing it allocates the transient resources (aliasing the memory of those whose lifetimes
+// do not overlap) and records the passes.
+//
+//     let mut graph = RenderGraph::new();
+//     let depth = graph.create_image("depth", ImageDesc::new(vk::Format::D32_SFLOAT, extent));
+//     let backbuffer = graph.import_image("backbuffer", ImportedImage::swapchain(image, view, format, extent));
+//     graph.add_pass("opaque", PassKind::Graphics)
+//         .write_image(depth, ImageAccess::DepthAttachmentWrite)
+//         .write_image(backbuffer, ImageAccess::ColorAttachmentWrite)
+//         .execute(|context| { /* record draws into context.command_buffer */ });
+//     let mut compiled = graph.compile()?;
+//     compiled.execute(vk_app, command_buffer)?;
+
+mod access;
+pub use access::*;
+mod error;
+pub use error::*;
+mod compile;
+pub use compile::*;
+mod aliasing;
+mod execute;
+pub use execute::PassContext;
+mod dot;
+
+use ash::vk;
+
+// shared with images created outside the graph
+pub use super::image::ImageDesc;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ImageHandle(usize);
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct BufferHandle(usize);
+
+impl ImageHandle {
+    pub fn index(&self) -> usize {
+        self.0
+    }
+}
+
+impl BufferHandle {
+    pub fn index(&self) -> usize {
+        self.0
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct BufferDesc {
+    pub size: vk::DeviceSize,
+}
+
+impl BufferDesc {
+    pub fn new(size: vk::DeviceSize) -> Self {
+        BufferDesc { size }
+    }
+}
+
+// Image owned outside the graph ; `initial_access` is its state when the graph starts and `final_access`, if any,
+// the state it is left in
+#[derive(Copy, Clone, Debug)]
+pub struct ImportedImage {
+    pub image: vk::Image,
+    pub view: vk::ImageView,
+    pub desc: ImageDesc,
+    pub initial_access: AccessInfo,
+    pub final_access: Option<AccessInfo>,
+}
+
+impl ImportedImage {
+    pub fn new(image: vk::Image, view: vk::ImageView, desc: ImageDesc) -> Self {
+        ImportedImage {
+            image,
+            view,
+            desc,
+            initial_access: AccessInfo::none(),
+            final_access: None,
+        }
+    }
+
+    // freshly acquired swapchain image (its acquire semaphore waited on at COLOR_ATTACHMENT_OUTPUT), handed to
+    // presentation afterwards
+    pub fn swapchain(image: vk::Image, view: vk::ImageView, format: vk::Format, extent: vk::Extent2D) -> Self {
+        ImportedImage {
+            initial_access: AccessInfo::new(
+                vk::PipelineStageFlags2::COLOR_ATTACHMENT_OUTPUT,
+                vk::AccessFlags2::NONE,
+                vk::ImageLayout::UNDEFINED,
+            ),
+            final_access: Some(ImageAccess::Present.info()),
+            ..Self::new(image, view, ImageDesc::new(format, extent))
+        }
+    }
+
+    pub fn with_initial_access(mut self, initial_access: AccessInfo) -> Self {
+        self.initial_access = initial_access;
+        self
+    }
+
+    pub fn with_final_access(mut self, final_access: Option<AccessInfo>) -> Self {
+        self.final_access = final_access;
+        self
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct ImportedBuffer {
+    pub buffer: vk::Buffer,
+    pub desc: BufferDesc,
+    pub initial_access: AccessInfo,
+    pub final_access: Option<AccessInfo>,
+}
+
+impl ImportedBuffer {
+    pub fn new(buffer: vk::Buffer, desc: BufferDesc) -> Self {
+        ImportedBuffer {
+            buffer,
+            desc,
+            initial_access: AccessInfo::none(),
+            final_access: None,
+        }
+    }
+
+    pub fn with_initial_access(mut self, initial_access: AccessInfo) -> Self {
+        self.initial_access = initial_access;
+        self
+    }
+
+    pub fn with_final_access(mut self, final_access: Option<AccessInfo>) -> Self {
+        self.final_access = final_access;
+        self
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+pub(crate) enum ImageSource {
+    Transient(ImageDesc),
+    Imported(ImportedImage),
+}
+
+#[derive(Copy, Clone, Debug)]
+pub(crate) enum BufferSource {
+    Transient(BufferDesc),
+    Imported(ImportedBuffer),
+}
+
+impl ImageSource {
+    pub(crate) fn desc(&self) -> &ImageDesc {
+        match self {
+            ImageSource::Transient(desc) => desc,
+            ImageSource::Imported(imported) => &imported.desc,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub(crate) struct ImageResource {
+    pub(crate) name: String,
+    pub(crate) source: ImageSource,
+}
+
+#[derive(Clone, Debug)]
+pub(crate) struct BufferResource {
+    pub(crate) name: String,
+    pub(crate) source: BufferSource,
+}
+
+// decides the label color in captures and the node color in the DOT dump
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum PassKind {
+    Graphics,
+    Compute,
+    Transfer,
+}
+
+pub(crate) type PassCallback<'a> = Box<dyn FnMut(&PassContext) + 'a>;
+
+pub(crate) struct Pass<'a> {
+    pub(crate) name: String,
+    pub(crate) kind: PassKind,
+    pub(crate) image_accesses: Vec<(ImageHandle, ImageAccess, bool)>,
+    pub(crate) buffer_accesses: Vec<(BufferHandle, BufferAccess, bool)>,
+    // kept even when nothing reads its outputs, e.g. readbacks or passes writing through raw handles
+    pub(crate) side_effects: bool,
+    pub(crate) callback: PassCallback<'a>,
+}
+
+#[derive(Default)]
+pub struct RenderGraph<'a> {
+    images: Vec<ImageResource>,
+    buffers: Vec<BufferResource>,
+    passes: Vec<Pass<'a>>,
+}
+
+impl<'a> RenderGraph<'a> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // transient images live for this graph only, their memory may be shared with other transient resources
+    pub fn create_image(&mut self, name: impl Into<String>, desc: ImageDesc) -> ImageHandle {
+        self.images.push(ImageResource { name: name.into(), source: ImageSource::Transient(desc) });
+        ImageHandle(self.images.len() - 1)
+    }
+
+    pub fn import_image(&mut self, name: impl Into<String>, imported: ImportedImage) -> ImageHandle {
+        self.images.push(ImageResource { name: name.into(), source: ImageSource::Imported(imported) });
+        ImageHandle(self.images.len() - 1)
+    }
+
+    pub fn create_buffer(&mut self, name: impl Into<String>, desc: BufferDesc) -> BufferHandle {
+        self.buffers.push(BufferResource { name: name.into(), source: BufferSource::Transient(desc) });
+        BufferHandle(self.buffers.len() - 1)
+    }
+
+    pub fn import_buffer(&mut self, name: impl Into<String>, imported: ImportedBuffer) -> BufferHandle {
+        self.buffers.push(BufferResource { name: name.into(), source: BufferSource::Imported(imported) });
+        BufferHandle(self.buffers.len() - 1)
+    }
+
+    // the pass is only added once PassBuilder::execute is called
+    pub fn add_pass(&mut self, name: impl Into<String>, kind: PassKind) -> PassBuilder<'_, 'a> {
+        PassBuilder {
+            graph: self,
+            name: name.into(),
+            kind,
+            image_accesses: vec![],
+            buffer_accesses: vec![],
+            side_effects: false,
+        }
+    }
+
+    pub fn compile(self) -> RenderGraphResult<CompiledGraph<'a>> {
+        compile::compile(self.images, self.buffers, self.passes)
+    }
+}
+
+pub struct PassBuilder<'g, 'a> {
+    graph: &'g mut RenderGraph<'a>,
+    name: String,
+    kind: PassKind,
+    image_accesses: Vec<(ImageHandle, ImageAccess, bool)>,
+    buffer_accesses: Vec<(BufferHandle, BufferAccess, bool)>,
+    side_effects: bool,
+}
+
+impl<'a> PassBuilder<'_, 'a> {
+    pub fn read_image(mut self, image: ImageHandle, access: ImageAccess) -> Self {
+        self.image_accesses.push((image, access, false));
+        self
+    }
+
+    pub fn write_image(mut self, image: ImageHandle, access: ImageAccess) -> Self {
+        self.image_accesses.push((image, access, true));
+        self
+    }
+
+    pub fn read_buffer(mut self, buffer: BufferHandle, access: BufferAccess) -> Self {
+        self.buffer_accesses.push((buffer, access, false));
+        self
+    }
+
+    pub fn write_buffer(mut self, buffer: BufferHandle, access: BufferAccess) -> Self {
+        self.buffer_accesses.push((buffer, access, true));
+        self
+    }
+
+    pub fn side_effects(mut self) -> Self {
+        self.side_effects = true;
+        self
+    }
+
+    pub fn execute(self, callback: impl FnMut(&PassContext) + 'a) {
+        self.graph.passes.push(Pass {
+            name: self.name,
+            kind: self.kind,
+            image_accesses: self.image_accesses,
+            buffer_accesses: self.buffer_accesses,
+            side_effects: self.side_effects,
+            callback: Box::new(callback),
+        });
+    }
+}
@@ -0,0 +1,375 @@
+// Attachments a window renders into besides its swapchain images: a depth(/stencil) buffer in the best format the
+// device offers, a multisampled color buffer resolved into the swapchain image when MSAA is on, and named offscreen
+// targets sized after the swapchain or fixed. Every window owns one set (ManagedWindow::render_targets), recreated
+// together with its swapchain ; offscreen targets are added with AppHandler::add_offscreen_target.
+//
+//     let targets = managed_window.render_targets().unwrap();
+//     let color = targets.color_attachment(swapchain.image_views()[image_index], Some([0.0, 0.0, 0.0, 1.0]));
+//     let depth = targets.depth_attachment(Some(1.0));
+//     let rendering_info = vk::RenderingInfo::default()
+//         .render_area(targets.extent().into())
+//         .layer_count(1)
+//         .color_attachments(std::slice::from_ref(&color))
+//         .depth_attachment(depth.as_ref().unwrap());
+
+use std::collections::BTreeMap;
+
+use ash::vk;
+use tracing::debug;
+
+use crate::config::RendererConfig;
+use super::image::{find_supported_format, GpuImage, ImageDesc};
+use super::{RenderTargetError, RenderTargetResult, VkApp};
+
+// Best depth format usable as an attachment, with a stencil aspect when asked for
+pub fn find_depth_format(vk_app: &VkApp, stencil: bool) -> Option<vk::Format> {
+    let candidates: &[vk::Format] = if stencil {
+        &[vk::Format::D32_SFLOAT_S8_UINT, vk::Format::D24_UNORM_S8_UINT, vk::Format::D16_UNORM_S8_UINT]
+    } else {
+        &[vk::Format::D32_SFLOAT, vk::Format::X8_D24_UNORM_PACK32, vk::Format::D16_UNORM]
+    };
+    find_supported_format(vk_app, candidates, vk::FormatFeatureFlags::DEPTH_STENCIL_ATTACHMENT)
+}
+
+// Highest sample count not above `requested` that color and depth framebuffer attachments both support ; a single
+// sample when `requested` is 0 or 1
+pub fn supported_sample_count(vk_app: &VkApp, requested: u32) -> vk::SampleCountFlags {
+    let limits = unsafe {
+        vk_app.instance().get_physical_device_properties(vk_app.physical_device()).limits
+    };
+    let supported = limits.framebuffer_color_sample_counts & limits.framebuffer_depth_sample_counts;
+    [64, 32, 16, 8, 4, 2]
+        .into_iter()
+        .filter(|&count| count <= requested)
+        .map(vk::SampleCountFlags::from_raw)
+        .find(|&count| supported.contains(count))
+        .unwrap_or(vk::SampleCountFlags::TYPE_1)
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum TargetSize {
+    // follows the swapchain extent
+    Swapchain,
+    // swapchain extent times the factor, e.g. 0.5 for half resolution effects
+    Scaled(f32),
+    Fixed(vk::Extent2D),
+}
+
+impl TargetSize {
+    pub fn extent(&self, swapchain_extent: vk::Extent2D) -> vk::Extent2D {
+        let extent = match *self {
+            TargetSize::Swapchain => swapchain_extent,
+            TargetSize::Scaled(factor) => vk::Extent2D {
+                width: (swapchain_extent.width as f32 * factor).round() as u32,
+                height: (swapchain_extent.height as f32 * factor).round() as u32,
+            },
+            TargetSize::Fixed(extent) => extent,
+        };
+        vk::Extent2D { width: extent.width.max(1), height: extent.height.max(1) }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct OffscreenTargetDesc {
+    pub format: vk::Format,
+    pub usage: vk::ImageUsageFlags,
+    pub size: TargetSize,
+    // rendered with the window's MSAA sample count and resolved into a single sample image
+    pub multisampled: bool,
+}
+
+impl OffscreenTargetDesc {
+    pub fn new(format: vk::Format, usage: vk::ImageUsageFlags) -> Self {
+        OffscreenTargetDesc {
+            format,
+            usage,
+            size: TargetSize::Swapchain,
+            multisampled: false,
+        }
+    }
+
+    pub fn with_size(mut self, size: TargetSize) -> Self {
+        self.size = size;
+        self
+    }
+
+    pub fn multisampled(mut self) -> Self {
+        self.multisampled = true;
+        self
+    }
+}
+
+// An offscreen target ; passes render into `attachment` and read `output`, which is the resolved image when multisampled
+pub struct RenderTarget {
+    attachment: GpuImage,
+    resolve: Option<GpuImage>,
+}
+
+impl RenderTarget {
+    // GETTERS
+
+    pub fn attachment(&self) -> &GpuImage {
+        &self.attachment
+    }
+
+    pub fn resolve(&self) -> Option<&GpuImage> {
+        self.resolve.as_ref()
+    }
+
+    pub fn output(&self) -> &GpuImage {
+        self.resolve.as_ref().unwrap_or(&self.attachment)
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct RenderTargetSettings {
+    pub depth: bool,
+    pub stencil: bool,
+    // clamped with supported_sample_count
+    pub msaa_samples: u32,
+}
+
+impl Default for RenderTargetSettings {
+    fn default() -> Self {
+        RenderTargetSettings {
+            depth: true,
+            stencil: false,
+            msaa_samples: 1,
+        }
+    }
+}
+
+impl From<&RendererConfig> for RenderTargetSettings {
+    fn from(config: &RendererConfig) -> Self {
+        RenderTargetSettings {
+            depth: true,
+            stencil: config.stencil,
+            msaa_samples: config.msaa_samples,
+        }
+    }
+}
+
+pub struct RenderTargets {
+    settings: RenderTargetSettings,
+    color_format: vk::Format,
+    depth_format: Option<vk::Format>,
+    samples: vk::SampleCountFlags,
+    extent: vk::Extent2D,
+    depth: Option<GpuImage>,
+    // rendered into instead of the swapchain image when multisampled, then resolved into it
+    msaa_color: Option<GpuImage>,
+    offscreen: BTreeMap<String, (OffscreenTargetDesc, RenderTarget)>,
+}
+
+impl RenderTargets {
+    // `color_format` and `extent` are those of the swapchain the targets go with
+    pub fn new(vk_app: &VkApp, settings: RenderTargetSettings, color_format: vk::Format, extent: vk::Extent2D) -> RenderTargetResult<Self> {
+        let depth_format = match settings.depth {
+            true => Some(find_depth_format(vk_app, settings.stencil).ok_or(RenderTargetError::NoDepthFormatError(settings.stencil))?),
+            false => None,
+        };
+        let samples = supported_sample_count(vk_app, settings.msaa_samples);
+        if samples.as_raw() != settings.msaa_samples.max(1) {
+            debug!(target: "torii::vulkan", requested = settings.msaa_samples, used = samples.as_raw(), "Clamped MSAA sample count");
+        }
+
+        let mut render_targets = RenderTargets {
+            settings,
+            color_format,
+            depth_format,
+            samples,
+            extent,
+            depth: None,
+            msaa_color: None,
+            offscreen: BTreeMap::new(),
+        };
+        render_targets.recreate(vk_app)?;
+        Ok(render_targets)
+    }
+
+    // Follows a recreated swapchain ; nothing happens while the window is minimized (zero extent) or when neither
+    // the extent nor the format changed. Waits for the device to be idle before destroying the old images.
+    pub fn resize(&mut self, vk_app: &VkApp, color_format: vk::Format, extent: vk::Extent2D) -> RenderTargetResult<()> {
+        if extent.width == 0 || extent.height == 0 || (extent == self.extent && color_format == self.color_format) {
+            return Ok(());
+        }
+        unsafe {
+            vk_app.device().device_wait_idle()?;
+        }
+        self.color_format = color_format;
+        self.extent = extent;
+        self.recreate(vk_app)
+    }
+
+    // Adds (or replaces) the offscreen target called `name`
+    pub fn add_offscreen(&mut self, vk_app: &VkApp, name: &str, desc: OffscreenTargetDesc) -> RenderTargetResult<&RenderTarget> {
+        let target = self.create_offscreen(vk_app, name, &desc)?;
+        self.offscreen.insert(name.to_owned(), (desc, target));
+        Ok(&self.offscreen[name].1)
+    }
+
+    // the GPU must be done with the target
+    pub fn remove_offscreen(&mut self, name: &str) -> bool {
+        self.offscreen.remove(name).is_some()
+    }
+
+    pub fn offscreen(&self, name: &str) -> Option<&RenderTarget> {
+        self.offscreen.get(name).map(|(_, target)| target)
+    }
+
+    // Color attachment for dynamic rendering into the swapchain image behind `swapchain_view` (in
+    // COLOR_ATTACHMENT_OPTIMAL) ; with MSAA the multisampled image is rendered into and resolved into the swapchain one
+    pub fn color_attachment(&self, swapchain_view: vk::ImageView, clear: Option<[f32; 4]>) -> vk::RenderingAttachmentInfo<'static> {
+        let attachment = vk::RenderingAttachmentInfo::default()
+            .image_layout(vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL)
+            .load_op(if clear.is_some() { vk::AttachmentLoadOp::CLEAR } else { vk::AttachmentLoadOp::LOAD })
+            .clear_value(vk::ClearValue { color: vk::ClearColorValue { float32: clear.unwrap_or_default() } });
+        match self.msaa_color.as_ref() {
+            Some(msaa_color) => attachment
+                .image_view(msaa_color.view())
+                // only the resolved image is kept
+                .store_op(vk::AttachmentStoreOp::DONT_CARE)
+                .resolve_mode(vk::ResolveModeFlags::AVERAGE)
+                .resolve_image_view(swapchain_view)
+                .resolve_image_layout(vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL),
+            None => attachment
+                .image_view(swapchain_view)
+                .store_op(vk::AttachmentStoreOp::STORE),
+        }
+    }
+
+    // Depth attachment in DEPTH_STENCIL_ATTACHMENT_OPTIMAL, None without a depth buffer
+    pub fn depth_attachment(&self, clear: Option<f32>) -> Option<vk::RenderingAttachmentInfo<'static>> {
+        let depth = self.depth.as_ref()?;
+        Some(vk::RenderingAttachmentInfo::default()
+            .image_view(depth.view())
+            .image_layout(vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL)
+            .load_op(if clear.is_some() { vk::AttachmentLoadOp::CLEAR } else { vk::AttachmentLoadOp::LOAD })
+            .store_op(vk::AttachmentStoreOp::STORE)
+            .clear_value(vk::ClearValue {
+                depth_stencil: vk::ClearDepthStencilValue { depth: clear.unwrap_or(1.0), stencil: 0 },
+            }))
+    }
+
+    // GETTERS
+
+    pub fn settings(&self) -> RenderTargetSettings {
+        self.settings
+    }
+
+    pub fn color_format(&self) -> vk::Format {
+        self.color_format
+    }
+
+    pub fn depth_format(&self) -> Option<vk::Format> {
+        self.depth_format
+    }
+
+    pub fn has_stencil(&self) -> bool {
+        self.depth.as_ref().is_some_and(|depth| depth.desc().aspect_mask().contains(vk::ImageAspectFlags::STENCIL))
+    }
+
+    pub fn samples(&self) -> vk::SampleCountFlags {
+        self.samples
+    }
+
+    pub fn extent(&self) -> vk::Extent2D {
+        self.extent
+    }
+
+    pub fn depth(&self) -> Option<&GpuImage> {
+        self.depth.as_ref()
+    }
+
+    pub fn msaa_color(&self) -> Option<&GpuImage> {
+        self.msaa_color.as_ref()
+    }
+}
+
+impl RenderTargets {
+    fn recreate(&mut self, vk_app: &VkApp) -> RenderTargetResult<()> {
+        // drop the old images first so their memory can be reused
+        self.depth = None;
+        self.msaa_color = None;
+
+        if let Some(depth_format) = self.depth_format {
+            self.depth = Some(GpuImage::new(
+                vk_app,
+                ImageDesc::new(depth_format, self.extent).with_samples(self.samples),
+                vk::ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT,
+                "depth target",
+            )?);
+        }
+        if self.samples != vk::SampleCountFlags::TYPE_1 {
+            self.msaa_color = Some(GpuImage::new(
+                vk_app,
+                ImageDesc::new(self.color_format, self.extent).with_samples(self.samples),
+                // never read back once resolved, lazily allocated memory would do on tilers
+                vk::ImageUsageFlags::COLOR_ATTACHMENT | vk::ImageUsageFlags::TRANSIENT_ATTACHMENT,
+                "msaa color target",
+            )?);
+        }
+
+        let offscreen = std::mem::take(&mut self.offscreen);
+        for (name, (desc, target)) in offscreen {
+            drop(target);
+            let target = self.create_offscreen(vk_app, &name, &desc)?;
+            self.offscreen.insert(name, (desc, target));
+        }
+
+        debug!(
+            target: "torii::vulkan",
+            width = self.extent.width, height = self.extent.height,
+            depth_format = ?self.depth_format, samples = self.samples.as_raw(), offscreen_targets = self.offscreen.len(),
+            "Created render targets"
+        );
+        Ok(())
+    }
+
+    fn create_offscreen(&self, vk_app: &VkApp, name: &str, desc: &OffscreenTargetDesc) -> RenderTargetResult<RenderTarget> {
+        let attachment_usage = match Self::is_depth_format(desc.format) {
+            true => vk::ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT,
+            false => vk::ImageUsageFlags::COLOR_ATTACHMENT,
+        };
+        let usage = desc.usage | attachment_usage;
+        if find_supported_format(vk_app, &[desc.format], Self::format_features(usage)).is_none() {
+            return Err(RenderTargetError::UnsupportedFormatError { format: desc.format, usage });
+        }
+
+        let image_desc = ImageDesc::new(desc.format, desc.size.extent(self.extent));
+        let multisampled = desc.multisampled && self.samples != vk::SampleCountFlags::TYPE_1;
+        if !multisampled {
+            return Ok(RenderTarget {
+                attachment: GpuImage::new(vk_app, image_desc, usage, name)?,
+                resolve: None,
+            });
+        }
+        Ok(RenderTarget {
+            attachment: GpuImage::new(
+                vk_app,
+                image_desc.with_samples(self.samples),
+                attachment_usage | vk::ImageUsageFlags::TRANSIENT_ATTACHMENT,
+                &format!("{name} [msaa]"),
+            )?,
+            resolve: Some(GpuImage::new(vk_app, image_desc, usage, name)?),
+        })
+    }
+
+    fn is_depth_format(format: vk::Format) -> bool {
+        ImageDesc::new(format, vk::Extent2D::default()).aspect_mask().contains(vk::ImageAspectFlags::DEPTH)
+    }
+
+    fn format_features(usage: vk::ImageUsageFlags) -> vk::FormatFeatureFlags {
+        [
+            (vk::ImageUsageFlags::COLOR_ATTACHMENT, vk::FormatFeatureFlags::COLOR_ATTACHMENT),
+            (vk::ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT, vk::FormatFeatureFlags::DEPTH_STENCIL_ATTACHMENT),
+            (vk::ImageUsageFlags::SAMPLED, vk::FormatFeatureFlags::SAMPLED_IMAGE),
+            (vk::ImageUsageFlags::STORAGE, vk::FormatFeatureFlags::STORAGE_IMAGE),
+            (vk::ImageUsageFlags::TRANSFER_SRC, vk::FormatFeatureFlags::TRANSFER_SRC),
+            (vk::ImageUsageFlags::TRANSFER_DST, vk::FormatFeatureFlags::TRANSFER_DST),
+        ]
+        .into_iter()
+        .filter(|(image_usage, _)| usage.contains(*image_usage))
+        .fold(vk::FormatFeatureFlags::empty(), |features, (_, feature)| features | feature)
+    }
+}
@@ -0,0 +1,315 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::ffi::CString;
+use std::path::Path;
+
+use ash::{vk, Entry, Instance};
+use serde::Serialize;
+
+use super::{loader, requirements, utility, versioning, VkError, VkResult};
+
+// formats whose support is listed in the report ; the usual color targets, depth formats and compressed families
+const REPORTED_FORMATS: &[vk::Format] = &[
+    vk::Format::R8_UNORM,
+    vk::Format::R8G8_UNORM,
+    vk::Format::R8G8B8A8_UNORM,
+    vk::Format::R8G8B8A8_SRGB,
+    vk::Format::B8G8R8A8_UNORM,
+    vk::Format::B8G8R8A8_SRGB,
+    vk::Format::A2B10G10R10_UNORM_PACK32,
+    vk::Format::B10G11R11_UFLOAT_PACK32,
+    vk::Format::R16G16_SFLOAT,
+    vk::Format::R16G16B16A16_SFLOAT,
+    vk::Format::R32_SFLOAT,
+    vk::Format::R32G32_SFLOAT,
+    vk::Format::R32G32B32_SFLOAT,
+    vk::Format::R32G32B32A32_SFLOAT,
+    vk::Format::D16_UNORM,
+    vk::Format::X8_D24_UNORM_PACK32,
+    vk::Format::D32_SFLOAT,
+    vk::Format::D24_UNORM_S8_UINT,
+    vk::Format::D32_SFLOAT_S8_UINT,
+    vk::Format::BC1_RGBA_SRGB_BLOCK,
+    vk::Format::BC3_SRGB_BLOCK,
+    vk::Format::BC5_UNORM_BLOCK,
+    vk::Format::BC6H_UFLOAT_BLOCK,
+    vk::Format::BC7_SRGB_BLOCK,
+    vk::Format::ETC2_R8G8B8A8_SRGB_BLOCK,
+    vk::Format::ASTC_4X4_SRGB_BLOCK,
+];
+
+// Everything worth knowing about one physical device, e.g. to attach to a bug report (see the torii-info binary)
+#[derive(Clone, Debug, Serialize)]
+pub struct DeviceReport {
+    pub name: String,
+    pub device_type: String,
+    pub vendor_id: u32,
+    pub device_id: u32,
+    pub api_version: String,
+    pub driver_version: u32,
+    pub limits: DeviceLimitsReport,
+    // `group.feature` (as in device_feature!) -> supported
+    pub features: BTreeMap<String, bool>,
+    pub extensions: Vec<ExtensionReport>,
+    pub memory_heaps: Vec<MemoryHeapReport>,
+    pub formats: Vec<FormatReport>,
+    pub queue_families: Vec<QueueFamilyReport>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct DeviceLimitsReport {
+    pub max_image_dimension_2d: u32,
+    pub max_image_dimension_3d: u32,
+    pub max_image_dimension_cube: u32,
+    pub max_image_array_layers: u32,
+    pub max_uniform_buffer_range: u32,
+    pub max_storage_buffer_range: u32,
+    pub max_push_constants_size: u32,
+    pub max_memory_allocation_count: u32,
+    pub max_sampler_allocation_count: u32,
+    pub max_bound_descriptor_sets: u32,
+    pub max_per_stage_resources: u32,
+    pub max_descriptor_set_sampled_images: u32,
+    pub max_descriptor_set_storage_buffers: u32,
+    pub max_vertex_input_attributes: u32,
+    pub max_vertex_input_bindings: u32,
+    pub max_compute_shared_memory_size: u32,
+    pub max_compute_work_group_count: [u32; 3],
+    pub max_compute_work_group_invocations: u32,
+    pub max_compute_work_group_size: [u32; 3],
+    pub max_draw_indirect_count: u32,
+    pub max_sampler_anisotropy: f32,
+    pub max_viewports: u32,
+    pub max_viewport_dimensions: [u32; 2],
+    pub max_framebuffer_width: u32,
+    pub max_framebuffer_height: u32,
+    pub max_color_attachments: u32,
+    pub framebuffer_color_sample_counts: String,
+    pub framebuffer_depth_sample_counts: String,
+    pub min_uniform_buffer_offset_alignment: u64,
+    pub min_storage_buffer_offset_alignment: u64,
+    pub non_coherent_atom_size: u64,
+    pub timestamp_compute_and_graphics: bool,
+    pub timestamp_period: f32,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct ExtensionReport {
+    pub name: String,
+    pub spec_version: u32,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct MemoryHeapReport {
+    pub size: u64,
+    pub flags: String,
+    // property flags of every memory type allocated from this heap
+    pub memory_types: Vec<String>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct FormatReport {
+    pub format: String,
+    pub linear_tiling: String,
+    pub optimal_tiling: String,
+    pub buffer: String,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct QueueFamilyReport {
+    pub index: u32,
+    pub queue_count: u32,
+    pub flags: String,
+    pub timestamp_valid_bits: u32,
+    pub min_image_transfer_granularity: [u32; 3],
+}
+
+impl DeviceReport {
+    // `instance_api_version` bounds which feature structs can be queried, as for device selection
+    pub fn new(instance: &Instance, physical_device: vk::PhysicalDevice, instance_api_version: u32) -> Self {
+        let (properties, queue_families, memory_properties, extension_properties) = unsafe {
+            (
+                instance.get_physical_device_properties(physical_device),
+                instance.get_physical_device_queue_family_properties(physical_device),
+                instance.get_physical_device_memory_properties(physical_device),
+                instance.enumerate_device_extension_properties(physical_device).unwrap_or_default(),
+            )
+        };
+        let device_api_version = versioning::major_minor(properties.api_version).min(versioning::major_minor(instance_api_version));
+        let extension_names = requirements::extension_names(&extension_properties);
+        let device_features = Self::query_features(instance, physical_device, device_api_version, &extension_names);
+
+        DeviceReport {
+            name: utility::vk_to_string(&properties.device_name),
+            device_type: format!("{:?}", properties.device_type),
+            vendor_id: properties.vendor_id,
+            device_id: properties.device_id,
+            api_version: versioning::version_string(properties.api_version),
+            driver_version: properties.driver_version,
+            limits: DeviceLimitsReport::from(&properties.limits),
+            features: requirements::ALL_FEATURES
+                .iter()
+                .map(|&feature| (feature.name.to_owned(), device_features.is_enabled(feature)))
+                .collect(),
+            extensions: extension_properties
+                .iter()
+                .map(|extension| ExtensionReport {
+                    name: utility::vk_to_string(&extension.extension_name),
+                    spec_version: extension.spec_version,
+                })
+                .collect(),
+            memory_heaps: memory_properties.memory_heaps_as_slice()
+                .iter()
+                .enumerate()
+                .map(|(heap_index, heap)| MemoryHeapReport {
+                    size: heap.size,
+                    flags: format!("{:?}", heap.flags),
+                    memory_types: memory_properties.memory_types_as_slice()
+                        .iter()
+                        .filter(|memory_type| memory_type.heap_index as usize == heap_index)
+                        .map(|memory_type| format!("{:?}", memory_type.property_flags))
+                        .collect(),
+                })
+                .collect(),
+            formats: REPORTED_FORMATS
+                .iter()
+                .filter_map(|&format| {
+                    let format_properties = unsafe { instance.get_physical_device_format_properties(physical_device, format) };
+                    let supported = !format_properties.linear_tiling_features.is_empty()
+                        || !format_properties.optimal_tiling_features.is_empty()
+                        || !format_properties.buffer_features.is_empty();
+                    supported.then(|| FormatReport {
+                        format: format!("{format:?}"),
+                        linear_tiling: format!("{:?}", format_properties.linear_tiling_features),
+                        optimal_tiling: format!("{:?}", format_properties.optimal_tiling_features),
+                        buffer: format!("{:?}", format_properties.buffer_features),
+                    })
+                })
+                .collect(),
+            queue_families: queue_families
+                .iter()
+                .enumerate()
+                .map(|(index, queue_family)| QueueFamilyReport {
+                    index: index as u32,
+                    queue_count: queue_family.queue_count,
+                    flags: format!("{:?}", queue_family.queue_flags),
+                    timestamp_valid_bits: queue_family.timestamp_valid_bits,
+                    min_image_transfer_granularity: [
+                        queue_family.min_image_transfer_granularity.width,
+                        queue_family.min_image_transfer_granularity.height,
+                        queue_family.min_image_transfer_granularity.depth,
+                    ],
+                })
+                .collect(),
+        }
+    }
+
+    // Reports every device visible to the loader, through a short lived instance that needs no window
+    pub fn collect_all(loader_path: Option<&Path>) -> VkResult<Vec<DeviceReport>> {
+        let entry = loader::load_entry(loader_path)?;
+        let instance_api_version = unsafe { entry.try_enumerate_instance_version() }
+            .ok()
+            .flatten()
+            .map(versioning::major_minor)
+            .unwrap_or(versioning::API_VERSION_1_0)
+            .min(versioning::API_VERSION_1_3);
+        let instance = Self::create_instance(&entry, instance_api_version)?;
+
+        // a failing driver or loader must show as such, not as a machine without GPUs
+        let reports = unsafe { instance.enumerate_physical_devices() }
+            .map_err(VkError::PhysicalDeviceEnumerationError)
+            .map(|physical_devices| {
+                physical_devices
+                    .into_iter()
+                    .map(|physical_device| DeviceReport::new(&instance, physical_device, instance_api_version))
+                    .collect()
+            });
+        unsafe {
+            instance.destroy_instance(None);
+        }
+        reports
+    }
+
+    pub fn to_json(&self) -> serde_json::Result<String> {
+        serde_json::to_string_pretty(self)
+    }
+}
+
+impl DeviceReport {
+    fn query_features(instance: &Instance, physical_device: vk::PhysicalDevice, device_api_version: u32, extension_names: &BTreeSet<CString>) -> requirements::DeviceFeatures {
+        let mut device_features = requirements::DeviceFeatures::default();
+        if device_api_version >= versioning::API_VERSION_1_1 {
+            device_features.with_chain(device_api_version, extension_names, |features2| unsafe {
+                instance.get_physical_device_features2(physical_device, features2);
+            });
+        } else {
+            device_features.core = unsafe { instance.get_physical_device_features(physical_device) };
+        }
+        device_features
+    }
+
+    fn create_instance(entry: &Entry, instance_api_version: u32) -> VkResult<Instance> {
+        let available_extensions = requirements::extension_names(&unsafe {
+            entry.enumerate_instance_extension_properties(None).unwrap_or_default()
+        });
+        // also list portability implementations, which would otherwise stay hidden
+        let portability = available_extensions.contains(ash::khr::portability_enumeration::NAME);
+        let mut extension_names = vec![];
+        if portability {
+            extension_names.push(ash::khr::portability_enumeration::NAME.as_ptr());
+        }
+        if instance_api_version < versioning::API_VERSION_1_1 && available_extensions.contains(ash::khr::get_physical_device_properties2::NAME) {
+            extension_names.push(ash::khr::get_physical_device_properties2::NAME.as_ptr());
+        }
+
+        let engine_name = CString::new(versioning::ENGINE_NAME).unwrap();
+        let app_info = vk::ApplicationInfo::default()
+            .engine_name(&engine_name)
+            .engine_version(versioning::ENGINE_VERSION)
+            .api_version(instance_api_version);
+        let create_info = vk::InstanceCreateInfo::default()
+            .flags(if portability { vk::InstanceCreateFlags::ENUMERATE_PORTABILITY_KHR } else { vk::InstanceCreateFlags::empty() })
+            .application_info(&app_info)
+            .enabled_extension_names(&extension_names);
+        unsafe { entry.create_instance(&create_info, None) }.map_err(VkError::InstanceCreationError)
+    }
+}
+
+impl From<&vk::PhysicalDeviceLimits> for DeviceLimitsReport {
+    fn from(limits: &vk::PhysicalDeviceLimits) -> Self {
+        DeviceLimitsReport {
+            max_image_dimension_2d: limits.max_image_dimension2_d,
+            max_image_dimension_3d: limits.max_image_dimension3_d,
+            max_image_dimension_cube: limits.max_image_dimension_cube,
+            max_image_array_layers: limits.max_image_array_layers,
+            max_uniform_buffer_range: limits.max_uniform_buffer_range,
+            max_storage_buffer_range: limits.max_storage_buffer_range,
+            max_push_constants_size: limits.max_push_constants_size,
+            max_memory_allocation_count: limits.max_memory_allocation_count,
+            max_sampler_allocation_count: limits.max_sampler_allocation_count,
+            max_bound_descriptor_sets: limits.max_bound_descriptor_sets,
+            max_per_stage_resources: limits.max_per_stage_resources,
+            max_descriptor_set_sampled_images: limits.max_descriptor_set_sampled_images,
+            max_descriptor_set_storage_buffers: limits.max_descriptor_set_storage_buffers,
+            max_vertex_input_attributes: limits.max_vertex_input_attributes,
+            max_vertex_input_bindings: limits.max_vertex_input_bindings,
+            max_compute_shared_memory_size: limits.max_compute_shared_memory_size,
+            max_compute_work_group_count: limits.max_compute_work_group_count,
+            max_compute_work_group_invocations: limits.max_compute_work_group_invocations,
+            max_compute_work_group_size: limits.max_compute_work_group_size,
+            max_draw_indirect_count: limits.max_draw_indirect_count,
+            max_sampler_anisotropy: limits.max_sampler_anisotropy,
+            max_viewports: limits.max_viewports,
+            max_viewport_dimensions: limits.max_viewport_dimensions,
+            max_framebuffer_width: limits.max_framebuffer_width,
+            max_framebuffer_height: limits.max_framebuffer_height,
+            max_color_attachments: limits.max_color_attachments,
+            framebuffer_color_sample_counts: format!("{:?}", limits.framebuffer_color_sample_counts),
+            framebuffer_depth_sample_counts: format!("{:?}", limits.framebuffer_depth_sample_counts),
+            min_uniform_buffer_offset_alignment: limits.min_uniform_buffer_offset_alignment,
+            min_storage_buffer_offset_alignment: limits.min_storage_buffer_offset_alignment,
+            non_coherent_atom_size: limits.non_coherent_atom_size,
+            timestamp_compute_and_graphics: limits.timestamp_compute_and_graphics == vk::TRUE,
+            timestamp_period: limits.timestamp_period,
+        }
+    }
+}
@@ -0,0 +1,397 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::ffi::{CStr, CString};
+use std::ptr;
+
+use ash::vk;
+
+// Whether a missing extension / feature disqualifies a device (Required) or is merely left disabled (Optional)
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Requirement {
+    Optional,
+    Required,
+}
+
+// Every feature struct the engine knows how to negotiate. The vulkan 1.1/1.2/1.3 structs are only chained when the
+// device and instance both support that version, extension structs only when their extension is enabled.
+#[derive(Clone, Default)]
+pub struct DeviceFeatures {
+    pub core: vk::PhysicalDeviceFeatures,
+    pub vulkan11: vk::PhysicalDeviceVulkan11Features<'static>,
+    pub vulkan12: vk::PhysicalDeviceVulkan12Features<'static>,
+    pub vulkan13: vk::PhysicalDeviceVulkan13Features<'static>,
+    pub mesh_shader: vk::PhysicalDeviceMeshShaderFeaturesEXT<'static>,
+}
+
+// Points at a single VkBool32 of DeviceFeatures ; built with the `device_feature!` macro
+#[derive(Copy, Clone)]
+pub struct FeatureRequest {
+    pub name: &'static str,
+    pub accessor: fn(&mut DeviceFeatures) -> &mut vk::Bool32,
+}
+
+impl std::fmt::Debug for FeatureRequest {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name)
+    }
+}
+
+// device_feature!(vulkan13.synchronization2) names the `synchronization2` member of `DeviceFeatures::vulkan13`
+#[macro_export]
+macro_rules! device_feature {
+    ($group:ident . $feature:ident) => {
+        $crate::vulkan_api::requirements::FeatureRequest {
+            name: concat!(stringify!($group), ".", stringify!($feature)),
+            accessor: |features| &mut features.$group.$feature,
+        }
+    };
+}
+
+impl DeviceFeatures {
+    pub fn is_enabled(&self, feature: FeatureRequest) -> bool {
+        *(feature.accessor)(&mut self.clone()) == vk::TRUE
+    }
+
+    pub fn enable(&mut self, feature: FeatureRequest) {
+        *(feature.accessor)(self) = vk::TRUE;
+    }
+
+    // Builds a PhysicalDeviceFeatures2 chain over the structs valid for `api_version` and `device_extensions`, hands
+    // it to `f` (to query or to create a device with) and copies the core features back out afterwards
+    pub(crate) fn with_chain<R>(
+        &mut self,
+        api_version: u32,
+        device_extensions: &BTreeSet<CString>,
+        f: impl FnOnce(&mut vk::PhysicalDeviceFeatures2) -> R,
+    ) -> R {
+        // p_next pointers left over from a previous chain (or a clone) must not leak into this one
+        self.vulkan11.p_next = ptr::null_mut();
+        self.vulkan12.p_next = ptr::null_mut();
+        self.vulkan13.p_next = ptr::null_mut();
+        self.mesh_shader.p_next = ptr::null_mut();
+
+        let mut features2 = vk::PhysicalDeviceFeatures2::default().features(self.core);
+        if api_version >= vk::API_VERSION_1_2 {
+            features2 = features2.push_next(&mut self.vulkan11).push_next(&mut self.vulkan12);
+        }
+        if api_version >= vk::API_VERSION_1_3 {
+            features2 = features2.push_next(&mut self.vulkan13);
+        }
+        if device_extensions.contains(ash::ext::mesh_shader::NAME) {
+            features2 = features2.push_next(&mut self.mesh_shader);
+        }
+
+        let result = f(&mut features2);
+        let core = features2.features;
+        self.core = core;
+        result
+    }
+}
+
+// Declarative list of what the engine and the app need from vulkan ; subsystems add to it before the VkApp is
+// created and `VkApp` then picks a device satisfying every Required entry, enabling whichever Optional ones it can
+#[derive(Clone, Default, Debug)]
+pub struct VkRequirements {
+    instance_extensions: BTreeMap<CString, Requirement>,
+    device_extensions: BTreeMap<CString, Requirement>,
+    features: Vec<(FeatureRequest, Requirement)>,
+}
+
+impl VkRequirements {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn require_instance_extension(&mut self, name: &CStr) -> &mut Self {
+        Self::insert(&mut self.instance_extensions, name, Requirement::Required);
+        self
+    }
+
+    pub fn request_instance_extension(&mut self, name: &CStr) -> &mut Self {
+        Self::insert(&mut self.instance_extensions, name, Requirement::Optional);
+        self
+    }
+
+    pub fn require_device_extension(&mut self, name: &CStr) -> &mut Self {
+        Self::insert(&mut self.device_extensions, name, Requirement::Required);
+        self
+    }
+
+    pub fn request_device_extension(&mut self, name: &CStr) -> &mut Self {
+        Self::insert(&mut self.device_extensions, name, Requirement::Optional);
+        self
+    }
+
+    pub fn require_feature(&mut self, feature: FeatureRequest) -> &mut Self {
+        self.insert_feature(feature, Requirement::Required);
+        self
+    }
+
+    pub fn request_feature(&mut self, feature: FeatureRequest) -> &mut Self {
+        self.insert_feature(feature, Requirement::Optional);
+        self
+    }
+
+    // Required wins over Optional when both sides list the same entry
+    pub fn merge(&mut self, other: &VkRequirements) -> &mut Self {
+        for (name, &requirement) in other.instance_extensions.iter() {
+            Self::insert(&mut self.instance_extensions, name, requirement);
+        }
+        for (name, &requirement) in other.device_extensions.iter() {
+            Self::insert(&mut self.device_extensions, name, requirement);
+        }
+        for &(feature, requirement) in other.features.iter() {
+            self.insert_feature(feature, requirement);
+        }
+        self
+    }
+
+    // GETTERS
+
+    pub fn instance_extensions(&self) -> &BTreeMap<CString, Requirement> {
+        &self.instance_extensions
+    }
+
+    pub fn device_extensions(&self) -> &BTreeMap<CString, Requirement> {
+        &self.device_extensions
+    }
+
+    pub fn features(&self) -> &[(FeatureRequest, Requirement)] {
+        &self.features
+    }
+
+    // NEGOTIATION
+
+    // Instance extensions to enable out of the available ones, or the missing required ones
+    pub(crate) fn negotiate_instance_extensions(&self, available: &BTreeSet<CString>) -> Result<BTreeSet<CString>, Vec<String>> {
+        Self::negotiate_extensions(&self.instance_extensions, available)
+    }
+
+    // What a device supporting `available_extensions` / `available_features` would get enabled, or every reason
+    // the device is unsuitable
+    pub(crate) fn negotiate_device(
+        &self,
+        available_extensions: &BTreeSet<CString>,
+        available_features: &DeviceFeatures,
+    ) -> Result<NegotiatedDevice, Vec<String>> {
+        let mut missing = match Self::negotiate_extensions(&self.device_extensions, available_extensions) {
+            Ok(_) => vec![],
+            Err(missing) => missing,
+        };
+        let enabled_extensions: BTreeSet<CString> = self.device_extensions
+            .keys()
+            .filter(|name| available_extensions.contains(*name))
+            .cloned()
+            .collect();
+
+        let mut enabled_features = DeviceFeatures::default();
+        let mut optional_count = enabled_extensions
+            .iter()
+            .filter(|name| self.device_extensions[*name] == Requirement::Optional)
+            .count();
+        for &(feature, requirement) in self.features.iter() {
+            if available_features.is_enabled(feature) {
+                enabled_features.enable(feature);
+                if requirement == Requirement::Optional {
+                    optional_count += 1;
+                }
+            } else if requirement == Requirement::Required {
+                missing.push(format!("feature {}", feature.name));
+            }
+        }
+
+        if missing.is_empty() {
+            Ok(NegotiatedDevice {
+                enabled_extensions,
+                enabled_features,
+                optional_count,
+            })
+        } else {
+            Err(missing)
+        }
+    }
+}
+
+impl VkRequirements {
+    fn insert(map: &mut BTreeMap<CString, Requirement>, name: &CStr, requirement: Requirement) {
+        let entry = map.entry(name.to_owned()).or_insert(requirement);
+        *entry = (*entry).max(requirement);
+    }
+
+    fn insert_feature(&mut self, feature: FeatureRequest, requirement: Requirement) {
+        match self.features.iter_mut().find(|(existing, _)| existing.name == feature.name) {
+            Some((_, existing)) => *existing = (*existing).max(requirement),
+            None => self.features.push((feature, requirement)),
+        }
+    }
+
+    fn negotiate_extensions(requested: &BTreeMap<CString, Requirement>, available: &BTreeSet<CString>) -> Result<BTreeSet<CString>, Vec<String>> {
+        let missing: Vec<String> = requested
+            .iter()
+            .filter(|(name, &requirement)| requirement == Requirement::Required && !available.contains(*name))
+            .map(|(name, _)| format!("extension {}", name.to_string_lossy()))
+            .collect();
+        if !missing.is_empty() {
+            return Err(missing);
+        }
+        Ok(requested.keys().filter(|name| available.contains(*name)).cloned().collect())
+    }
+}
+
+// Outcome of negotiating the requirements against one device
+pub(crate) struct NegotiatedDevice {
+    pub(crate) enabled_extensions: BTreeSet<CString>,
+    pub(crate) enabled_features: DeviceFeatures,
+    // number of optional extensions and features the device supports, used to rank otherwise equal devices
+    pub(crate) optional_count: usize,
+}
+
+pub(crate) fn extension_names(extension_properties: &[vk::ExtensionProperties]) -> BTreeSet<CString> {
+    extension_properties
+        .iter()
+        .filter_map(|extension| extension.extension_name_as_c_str().ok())
+        .map(CStr::to_owned)
+        .collect()
+}
+
+// Every feature DeviceFeatures can hold, in struct order ; used to list a device's capabilities (see report)
+pub const ALL_FEATURES: &[FeatureRequest] = &[
+    // PhysicalDeviceFeatures
+    device_feature!(core.robust_buffer_access),
+    device_feature!(core.full_draw_index_uint32),
+    device_feature!(core.image_cube_array),
+    device_feature!(core.independent_blend),
+    device_feature!(core.geometry_shader),
+    device_feature!(core.tessellation_shader),
+    device_feature!(core.sample_rate_shading),
+    device_feature!(core.dual_src_blend),
+    device_feature!(core.logic_op),
+    device_feature!(core.multi_draw_indirect),
+    device_feature!(core.draw_indirect_first_instance),
+    device_feature!(core.depth_clamp),
+    device_feature!(core.depth_bias_clamp),
+    device_feature!(core.fill_mode_non_solid),
+    device_feature!(core.depth_bounds),
+    device_feature!(core.wide_lines),
+    device_feature!(core.large_points),
+    device_feature!(core.alpha_to_one),
+    device_feature!(core.multi_viewport),
+    device_feature!(core.sampler_anisotropy),
+    device_feature!(core.texture_compression_etc2),
+    device_feature!(core.texture_compression_astc_ldr),
+    device_feature!(core.texture_compression_bc),
+    device_feature!(core.occlusion_query_precise),
+    device_feature!(core.pipeline_statistics_query),
+    device_feature!(core.vertex_pipeline_stores_and_atomics),
+    device_feature!(core.fragment_stores_and_atomics),
+    device_feature!(core.shader_tessellation_and_geometry_point_size),
+    device_feature!(core.shader_image_gather_extended),
+    device_feature!(core.shader_storage_image_extended_formats),
+    device_feature!(core.shader_storage_image_multisample),
+    device_feature!(core.shader_storage_image_read_without_format),
+    device_feature!(core.shader_storage_image_write_without_format),
+    device_feature!(core.shader_uniform_buffer_array_dynamic_indexing),
+    device_feature!(core.shader_sampled_image_array_dynamic_indexing),
+    device_feature!(core.shader_storage_buffer_array_dynamic_indexing),
+    device_feature!(core.shader_storage_image_array_dynamic_indexing),
+    device_feature!(core.shader_clip_distance),
+    device_feature!(core.shader_cull_distance),
+    device_feature!(core.shader_float64),
+    device_feature!(core.shader_int64),
+    device_feature!(core.shader_int16),
+    device_feature!(core.shader_resource_residency),
+    device_feature!(core.shader_resource_min_lod),
+    device_feature!(core.sparse_binding),
+    device_feature!(core.sparse_residency_buffer),
+    device_feature!(core.sparse_residency_image2_d),
+    device_feature!(core.sparse_residency_image3_d),
+    device_feature!(core.sparse_residency2_samples),
+    device_feature!(core.sparse_residency4_samples),
+    device_feature!(core.sparse_residency8_samples),
+    device_feature!(core.sparse_residency16_samples),
+    device_feature!(core.sparse_residency_aliased),
+    device_feature!(core.variable_multisample_rate),
+    device_feature!(core.inherited_queries),
+    // PhysicalDeviceVulkan11Features
+    device_feature!(vulkan11.storage_buffer16_bit_access),
+    device_feature!(vulkan11.uniform_and_storage_buffer16_bit_access),
+    device_feature!(vulkan11.storage_push_constant16),
+    device_feature!(vulkan11.storage_input_output16),
+    device_feature!(vulkan11.multiview),
+    device_feature!(vulkan11.multiview_geometry_shader),
+    device_feature!(vulkan11.multiview_tessellation_shader),
+    device_feature!(vulkan11.variable_pointers_storage_buffer),
+    device_feature!(vulkan11.variable_pointers),
+    device_feature!(vulkan11.protected_memory),
+    device_feature!(vulkan11.sampler_ycbcr_conversion),
+    device_feature!(vulkan11.shader_draw_parameters),
+    // PhysicalDeviceVulkan12Features
+    device_feature!(vulkan12.sampler_mirror_clamp_to_edge),
+    device_feature!(vulkan12.draw_indirect_count),
+    device_feature!(vulkan12.storage_buffer8_bit_access),
+    device_feature!(vulkan12.uniform_and_storage_buffer8_bit_access),
+    device_feature!(vulkan12.storage_push_constant8),
+    device_feature!(vulkan12.shader_buffer_int64_atomics),
+    device_feature!(vulkan12.shader_shared_int64_atomics),
+    device_feature!(vulkan12.shader_float16),
+    device_feature!(vulkan12.shader_int8),
+    device_feature!(vulkan12.descriptor_indexing),
+    device_feature!(vulkan12.shader_input_attachment_array_dynamic_indexing),
+    device_feature!(vulkan12.shader_uniform_texel_buffer_array_dynamic_indexing),
+    device_feature!(vulkan12.shader_storage_texel_buffer_array_dynamic_indexing),
+    device_feature!(vulkan12.shader_uniform_buffer_array_non_uniform_indexing),
+    device_feature!(vulkan12.shader_sampled_image_array_non_uniform_indexing),
+    device_feature!(vulkan12.shader_storage_buffer_array_non_uniform_indexing),
+    device_feature!(vulkan12.shader_storage_image_array_non_uniform_indexing),
+    device_feature!(vulkan12.shader_input_attachment_array_non_uniform_indexing),
+    device_feature!(vulkan12.shader_uniform_texel_buffer_array_non_uniform_indexing),
+    device_feature!(vulkan12.shader_storage_texel_buffer_array_non_uniform_indexing),
+    device_feature!(vulkan12.descriptor_binding_uniform_buffer_update_after_bind),
+    device_feature!(vulkan12.descriptor_binding_sampled_image_update_after_bind),
+    device_feature!(vulkan12.descriptor_binding_storage_image_update_after_bind),
+    device_feature!(vulkan12.descriptor_binding_storage_buffer_update_after_bind),
+    device_feature!(vulkan12.descriptor_binding_uniform_texel_buffer_update_after_bind),
+    device_feature!(vulkan12.descriptor_binding_storage_texel_buffer_update_after_bind),
+    device_feature!(vulkan12.descriptor_binding_update_unused_while_pending),
+    device_feature!(vulkan12.descriptor_binding_partially_bound),
+    device_feature!(vulkan12.descriptor_binding_variable_descriptor_count),
+    device_feature!(vulkan12.runtime_descriptor_array),
+    device_feature!(vulkan12.sampler_filter_minmax),
+    device_feature!(vulkan12.scalar_block_layout),
+    device_feature!(vulkan12.imageless_framebuffer),
+    device_feature!(vulkan12.uniform_buffer_standard_layout),
+    device_feature!(vulkan12.shader_subgroup_extended_types),
+    device_feature!(vulkan12.separate_depth_stencil_layouts),
+    device_feature!(vulkan12.host_query_reset),
+    device_feature!(vulkan12.timeline_semaphore),
+    device_feature!(vulkan12.buffer_device_address),
+    device_feature!(vulkan12.buffer_device_address_capture_replay),
+    device_feature!(vulkan12.buffer_device_address_multi_device),
+    device_feature!(vulkan12.vulkan_memory_model),
+    device_feature!(vulkan12.vulkan_memory_model_device_scope),
+    device_feature!(vulkan12.vulkan_memory_model_availability_visibility_chains),
+    device_feature!(vulkan12.shader_output_viewport_index),
+    device_feature!(vulkan12.shader_output_layer),
+    device_feature!(vulkan12.subgroup_broadcast_dynamic_id),
+    // PhysicalDeviceVulkan13Features
+    device_feature!(vulkan13.robust_image_access),
+    device_feature!(vulkan13.inline_uniform_block),
+    device_feature!(vulkan13.descriptor_binding_inline_uniform_block_update_after_bind),
+    device_feature!(vulkan13.pipeline_creation_cache_control),
+    device_feature!(vulkan13.private_data),
+    device_feature!(vulkan13.shader_demote_to_helper_invocation),
+    device_feature!(vulkan13.shader_terminate_invocation),
+    device_feature!(vulkan13.subgroup_size_control),
+    device_feature!(vulkan13.compute_full_subgroups),
+    device_feature!(vulkan13.synchronization2),
+    device_feature!(vulkan13.texture_compression_astc_hdr),
+    device_feature!(vulkan13.shader_zero_initialize_workgroup_memory),
+    device_feature!(vulkan13.dynamic_rendering),
+    device_feature!(vulkan13.shader_integer_dot_product),
+    device_feature!(vulkan13.maintenance4),
+    // PhysicalDeviceMeshShaderFeaturesEXT
+    device_feature!(mesh_shader.task_shader),
+    device_feature!(mesh_shader.mesh_shader),
+    device_feature!(mesh_shader.multiview_mesh_shader),
+    device_feature!(mesh_shader.primitive_fragment_shading_rate_mesh_shader),
+    device_feature!(mesh_shader.mesh_shader_queries),
+];
@@ -0,0 +1,113 @@
+use ash::{vk, Device};
+
+use super::VkApp;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct SamplerDesc {
+    pub filter: vk::Filter,
+    pub mipmap_mode: vk::SamplerMipmapMode,
+    pub address_mode: vk::SamplerAddressMode,
+    // only used when the device has samplerAnisotropy enabled, clamped to its limit
+    pub max_anisotropy: Option<f32>,
+    // depth comparison of shadow samplers
+    pub compare: Option<vk::CompareOp>,
+}
+
+impl SamplerDesc {
+    // material textures
+    pub fn linear_repeat() -> Self {
+        SamplerDesc {
+            filter: vk::Filter::LINEAR,
+            mipmap_mode: vk::SamplerMipmapMode::LINEAR,
+            address_mode: vk::SamplerAddressMode::REPEAT,
+            max_anisotropy: Some(16.0),
+            compare: None,
+        }
+    }
+
+    // lookup tables, environment maps, render targets
+    pub fn linear_clamp() -> Self {
+        SamplerDesc {
+            address_mode: vk::SamplerAddressMode::CLAMP_TO_EDGE,
+            max_anisotropy: None,
+            ..Self::linear_repeat()
+        }
+    }
+
+    // pixel art
+    pub fn nearest_clamp() -> Self {
+        SamplerDesc {
+            filter: vk::Filter::NEAREST,
+            mipmap_mode: vk::SamplerMipmapMode::NEAREST,
+            ..Self::linear_clamp()
+        }
+    }
+
+    // shadow maps, lit where the reference is closer than the stored depth ; `filter` must be supported by the
+    // depth format (linear gives 2x2 PCF per tap)
+    pub fn shadow(filter: vk::Filter) -> Self {
+        SamplerDesc {
+            filter,
+            mipmap_mode: vk::SamplerMipmapMode::NEAREST,
+            compare: Some(vk::CompareOp::LESS_OR_EQUAL),
+            ..Self::linear_clamp()
+        }
+    }
+}
+
+pub struct Sampler {
+    device: Device,
+    sampler: vk::Sampler,
+    desc: SamplerDesc,
+}
+
+impl Sampler {
+    pub fn new(vk_app: &VkApp, desc: SamplerDesc, name: &str) -> Result<Self, vk::Result> {
+        let max_anisotropy = desc.max_anisotropy
+            .filter(|_| vk_app.is_feature_enabled(crate::device_feature!(core.sampler_anisotropy)))
+            .map(|max_anisotropy| {
+                let limit = unsafe {
+                    vk_app.instance().get_physical_device_properties(vk_app.physical_device()).limits.max_sampler_anisotropy
+                };
+                max_anisotropy.min(limit)
+            });
+        let create_info = vk::SamplerCreateInfo::default()
+            .mag_filter(desc.filter)
+            .min_filter(desc.filter)
+            .mipmap_mode(desc.mipmap_mode)
+            .address_mode_u(desc.address_mode)
+            .address_mode_v(desc.address_mode)
+            .address_mode_w(desc.address_mode)
+            .anisotropy_enable(max_anisotropy.is_some())
+            .max_anisotropy(max_anisotropy.unwrap_or(1.0))
+            .compare_enable(desc.compare.is_some())
+            .compare_op(desc.compare.unwrap_or(vk::CompareOp::ALWAYS))
+            .min_lod(0.0)
+            .max_lod(vk::LOD_CLAMP_NONE);
+        let sampler = unsafe { vk_app.device().create_sampler(&create_info, None)? };
+        vk_app.debug_utils().set_object_name(sampler, name);
+        Ok(Sampler {
+            device: vk_app.device().clone(),
+            sampler,
+            desc,
+        })
+    }
+
+    // GETTERS
+
+    pub fn sampler(&self) -> vk::Sampler {
+        self.sampler
+    }
+
+    pub fn desc(&self) -> &SamplerDesc {
+        &self.desc
+    }
+}
+
+impl Drop for Sampler {
+    fn drop(&mut self) {
+        unsafe {
+            self.device.destroy_sampler(self.sampler, None);
+        }
+    }
+}
@@ -0,0 +1,287 @@
+use std::collections::BTreeMap;
+
+use ash::{vk, Device};
+use naga::back::spv as spv_out;
+use naga::front::{glsl, spv as spv_in};
+use naga::valid::{Capabilities, ValidationFlags, Validator};
+
+use super::ShaderError;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum ShaderStage {
+    Vertex,
+    Fragment,
+    Compute,
+    Task,
+    Mesh,
+}
+
+impl ShaderStage {
+    pub fn vk_stage(&self) -> vk::ShaderStageFlags {
+        match self {
+            ShaderStage::Vertex => vk::ShaderStageFlags::VERTEX,
+            ShaderStage::Fragment => vk::ShaderStageFlags::FRAGMENT,
+            ShaderStage::Compute => vk::ShaderStageFlags::COMPUTE,
+            ShaderStage::Task => vk::ShaderStageFlags::TASK_EXT,
+            ShaderStage::Mesh => vk::ShaderStageFlags::MESH_EXT,
+        }
+    }
+
+    fn naga_stage(&self) -> naga::ShaderStage {
+        match self {
+            ShaderStage::Vertex => naga::ShaderStage::Vertex,
+            ShaderStage::Fragment => naga::ShaderStage::Fragment,
+            ShaderStage::Compute => naga::ShaderStage::Compute,
+            ShaderStage::Task => naga::ShaderStage::Task,
+            ShaderStage::Mesh => naga::ShaderStage::Mesh,
+        }
+    }
+
+    fn from_naga(stage: naga::ShaderStage) -> Option<Self> {
+        match stage {
+            naga::ShaderStage::Vertex => Some(ShaderStage::Vertex),
+            naga::ShaderStage::Fragment => Some(ShaderStage::Fragment),
+            naga::ShaderStage::Compute => Some(ShaderStage::Compute),
+            naga::ShaderStage::Task => Some(ShaderStage::Task),
+            naga::ShaderStage::Mesh => Some(ShaderStage::Mesh),
+            _ => None,
+        }
+    }
+}
+
+// One descriptor the shader expects, as declared with layout(set = .., binding = ..)
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ReflectedBinding {
+    pub set: u32,
+    pub binding: u32,
+    pub name: Option<String>,
+    pub descriptor_type: vk::DescriptorType,
+    pub count: u32,
+    // storage buffers and images the shader may write to
+    pub writable: bool,
+}
+
+#[derive(Clone, Debug)]
+pub struct ShaderReflection {
+    pub stage: ShaderStage,
+    pub entry_point: String,
+    // only meaningful for compute, task and mesh shaders
+    pub workgroup_size: [u32; 3],
+    pub bindings: Vec<ReflectedBinding>,
+    pub push_constant_size: u32,
+}
+
+impl ShaderReflection {
+    pub fn binding(&self, set: u32, binding: u32) -> Option<&ReflectedBinding> {
+        self.bindings.iter().find(|reflected| reflected.set == set && reflected.binding == binding)
+    }
+
+    pub fn binding_by_name(&self, name: &str) -> Option<&ReflectedBinding> {
+        self.bindings.iter().find(|reflected| reflected.name.as_deref() == Some(name))
+    }
+
+    // set index -> bindings of that set, sorted by binding
+    pub fn sets(&self) -> BTreeMap<u32, Vec<&ReflectedBinding>> {
+        let mut sets: BTreeMap<u32, Vec<&ReflectedBinding>> = BTreeMap::new();
+        for reflected in self.bindings.iter() {
+            sets.entry(reflected.set).or_default().push(reflected);
+        }
+        for bindings in sets.values_mut() {
+            bindings.sort_by_key(|reflected| reflected.binding);
+        }
+        sets
+    }
+}
+
+// SPIR-V ready for vkCreateShaderModule, along with what it expects from the pipeline layout
+#[derive(Clone, Debug)]
+pub struct Shader {
+    spirv: Vec<u32>,
+    reflection: ShaderReflection,
+}
+
+impl Shader {
+    // Vulkan flavoured GLSL (layout(set, binding), push_constant blocks) ; textures and samplers must be declared
+    // separately (texture2D + sampler) rather than as combined sampler2D uniforms
+    pub fn from_glsl(source: &str, stage: ShaderStage) -> Result<Self, ShaderError> {
+        Self::from_glsl_with_defines(source, stage, &[])
+    }
+
+    pub fn from_glsl_with_defines(source: &str, stage: ShaderStage, defines: &[(&str, &str)]) -> Result<Self, ShaderError> {
+        let options = glsl::Options {
+            stage: stage.naga_stage(),
+            defines: defines.iter().map(|&(name, value)| (name.to_owned(), value.to_owned())).collect(),
+        };
+        let module = glsl::Frontend::default()
+            .parse(&options, source)
+            .map_err(|errors| ShaderError::ParseError(errors.emit_to_string(source)))?;
+        Self::from_module(&module, stage)
+    }
+
+    // WGSL is only used where the GLSL frontend falls short, e.g. task and mesh shaders (`enable wgpu_mesh_shader;`) ;
+    // one source may hold several entry points, `stage` picks which one is compiled
+    pub fn from_wgsl(source: &str, stage: ShaderStage) -> Result<Self, ShaderError> {
+        let module = naga::front::wgsl::parse_str(source)
+            .map_err(|error| ShaderError::ParseError(error.emit_to_string(source)))?;
+        Self::from_module(&module, stage)
+    }
+
+    // Precompiled SPIR-V is used as is, only reflected ; `stage` picks the entry point when there are several
+    pub fn from_spirv(spirv: &[u32], stage: Option<ShaderStage>) -> Result<Self, ShaderError> {
+        let bytes: Vec<u8> = spirv.iter().flat_map(|word| word.to_le_bytes()).collect();
+        let options = spv_in::Options {
+            adjust_coordinate_space: false,
+            strict_capabilities: false,
+            block_ctx_dump_prefix: None,
+        };
+        let module = spv_in::parse_u8_slice(&bytes, &options)
+            .map_err(|error| ShaderError::ParseError(error.to_string()))?;
+        // precompiled modules naga cannot validate are still reflected, with every global counted as used
+        let module_info = Self::validate(&module).ok();
+        Ok(Shader {
+            reflection: Self::reflect(&module, module_info.as_ref(), stage)?,
+            spirv: spirv.to_vec(),
+        })
+    }
+
+    pub fn from_spirv_bytes(bytes: &[u8], stage: Option<ShaderStage>) -> Result<Self, ShaderError> {
+        if !bytes.len().is_multiple_of(4) {
+            return Err(ShaderError::ParseError(String::from("SPIR-V byte length is not a multiple of 4")));
+        }
+        let words: Vec<u32> = bytes
+            .chunks_exact(4)
+            .map(|chunk| u32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]))
+            .collect();
+        Self::from_spirv(&words, stage)
+    }
+
+    pub fn create_module(&self, device: &Device) -> Result<vk::ShaderModule, vk::Result> {
+        let create_info = vk::ShaderModuleCreateInfo::default().code(&self.spirv);
+        unsafe { device.create_shader_module(&create_info, None) }
+    }
+
+    // GETTERS
+
+    pub fn spirv(&self) -> &[u32] {
+        &self.spirv
+    }
+
+    pub fn reflection(&self) -> &ShaderReflection {
+        &self.reflection
+    }
+
+    pub fn stage(&self) -> ShaderStage {
+        self.reflection.stage
+    }
+
+    pub fn entry_point(&self) -> &str {
+        &self.reflection.entry_point
+    }
+}
+
+impl Shader {
+    fn from_module(module: &naga::Module, stage: ShaderStage) -> Result<Self, ShaderError> {
+        let module_info = Self::validate(module)?;
+
+        let entry_point = module.entry_points
+            .iter()
+            .find(|entry_point| entry_point.stage == stage.naga_stage())
+            .ok_or(ShaderError::MissingEntryPointError(stage))?;
+        let spirv_options = spv_out::Options {
+            // VK_EXT_mesh_shader modules need SPIR-V 1.4 (vulkan 1.2 or VK_KHR_spirv_1_4), the rest stays on 1.0
+            lang_version: match stage {
+                ShaderStage::Task | ShaderStage::Mesh => (1, 4),
+                _ => (1, 0),
+            },
+            // the sources are written for vulkan already, their clip space must not be flipped
+            flags: spv_out::WriterFlags::empty(),
+            ..spv_out::Options::default()
+        };
+        let pipeline_options = spv_out::PipelineOptions {
+            shader_stage: stage.naga_stage(),
+            entry_point: entry_point.name.clone(),
+        };
+        let spirv = spv_out::write_vec(module, &module_info, &spirv_options, Some(&pipeline_options))
+            .map_err(|error| ShaderError::SpirvWriteError(error.to_string()))?;
+
+        Ok(Shader {
+            reflection: Self::reflect(module, Some(&module_info), Some(stage))?,
+            spirv,
+        })
+    }
+
+    fn validate(module: &naga::Module) -> Result<naga::valid::ModuleInfo, ShaderError> {
+        Validator::new(ValidationFlags::all(), Capabilities::all())
+            .validate(module)
+            .map_err(|error| ShaderError::ValidationError(error.into_inner().to_string()))
+    }
+
+    fn reflect(module: &naga::Module, module_info: Option<&naga::valid::ModuleInfo>, stage: Option<ShaderStage>) -> Result<ShaderReflection, ShaderError> {
+        let (entry_point_index, entry_point) = module.entry_points
+            .iter()
+            .enumerate()
+            .find(|(_, entry_point)| stage.is_none_or(|stage| entry_point.stage == stage.naga_stage()))
+            .ok_or(ShaderError::MissingEntryPointError(stage.unwrap_or(ShaderStage::Compute)))?;
+        // a source with several entry points declares globals some of them never touch
+        let is_used = |handle: naga::Handle<naga::GlobalVariable>| {
+            module_info.is_none_or(|module_info| !module_info.get_entry_point(entry_point_index)[handle].is_empty())
+        };
+        let entry_stage = ShaderStage::from_naga(entry_point.stage)
+            .ok_or_else(|| ShaderError::UnsupportedStageError(format!("{:?}", entry_point.stage)))?;
+
+        let mut bindings: Vec<ReflectedBinding> = vec![];
+        let mut push_constant_size = 0;
+        for (handle, variable) in module.global_variables.iter() {
+            if !is_used(handle) {
+                continue;
+            }
+            if variable.space == naga::AddressSpace::Immediate {
+                push_constant_size = module.types[variable.ty].inner.size(module.to_ctx());
+                continue;
+            }
+            let Some(resource_binding) = variable.binding else { continue };
+
+            let (ty, count) = match module.types[variable.ty].inner {
+                naga::TypeInner::BindingArray { base, size: naga::ArraySize::Constant(size) } => (base, size.get()),
+                naga::TypeInner::BindingArray { base, .. } => (base, 1),
+                _ => (variable.ty, 1),
+            };
+            let (descriptor_type, writable) = match (variable.space, &module.types[ty].inner) {
+                (naga::AddressSpace::Uniform, _) => (vk::DescriptorType::UNIFORM_BUFFER, false),
+                (naga::AddressSpace::Storage { access }, _) => (vk::DescriptorType::STORAGE_BUFFER, access.contains(naga::StorageAccess::STORE)),
+                (naga::AddressSpace::Handle, naga::TypeInner::Image { class: naga::ImageClass::Storage { access, .. }, .. }) => {
+                    (vk::DescriptorType::STORAGE_IMAGE, access.contains(naga::StorageAccess::STORE))
+                },
+                (naga::AddressSpace::Handle, naga::TypeInner::Image { .. }) => (vk::DescriptorType::SAMPLED_IMAGE, false),
+                (naga::AddressSpace::Handle, naga::TypeInner::Sampler { .. }) => (vk::DescriptorType::SAMPLER, false),
+                (naga::AddressSpace::Handle, naga::TypeInner::AccelerationStructure { .. }) => (vk::DescriptorType::ACCELERATION_STRUCTURE_KHR, false),
+                (space, inner) => return Err(ShaderError::UnsupportedBindingError(format!("{space:?} {inner:?}"))),
+            };
+
+            // SPIR-V combined image samplers come out of naga as an image and a sampler on the same binding
+            let existing = bindings
+                .iter_mut()
+                .find(|reflected| reflected.set == resource_binding.group && reflected.binding == resource_binding.binding);
+            match existing {
+                Some(existing) => existing.descriptor_type = vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+                None => bindings.push(ReflectedBinding {
+                    set: resource_binding.group,
+                    binding: resource_binding.binding,
+                    name: variable.name.clone(),
+                    descriptor_type,
+                    count,
+                    writable,
+                }),
+            }
+        }
+        bindings.sort_by_key(|reflected| (reflected.set, reflected.binding));
+
+        Ok(ShaderReflection {
+            stage: entry_stage,
+            entry_point: entry_point.name.clone(),
+            workgroup_size: entry_point.workgroup_size,
+            bindings,
+            push_constant_size,
+        })
+    }
+}
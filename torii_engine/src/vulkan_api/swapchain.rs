@@ -0,0 +1,261 @@
+use ash::vk;
+use ash::Device;
+
+use winit::window::Window;
+use tracing::debug;
+
+use super::VkApp;
+use super::debug_utils::DebugUtils;
+
+// Per-window presentation state: the surface of a window and the swapchain built on top of it.
+// Every window shares the single logical device owned by `VkApp`.
+pub struct Swapchain {
+    device: Device,
+    debug_utils: DebugUtils,
+    debug_name: String,
+    surface_loader: ash::khr::surface::Instance,
+    swapchain_loader: ash::khr::swapchain::Device,
+    physical_device: vk::PhysicalDevice,
+    queue_family_indices: Vec<u32>,
+
+    surface: vk::SurfaceKHR,
+    swapchain: vk::SwapchainKHR,
+    format: vk::SurfaceFormatKHR,
+    present_mode: vk::PresentModeKHR,
+    vsync: bool,
+    extent: vk::Extent2D,
+    images: Vec<vk::Image>,
+    image_views: Vec<vk::ImageView>,
+}
+
+impl Swapchain {
+    pub fn new(vk_app: &VkApp, window: &Window) -> Self {
+        let surface = vk_app.create_surface(window);
+
+        let is_present_supported = unsafe {
+            vk_app.surface_loader()
+                .get_physical_device_surface_support(vk_app.physical_device(), vk_app.present_queue_family(), surface)
+                .unwrap_or(false)
+        };
+        if !is_present_supported {
+            panic!("Present queue family of the chosen device cannot present to this window!");
+        }
+
+        let mut queue_family_indices = vec![vk_app.graphics_queue_family()];
+        if vk_app.present_queue_family() != vk_app.graphics_queue_family() {
+            queue_family_indices.push(vk_app.present_queue_family());
+        }
+
+        let mut swapchain = Swapchain {
+            device: vk_app.device().clone(),
+            debug_utils: vk_app.debug_utils().clone(),
+            debug_name: window.title(),
+            surface_loader: vk_app.surface_loader().clone(),
+            swapchain_loader: ash::khr::swapchain::Device::new(vk_app.instance(), vk_app.device()),
+            physical_device: vk_app.physical_device(),
+            queue_family_indices,
+            surface,
+            swapchain: vk::SwapchainKHR::null(),
+            format: vk::SurfaceFormatKHR::default(),
+            present_mode: vk::PresentModeKHR::FIFO,
+            vsync: vk_app.vk_prop().renderer_config.vsync,
+            extent: vk::Extent2D::default(),
+            images: vec![],
+            image_views: vec![],
+        };
+
+        let size = window.inner_size();
+        swapchain.recreate(size.width, size.height);
+        swapchain
+    }
+
+    // Rebuilds the swapchain (and its image views) for a new window size, reusing the old swapchain
+    // handle so the driver can recycle resources
+    pub fn recreate(&mut self, width: u32, height: u32) {
+        unsafe {
+            let capabilities = self.surface_loader
+                .get_physical_device_surface_capabilities(self.physical_device, self.surface)
+                .expect("Failed to query surface capabilities!");
+            let formats = self.surface_loader
+                .get_physical_device_surface_formats(self.physical_device, self.surface)
+                .expect("Failed to query surface formats!");
+            let present_modes = self.surface_loader
+                .get_physical_device_surface_present_modes(self.physical_device, self.surface)
+                .expect("Failed to query surface present modes!");
+
+            // a minimized window has a zero sized surface, a swapchain cannot be created for it ; the current one and
+            // its properties stay as they are
+            let extent = Self::choose_extent(&capabilities, width, height);
+            if extent.width == 0 || extent.height == 0 {
+                return;
+            }
+            self.format = Self::choose_surface_format(&formats);
+            self.present_mode = Self::choose_present_mode(&present_modes, self.vsync);
+            self.extent = extent;
+
+            let mut image_count = capabilities.min_image_count + 1;
+            if capabilities.max_image_count > 0 && image_count > capabilities.max_image_count {
+                image_count = capabilities.max_image_count;
+            }
+
+            let sharing_mode = if self.queue_family_indices.len() > 1 {
+                vk::SharingMode::CONCURRENT
+            } else {
+                vk::SharingMode::EXCLUSIVE
+            };
+
+            let old_swapchain = self.swapchain;
+            let create_info = vk::SwapchainCreateInfoKHR::default()
+                .surface(self.surface)
+                .min_image_count(image_count)
+                .image_format(self.format.format)
+                .image_color_space(self.format.color_space)
+                .image_extent(self.extent)
+                .image_array_layers(1)
+                .image_usage(vk::ImageUsageFlags::COLOR_ATTACHMENT | vk::ImageUsageFlags::TRANSFER_DST)
+                .image_sharing_mode(sharing_mode)
+                .queue_family_indices(&self.queue_family_indices)
+                .pre_transform(capabilities.current_transform)
+                .composite_alpha(vk::CompositeAlphaFlagsKHR::OPAQUE)
+                .present_mode(self.present_mode)
+                .clipped(true)
+                .old_swapchain(old_swapchain);
+
+            self.swapchain = self.swapchain_loader
+                .create_swapchain(&create_info, None)
+                .expect("Failed to create swapchain!");
+
+            // frames still in flight may render to or present the old images, they must be done before those go away
+            if old_swapchain != vk::SwapchainKHR::null() {
+                self.device.device_wait_idle().expect("Failed to wait for the device before destroying the old swapchain!");
+            }
+            self.destroy_image_views();
+            if old_swapchain != vk::SwapchainKHR::null() {
+                self.swapchain_loader.destroy_swapchain(old_swapchain, None);
+            }
+
+            debug!(
+                target: "torii::vulkan",
+                width = self.extent.width, height = self.extent.height, image_count,
+                format = ?self.format.format, present_mode = ?self.present_mode,
+                "Created swapchain"
+            );
+
+            self.images = self.swapchain_loader
+                .get_swapchain_images(self.swapchain)
+                .expect("Failed to get swapchain images!");
+            self.image_views = self.images
+                .iter()
+                .map(|&image| {
+                    let view_info = vk::ImageViewCreateInfo::default()
+                        .image(image)
+                        .view_type(vk::ImageViewType::TYPE_2D)
+                        .format(self.format.format)
+                        .subresource_range(vk::ImageSubresourceRange {
+                            aspect_mask: vk::ImageAspectFlags::COLOR,
+                            base_mip_level: 0,
+                            level_count: 1,
+                            base_array_layer: 0,
+                            layer_count: 1,
+                        });
+                    self.device
+                        .create_image_view(&view_info, None)
+                        .expect("Failed to create swapchain image view!")
+                })
+                .collect();
+            
+            self.debug_utils.set_object_name(self.surface, &format!("{} [surface]", self.debug_name));
+            self.debug_utils.set_object_name(self.swapchain, &format!("{} [swapchain]", self.debug_name));
+            for (index, (&image, &image_view)) in self.images.iter().zip(self.image_views.iter()).enumerate() {
+                self.debug_utils.set_object_name(image, &format!("{} [swapchain image {index}]", self.debug_name));
+                self.debug_utils.set_object_name(image_view, &format!("{} [swapchain image view {index}]", self.debug_name));
+            }
+        }
+    }
+
+    // GETTERS
+
+    pub fn surface(&self) -> vk::SurfaceKHR {
+        self.surface
+    }
+
+    pub fn swapchain(&self) -> vk::SwapchainKHR {
+        self.swapchain
+    }
+
+    pub fn format(&self) -> vk::SurfaceFormatKHR {
+        self.format
+    }
+
+    pub fn present_mode(&self) -> vk::PresentModeKHR {
+        self.present_mode
+    }
+
+    pub fn extent(&self) -> vk::Extent2D {
+        self.extent
+    }
+
+    pub fn images(&self) -> &[vk::Image] {
+        &self.images
+    }
+
+    pub fn image_views(&self) -> &[vk::ImageView] {
+        &self.image_views
+    }
+}
+
+impl Swapchain {
+    fn choose_surface_format(formats: &[vk::SurfaceFormatKHR]) -> vk::SurfaceFormatKHR {
+        formats
+            .iter()
+            .find(|format| {
+                format.format == vk::Format::B8G8R8A8_SRGB && format.color_space == vk::ColorSpaceKHR::SRGB_NONLINEAR
+            })
+            .or(formats.first())
+            .copied()
+            .expect("Surface does not report any formats!")
+    }
+
+    fn choose_present_mode(present_modes: &[vk::PresentModeKHR], vsync: bool) -> vk::PresentModeKHR {
+        // FIFO is the only mode the spec guarantees, and the only one that never tears
+        if vsync {
+            return vk::PresentModeKHR::FIFO;
+        }
+        [vk::PresentModeKHR::MAILBOX, vk::PresentModeKHR::IMMEDIATE]
+            .into_iter()
+            .find(|present_mode| present_modes.contains(present_mode))
+            .unwrap_or(vk::PresentModeKHR::FIFO)
+    }
+
+    fn choose_extent(capabilities: &vk::SurfaceCapabilitiesKHR, width: u32, height: u32) -> vk::Extent2D {
+        if capabilities.current_extent.width != u32::MAX {
+            capabilities.current_extent
+        } else {
+            vk::Extent2D {
+                width: width.clamp(capabilities.min_image_extent.width, capabilities.max_image_extent.width),
+                height: height.clamp(capabilities.min_image_extent.height, capabilities.max_image_extent.height),
+            }
+        }
+    }
+
+    fn destroy_image_views(&mut self) {
+        unsafe {
+            for image_view in self.image_views.drain(..) {
+                self.device.destroy_image_view(image_view, None);
+            }
+        }
+    }
+}
+
+impl Drop for Swapchain {
+    fn drop(&mut self) {
+        unsafe {
+            let _ = self.device.device_wait_idle();
+            self.destroy_image_views();
+            if self.swapchain != vk::SwapchainKHR::null() {
+                self.swapchain_loader.destroy_swapchain(self.swapchain, None);
+            }
+            self.surface_loader.destroy_surface(self.surface, None);
+        }
+    }
+}
@@ -1,35 +1,154 @@
-﻿use std::ffi::CStr;
+﻿use std::borrow::Cow;
+use std::collections::HashSet;
+use std::ffi::{c_void, CStr, CString};
+use std::os::raw::c_char;
+use std::sync::{Arc, Mutex};
 use ash::{ext, vk, Entry, Instance};
+use tracing::{debug, warn, Level};
 
+// A validation / debug message as handed to the user callback of DebugModuleProp
+#[derive(Clone, Debug)]
+pub struct DebugMessage {
+    pub severity: vk::DebugUtilsMessageSeverityFlagsEXT,
+    pub message_type: vk::DebugUtilsMessageTypeFlagsEXT,
+    pub message_id_name: String,
+    pub message_id_number: i32,
+    pub message: String,
+    pub objects: Vec<DebugObject>,
+}
+
+#[derive(Clone, Debug)]
+pub struct DebugObject {
+    pub object_type: vk::ObjectType,
+    pub object_handle: u64,
+    pub object_name: Option<String>,
+}
+
+pub type DebugMessageCallback = Arc<dyn Fn(&DebugMessage) + Send + Sync>;
+
+// What to do when the validation layers report an error ; only honoured in debug builds, release builds always log
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum ValidationErrorAction {
+    #[default]
+    Log,
+    // the callback records the error and VkApp::check_validation_errors panics with it, after the vulkan call
+    // returned (unwinding out of the callback would abort the process) ; the app handler checks after every update
+    // and render
+    Panic,
+    // raises a breakpoint trap so an attached debugger stops at the offending vulkan call
+    DebugBreak,
+}
+
+// Optional checks of the Khronos validation layer ; all of them slow validation down considerably.
+// GPU-assisted validation needs the fragmentStoresAndAtomics / vertexPipelineStoresAndAtomics device features and
+// debug printf needs VK_KHR_shader_non_semantic_info (core in 1.3) on the device.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct ValidationFeatures {
+    pub synchronization: bool,
+    pub gpu_assisted: bool,
+    pub best_practices: bool,
+    // forwards shader debugPrintfEXT output to the `torii::shader::printf` log target
+    pub debug_printf: bool,
+}
+
+impl ValidationFeatures {
+    pub fn any(&self) -> bool {
+        self.synchronization || self.gpu_assisted || self.best_practices || self.debug_printf
+    }
+}
+
+#[derive(Clone)]
 pub struct DebugModuleProp {
-    pub required_validation_layers: [&'static str; 1],
+    pub required_validation_layers: Vec<String>,
+    pub message_severity: vk::DebugUtilsMessageSeverityFlagsEXT,
+    pub message_type: vk::DebugUtilsMessageTypeFlagsEXT,
+    // messages matching either their id number or their id name (e.g. "VUID-vkCmdDraw-None-02699") are dropped
+    pub suppressed_message_ids: HashSet<i32>,
+    pub suppressed_message_names: HashSet<String>,
+    pub on_error: ValidationErrorAction,
+    pub validation_features: ValidationFeatures,
+    // called for every message that passes the filters, in addition to logging it
+    pub user_callback: Option<DebugMessageCallback>,
 }
 
 impl Default for DebugModuleProp {
     fn default() -> Self {
         DebugModuleProp {
-            required_validation_layers: ["VK_LAYER_KHRONOS_validation"]
+            required_validation_layers: vec![String::from("VK_LAYER_KHRONOS_validation")],
+            message_severity: vk::DebugUtilsMessageSeverityFlagsEXT::WARNING | vk::DebugUtilsMessageSeverityFlagsEXT::ERROR,
+            message_type: vk::DebugUtilsMessageTypeFlagsEXT::GENERAL | vk::DebugUtilsMessageTypeFlagsEXT::PERFORMANCE
+                | vk::DebugUtilsMessageTypeFlagsEXT::VALIDATION,
+            suppressed_message_ids: HashSet::new(),
+            suppressed_message_names: HashSet::new(),
+            on_error: ValidationErrorAction::Log,
+            validation_features: ValidationFeatures::default(),
+            user_callback: None,
+        }
+    }
+}
+
+// Everything the messenger callback needs, reached through its user data pointer ; boxed so the address stays put
+pub(crate) struct DebugMessengerState {
+    message_severity: vk::DebugUtilsMessageSeverityFlagsEXT,
+    debug_printf: bool,
+    suppressed_message_ids: HashSet<i32>,
+    suppressed_message_names: HashSet<String>,
+    on_error: ValidationErrorAction,
+    user_callback: Option<DebugMessageCallback>,
+    // first error since the last check, recorded for ValidationErrorAction::Panic
+    pending_error: Mutex<Option<String>>,
+}
+
+impl DebugMessengerState {
+    // the error recorded for ValidationErrorAction::Panic since the last call, if any
+    pub(crate) fn take_error(&self) -> Option<String> {
+        self.pending_error.lock().map_or(None, |mut pending_error| pending_error.take())
+    }
+}
+
+impl DebugModuleProp {
+    // debug printf output arrives as INFO messages, so INFO is requested from the layer and filtered again in the
+    // callback for everything that is not printf output
+    fn messenger_severity(&self) -> vk::DebugUtilsMessageSeverityFlagsEXT {
+        if self.validation_features.debug_printf {
+            self.message_severity | vk::DebugUtilsMessageSeverityFlagsEXT::INFO
+        } else {
+            self.message_severity
         }
     }
+    pub(crate) fn messenger_state(&self) -> Box<DebugMessengerState> {
+        Box::new(DebugMessengerState {
+            message_severity: self.message_severity,
+            debug_printf: self.validation_features.debug_printf,
+            suppressed_message_ids: self.suppressed_message_ids.clone(),
+            suppressed_message_names: self.suppressed_message_names.clone(),
+            on_error: self.on_error,
+            user_callback: self.user_callback.clone(),
+            pending_error: Mutex::new(None),
+        })
+    }
 }
 
 pub struct DebugModule {
     debug_utils_loader: ext::debug_utils::Instance, // stores instance-level debugging functions
     debug_messenger: vk::DebugUtilsMessengerEXT, // messenger object, handles passing debug messages to debug callback
+    messenger_state: Box<DebugMessengerState>, // must outlive the messenger, it is read by the debug callback
 }
 
 impl DebugModule {
-    pub fn new(entry: &Entry, instance: &Instance) -> Self {
-        let (debug_utils_loader, debug_messenger) = Self::setup_debug_utils(&entry, &instance);
+    pub fn new(entry: &Entry, instance: &Instance, debug_module_prop: &DebugModuleProp) -> Self {
+        let messenger_state = debug_module_prop.messenger_state();
+        let (debug_utils_loader, debug_messenger) = Self::setup_debug_utils(entry, instance, debug_module_prop, &messenger_state);
         
         DebugModule {
             debug_utils_loader,
             debug_messenger,
+            messenger_state,
         }
     }
-    pub fn setup_debug_utils(entry: &Entry, instance: &Instance) -> (ext::debug_utils::Instance, vk::DebugUtilsMessengerEXT){
+    pub(crate) fn setup_debug_utils(entry: &Entry, instance: &Instance, debug_module_prop: &DebugModuleProp, messenger_state: &DebugMessengerState) -> (ext::debug_utils::Instance, vk::DebugUtilsMessengerEXT){
         let debug_utils_loader= ext::debug_utils::Instance::new(entry, instance);
-        let messenger_ci = Self::populate_debug_messenger_create_info();
+        let messenger_ci = Self::populate_debug_messenger_create_info(debug_module_prop, messenger_state);
         let utils_messenger = unsafe {
             debug_utils_loader
                 .create_debug_utils_messenger(&messenger_ci, None)
@@ -38,18 +157,18 @@ impl DebugModule {
 
         (debug_utils_loader, utils_messenger)
     }
-    pub fn populate_debug_messenger_create_info() -> vk::DebugUtilsMessengerCreateInfoEXT<'static> {
-        vk::DebugUtilsMessengerCreateInfoEXT::default()
+    pub(crate) fn populate_debug_messenger_create_info<'a>(debug_module_prop: &DebugModuleProp, messenger_state: &'a DebugMessengerState) -> vk::DebugUtilsMessengerCreateInfoEXT<'a> {
+        let mut create_info = vk::DebugUtilsMessengerCreateInfoEXT::default()
             .flags(vk::DebugUtilsMessengerCreateFlagsEXT::empty())
-            .message_severity(
-                vk::DebugUtilsMessageSeverityFlagsEXT::WARNING | vk::DebugUtilsMessageSeverityFlagsEXT::ERROR
-                //| vk::DebugUtilsMessageSeverityFlagsEXT::VERBOSE | vk::DebugUtilsMessageSeverityFlagsEXT::INFO
-            )
-            .message_type(
-                vk::DebugUtilsMessageTypeFlagsEXT::GENERAL | vk::DebugUtilsMessageTypeFlagsEXT::PERFORMANCE
-                    | vk::DebugUtilsMessageTypeFlagsEXT::VALIDATION
-            )
-            .pfn_user_callback(Some(vulkan_debug_utils_callback))
+            .message_severity(debug_module_prop.messenger_severity())
+            .message_type(debug_module_prop.message_type)
+            .pfn_user_callback(Some(vulkan_debug_utils_callback));
+        create_info.p_user_data = messenger_state as *const DebugMessengerState as *mut c_void;
+        create_info
+    }
+
+    pub(crate) fn take_error(&self) -> Option<String> {
+        self.messenger_state.take_error()
     }
 }
 
@@ -66,58 +185,268 @@ unsafe extern "system" fn vulkan_debug_utils_callback(
     message_severity: vk::DebugUtilsMessageSeverityFlagsEXT,
     message_type: vk::DebugUtilsMessageTypeFlagsEXT,
     p_callback_data: *const vk::DebugUtilsMessengerCallbackDataEXT,
-    _p_user_data: *mut std::os::raw::c_void,
+    p_user_data: *mut c_void,
 ) -> vk::Bool32 {
-    let severity = match message_severity {
-        vk::DebugUtilsMessageSeverityFlagsEXT::VERBOSE => "[Verbose]",
-        vk::DebugUtilsMessageSeverityFlagsEXT::WARNING => "[Warning]",
-        vk::DebugUtilsMessageSeverityFlagsEXT::ERROR => "[Error]",
-        vk::DebugUtilsMessageSeverityFlagsEXT::INFO => "[INFO]",
-        _ => "[Unknown]"
-    };
+    let callback_data = &*p_callback_data;
+    let messenger_state = (p_user_data as *const DebugMessengerState).as_ref();
+    
+    let message_id_name = raw_to_str(callback_data.p_message_id_name);
+    let message_id_number = callback_data.message_id_number;
+    if let Some(messenger_state) = messenger_state {
+        if messenger_state.suppressed_message_ids.contains(&message_id_number)
+            || messenger_state.suppressed_message_names.contains(message_id_name.as_ref()) {
+            return vk::FALSE;
+        }
+        
+        if messenger_state.debug_printf && message_id_name.contains("DEBUG-PRINTF") {
+            let message = raw_to_str(callback_data.p_message);
+            // the layer prefixes the shader output with the call that triggered it, keep only what the shader printed
+            let shader_output = message.rsplit_once(" | ").map_or(message.as_ref(), |(_, shader_output)| shader_output);
+            tracing::info!(target: "torii::shader::printf", "{}", shader_output.trim_end());
+            if let Some(user_callback) = messenger_state.user_callback.as_ref() {
+                user_callback(&DebugMessage {
+                    severity: message_severity,
+                    message_type,
+                    message_id_name: message_id_name.into_owned(),
+                    message_id_number,
+                    message: message.into_owned(),
+                    objects: vec![],
+                });
+            }
+            return vk::FALSE;
+        }
+        if !messenger_state.message_severity.contains(message_severity) {
+            return vk::FALSE;
+        }
+    }
     
     let types = match message_type {
-        vk::DebugUtilsMessageTypeFlagsEXT::GENERAL => "[General]",
-        vk::DebugUtilsMessageTypeFlagsEXT::PERFORMANCE => "[Performance]",
-        vk::DebugUtilsMessageTypeFlagsEXT::VALIDATION => "[Validation]",
-        _ => "[Unknown]",
+        vk::DebugUtilsMessageTypeFlagsEXT::GENERAL => "General",
+        vk::DebugUtilsMessageTypeFlagsEXT::PERFORMANCE => "Performance",
+        vk::DebugUtilsMessageTypeFlagsEXT::VALIDATION => "Validation",
+        vk::DebugUtilsMessageTypeFlagsEXT::DEVICE_ADDRESS_BINDING => "Device Address Binding",
+        _ => "Unknown",
+    };
+    
+    let message = raw_to_str(callback_data.p_message);
+    let debug_objects: Vec<DebugObject> = if callback_data.p_objects.is_null() {
+        vec![]
+    } else {
+        std::slice::from_raw_parts(callback_data.p_objects, callback_data.object_count as usize)
+            .iter()
+            .map(|object| DebugObject {
+                object_type: object.object_type,
+                object_handle: object.object_handle,
+                object_name: Some(raw_to_str(object.p_object_name).into_owned()).filter(|name| !name.is_empty()),
+            })
+            .collect()
+    };
+    let objects = debug_objects
+        .iter()
+        .map(|object| format!("{:?} {:#x} ({})", object.object_type, object.object_handle, object.object_name.as_deref().unwrap_or("unnamed")))
+        .collect::<Vec<String>>()
+        .join(", ");
+    
+    macro_rules! validation_event {
+        ($level:expr) => {
+            tracing::event!(
+                target: "torii::vulkan::validation", $level,
+                message_type = types, message_id_name = message_id_name.as_ref(), message_id_number, objects,
+                "{}", message
+            )
+        };
+    }
+    match message_severity {
+        vk::DebugUtilsMessageSeverityFlagsEXT::ERROR => validation_event!(Level::ERROR),
+        vk::DebugUtilsMessageSeverityFlagsEXT::WARNING => validation_event!(Level::WARN),
+        vk::DebugUtilsMessageSeverityFlagsEXT::INFO => validation_event!(Level::INFO),
+        _ => validation_event!(Level::TRACE),
+    }
+    
+    let Some(messenger_state) = messenger_state else {
+        return vk::FALSE;
     };
+    if let Some(user_callback) = messenger_state.user_callback.as_ref() {
+        user_callback(&DebugMessage {
+            severity: message_severity,
+            message_type,
+            message_id_name: message_id_name.clone().into_owned(),
+            message_id_number,
+            message: message.clone().into_owned(),
+            objects: debug_objects,
+        });
+    }
     
-    let message = CStr::from_ptr((*p_callback_data).p_message);
-    println!("[Debug]{}{}{:?}", severity, types, message);
+    if cfg!(debug_assertions) && message_severity == vk::DebugUtilsMessageSeverityFlagsEXT::ERROR {
+        match messenger_state.on_error {
+            ValidationErrorAction::Log => (),
+            ValidationErrorAction::Panic => {
+                if let Ok(mut pending_error) = messenger_state.pending_error.lock() {
+                    pending_error.get_or_insert_with(|| format!("Vulkan validation error [{message_id_name}]: {message}"));
+                }
+            },
+            ValidationErrorAction::DebugBreak => debug_break(),
+        }
+    }
     
     vk::FALSE
 }
 
+fn debug_break() {
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    unsafe { std::arch::asm!("int3") };
+    #[cfg(target_arch = "aarch64")]
+    unsafe { std::arch::asm!("brk #0xf000") };
+}
+
+// How the optional validation features get to the layer: VK_EXT_layer_settings on recent layers, the deprecated
+// VK_EXT_validation_features otherwise
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub(crate) enum ValidationFeatureMechanism {
+    LayerSettings,
+    ValidationFeatures,
+    Unsupported,
+}
+
+pub(crate) fn validation_feature_mechanism(debug_module_prop: &DebugModuleProp, entry: &Entry) -> ValidationFeatureMechanism {
+    let Some(layer_name) = debug_module_prop.required_validation_layers.first() else {
+        return ValidationFeatureMechanism::Unsupported;
+    };
+    let Ok(layer_name) = CString::new(layer_name.as_str()) else {
+        return ValidationFeatureMechanism::Unsupported;
+    };
+    let layer_extensions = unsafe {
+        entry.enumerate_instance_extension_properties(Some(&layer_name)).unwrap_or_default()
+    };
+    let has_extension = |name: &CStr| layer_extensions
+        .iter()
+        .any(|extension| extension.extension_name_as_c_str() == Ok(name));
+    
+    if has_extension(ext::layer_settings::NAME) {
+        ValidationFeatureMechanism::LayerSettings
+    } else if has_extension(ext::validation_features::NAME) {
+        ValidationFeatureMechanism::ValidationFeatures
+    } else {
+        ValidationFeatureMechanism::Unsupported
+    }
+}
+
+// Owns every string and value the layer settings point at ; must stay alive until the instance is created
+pub(crate) struct ValidationLayerSettings {
+    _names: Vec<CString>,
+    _bool_values: Box<[vk::Bool32; 2]>,
+    _string_values: Vec<CString>,
+    _string_pointers: Vec<*const c_char>,
+    settings: Vec<vk::LayerSettingEXT<'static>>,
+    enabled_features: Vec<vk::ValidationFeatureEnableEXT>,
+}
+
+impl ValidationLayerSettings {
+    const LAYER_NAME: &'static CStr = c"VK_LAYER_KHRONOS_validation";
+
+    pub(crate) fn new(validation_features: &ValidationFeatures) -> Self {
+        let mut validation_features = *validation_features;
+        // older layers refuse to run GPU-assisted validation and debug printf at the same time
+        if validation_features.gpu_assisted && validation_features.debug_printf {
+            warn!(target: "torii::vulkan", "GPU-assisted validation and debug printf are mutually exclusive, disabling debug printf");
+            validation_features.debug_printf = false;
+        }
+        
+        let bool_values = Box::new([vk::FALSE, vk::TRUE]);
+        let as_bool = |enabled: bool| &bool_values[enabled as usize] as *const vk::Bool32 as *const c_void;
+        
+        let gpu_based = if validation_features.gpu_assisted {
+            "GPU_BASED_GPU_ASSISTED"
+        } else if validation_features.debug_printf {
+            "GPU_BASED_DEBUG_PRINTF"
+        } else {
+            "GPU_BASED_NONE"
+        };
+        let string_values = vec![CString::new(gpu_based).unwrap()];
+        let string_pointers: Vec<*const c_char> = string_values.iter().map(|value| value.as_ptr()).collect();
+        
+        let setting_values: [(&str, vk::LayerSettingTypeEXT, *const c_void); 4] = [
+            ("validate_sync", vk::LayerSettingTypeEXT::BOOL32, as_bool(validation_features.synchronization)),
+            ("validate_best_practices", vk::LayerSettingTypeEXT::BOOL32, as_bool(validation_features.best_practices)),
+            ("validate_gpu_based", vk::LayerSettingTypeEXT::STRING, string_pointers.as_ptr() as *const c_void),
+            // printf output goes through the debug messenger instead of straight to stdout
+            ("printf_to_stdout", vk::LayerSettingTypeEXT::BOOL32, as_bool(false)),
+        ];
+        let names: Vec<CString> = setting_values.iter().map(|(name, _, _)| CString::new(*name).unwrap()).collect();
+        let settings = setting_values
+            .iter()
+            .zip(names.iter())
+            .map(|(&(_, ty, value), name)| {
+                // `LayerSettingEXT::values` counts bytes, the layer expects a count of values
+                let mut setting = vk::LayerSettingEXT::default().ty(ty);
+                setting.p_layer_name = Self::LAYER_NAME.as_ptr();
+                setting.p_setting_name = name.as_ptr();
+                setting.value_count = 1;
+                setting.p_values = value;
+                setting
+            })
+            .collect();
+        
+        let enabled_features = [
+            (validation_features.synchronization, vk::ValidationFeatureEnableEXT::SYNCHRONIZATION_VALIDATION),
+            (validation_features.gpu_assisted, vk::ValidationFeatureEnableEXT::GPU_ASSISTED),
+            (validation_features.gpu_assisted, vk::ValidationFeatureEnableEXT::GPU_ASSISTED_RESERVE_BINDING_SLOT),
+            (validation_features.best_practices, vk::ValidationFeatureEnableEXT::BEST_PRACTICES),
+            (validation_features.debug_printf, vk::ValidationFeatureEnableEXT::DEBUG_PRINTF),
+        ]
+            .into_iter()
+            .filter_map(|(enabled, feature)| enabled.then_some(feature))
+            .collect();
+        
+        ValidationLayerSettings {
+            _names: names,
+            _bool_values: bool_values,
+            _string_values: string_values,
+            _string_pointers: string_pointers,
+            settings,
+            enabled_features,
+        }
+    }
+    
+    pub(crate) fn layer_settings_create_info(&self) -> vk::LayerSettingsCreateInfoEXT<'_> {
+        vk::LayerSettingsCreateInfoEXT::default().settings(&self.settings)
+    }
+    
+    pub(crate) fn validation_features_create_info(&self) -> vk::ValidationFeaturesEXT<'_> {
+        vk::ValidationFeaturesEXT::default().enabled_validation_features(&self.enabled_features)
+    }
+}
+
+// validation messages may carry null strings (e.g. no message id name)
+unsafe fn raw_to_str<'a>(raw_string: *const c_char) -> Cow<'a, str> {
+    if raw_string.is_null() {
+        Cow::Borrowed("")
+    } else {
+        CStr::from_ptr(raw_string).to_string_lossy()
+    }
+}
+
 pub fn check_validation_layer_support(debug_module_prop: &DebugModuleProp, entry: &Entry) -> bool {
     unsafe {
         let layer_properties = entry
             .enumerate_instance_layer_properties()
             .expect("Failed to enumerate Instance Layers Properties");
 
-        if layer_properties.len() <= 0 {
-            eprintln!("No available layers.");
+        if layer_properties.is_empty() {
+            warn!(target: "torii::vulkan", "No available instance layers");
             return false;
-        } else {
-            println!("Instance Available Layers: ");
-            for layer in layer_properties.iter() {
-                let layer_name = super::utility::vk_to_string(&layer.layer_name);
-                println!("\t{}", layer_name);
-            }
+        }
+        for layer in layer_properties.iter() {
+            debug!(target: "torii::vulkan", layer_name = super::utility::vk_to_string(&layer.layer_name), "Instance layer available");
         }
 
         for required_layer_name in debug_module_prop.required_validation_layers.iter() {
-            let mut is_layer_found = false;
-
-            for layer_property in layer_properties.iter() {
-                let test_layer_name = super::utility::vk_to_string(&layer_property.layer_name);
-                if (*required_layer_name) == test_layer_name {
-                    is_layer_found = true;
-                    break;
-                }
-            }
+            let is_layer_found = layer_properties
+                .iter()
+                .any(|layer_property| super::utility::vk_to_string(&layer_property.layer_name) == *required_layer_name);
 
-            if is_layer_found == false {
+            if !is_layer_found {
+                warn!(target: "torii::vulkan", layer_name = required_layer_name, "Required validation layer not available");
                 return false;
             }
         }
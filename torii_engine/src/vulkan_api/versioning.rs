@@ -1,15 +1,17 @@
 ﻿pub use ash::vk::{API_VERSION_1_0, API_VERSION_1_1, API_VERSION_1_2, API_VERSION_1_3};
-pub use ash::vk::make_api_version;
+pub use ash::vk::{api_version_major, api_version_minor, api_version_patch, make_api_version};
 
 pub const ENGINE_VERSION: u32 = make_api_version(0, 0, 1, 0);
-pub const ENGINE_NAME: &'static str = "Torii Engine";
+pub const ENGINE_NAME: &str = "Torii Engine";
 
 pub struct VkAppInfo {
     engine_version: u32,
-    api_version: u32,
+    // the instance and device are created with the highest version both support within this range
+    min_api_version: u32,
+    max_api_version: u32,
     application_version: u32,
     engine_name: &'static str,
-    app_name: &'static str,
+    app_name: String,
 }
 
 impl Default for VkAppInfo {
@@ -19,20 +21,27 @@ impl Default for VkAppInfo {
 }
 
 impl VkAppInfo {
-    pub fn new(application_version: u32, app_name: &'static str) -> Self {
+    pub fn new(application_version: u32, app_name: impl Into<String>) -> Self {
         VkAppInfo {
             engine_version: ENGINE_VERSION,
-            api_version: API_VERSION_1_3,
+            min_api_version: API_VERSION_1_0,
+            max_api_version: API_VERSION_1_3,
             application_version,
             engine_name: ENGINE_NAME,
-            app_name,
+            app_name: app_name.into(),
         }
     }
     
+    pub fn with_api_version_range(mut self, min_api_version: u32, max_api_version: u32) -> Self {
+        self.min_api_version = min_api_version;
+        self.max_api_version = max_api_version;
+        self
+    }
+    
     // GETTERS
 
-    pub fn app_name(&self) -> &'static str {
-        self.app_name
+    pub fn app_name(&self) -> &str {
+        &self.app_name
     }
 
     pub fn engine_name(&self) -> &'static str {
@@ -43,11 +52,24 @@ impl VkAppInfo {
         self.application_version
     }
 
-    pub fn api_version(&self) -> u32 {
-        self.api_version
+    pub fn min_api_version(&self) -> u32 {
+        self.min_api_version
+    }
+
+    pub fn max_api_version(&self) -> u32 {
+        self.max_api_version
     }
 
     pub fn engine_version(&self) -> u32 {
         self.engine_version
     }
-}
\ No newline at end of file
+}
+
+// drops the patch (and variant) part, which never matters when comparing supported versions
+pub fn major_minor(version: u32) -> u32 {
+    make_api_version(0, api_version_major(version), api_version_minor(version), 0)
+}
+
+pub fn version_string(version: u32) -> String {
+    format!("{}.{}.{}", api_version_major(version), api_version_minor(version), api_version_patch(version))
+}
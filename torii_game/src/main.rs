@@ -1,9 +1,119 @@
+use std::time::Duration;
+
 use torii_engine::*;
+use torii_engine::application_handler::{App, AppHandler, FullscreenMode, WindowDetails, WindowHandle};
 use anyhow::Result;
 
+use winit::event::{ElementState, WindowEvent};
+use winit::keyboard::{KeyCode, PhysicalKey};
+use tracing::{error, info};
+use tracing_subscriber::EnvFilter;
+
+// filter directives for the log output, e.g. TORII_LOG=torii::vulkan=debug,info
+const LOG_FILTER_ENV: &str = "TORII_LOG";
+
+enum GameEvents {
+    ToggleFullscreen(WindowHandle),
+}
+
+#[derive(Default)]
+struct Game {
+    fixed_updates: u64,
+    frames_since_report: u64,
+    last_report: Duration,
+}
+
+impl App<GameEvents> for Game {
+    fn init(&mut self, app_handler: &mut AppHandler<GameEvents>) {
+        info!(windows = app_handler.window_handles().len(), "Game started");
+        for monitor in app_handler.monitors() {
+            info!(index = monitor.index, name = ?monitor.name, width = monitor.size.width, height = monitor.size.height, "Monitor");
+        }
+    }
+
+    fn update(&mut self, app_handler: &mut AppHandler<GameEvents>) {
+        self.frames_since_report += 1;
+
+        let elapsed = app_handler.clock().elapsed();
+        if elapsed - self.last_report >= Duration::from_secs(5) {
+            let seconds = (elapsed - self.last_report).as_secs_f64();
+            info!(fps = self.frames_since_report as f64 / seconds, fixed_updates = self.fixed_updates, "Frame rate");
+            self.frames_since_report = 0;
+            self.last_report = elapsed;
+        }
+    }
+
+    fn fixed_update(&mut self, _app_handler: &mut AppHandler<GameEvents>) {
+        self.fixed_updates += 1;
+    }
+
+    fn on_window_event(&mut self, app_handler: &mut AppHandler<GameEvents>, window: WindowHandle, event: &WindowEvent) {
+        let WindowEvent::KeyboardInput { event: key_event, .. } = event else {
+            return;
+        };
+        if key_event.state != ElementState::Pressed || key_event.repeat {
+            return;
+        }
+
+        let result = match key_event.physical_key {
+            PhysicalKey::Code(KeyCode::F11) => app_handler.send_user_event(GameEvents::ToggleFullscreen(window)),
+            PhysicalKey::Code(KeyCode::KeyN) => {
+                let window_details = WindowDetails {
+                    window_title: format!("Torii Window {}", app_handler.window_handles().len() + 1),
+                    ..app_handler.window_details().clone()
+                };
+                app_handler.create_window(window_details).map(|_| ())
+            },
+            PhysicalKey::Code(KeyCode::KeyW) => app_handler.close_window(window),
+            PhysicalKey::Code(KeyCode::Escape) => {
+                app_handler.request_exit();
+                Ok(())
+            },
+            _ => Ok(()),
+        };
+        if let Err(error) = result {
+            error!(%error);
+        }
+    }
+
+    fn on_user_event(&mut self, app_handler: &mut AppHandler<GameEvents>, event: GameEvents) {
+        match event {
+            GameEvents::ToggleFullscreen(window) => {
+                let fullscreen = match app_handler.window(window).map(|managed_window| managed_window.fullscreen()) {
+                    Ok(FullscreenMode::Windowed) => FullscreenMode::Borderless { monitor: None },
+                    Ok(_) => FullscreenMode::Windowed,
+                    Err(_) => return,
+                };
+                if let Err(error) = app_handler.set_fullscreen(window, fullscreen) {
+                    error!(%error);
+                }
+            },
+        }
+    }
+
+    fn exit(&mut self, app_handler: &mut AppHandler<GameEvents>) {
+        info!(frames = app_handler.clock().frame(), "Game exiting");
+    }
+}
+
+fn init_logging(json: bool) {
+    let filter = EnvFilter::try_from_env(LOG_FILTER_ENV).unwrap_or_else(|_| EnvFilter::new("info"));
+    let subscriber = tracing_subscriber::fmt().with_env_filter(filter);
+    if json {
+        subscriber.json().init();
+    } else {
+        subscriber.init();
+    }
+}
+
 fn main() -> Result<()> {
-    let _app = application_handler::AppHandler::new()?
+    init_logging(std::env::args().any(|arg| arg == "--log-json"));
+
+    let config = config::EngineConfig::from_args_and_env(std::env::args().skip(1))?;
+
+    let _app = AppHandler::with_config(config)?
+        .with_app(Game::default())
         .start_loop()?;
-    
+
     Ok(())
-}
\ No newline at end of file
+}